@@ -0,0 +1,70 @@
+//! Decoding throughput over typical `javac` output.
+//!
+//! Run with `cargo bench --bench decode`
+
+extern crate java_reader;
+
+use java_reader::bytecode::Decoder;
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+
+/// `static int sum(int[] a)`, a counted loop over an array
+const SUM: &[u8] = &[
+    0x03, 0x3C, 0x03, 0x3D, 0x1C, 0x2A, 0xBE, 0xA2, 0x00, 0x0F,
+    0x1B, 0x2A, 0x1C, 0x2E, 0x60, 0x3C, 0x84, 0x02, 0x01, 0xA7,
+    0xFF, 0xF1, 0x1B, 0xAC
+];
+
+/// `public String toString()`, string concatenation
+const TO_STRING: &[u8] = &[
+    0xBB, 0x00, 0x02, 0x59, 0xB7, 0x00, 0x03, 0x12, 0x04, 0xB6,
+    0x00, 0x05, 0x2A, 0xB4, 0x00, 0x06, 0xB6, 0x00, 0x07, 0x12,
+    0x08, 0xB6, 0x00, 0x05, 0x2A, 0xB4, 0x00, 0x09, 0xB6, 0x00,
+    0x0A, 0xB6, 0x00, 0x0B, 0xB0
+];
+
+/// `static String name(int k)`, a dense switch
+const SWITCH: &[u8] = &[
+    0x1A, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x00, 0x24, 0x00, 0x00,
+    0x00, 0x01, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x1B,
+    0x00, 0x00, 0x00, 0x1E, 0x00, 0x00, 0x00, 0x21, 0x12, 0x02,
+    0xB0, 0x12, 0x03, 0xB0, 0x12, 0x04, 0xB0, 0x12, 0x05, 0xB0
+];
+
+/// `static double pick(int k)`, a sparse switch and a wide local
+const LOOKUP: &[u8] = &[
+    0xAB, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x23, 0x00, 0x00,
+    0x00, 0x02, 0x00, 0x00, 0x00, 0x0A, 0x00, 0x00, 0x00, 0x1C,
+    0x00, 0x00, 0x03, 0xE8, 0x00, 0x00, 0x00, 0x1E, 0x0F, 0xAF,
+    0xC4, 0x18, 0x01, 0x2C, 0xAF, 0x0E, 0xAF
+];
+
+fn bench(name: &str, code: &[u8]) {
+    let budget = Duration::from_millis(500);
+    let start = Instant::now();
+    let mut runs = 0u64;
+    let mut ops = 0u64;
+    while start.elapsed() < budget {
+        for _ in 0..1000 {
+            for op in Decoder::new(black_box(code)) {
+                black_box(op.unwrap());
+                ops += 1;
+            }
+        }
+        runs += 1000;
+    }
+    let secs = start.elapsed().as_secs_f64();
+    let bytes = (runs * code.len() as u64) as f64;
+    println!("{:<10} {:>8.1} MB/s {:>8.1} Mops/s",
+        name,
+        bytes / secs / 1_000_000.0,
+        ops as f64 / secs / 1_000_000.0);
+}
+
+fn main() {
+    bench("sum", SUM);
+    bench("toString", TO_STRING);
+    bench("switch", SWITCH);
+    bench("lookup", LOOKUP);
+}
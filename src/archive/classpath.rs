@@ -0,0 +1,436 @@
+//! Finding classes by binary name across directories, jars and runtime
+//! images.
+//!
+//! Elements are searched in the order they were added and the first one
+//! holding a class wins, shadowing the others. Nothing is read until a
+//! class is asked for, and parsed classes are kept for later lookups.
+
+use super::super::clazz::{Class, Fault};
+use super::release::versioned;
+use super::{Archive, ArchiveFault, Image, ImageFault};
+
+use std::borrow::Cow;
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+const MODULE_INFO: &str = "module-info";
+const META_INF: &str = "META-INF/";
+
+/// Why a class could not be found or loaded
+#[derive(Clone,Debug)]
+pub enum ClasspathFault {
+    /// No element has the class
+    NotFound,
+    /// A file or directory couldn't be read
+    Io(io::ErrorKind),
+    Entry(ArchiveFault),
+    Resource(ImageFault),
+    Class(Fault),
+    /// The class file declares another name than its path gives
+    WrongName(String),
+    /// The class is shadowed by the one of an earlier element
+    Shadowed(usize),
+    /// The element has more than one entry of this name
+    Duplicate
+}
+impl fmt::Display for ClasspathFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClasspathFault::NotFound => write!(f, "not found"),
+            ClasspathFault::Io(x) => write!(f, "{}", x),
+            ClasspathFault::Entry(x) => write!(f, "{}", x),
+            ClasspathFault::Resource(ref x) => write!(f, "{}", x),
+            ClasspathFault::Class(x) => write!(f, "malformed class: {:?}", x),
+            ClasspathFault::WrongName(ref x) => write!(f, "declares class {}", x),
+            ClasspathFault::Shadowed(x) => write!(f, "shadowed by element {}", x),
+            ClasspathFault::Duplicate => write!(f, "duplicate entry")
+        }
+    }
+}
+
+/// A failure to load a class
+#[derive(Clone,Debug)]
+pub struct ClasspathError {
+    /// Binary name of the class, or the path of an unreadable directory
+    pub name: String,
+    /// Index of the element at fault, if any
+    pub element: Option<usize>,
+    pub reason: ClasspathFault
+}
+impl fmt::Display for ClasspathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.element {
+            Option::Some(i) => write!(f, "{} in class {} of element {}", self.reason, self.name, i),
+            Option::None => write!(f, "{} in class {}", self.reason, self.name)
+        }
+    }
+}
+
+/// Somewhere classes are looked for
+#[derive(Debug)]
+enum Element<'a> {
+    /// Class files under a directory, by package
+    Directory(PathBuf),
+    Archive(Archive<'a>),
+    /// A runtime image, with the module of each package
+    Image(Image<'a>, HashMap<String, String>)
+}
+
+/// A class as loaded, with the bytes it borrows from
+#[derive(Debug)]
+struct Loaded<'a> {
+    /// Declared first to be dropped before the bytes it borrows
+    class: Class<'static>,
+    _data: Cow<'a, [u8]>
+}
+
+/// An ordered list of places to load classes from
+#[derive(Debug,Default)]
+pub struct Classpath<'a> {
+    elements: Vec<Element<'a>>,
+    /// Release multi-release jars are read for, their base entries
+    /// when `None`
+    release: Option<u32>,
+    /// Parsed classes by name, only ever added to while shared
+    loaded: RefCell<HashMap<String, Box<Loaded<'a>>>>
+}
+
+impl<'a> Classpath<'a> {
+
+    pub fn new() -> Classpath<'a> {
+        Classpath::default()
+    }
+
+    /// Look for class files under `path`, `java/lang/String` being read
+    /// from `java/lang/String.class`
+    pub fn add_directory<P: AsRef<Path>>(&mut self, path: P) {
+        self.elements.push(Element::Directory(path.as_ref().to_path_buf()));
+    }
+
+    pub fn add_archive(&mut self, archive: Archive<'a>) {
+        self.elements.push(Element::Archive(archive));
+    }
+
+    /// Look for classes in every module of a runtime image
+    pub fn add_image(&mut self, image: Image<'a>) {
+        let mut packages = HashMap::new();
+        for module in image.modules() {
+            for package in image.packages(module) {
+                packages.entry(package.to_string()).or_insert_with(|| module.to_string());
+            }
+        }
+        self.elements.push(Element::Image(image, packages));
+    }
+
+    /// Read multi-release jars as a runtime of `release` does
+    ///
+    /// Classes loaded so far are forgotten.
+    pub fn set_release(&mut self, release: u32) {
+        self.release = Some(release);
+        self.loaded.get_mut().clear();
+    }
+
+    /// Number of elements
+    pub fn len(&self) -> usize {
+        self.elements.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.elements.is_empty()
+    }
+
+    /// Index of the first element holding a class, without reading it
+    pub fn find(&self, name: &str) -> Option<usize> {
+        if !valid_name(name) {
+            return None;
+        }
+        let file = format!("{}.class", name);
+        (0..self.elements.len()).find(|&i| match self.elements[i] {
+            Element::Directory(ref dir) => path_of(dir, &file).is_file(),
+            Element::Archive(ref archive) => self.entry(archive, &file).is_some(),
+            Element::Image(ref image, ref packages) => image_name(packages, &file)
+                .is_some_and(|x| image.find(&x).is_some())
+        })
+    }
+
+    /// The bytes of a class, read afresh on every call
+    pub fn bytes(&self, name: &str) -> Result<Cow<'a, [u8]>, ClasspathError> {
+        self.read(name).map(|x| x.1)
+    }
+
+    /// The parsed class, loaded on first use
+    ///
+    /// The class must declare the name it is found by.
+    pub fn class(&self, name: &str) -> Result<&Class<'_>, ClasspathError> {
+        if let Option::Some(loaded) = self.loaded.borrow().get(name) {
+            return Ok(self.keep(loaded));
+        }
+        let (element, data) = self.read(name)?;
+        let err = |reason| ClasspathError { name: name.to_string(), element: Some(element), reason };
+        // SAFETY: the bytes are borrowed for `'a` or owned by a `Vec`
+        // whose buffer doesn't move when `data` is moved into the boxed
+        // `Loaded` below. That `Vec` is never touched again and is
+        // dropped after the class, so the bytes outlive the class.
+        let bytes = unsafe { &*(&*data as *const [u8]) };
+        let class = Class::parse(bytes).map_err(|e| err(ClasspathFault::Class(e)))?;
+        check_name(&class, name).map_err(err)?;
+        let mut loaded = self.loaded.borrow_mut();
+        let loaded = loaded.entry(name.to_string()).or_insert(Box::new(Loaded { class, _data: data }));
+        Ok(self.keep(loaded))
+    }
+
+    /// Hand out a class for as long as the classpath is borrowed
+    fn keep(&self, loaded: &Loaded<'a>) -> &Class<'_> {
+        // SAFETY: `Loaded` is boxed, so it stays put when `loaded` grows.
+        // Entries are never removed or replaced while shared, only by
+        // `set_release` and `Drop`, which need `&mut self` and so can't
+        // run while the returned reference, tied to `&self`, is alive.
+        unsafe { &(*(loaded as *const Loaded<'a>)).class }
+    }
+
+    /// The first element with the class and its bytes
+    fn read(&self, name: &str) -> Result<(usize, Cow<'a, [u8]>), ClasspathError> {
+        let not_found = ClasspathError { name: name.to_string(), element: None, reason: ClasspathFault::NotFound };
+        if !valid_name(name) {
+            return Err(not_found);
+        }
+        let file = format!("{}.class", name);
+        for i in 0..self.elements.len() {
+            match self.read_from(i, &file) {
+                Ok(Option::Some(data)) => return Ok((i, data)),
+                Ok(Option::None) => {},
+                Err(reason) => return Err(ClasspathError { name: name.to_string(), element: Some(i), reason })
+            };
+        }
+        Err(not_found)
+    }
+
+    /// The bytes of a file of one element, `None` if it isn't there
+    fn read_from(&self, element: usize, file: &str) -> Result<Option<Cow<'a, [u8]>>, ClasspathFault> {
+        match self.elements[element] {
+            Element::Directory(ref dir) => {
+                let path = path_of(dir, file);
+                if !path.is_file() {
+                    return Ok(None);
+                }
+                fs::read(path).map(|x| Some(Cow::Owned(x))).map_err(|e| ClasspathFault::Io(e.kind()))
+            },
+            Element::Archive(ref archive) => match self.entry(archive, file) {
+                Option::Some(entry) => archive.read(entry).map(Some).map_err(|e| ClasspathFault::Entry(e.reason)),
+                Option::None => Ok(None)
+            },
+            Element::Image(ref image, ref packages) => match image_name(packages, file).and_then(|x| image.find(&x)) {
+                Option::Some(resource) => image.read(resource).map(Some).map_err(|e| ClasspathFault::Resource(e.reason)),
+                Option::None => Ok(None)
+            }
+        }
+    }
+
+    fn entry<'b>(&self, archive: &'b Archive<'a>, file: &str) -> Option<&'b super::Entry> {
+        match self.release {
+            Option::Some(release) => archive.lookup(file, release),
+            Option::None => archive.by_name(file)
+        }
+    }
+
+    /// Names of the classes of an element, sorted
+    ///
+    /// `module-info` and whatever is under `META-INF` are left out,
+    /// apart from the versioned classes of a multi-release jar.
+    fn class_names(&self, element: usize) -> io::Result<Vec<String>> {
+        let mut names = Vec::new();
+        match self.elements[element] {
+            Element::Directory(ref dir) => walk(dir, "", &mut names)?,
+            Element::Archive(ref archive) => {
+                for entry in archive.entries().iter().filter(|e| e.is_class()) {
+                    let name = match (versioned(&entry.name), self.release) {
+                        (Option::Some((r, name)), Option::Some(release)) if archive.is_multi_release() &&
+                            archive.releases().contains(&r) && r <= release => name,
+                        _ => entry.name.as_str()
+                    };
+                    names.push(name[..name.len() - ".class".len()].to_string());
+                }
+            },
+            Element::Image(ref image, _) => {
+                for resource in image.resources().iter().filter(|r| r.is_class()) {
+                    names.push(match resource.parent.as_str() {
+                        "" => resource.base.clone(),
+                        parent => format!("{}/{}", parent, resource.base)
+                    });
+                }
+            }
+        };
+        names.retain(|x| x != MODULE_INFO && !x.starts_with(META_INF));
+        names.sort();
+        names.dedup();
+        Ok(names)
+    }
+
+    /// Check every class of every element
+    ///
+    /// Each class must parse and declare the name its path gives, and
+    /// classes shadowed by an earlier element or repeated within an
+    /// archive are reported.
+    pub fn check(&self) -> Vec<ClasspathError> {
+        let mut errors = Vec::new();
+        let mut first = HashMap::new();
+        for i in 0..self.elements.len() {
+            let names = match self.class_names(i) {
+                Ok(x) => x,
+                Err(e) => {
+                    let name = match self.elements[i] {
+                        Element::Directory(ref dir) => dir.display().to_string(),
+                        _ => String::new()
+                    };
+                    errors.push(ClasspathError { name, element: Some(i), reason: ClasspathFault::Io(e.kind()) });
+                    continue;
+                }
+            };
+            if let Element::Archive(ref archive) = self.elements[i] {
+                let mut seen = HashSet::new();
+                for entry in archive.entries().iter().filter(|e| e.is_class()) {
+                    if !seen.insert(entry.name.as_str()) {
+                        let name = entry.name[..entry.name.len() - ".class".len()].to_string();
+                        errors.push(ClasspathError { name, element: Some(i), reason: ClasspathFault::Duplicate });
+                    }
+                }
+            }
+            for name in names {
+                let err = |reason| ClasspathError { name: name.clone(), element: Some(i), reason };
+                if let Option::Some(&j) = first.get(&name) {
+                    errors.push(err(ClasspathFault::Shadowed(j)));
+                } else {
+                    first.insert(name.clone(), i);
+                }
+                let data = match self.read_from(i, &format!("{}.class", name)) {
+                    Ok(Option::Some(x)) => x,
+                    Ok(Option::None) => continue,
+                    Err(reason) => {
+                        errors.push(err(reason));
+                        continue;
+                    }
+                };
+                let checked = Class::parse(&data)
+                    .map_err(ClasspathFault::Class)
+                    .and_then(|class| check_name(&class, &name));
+                if let Err(reason) = checked {
+                    errors.push(err(reason));
+                }
+            }
+        }
+        errors
+    }
+}
+
+/// A binary name made of `/` separated parts, none of them `.` or `..`
+fn valid_name(name: &str) -> bool {
+    name.split('/').all(|x| !x.is_empty() && x != "." && x != ".." && !x.contains('\\'))
+}
+
+fn path_of(dir: &Path, file: &str) -> PathBuf {
+    file.split('/').fold(dir.to_path_buf(), |path, x| path.join(x))
+}
+
+/// The full resource name of a class file in a runtime image
+fn image_name(packages: &HashMap<String, String>, file: &str) -> Option<String> {
+    let package = &file[..file.rfind('/')?];
+    packages.get(package).map(|module| format!("/{}/{}", module, file))
+}
+
+fn check_name(class: &Class, name: &str) -> Result<(), ClasspathFault> {
+    let declared = class.get_this_class().map_err(ClasspathFault::Class)?;
+    match declared == name {
+        true => Ok(()),
+        false => Err(ClasspathFault::WrongName(declared.into_owned()))
+    }
+}
+
+/// Collect the names of the class files under `dir`
+fn walk(dir: &Path, prefix: &str, names: &mut Vec<String>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let file = match entry.file_name().into_string() {
+            Ok(x) => x,
+            Err(_) => continue
+        };
+        if entry.file_type()?.is_dir() {
+            walk(&entry.path(), &format!("{}{}/", prefix, file), names)?;
+        } else if let Option::Some(base) = file.strip_suffix(".class") {
+            names.push(format!("{}{}", prefix, base));
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_classpath() {
+    use super::super::bytecode::assemble_class;
+    use super::build_zip;
+    use super::jimage::build_image;
+
+    let class = |name: &str, field: bool| assemble_class(&format!(
+        ".class public {}\n.super java/lang/Object\n{}", name, if field { ".field x I\n" } else { "" })).unwrap();
+
+    let dir = ::std::env::temp_dir().join(format!("classpath-{}", ::std::process::id()));
+    fs::create_dir_all(dir.join("demo")).unwrap();
+    fs::write(dir.join("demo/A.class"), class("demo/A", false)).unwrap();
+    fs::write(dir.join("demo/Wrong.class"), class("demo/Other", false)).unwrap();
+
+    let (a, b, versioned_b) = (class("demo/A", true), class("demo/B", false), class("demo/B", true));
+    let c = class("demo/C", false);
+    let jar = build_zip(&[
+        ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\nMulti-Release: true\r\n\r\n"),
+        ("META-INF/versions/11/demo/B.class", &versioned_b),
+        ("module-info.class", b"not checked"),
+        ("demo/A.class", &a),
+        ("demo/B.class", &b),
+        ("demo/C.class", &c),
+        ("demo/C.class", &c)
+    ]);
+    let (thing, other) = (class("java/lang/Thing", false), class("java/lang/Object", false));
+    let image = build_image(&[
+        ("java.base", "java/lang", "Thing", "class", &thing, 0),
+        ("java.base", "java/lang", "Object", "class", &other, 0),
+        ("java.base", "", "module-info", "class", b"", 0)
+    ], &mut Vec::new());
+
+    let mut classpath = Classpath::new();
+    classpath.add_directory(&dir);
+    classpath.add_archive(Archive::parse(&jar).unwrap());
+    classpath.add_image(Image::parse(&image).unwrap());
+    assert_eq!(classpath.len(), 3);
+
+    let found = ["demo/A", "demo/B", "java/lang/Thing", "java/lang/Nothing", "demo/../demo/A", "/demo/A"].iter()
+        .map(|x| classpath.find(x))
+        .collect::<Vec<_>>();
+    assert_eq!(found, [Some(0), Some(1), Some(2), None, None, None]);
+    assert_eq!(&classpath.bytes("java/lang/Thing").unwrap()[..], &thing[..]);
+
+    let first = classpath.class("demo/A").unwrap();
+    assert_eq!(first.get_fields_count(), 0);
+    assert!(::std::ptr::eq(first, classpath.class("demo/A").unwrap()));
+    assert_eq!(classpath.class("demo/B").unwrap().get_fields_count(), 0);
+    assert_eq!(classpath.class("java/lang/Thing").unwrap().get_this_class().unwrap(), "java/lang/Thing");
+    let errors = ["demo/Wrong", "demo/Missing"].iter()
+        .map(|x| classpath.class(x).unwrap_err().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(errors, ["declares class demo/Other in class demo/Wrong of element 0", "not found in class demo/Missing"]);
+
+    let errors = classpath.check().iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(errors, [
+        "declares class demo/Other in class demo/Wrong of element 0",
+        "duplicate entry in class demo/C of element 1",
+        "shadowed by element 0 in class demo/A of element 1"
+    ]);
+
+    // classes loaded before are dropped and loaded again
+    classpath.set_release(11);
+    assert_eq!(classpath.class("demo/B").unwrap().get_fields_count(), 1);
+    assert_eq!(classpath.class("demo/A").unwrap().get_fields_count(), 0);
+    assert_eq!(classpath.class("java/lang/Thing").unwrap().get_this_class().unwrap(), "java/lang/Thing");
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,309 @@
+//! A raw DEFLATE decoder, RFC 1951.
+//!
+//! Decodes canonical Huffman codes a bit at a time, in the manner of
+//! zlib's `puff`. That's plenty for class files and keeps the crate free
+//! of dependencies.
+
+use std::fmt;
+
+/// Longest code in bits
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31,
+    35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2,
+    3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0
+];
+const DIST_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193,
+    257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577
+];
+const DIST_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6,
+    7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13
+];
+
+/// Order code length code lengths are sent in
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+/// Why a DEFLATE stream could not be decoded
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum InflateFault {
+    /// The input ended inside the stream
+    Truncated,
+    /// Block type 3
+    BadBlockType,
+    /// A stored block whose length check failed
+    BadStoredLength,
+    /// Code lengths that don't form a usable Huffman code, or a code
+    /// outside of it
+    BadCode,
+    /// A back reference before the start of the output
+    BadDistance,
+    /// More output than the limit allows
+    TooLong
+}
+impl fmt::Display for InflateFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            InflateFault::Truncated => write!(f, "truncated deflate stream"),
+            InflateFault::BadBlockType => write!(f, "invalid deflate block type"),
+            InflateFault::BadStoredLength => write!(f, "invalid stored block length"),
+            InflateFault::BadCode => write!(f, "invalid Huffman code"),
+            InflateFault::BadDistance => write!(f, "distance too far back"),
+            InflateFault::TooLong => write!(f, "output longer than expected")
+        }
+    }
+}
+
+/// A canonical Huffman code, as code counts per length and symbols in
+/// code order
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>
+}
+impl Huffman {
+
+    /// Build from the code length of each symbol, `0` for unused ones
+    ///
+    /// Incomplete codes are accepted, as a lone distance code is legal.
+    fn new(lengths: &[u8]) -> Result<Huffman, InflateFault> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for &l in lengths {
+            counts[l as usize] += 1;
+        }
+        let mut left = 1i32;
+        for &count in counts.iter().skip(1) {
+            left = (left << 1) - count as i32;
+            if left < 0 {
+                return Err(InflateFault::BadCode);
+            }
+        }
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for len in 1..=MAX_BITS {
+            offsets[len + 1] = offsets[len] + counts[len];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &l) in lengths.iter().enumerate() {
+            if l != 0 {
+                symbols[offsets[l as usize] as usize] = symbol as u16;
+                offsets[l as usize] += 1;
+            }
+        }
+        Ok(Huffman { counts, symbols })
+    }
+}
+
+/// Reads bits least significant first
+struct Bits<'a> {
+    input: &'a [u8],
+    pos: usize,
+    buffer: u32,
+    count: u32
+}
+impl<'a> Bits<'a> {
+
+    fn take(&mut self, n: u32) -> Result<u32, InflateFault> {
+        while self.count < n {
+            let byte = *self.input.get(self.pos).ok_or(InflateFault::Truncated)?;
+            self.pos += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer = self.buffer.checked_shr(n).unwrap_or(0);
+        self.count -= n;
+        Ok(value)
+    }
+
+    fn decode(&mut self, h: &Huffman) -> Result<u16, InflateFault> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+        for len in 1..=MAX_BITS {
+            code |= self.take(1)? as i32;
+            let count = h.counts[len] as i32;
+            if code - first < count {
+                return Ok(h.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(InflateFault::BadCode)
+    }
+
+    /// Skip to the next byte boundary
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+/// Decompress a raw DEFLATE stream, failing once the output would
+/// exceed `limit` bytes
+///
+/// Returns the output and the number of input bytes used.
+pub fn inflate(input: &[u8], limit: usize) -> Result<(Vec<u8>, usize), InflateFault> {
+    let mut out = Vec::with_capacity(limit.min(input.len().saturating_mul(4)));
+    let mut bits = Bits { input, pos: 0, buffer: 0, count: 0 };
+    loop {
+        let last = bits.take(1)? == 1;
+        match bits.take(2)? {
+            0 => stored(&mut bits, &mut out, limit)?,
+            1 => {
+                let (lengths, distances) = fixed_codes();
+                codes(&mut bits, &mut out, limit, &lengths, &distances)?;
+            },
+            2 => {
+                let (lengths, distances) = dynamic_codes(&mut bits)?;
+                codes(&mut bits, &mut out, limit, &lengths, &distances)?;
+            },
+            _ => return Err(InflateFault::BadBlockType)
+        };
+        if last {
+            return Ok((out, bits.pos));
+        }
+    }
+}
+
+fn stored(bits: &mut Bits, out: &mut Vec<u8>, limit: usize) -> Result<(), InflateFault> {
+    bits.align();
+    let header = bits.input.get(bits.pos..bits.pos + 4).ok_or(InflateFault::Truncated)?;
+    let len = u16::from_le_bytes([header[0], header[1]]);
+    if len != !u16::from_le_bytes([header[2], header[3]]) {
+        return Err(InflateFault::BadStoredLength);
+    }
+    bits.pos += 4;
+    let data = bits.input.get(bits.pos..bits.pos + len as usize).ok_or(InflateFault::Truncated)?;
+    if out.len() + data.len() > limit {
+        return Err(InflateFault::TooLong);
+    }
+    out.extend_from_slice(data);
+    bits.pos += len as usize;
+    Ok(())
+}
+
+fn fixed_codes() -> (Huffman, Huffman) {
+    let mut lengths = [0u8; 288];
+    for (symbol, l) in lengths.iter_mut().enumerate() {
+        *l = match symbol {
+            0..=143 => 8,
+            144..=255 => 9,
+            256..=279 => 7,
+            _ => 8
+        };
+    }
+    (Huffman::new(&lengths).unwrap(), Huffman::new(&[5; 30]).unwrap())
+}
+
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), InflateFault> {
+    let nlen = bits.take(5)? as usize + 257;
+    let ndist = bits.take(5)? as usize + 1;
+    let ncode = bits.take(4)? as usize + 4;
+    if nlen > 286 || ndist > 30 {
+        return Err(InflateFault::BadCode);
+    }
+    let mut code_lengths = [0u8; 19];
+    for &i in CODE_LENGTH_ORDER.iter().take(ncode) {
+        code_lengths[i] = bits.take(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths)?;
+
+    let mut lengths = Vec::with_capacity(nlen + ndist);
+    while lengths.len() < nlen + ndist {
+        let symbol = bits.decode(&code)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.last().ok_or(InflateFault::BadCode)?, 3 + bits.take(2)?),
+            17 => (0, 3 + bits.take(3)?),
+            _ => (0, 11 + bits.take(7)?)
+        };
+        if lengths.len() + repeat as usize > nlen + ndist {
+            return Err(InflateFault::BadCode);
+        }
+        lengths.extend((0..repeat).map(|_| value));
+    }
+    if lengths[256] == 0 {
+        // no end of block code
+        return Err(InflateFault::BadCode);
+    }
+    Ok((Huffman::new(&lengths[..nlen])?, Huffman::new(&lengths[nlen..])?))
+}
+
+fn codes(bits: &mut Bits, out: &mut Vec<u8>, limit: usize, lengths: &Huffman, distances: &Huffman) -> Result<(), InflateFault> {
+    loop {
+        let symbol = bits.decode(lengths)? as usize;
+        if symbol < 256 {
+            if out.len() >= limit {
+                return Err(InflateFault::TooLong);
+            }
+            out.push(symbol as u8);
+            continue;
+        }
+        if symbol == 256 {
+            return Ok(());
+        }
+        let i = symbol - 257;
+        if i >= LENGTH_BASE.len() {
+            return Err(InflateFault::BadCode);
+        }
+        let len = LENGTH_BASE[i] as usize + bits.take(LENGTH_EXTRA[i] as u32)? as usize;
+        let d = bits.decode(distances)? as usize;
+        if d >= DIST_BASE.len() {
+            return Err(InflateFault::BadCode);
+        }
+        let dist = DIST_BASE[d] as usize + bits.take(DIST_EXTRA[d] as u32)? as usize;
+        if dist > out.len() {
+            return Err(InflateFault::BadDistance);
+        }
+        if out.len() + len > limit {
+            return Err(InflateFault::TooLong);
+        }
+        let start = out.len() - dist;
+        for k in 0..len {
+            let byte = out[start + k];
+            out.push(byte);
+        }
+    }
+}
+
+const fn crc_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut c = i as u32;
+        let mut k = 0;
+        while k < 8 {
+            c = if c & 1 != 0 { 0xEDB8_8320 ^ (c >> 1) } else { c >> 1 };
+            k += 1;
+        }
+        table[i] = c;
+        i += 1;
+    }
+    table
+}
+
+const CRC_TABLE: [u32; 256] = crc_table();
+
+/// The CRC-32 used by zip
+pub fn crc32(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |c, &b| CRC_TABLE[((c ^ b as u32) & 0xFF) as usize] ^ (c >> 8))
+}
+
+#[test]
+fn test_inflate() {
+    // "hello hello hello!" with fixed codes and a back reference
+    let data = b"\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x8a\x00";
+    let (out, used) = inflate(data, 100).unwrap();
+    assert_eq!((out.as_slice(), used), (&b"hello hello hello!"[..], data.len()));
+    assert_eq!(inflate(data, 10), Err(InflateFault::TooLong));
+    assert_eq!(inflate(&data[..5], 100), Err(InflateFault::Truncated));
+    // one stored block
+    assert_eq!(inflate(b"\x01\x03\x00\xfc\xffabc", 10).unwrap().0, b"abc");
+    assert_eq!(inflate(b"\x01\x03\x00\xfc\xfeabc", 10), Err(InflateFault::BadStoredLength));
+    assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+}
@@ -0,0 +1,687 @@
+//! Reading the JDK runtime image, `lib/modules`.
+//!
+//! Since JDK 9 the platform classes live in a jimage container rather
+//! than `rt.jar`. The file starts with an index: a header, a perfect hash
+//! table of resource names split into redirect and offsets tables, the
+//! location attributes of every resource and a table of strings. Resource
+//! content follows the index.
+//!
+//! Besides the resources of each module the image describes its own
+//! directory tree with `/modules/...` and `/packages/...` entries.
+//!
+//! Resources may be compressed by `jlink --compress`, either with zlib or
+//! by sharing constant pool strings with the strings table. Both are
+//! undone by `Image::read`.
+
+use super::super::clazz::{Class, Fault, decode_modified_utf8};
+use super::inflate::{InflateFault, inflate};
+
+use std::borrow::Cow;
+use std::collections::BTreeSet;
+use std::fmt;
+
+const MAGIC: u32 = 0xCAFE_DADA;
+const MAJOR_VERSION: u16 = 1;
+const HEADER_SIZE: usize = 28;
+
+const COMPRESSED_MAGIC: u32 = 0xCAFE_FAFA;
+const COMPRESSED_HEADER_SIZE: usize = 29;
+
+/// Seed of the name hash, also its multiplier
+const HASH_SEED: i32 = 0x0100_0193;
+
+/// Location attribute kinds
+const ATTRIBUTE_END: u8 = 0;
+const ATTRIBUTE_MODULE: u8 = 1;
+const ATTRIBUTE_PARENT: u8 = 2;
+const ATTRIBUTE_BASE: u8 = 3;
+const ATTRIBUTE_EXTENSION: u8 = 4;
+const ATTRIBUTE_OFFSET: u8 = 5;
+const ATTRIBUTE_COMPRESSED: u8 = 6;
+const ATTRIBUTE_UNCOMPRESSED: u8 = 7;
+
+/// Constant pool tags the string sharing compressor substitutes for
+/// `Utf8` constants
+const EXTERNALIZED_STRING: u8 = 23;
+const EXTERNALIZED_STRING_DESCRIPTOR: u8 = 25;
+
+/// Why an image or resource could not be read
+#[derive(Clone,Debug)]
+pub enum ImageFault {
+    /// The magic number is wrong
+    NotAnImage,
+    /// Major and minor version of an image format this doesn't know
+    UnsupportedVersion(u16, u16),
+    /// The index doesn't fit in the file
+    BadHeader,
+    /// Location attributes that are malformed or point outside the image
+    BadLocation,
+    /// A string that doesn't end or isn't modified UTF-8
+    BadString,
+    /// Compressed by a decompressor other than `zip` or `compact-cp`
+    UnsupportedCompression(String),
+    /// A compressed resource whose header or content is malformed
+    BadCompression,
+    Inflate(InflateFault),
+    /// The content doesn't match the size in the location
+    BadSize,
+    /// The resource is not a valid class file
+    Class(Fault)
+}
+impl fmt::Display for ImageFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ImageFault::NotAnImage => write!(f, "not a jimage file"),
+            ImageFault::UnsupportedVersion(major, minor) => write!(f, "unsupported jimage version {}.{}", major, minor),
+            ImageFault::BadHeader => write!(f, "malformed header"),
+            ImageFault::BadLocation => write!(f, "malformed location"),
+            ImageFault::BadString => write!(f, "malformed string"),
+            ImageFault::UnsupportedCompression(ref x) => write!(f, "unsupported decompressor {}", x),
+            ImageFault::BadCompression => write!(f, "malformed compressed resource"),
+            ImageFault::Inflate(x) => write!(f, "{}", x),
+            ImageFault::BadSize => write!(f, "size mismatch"),
+            ImageFault::Class(x) => write!(f, "malformed class: {:?}", x)
+        }
+    }
+}
+
+/// A failure to read the image as a whole
+#[derive(Clone,Debug)]
+pub struct ImageError {
+    /// Offset of the offending structure in the image
+    pub offset: usize,
+    pub reason: ImageFault
+}
+impl fmt::Display for ImageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.reason, self.offset)
+    }
+}
+
+/// A failure to read one resource
+#[derive(Clone,Debug)]
+pub struct ResourceError {
+    /// Full name of the resource
+    pub name: String,
+    pub reason: ImageFault
+}
+impl fmt::Display for ResourceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in resource {}", self.reason, self.name)
+    }
+}
+
+/// A resource as described by its location attributes
+///
+/// `/java.base/java/lang/Object.class` has module `java.base`, parent
+/// `java/lang`, base `Object` and extension `class`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Resource {
+    pub module: String,
+    pub parent: String,
+    pub base: String,
+    pub extension: String,
+    /// Offset of the content, relative to the end of the index
+    pub offset: u64,
+    /// Size as stored, `0` when the resource isn't compressed
+    pub compressed_size: u64,
+    pub size: u64
+}
+impl Resource {
+
+    /// The full name the resource is looked up by
+    pub fn name(&self) -> String {
+        let mut name = String::new();
+        if !self.module.is_empty() {
+            name.push('/');
+            name.push_str(&self.module);
+            name.push('/');
+        }
+        if !self.parent.is_empty() {
+            name.push_str(&self.parent);
+            name.push('/');
+        }
+        name.push_str(&self.base);
+        if !self.extension.is_empty() {
+            name.push('.');
+            name.push_str(&self.extension);
+        }
+        name
+    }
+
+    /// Is this one of the `/modules/...` or `/packages/...` entries
+    /// describing the directory tree
+    pub fn is_directory(&self) -> bool {
+        self.module == "modules" || self.module == "packages"
+    }
+
+    pub fn is_class(&self) -> bool {
+        !self.is_directory() && self.extension == "class"
+    }
+}
+
+/// A jimage file held in memory
+#[derive(Clone,Debug)]
+pub struct Image<'a> {
+    data: &'a [u8],
+    /// Written in the byte order of the machine that made it
+    big_endian: bool,
+    redirect: &'a [u8],
+    strings: &'a [u8],
+    /// Where resource content starts
+    index_size: usize,
+    /// In offsets table order, which the redirect table indexes
+    resources: Vec<Resource>
+}
+
+impl<'a> Image<'a> {
+
+    /// Read the index of the image in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Image<'a>, ImageError> {
+        let err = |offset, reason| ImageError { offset, reason };
+        let header = data.get(..HEADER_SIZE).ok_or(err(0, ImageFault::NotAnImage))?;
+        let big_endian = match (read_u32(header, false), read_u32(header, true)) {
+            (MAGIC, _) => false,
+            (_, MAGIC) => true,
+            _ => return Err(err(0, ImageFault::NotAnImage))
+        };
+        let word = |at: usize| read_u32(&header[at..], big_endian) as usize;
+        let (major, minor) = ((word(4) >> 16) as u16, word(4) as u16);
+        if major != MAJOR_VERSION {
+            return Err(err(4, ImageFault::UnsupportedVersion(major, minor)));
+        }
+        let (table_length, locations_size, strings_size) = (word(16), word(20), word(24));
+
+        let mut pos = HEADER_SIZE;
+        let mut table = |len: usize| {
+            let start = pos;
+            pos = pos.checked_add(len)?;
+            data.get(start..pos)
+        };
+        let tables = table_length.checked_mul(4)
+            .and_then(|len| Some((table(len)?, table(len)?, table(locations_size)?, table(strings_size)?)));
+        let (redirect, offsets, locations, strings) = tables.ok_or(err(HEADER_SIZE, ImageFault::BadHeader))?;
+
+        let mut image = Image {
+            data,
+            big_endian,
+            redirect,
+            strings,
+            index_size: pos,
+            resources: Vec::with_capacity(table_length)
+        };
+        for i in 0..table_length {
+            let offset = read_u32(&offsets[i * 4..], big_endian) as usize;
+            let resource = locations.get(offset..)
+                .and_then(|x| image.location(x))
+                .ok_or(err(HEADER_SIZE + table_length * 8 + offset, ImageFault::BadLocation))?;
+            image.resources.push(resource);
+        }
+        Ok(image)
+    }
+
+    /// Decode the attributes of one location
+    fn location(&self, mut data: &[u8]) -> Option<Resource> {
+        let mut attributes = [0u64; 8];
+        loop {
+            let byte = *data.first()?;
+            let kind = byte >> 3;
+            if kind == ATTRIBUTE_END {
+                break;
+            }
+            let len = (byte & 7) as usize + 1;
+            let value = data.get(1..1 + len)?;
+            *attributes.get_mut(kind as usize)? = value.iter().fold(0, |acc, &b| acc << 8 | b as u64);
+            data = &data[1 + len..];
+        }
+        let string = |kind: u8| self.string(attributes[kind as usize] as usize).map(|x| x.into_owned());
+        Some(Resource {
+            module: string(ATTRIBUTE_MODULE)?,
+            parent: string(ATTRIBUTE_PARENT)?,
+            base: string(ATTRIBUTE_BASE)?,
+            extension: string(ATTRIBUTE_EXTENSION)?,
+            offset: attributes[ATTRIBUTE_OFFSET as usize],
+            compressed_size: attributes[ATTRIBUTE_COMPRESSED as usize],
+            size: attributes[ATTRIBUTE_UNCOMPRESSED as usize]
+        })
+    }
+
+    /// The raw bytes of the string at `offset` of the strings table
+    fn string_bytes(&self, offset: usize) -> Option<&'a [u8]> {
+        let rest = self.strings.get(offset..)?;
+        rest.iter().position(|&b| b == 0).map(|end| &rest[..end])
+    }
+
+    fn string(&self, offset: usize) -> Option<Cow<'a, str>> {
+        decode_modified_utf8(self.string_bytes(offset)?).ok()
+    }
+
+    /// Every resource, including the directory entries
+    pub fn resources(&self) -> &[Resource] {
+        &self.resources
+    }
+
+    /// Look a resource up by its full name through the hash table
+    pub fn find(&self, name: &str) -> Option<&Resource> {
+        let len = self.resources.len() as i32;
+        if len == 0 {
+            return None;
+        }
+        let index = hash(name, HASH_SEED) % len;
+        let redirect = read_u32(&self.redirect[index as usize * 4..], self.big_endian) as i32;
+        let index = match redirect {
+            0 => return None,
+            x if x < 0 => -1 - x,
+            x => hash(name, x) % len
+        };
+        self.resources.get(index as usize).filter(|r| r.name() == name)
+    }
+
+    /// Names of the modules in the image, sorted
+    pub fn modules(&self) -> Vec<&str> {
+        let modules = self.resources.iter()
+            .filter(|r| !r.is_directory() && !r.module.is_empty())
+            .map(|r| r.module.as_str())
+            .collect::<BTreeSet<_>>();
+        modules.into_iter().collect()
+    }
+
+    /// Packages of a module as internal names, sorted
+    ///
+    /// Any directory holding a resource is a package, apart from those
+    /// under `META-INF`.
+    pub fn packages(&self, module: &str) -> Vec<&str> {
+        let packages = self.resources.iter()
+            .filter(|r| r.module == module && !r.parent.is_empty())
+            .filter(|r| r.parent != "META-INF" && !r.parent.starts_with("META-INF/"))
+            .map(|r| r.parent.as_str())
+            .collect::<BTreeSet<_>>();
+        packages.into_iter().collect()
+    }
+
+    /// The uncompressed content of a resource, borrowed when it is
+    /// stored as is
+    pub fn read(&self, resource: &Resource) -> Result<Cow<'a, [u8]>, ResourceError> {
+        let err = |reason| ResourceError { name: resource.name(), reason };
+        let stored = match resource.compressed_size {
+            0 => resource.size,
+            x => x
+        };
+        let data = (self.index_size as u64).checked_add(resource.offset)
+            .and_then(|start| Some((start, start.checked_add(stored)?)))
+            .filter(|&(_, end)| end <= self.data.len() as u64)
+            .map(|(start, end)| &self.data[start as usize..end as usize])
+            .ok_or(err(ImageFault::BadLocation))?;
+        let mut data = Cow::Borrowed(data);
+        if resource.compressed_size != 0 {
+            // compressors may be stacked, each adds a header
+            while data.len() >= 4 && read_u32(&data, self.big_endian) == COMPRESSED_MAGIC {
+                data = Cow::Owned(self.decompress(&data, resource.size).map_err(err)?);
+            }
+        }
+        if data.len() as u64 != resource.size {
+            return Err(err(ImageFault::BadSize));
+        }
+        Ok(data)
+    }
+
+    /// Undo one layer of compression of a resource of `expected` bytes
+    fn decompress(&self, data: &[u8], expected: u64) -> Result<Vec<u8>, ImageFault> {
+        let header = data.get(..COMPRESSED_HEADER_SIZE).ok_or(ImageFault::BadCompression)?;
+        let wide = |at: usize| {
+            let (low, high) = (read_u32(&header[at..], self.big_endian), read_u32(&header[at + 4..], self.big_endian));
+            match self.big_endian {
+                true => (low as u64) << 32 | high as u64,
+                false => (high as u64) << 32 | low as u64
+            }
+        };
+        let (compressed, size) = (wide(4), wide(12));
+        let content = data.get(COMPRESSED_HEADER_SIZE..)
+            .filter(|x| x.len() as u64 == compressed)
+            .ok_or(ImageFault::BadCompression)?;
+        let limit = if size > usize::MAX as u64 { return Err(ImageFault::BadSize) } else { size as usize };
+        let decompressor = self.string(read_u32(&header[20..], self.big_endian) as usize).ok_or(ImageFault::BadString)?;
+        let out = match &*decompressor {
+            "zip" => {
+                // a zlib stream, without a preset dictionary
+                match content {
+                    [method, flags, ..] if method & 0x0F == 8 && flags & 0x20 == 0 => {
+                        inflate(&content[2..], limit).map(|x| x.0).map_err(ImageFault::Inflate)
+                    },
+                    _ => Err(ImageFault::BadCompression)
+                }
+            },
+            "compact-cp" => self.expand_strings(content, limit).ok_or(ImageFault::BadCompression),
+            _ => Err(ImageFault::UnsupportedCompression(decompressor.into_owned()))
+        }?;
+        // only the last layer gives the resource itself
+        let last = out.len() < 4 || read_u32(&out, self.big_endian) != COMPRESSED_MAGIC;
+        if last && size != expected {
+            return Err(ImageFault::BadSize);
+        }
+        Ok(out)
+    }
+
+    /// Rebuild a class whose `Utf8` constants were replaced by references
+    /// to the strings table
+    fn expand_strings(&self, data: &[u8], size: usize) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(size.min(data.len().saturating_mul(4)));
+        out.extend_from_slice(data.get(..10)?);
+        let count = u16::from_be_bytes([data[8], data[9]]);
+        let mut pos = 10;
+        let mut i = 1;
+        while i < count {
+            let tag = *data.get(pos)?;
+            pos += 1;
+            let utf8 = match tag {
+                EXTERNALIZED_STRING => {
+                    let index = compressed_int(data, &mut pos)?;
+                    Cow::Borrowed(self.string_bytes(index as usize)?)
+                },
+                EXTERNALIZED_STRING_DESCRIPTOR => {
+                    let descriptor = self.string_bytes(compressed_int(data, &mut pos)? as usize)?;
+                    let len = compressed_int(data, &mut pos)? as usize;
+                    let mut flow = data.get(pos..pos.checked_add(len)?)?;
+                    pos += len;
+                    let mut names = Vec::new();
+                    while !flow.is_empty() {
+                        let mut at = 0;
+                        names.push(compressed_int(flow, &mut at)?);
+                        flow = &flow[at..];
+                    }
+                    // each class name in the descriptor is a package and
+                    // a simple name
+                    let mut names = names.into_iter();
+                    let mut expanded = Vec::with_capacity(descriptor.len() * 2);
+                    for &b in descriptor {
+                        expanded.push(b);
+                        if b == b'L' {
+                            let package = self.string_bytes(names.next()? as usize)?;
+                            if !package.is_empty() {
+                                expanded.extend_from_slice(package);
+                                expanded.push(b'/');
+                            }
+                            expanded.extend_from_slice(self.string_bytes(names.next()? as usize)?);
+                        }
+                    }
+                    Cow::Owned(expanded)
+                },
+                1 => {
+                    let len = u16::from_be_bytes([*data.get(pos)?, *data.get(pos + 1)?]) as usize;
+                    let utf8 = data.get(pos + 2..pos + 2 + len)?;
+                    pos += 2 + len;
+                    Cow::Borrowed(utf8)
+                },
+                _ => {
+                    let len = constant_size(tag)?;
+                    out.push(tag);
+                    out.extend_from_slice(data.get(pos..pos + len)?);
+                    pos += len;
+                    // long and double take two entries
+                    i += if tag == 5 || tag == 6 { 2 } else { 1 };
+                    continue;
+                }
+            };
+            if utf8.len() > u16::MAX as usize {
+                return None;
+            }
+            out.push(1);
+            out.extend_from_slice(&(utf8.len() as u16).to_be_bytes());
+            out.extend_from_slice(&utf8);
+            i += 1;
+        }
+        out.extend_from_slice(&data[pos..]);
+        Some(out)
+    }
+
+    /// Read and parse every class resource in turn, borrowed for the call
+    /// of `f` only, as with `Archive::for_each_class`
+    pub fn for_each_class<F>(&self, mut f: F)
+        where F: FnMut(&Resource, Result<Class, ResourceError>)
+    {
+        for resource in self.resources.iter().filter(|r| r.is_class()) {
+            match self.read(resource) {
+                Ok(data) => f(resource, Class::parse(&data).map_err(|e| ResourceError {
+                    name: resource.name(),
+                    reason: ImageFault::Class(e)
+                })),
+                Err(e) => f(resource, Err(e))
+            };
+        }
+    }
+}
+
+fn read_u32(data: &[u8], big_endian: bool) -> u32 {
+    let bytes = [data[0], data[1], data[2], data[3]];
+    match big_endian {
+        true => u32::from_be_bytes(bytes),
+        false => u32::from_le_bytes(bytes)
+    }
+}
+
+/// The hash of a resource name in the redirect table
+fn hash(name: &str, seed: i32) -> i32 {
+    name.bytes().fold(seed, |h, b| h.wrapping_mul(HASH_SEED) ^ b as i32) & 0x7FFF_FFFF
+}
+
+/// An index of the string sharing compressor, one to four bytes
+fn compressed_int(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let header = *data.get(*pos)?;
+    let (len, first) = match header & 0x80 {
+        0 => (4, header as u32),
+        _ => (((header >> 5) & 3).max(1) as usize, (header & 0x1F) as u32)
+    };
+    let rest = data.get(*pos + 1..*pos + len)?;
+    *pos += len;
+    Some(rest.iter().fold(first, |acc, &b| acc << 8 | b as u32))
+}
+
+/// Size of a constant pool entry after its tag, other than `Utf8`
+fn constant_size(tag: u8) -> Option<usize> {
+    match tag {
+        7 | 8 | 16 | 19 | 20 => Some(2),
+        15 => Some(3),
+        3 | 4 | 9 | 10 | 11 | 12 | 17 | 18 => Some(4),
+        5 | 6 => Some(8),
+        _ => None
+    }
+}
+
+/// Module, parent, base, extension, stored content and uncompressed size,
+/// `0` when not compressed
+#[cfg(test)]
+pub type TestResource<'a> = (&'a str, &'a str, &'a str, &'a str, &'a [u8], u64);
+
+#[cfg(test)]
+pub fn build_image(resources: &[TestResource], strings: &mut Vec<u8>) -> Vec<u8> {
+    use std::collections::HashMap;
+    let mut interned = HashMap::new();
+    let mut intern = |s: &str, strings: &mut Vec<u8>| *interned.entry(s.to_string()).or_insert_with(|| {
+        let offset = strings.len() as u64;
+        strings.extend_from_slice(s.as_bytes());
+        strings.push(0);
+        offset
+    });
+    let pending = strings.clone();
+    strings.clear();
+    intern("", strings);
+    strings.extend_from_slice(&pending);
+
+    // place names the way the image writer does: colliding buckets get a
+    // seed, the rest point straight at a free slot
+    let len = resources.len();
+    let names = resources.iter().map(|r| Resource {
+        module: r.0.to_string(), parent: r.1.to_string(), base: r.2.to_string(), extension: r.3.to_string(),
+        offset: 0, compressed_size: 0, size: 0
+    }.name()).collect::<Vec<_>>();
+    let mut buckets = vec![Vec::new(); len];
+    for (i, name) in names.iter().enumerate() {
+        buckets[(hash(name, HASH_SEED) % len as i32) as usize].push(i);
+    }
+    let mut redirect = vec![0i32; len];
+    let mut slots = vec![None; len];
+    let mut order = (0..len).collect::<Vec<_>>();
+    order.sort_by_key(|&b| !buckets[b].len());
+    for &b in order.iter().filter(|&&b| buckets[b].len() > 1) {
+        let seed = (1..1000).find(|&seed| {
+            let wanted = buckets[b].iter().map(|&i| hash(&names[i], seed) as usize % len).collect::<BTreeSet<_>>();
+            wanted.len() == buckets[b].len() && wanted.iter().all(|&s| slots[s].is_none())
+        }).unwrap();
+        for &i in &buckets[b] {
+            slots[hash(&names[i], seed) as usize % len] = Some(i);
+        }
+        redirect[b] = seed;
+    }
+    for &b in order.iter().filter(|&&b| buckets[b].len() == 1) {
+        let free = slots.iter().position(|s| s.is_none()).unwrap();
+        slots[free] = Some(buckets[b][0]);
+        redirect[b] = -1 - free as i32;
+    }
+
+    let (mut offsets, mut locations, mut content) = (Vec::new(), Vec::new(), Vec::new());
+    for slot in slots {
+        let r = resources[slot.unwrap()];
+        offsets.extend_from_slice(&(locations.len() as u32).to_le_bytes());
+        let attributes = [
+            (ATTRIBUTE_MODULE, intern(r.0, strings)),
+            (ATTRIBUTE_PARENT, intern(r.1, strings)),
+            (ATTRIBUTE_BASE, intern(r.2, strings)),
+            (ATTRIBUTE_EXTENSION, intern(r.3, strings)),
+            (ATTRIBUTE_OFFSET, content.len() as u64),
+            (ATTRIBUTE_COMPRESSED, if r.5 == 0 { 0 } else { r.4.len() as u64 }),
+            (ATTRIBUTE_UNCOMPRESSED, if r.5 == 0 { r.4.len() as u64 } else { r.5 })
+        ];
+        for &(kind, value) in attributes.iter() {
+            let bytes = value.to_be_bytes();
+            let skip = bytes.iter().take(7).take_while(|&&b| b == 0).count();
+            locations.push(kind << 3 | (7 - skip) as u8);
+            locations.extend_from_slice(&bytes[skip..]);
+        }
+        locations.push(ATTRIBUTE_END);
+        content.extend_from_slice(r.4);
+    }
+
+    let mut out = Vec::new();
+    for &x in [MAGIC, 1 << 16, 0, len as u32, len as u32, locations.len() as u32, strings.len() as u32].iter() {
+        out.extend_from_slice(&x.to_le_bytes());
+    }
+    for x in redirect {
+        out.extend_from_slice(&x.to_le_bytes());
+    }
+    out.extend_from_slice(&offsets);
+    out.extend_from_slice(&locations);
+    out.extend_from_slice(strings);
+    out.extend_from_slice(&content);
+    out
+}
+
+#[test]
+fn test_image() {
+    use super::super::bytecode::assemble_class;
+    let class = assemble_class("\
+.class public java/lang/Thing
+.super java/lang/Object
+.field static x Ljava/lang/Object;
+").unwrap();
+
+    // zlib with a stored block
+    let mut zipped = b"\x78\x01\x01".to_vec();
+    zipped.extend_from_slice(&(class.len() as u16).to_le_bytes());
+    zipped.extend_from_slice(&(!(class.len() as u16)).to_le_bytes());
+    zipped.extend_from_slice(&class);
+    zipped.extend_from_slice(&[0; 4]);
+
+    // the same class with its names and descriptor in the strings table
+    let mut strings = b"zip\0compact-cp\0java/lang/Thing\0java/lang/Object\0L;\0java/lang\0Object\0".to_vec();
+    let index = |s: &str| {
+        let table = [&b"\0"[..], &strings].concat();
+        let at = table.windows(s.len() + 2).position(|w| w == [b"\0", s.as_bytes(), b"\0"].concat()).unwrap() + 1;
+        [0xC0 | (at >> 8) as u8, at as u8]
+    };
+    let mut shared = class[..10].to_vec();
+    let mut pos = 10;
+    for _ in 1..u16::from_be_bytes([class[8], class[9]]) {
+        let tag = class[pos];
+        let len = match tag {
+            1 => 2 + u16::from_be_bytes([class[pos + 1], class[pos + 2]]) as usize,
+            x => constant_size(x).unwrap()
+        };
+        let constant = &class[pos..pos + 1 + len];
+        pos += 1 + len;
+        let utf8 = match tag {
+            1 => std::str::from_utf8(&constant[3..]).unwrap(),
+            _ => ""
+        };
+        if utf8 == "java/lang/Thing" || utf8 == "java/lang/Object" {
+            shared.push(EXTERNALIZED_STRING);
+            shared.extend_from_slice(&index(utf8));
+        } else if utf8 == "Ljava/lang/Object;" {
+            shared.push(EXTERNALIZED_STRING_DESCRIPTOR);
+            shared.extend_from_slice(&index("L;"));
+            shared.push(0x80 | 0x20 | 4);
+            shared.extend_from_slice(&index("java/lang"));
+            shared.extend_from_slice(&index("Object"));
+        } else {
+            shared.extend_from_slice(constant);
+        }
+    }
+    shared.extend_from_slice(&class[pos..]);
+    assert!(shared.len() < class.len() - 40);
+
+    let compressed = |name: u32, content: &[u8], size: u64| {
+        let mut out = COMPRESSED_MAGIC.to_le_bytes().to_vec();
+        out.extend_from_slice(&(content.len() as u64).to_le_bytes());
+        out.extend_from_slice(&size.to_le_bytes());
+        out.extend_from_slice(&name.to_le_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0, 1]);
+        out.extend_from_slice(content);
+        out
+    };
+    let size = class.len() as u64;
+    let (zip_resource, shared_resource) = (compressed(1, &zipped, size), compressed(5, &shared, size));
+    // a header claiming far more than the resource holds
+    let huge_resource = compressed(5, &shared, 1 << 40);
+    let data = build_image(&[
+        ("java.base", "java/lang", "Thing", "class", &class, 0),
+        ("java.base", "java/lang", "Zipped", "class", &zip_resource, size),
+        ("java.base", "java/lang", "Shared", "class", &shared_resource, size),
+        ("java.base", "java/lang", "Broken", "class", &zip_resource[..40], size),
+        ("java.base", "java/lang", "Huge", "class", &huge_resource, size),
+        ("java.base", "META-INF", "NOTICE", "", b"notice", 0),
+        ("java.sql", "java/sql", "table", "properties", b"a=b", 0),
+        ("java.sql", "", "module-info", "class", b"\xca\xfe\xba\xbe", 0),
+        ("modules", "", "java.base/java", "", b"", 0),
+        ("packages", "", "java.lang", "", b"", 0)
+    ], &mut strings);
+
+    let image = Image::parse(&data).unwrap();
+    assert_eq!(image.resources().len(), 10);
+    assert_eq!(image.modules(), ["java.base", "java.sql"]);
+    assert_eq!(image.packages("java.base"), ["java/lang"]);
+    for r in image.resources() {
+        assert_eq!(image.find(&r.name()), Some(r));
+    }
+    assert_eq!(image.find("/java.base/java/lang/Missing.class"), None);
+    let notice = image.find("/java.base/META-INF/NOTICE").unwrap();
+    assert_eq!(&image.read(notice).unwrap()[..], b"notice");
+
+    let mut seen = Vec::new();
+    image.for_each_class(|r, c| seen.push(match c {
+        Ok(c) => format!("{} {}", r.base, c.get_super_class().unwrap()),
+        Err(e) => format!("{}", e)
+    }));
+    seen.sort();
+    assert_eq!(seen, [
+        "Shared java/lang/Object",
+        "Thing java/lang/Object",
+        "Zipped java/lang/Object",
+        "malformed class: ParseError in resource /java.sql/module-info.class",
+        "malformed compressed resource in resource /java.base/java/lang/Broken.class",
+        "size mismatch in resource /java.base/java/lang/Huge.class"
+    ]);
+    let shared = image.find("/java.base/java/lang/Shared.class").unwrap();
+    assert_eq!(&image.read(shared).unwrap()[..], &class[..]);
+
+    assert!(matches!(Image::parse(&data[..40]), Err(ImageError { reason: ImageFault::BadHeader, .. })));
+    assert!(matches!(Image::parse(&class), Err(ImageError { reason: ImageFault::NotAnImage, .. })));
+}
@@ -0,0 +1,195 @@
+//! Reading JMOD files, the packaged modules `jlink` links from.
+//!
+//! A JMOD is a zip behind a four byte header, `JM` and a major and minor
+//! version. Entries are grouped by their top directory into sections:
+//! classes and resources under `classes/`, configuration, native
+//! libraries and so on.
+
+use super::super::clazz::{Class, Fault, ModuleAttribute, PoolMembers, find_attribute, read_module};
+use super::{Archive, ArchiveError, ArchiveFault, Entry, EntryError, parse_entry};
+
+use std::borrow::Cow;
+
+const MAGIC: [u8; 2] = *b"JM";
+const MAJOR_VERSION: u8 = 1;
+const HEADER_SIZE: usize = 4;
+
+const MODULE_INFO: &str = "classes/module-info.class";
+
+/// A part of a JMOD, by top directory
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Section {
+    /// Classes and resources of the module, `classes/`
+    Classes,
+    /// Configuration files, `conf/`
+    Config,
+    /// C headers, `include/`
+    HeaderFiles,
+    /// License files, `legal/`
+    LegalNotices,
+    /// `man/`
+    ManPages,
+    /// Shared libraries, `lib/`
+    NativeLibs,
+    /// Executables, `bin/`
+    NativeCmds
+}
+impl Section {
+
+    pub fn all() -> [Section; 7] {
+        [Section::Classes, Section::Config, Section::HeaderFiles, Section::LegalNotices,
+         Section::ManPages, Section::NativeLibs, Section::NativeCmds]
+    }
+
+    /// The directory holding the section, with its trailing `/`
+    pub fn directory(self) -> &'static str {
+        match self {
+            Section::Classes => "classes/",
+            Section::Config => "conf/",
+            Section::HeaderFiles => "include/",
+            Section::LegalNotices => "legal/",
+            Section::ManPages => "man/",
+            Section::NativeLibs => "lib/",
+            Section::NativeCmds => "bin/"
+        }
+    }
+
+    /// Split an entry name into its section and the path within it
+    pub fn of(name: &str) -> Option<(Section, &str)> {
+        Section::all().iter()
+            .find(|s| name.starts_with(s.directory()))
+            .map(|&s| (s, &name[s.directory().len()..]))
+    }
+}
+
+/// A JMOD file held in memory
+#[derive(Clone,Debug)]
+pub struct Jmod<'a> {
+    archive: Archive<'a>,
+    version: (u8, u8)
+}
+
+impl<'a> Jmod<'a> {
+
+    /// Check the header and read the central directory
+    pub fn parse(data: &'a [u8]) -> Result<Jmod<'a>, ArchiveError> {
+        let err = |reason| ArchiveError { offset: 0, reason };
+        let version = match data.get(..HEADER_SIZE) {
+            Option::Some(&[a, b, major, minor]) if [a, b] == MAGIC => (major, minor),
+            _ => return Err(err(ArchiveFault::NotAJmod))
+        };
+        if version.0 != MAJOR_VERSION {
+            return Err(err(ArchiveFault::UnsupportedVersion(version.0, version.1)));
+        }
+        // the zip doesn't count the header in its offsets, as with any
+        // other prefix
+        let archive = Archive::parse(data)?;
+        Ok(Jmod { archive, version })
+    }
+
+    /// Major and minor version from the header
+    pub fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    /// The zip behind the header
+    pub fn archive(&self) -> &Archive<'a> {
+        &self.archive
+    }
+
+    /// Files of a section, with their path inside it
+    pub fn section(&self, section: Section) -> impl Iterator<Item = (&str, &Entry)> + '_ {
+        self.archive.entries().iter()
+            .filter(|e| !e.is_dir())
+            .filter_map(move |e| match Section::of(&e.name) {
+                Option::Some((s, path)) if s == section => Some((path, e)),
+                _ => None
+            })
+    }
+
+    /// The bytes of `module-info.class`
+    pub fn module_info(&self) -> Result<Cow<'a, [u8]>, EntryError> {
+        match self.archive.by_name(MODULE_INFO) {
+            Option::Some(entry) => self.archive.read(entry),
+            Option::None => Err(EntryError { name: MODULE_INFO.to_string(), reason: ArchiveFault::Missing })
+        }
+    }
+
+    /// The name of the module and its `Module` attribute
+    ///
+    /// Indices in the attribute refer to the constant pool of
+    /// `module_info`.
+    pub fn module(&self) -> Result<(String, ModuleAttribute), EntryError> {
+        let err = |reason| EntryError { name: MODULE_INFO.to_string(), reason };
+        let data = self.module_info()?;
+        let class = Class::parse(&data).map_err(|e| err(ArchiveFault::Class(e)))?;
+        let pool = class.get_constants();
+        let module = find_attribute(class.get_attributes(), pool, "Module")
+            .and_then(|attr| read_module(attr.data))
+            .ok_or(err(ArchiveFault::Class(Fault::AttributeParseError)))?;
+        let name = match pool.get(module.name) {
+            Option::Some(&PoolMembers::Module(x)) => pool.get_utf8(x),
+            _ => None
+        };
+        let name = name.ok_or(err(ArchiveFault::Class(Fault::UTF8LookupFailure)))?.into_owned();
+        Ok((name, module))
+    }
+
+    /// Read and parse every class of the `classes` section in turn,
+    /// `module-info` included
+    pub fn for_each_class<F>(&self, mut f: F)
+        where F: FnMut(&Entry, Result<Class, EntryError>)
+    {
+        for (_, entry) in self.section(Section::Classes).filter(|x| x.1.is_class()) {
+            match self.archive.read(entry) {
+                Ok(data) => f(entry, parse_entry(entry, &data)),
+                Err(e) => f(entry, Err(e))
+            };
+        }
+    }
+}
+
+#[test]
+fn test_jmod() {
+    use super::build_zip;
+
+    // module demo { requires java.base; exports demo.api; }
+    let module_info = b"\xca\xfe\xba\xbe\x00\x00\x00\x35\x00\x0a\
+        \x01\x00\x0bmodule-info\x07\x00\x01\x01\x00\x06Module\
+        \x01\x00\x04demo\x13\x00\x04\x01\x00\x09java.base\x13\x00\x06\
+        \x01\x00\x08demo/api\x14\x00\x08\
+        \x80\x00\x00\x02\x00\x00\x00\x00\x00\x00\x00\x00\x00\x01\
+        \x00\x03\x00\x00\x00\x1c\
+        \x00\x05\x00\x00\x00\x00\x00\x01\x00\x07\x80\x00\x00\x00\
+        \x00\x01\x00\x09\x00\x00\x00\x00\x00\x00\x00\x00\x00\x00";
+    let out = build_zip(&[
+        ("classes/module-info.class", module_info),
+        ("classes/demo/api/Api.class", b"not a class"),
+        ("classes/demo/api/messages.properties", b"hello=world"),
+        ("conf/demo.conf", b"x=1"),
+        ("legal/LICENSE", b"free")
+    ]);
+    let data = [&b"JM\x01\x00"[..], &out].concat();
+
+    let jmod = Jmod::parse(&data).unwrap();
+    assert_eq!(jmod.version(), (1, 0));
+    let classes = jmod.section(Section::Classes).map(|x| x.0).collect::<Vec<_>>();
+    assert_eq!(classes, ["module-info.class", "demo/api/Api.class", "demo/api/messages.properties"]);
+    assert_eq!(jmod.section(Section::Config).map(|x| x.0).collect::<Vec<_>>(), ["demo.conf"]);
+    assert_eq!(jmod.section(Section::NativeLibs).count(), 0);
+
+    let (name, module) = jmod.module().unwrap();
+    assert_eq!(name, "demo");
+    assert_eq!((module.requires.len(), module.exports.len()), (1, 1));
+
+    let mut seen = Vec::new();
+    jmod.for_each_class(|entry, class| seen.push(match class {
+        Ok(c) => c.get_this_class().unwrap().into_owned(),
+        Err(e) => format!("{} {}", entry.name, e)
+    }));
+    assert_eq!(seen, ["module-info", "classes/demo/api/Api.class malformed class: ParseError in entry classes/demo/api/Api.class"]);
+
+    assert!(matches!(Jmod::parse(&out), Err(ArchiveError { reason: ArchiveFault::NotAJmod, .. })));
+    let future = [&b"JM\x02\x00"[..], &out].concat();
+    assert!(matches!(Jmod::parse(&future), Err(ArchiveError { reason: ArchiveFault::UnsupportedVersion(2, 0), .. })));
+}
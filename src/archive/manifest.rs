@@ -0,0 +1,523 @@
+//! Jar manifests, `META-INF/MANIFEST.MF`.
+//!
+//! A manifest is a main section followed by per-entry sections, each
+//! beginning with a `Name` attribute. Sections are separated by blank
+//! lines. Attributes are `Name: value` lines of at most 72 bytes, longer
+//! ones continue on lines starting with a single space.
+//!
+//! As with the JDK, a final line without a line break is ignored, and
+//! sections naming the same entry are merged.
+
+use super::super::clazz::{Class, MethodAccessFlags};
+use super::{Archive, ArchiveFault};
+
+use std::collections::HashMap;
+use std::fmt;
+
+const MANIFEST: &str = "META-INF/MANIFEST.MF";
+
+const MANIFEST_VERSION: &str = "Manifest-Version";
+const SIGNATURE_VERSION: &str = "Signature-Version";
+const NAME: &str = "Name";
+const MAIN_CLASS: &str = "Main-Class";
+const CLASS_PATH: &str = "Class-Path";
+const AUTOMATIC_MODULE_NAME: &str = "Automatic-Module-Name";
+const MULTI_RELEASE: &str = "Multi-Release";
+const ADD_OPENS: &str = "Add-Opens";
+const ADD_EXPORTS: &str = "Add-Exports";
+
+/// Longest line written, in bytes without the line break
+const LINE_LENGTH: usize = 72;
+
+/// Longest attribute name
+const NAME_LENGTH: usize = 70;
+
+const KEYWORDS: [&str; 53] = [
+    "abstract", "assert", "boolean", "break", "byte", "case", "catch", "char", "class", "const",
+    "continue", "default", "do", "double", "else", "enum", "extends", "final", "finally", "float",
+    "for", "goto", "if", "implements", "import", "instanceof", "int", "interface", "long", "native",
+    "new", "package", "private", "protected", "public", "return", "short", "static", "strictfp", "super",
+    "switch", "synchronized", "this", "throw", "throws", "transient", "try", "void", "volatile", "while",
+    "true", "false", "null"
+];
+
+/// Why a manifest could not be read
+#[derive(Clone,Copy,Debug)]
+pub enum ManifestFault {
+    /// The manifest is not UTF-8
+    BadUtf8,
+    /// A continuation line with no attribute to continue
+    BadContinuation,
+    /// A line that isn't `Name: value` with a valid name
+    BadAttribute,
+    /// A section after the main one that doesn't start with `Name`
+    MissingName,
+    /// The manifest entry couldn't be read
+    Entry(ArchiveFault)
+}
+impl fmt::Display for ManifestFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ManifestFault::BadUtf8 => write!(f, "invalid UTF-8"),
+            ManifestFault::BadContinuation => write!(f, "continuation line without an attribute"),
+            ManifestFault::BadAttribute => write!(f, "malformed attribute"),
+            ManifestFault::MissingName => write!(f, "section without a Name attribute"),
+            ManifestFault::Entry(x) => write!(f, "{}", x)
+        }
+    }
+}
+
+/// A failure to read a manifest
+#[derive(Clone,Copy,Debug)]
+pub struct ManifestError {
+    /// Line of the problem, from 1, or `0` when the entry couldn't be read
+    pub line: usize,
+    pub reason: ManifestFault
+}
+impl fmt::Display for ManifestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at line {}", self.reason, self.line)
+    }
+}
+
+/// Where a manifest disagrees with the archive it describes
+#[derive(Clone,Debug)]
+pub enum ManifestMismatch {
+    /// `Main-Class` names a class that isn't in the archive
+    MissingMainClass(String),
+    /// The `Main-Class` entry couldn't be read or parsed
+    BadMainClass(String, ArchiveFault),
+    /// The `Main-Class` has no `public static void main(String[])`
+    NoMainMethod(String),
+    /// `Automatic-Module-Name` isn't a valid module name
+    BadModuleName(String),
+    /// An `Add-Opens` or `Add-Exports` item that isn't `module/package`,
+    /// with the attribute
+    BadTarget(&'static str, String),
+    /// A per-entry section for an entry that isn't in the archive
+    MissingEntry(String)
+}
+impl fmt::Display for ManifestMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ManifestMismatch::MissingMainClass(ref x) => write!(f, "main class {} not found", x),
+            ManifestMismatch::BadMainClass(ref x, reason) => write!(f, "main class {}: {}", x, reason),
+            ManifestMismatch::NoMainMethod(ref x) => write!(f, "main class {} has no public static void main(String[])", x),
+            ManifestMismatch::BadModuleName(ref x) => write!(f, "invalid automatic module name {}", x),
+            ManifestMismatch::BadTarget(attribute, ref x) => write!(f, "invalid {} target {}", attribute, x),
+            ManifestMismatch::MissingEntry(ref x) => write!(f, "section for missing entry {}", x)
+        }
+    }
+}
+
+/// The attributes of one section, in order
+///
+/// Names are compared ignoring ASCII case, as the JDK does.
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct Attributes {
+    values: Vec<(String, String)>
+}
+impl Attributes {
+
+    pub fn new() -> Attributes {
+        Attributes::default()
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.iter()
+            .find(|x| x.0.eq_ignore_ascii_case(name))
+            .map(|x| x.1.as_str())
+    }
+
+    /// Replace the value of an attribute, or add it at the end
+    pub fn set(&mut self, name: &str, value: &str) {
+        match self.values.iter_mut().find(|x| x.0.eq_ignore_ascii_case(name)) {
+            Option::Some(x) => x.1 = value.to_string(),
+            Option::None => self.values.push((name.to_string(), value.to_string()))
+        };
+    }
+
+    pub fn remove(&mut self, name: &str) -> Option<String> {
+        let i = self.values.iter().position(|x| x.0.eq_ignore_ascii_case(name))?;
+        Some(self.values.remove(i).1)
+    }
+
+    /// Names and values in order
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.values.iter().map(|x| (x.0.as_str(), x.1.as_str()))
+    }
+
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+}
+
+/// A parsed manifest
+#[derive(Clone,Debug,Default,PartialEq,Eq)]
+pub struct Manifest {
+    pub main: Attributes,
+    entries: Vec<(String, Attributes)>,
+    /// Position of each section in `entries` by entry name
+    index: HashMap<String, usize>
+}
+
+impl Manifest {
+
+    pub fn new() -> Manifest {
+        Manifest::default()
+    }
+
+    pub fn parse(data: &[u8]) -> Result<Manifest, ManifestError> {
+        let text = ::std::str::from_utf8(data).map_err(|e| ManifestError {
+            line: data[..e.valid_up_to()].iter().filter(|&&b| b == b'\n').count() + 1,
+            reason: ManifestFault::BadUtf8
+        })?;
+        let mut manifest = Manifest::new();
+        // the section being read, `None` for the main one
+        let mut section: Option<usize> = None;
+        let mut started = false;
+        let mut pending: Option<(usize, String)> = None;
+        for (number, line) in lines(text) {
+            if let Option::Some(more) = line.strip_prefix(' ') {
+                match pending {
+                    Option::Some((_, ref mut x)) => x.push_str(more),
+                    Option::None => return Err(ManifestError { line: number, reason: ManifestFault::BadContinuation })
+                };
+                continue;
+            }
+            if let Option::Some((at, attribute)) = pending.take() {
+                manifest.add(&mut section, &mut started, at, &attribute)?;
+            }
+            if line.is_empty() {
+                // the main section always exists, per-entry ones start
+                // with their first attribute
+                started = false;
+                if section.is_none() {
+                    section = Some(usize::MAX);
+                }
+            } else {
+                pending = Some((number, line.to_string()));
+            }
+        }
+        if let Option::Some((at, attribute)) = pending {
+            manifest.add(&mut section, &mut started, at, &attribute)?;
+        }
+        Ok(manifest)
+    }
+
+    /// Add a complete attribute line to the current section
+    fn add(&mut self, section: &mut Option<usize>, started: &mut bool, line: usize, attribute: &str) -> Result<(), ManifestError> {
+        let err = |reason| ManifestError { line, reason };
+        let (name, value) = match attribute.find(": ") {
+            Option::Some(i) => (&attribute[..i], &attribute[i + 2..]),
+            Option::None => return Err(err(ManifestFault::BadAttribute))
+        };
+        if !valid_name(name) {
+            return Err(err(ManifestFault::BadAttribute));
+        }
+        let index = match *section {
+            Option::None => {
+                self.main.set(name, value);
+                return Ok(());
+            },
+            Option::Some(_) if !*started => {
+                if !name.eq_ignore_ascii_case(NAME) {
+                    return Err(err(ManifestFault::MissingName));
+                }
+                *started = true;
+                *section = Some(self.section(value));
+                return Ok(());
+            },
+            Option::Some(i) => i
+        };
+        self.entries[index].1.set(name, value);
+        Ok(())
+    }
+
+    /// Position of the section for an entry, added if missing
+    fn section(&mut self, name: &str) -> usize {
+        if let Option::Some(&i) = self.index.get(name) {
+            return i;
+        }
+        self.entries.push((name.to_string(), Attributes::new()));
+        self.index.insert(name.to_string(), self.entries.len() - 1);
+        self.entries.len() - 1
+    }
+
+    /// Per-entry sections by entry name, in order, without their `Name`
+    pub fn entries(&self) -> &[(String, Attributes)] {
+        &self.entries
+    }
+
+    /// The section for an entry
+    pub fn entry(&self, name: &str) -> Option<&Attributes> {
+        self.index.get(name).map(|&i| &self.entries[i].1)
+    }
+
+    /// The section for an entry, added at the end if missing
+    pub fn entry_mut(&mut self, name: &str) -> &mut Attributes {
+        let i = self.section(name);
+        &mut self.entries[i].1
+    }
+
+    /// Write the manifest out
+    ///
+    /// The version attribute comes first, everything else stays in
+    /// order. Lines end with CR LF and are wrapped at 72 bytes.
+    pub fn write(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        let version = [MANIFEST_VERSION, SIGNATURE_VERSION].iter()
+            .find(|&&name| self.main.get(name).is_some())
+            .cloned();
+        if let Option::Some(name) = version {
+            write_attribute(&mut out, name, self.main.get(name).unwrap());
+        }
+        for (name, value) in self.main.iter() {
+            if !version.is_some_and(|v| v.eq_ignore_ascii_case(name)) {
+                write_attribute(&mut out, name, value);
+            }
+        }
+        out.extend_from_slice(b"\r\n");
+        for (entry, attributes) in &self.entries {
+            write_attribute(&mut out, NAME, entry);
+            for (name, value) in attributes.iter() {
+                write_attribute(&mut out, name, value);
+            }
+            out.extend_from_slice(b"\r\n");
+        }
+        out
+    }
+
+    /// `Main-Class`, as a binary name with dots
+    pub fn main_class(&self) -> Option<&str> {
+        self.main.get(MAIN_CLASS).map(|x| x.trim())
+    }
+
+    /// The relative URLs of `Class-Path`
+    pub fn class_path(&self) -> Vec<&str> {
+        self.list(CLASS_PATH)
+    }
+
+    pub fn automatic_module_name(&self) -> Option<&str> {
+        self.main.get(AUTOMATIC_MODULE_NAME).map(|x| x.trim())
+    }
+
+    /// Does the main section say `Multi-Release: true`
+    pub fn is_multi_release(&self) -> bool {
+        self.main.get(MULTI_RELEASE).is_some_and(|x| x.trim().eq_ignore_ascii_case("true"))
+    }
+
+    /// The `module/package` items of `Add-Opens`
+    pub fn add_opens(&self) -> Vec<&str> {
+        self.list(ADD_OPENS)
+    }
+
+    /// The `module/package` items of `Add-Exports`
+    pub fn add_exports(&self) -> Vec<&str> {
+        self.list(ADD_EXPORTS)
+    }
+
+    fn list(&self, name: &str) -> Vec<&str> {
+        self.main.get(name).map_or(Vec::new(), |x| x.split_whitespace().collect())
+    }
+
+    /// Check the manifest against the archive it came from
+    pub fn check(&self, archive: &Archive) -> Vec<ManifestMismatch> {
+        let mut mismatches = Vec::new();
+        if let Option::Some(main) = self.main_class() {
+            if let Err(e) = check_main_class(archive, main) {
+                mismatches.push(e);
+            }
+        }
+        if let Option::Some(name) = self.automatic_module_name() {
+            if !valid_qualified_name(name, '.') {
+                mismatches.push(ManifestMismatch::BadModuleName(name.to_string()));
+            }
+        }
+        for &(attribute, ref items) in [(ADD_OPENS, self.add_opens()), (ADD_EXPORTS, self.add_exports())].iter() {
+            for item in items {
+                let valid = match item.find('/') {
+                    Option::Some(i) => valid_qualified_name(&item[..i], '.') && valid_qualified_name(&item[i + 1..], '.'),
+                    Option::None => false
+                };
+                if !valid {
+                    mismatches.push(ManifestMismatch::BadTarget(attribute, item.to_string()));
+                }
+            }
+        }
+        for (name, _) in &self.entries {
+            // a directory names a package, for sealing
+            let found = match name.ends_with('/') {
+                true => archive.entries().iter().any(|e| e.name.starts_with(name.as_str())),
+                false => archive.by_name(name).is_some()
+            };
+            if !found {
+                mismatches.push(ManifestMismatch::MissingEntry(name.clone()));
+            }
+        }
+        mismatches
+    }
+}
+
+fn check_main_class(archive: &Archive, main: &str) -> Result<(), ManifestMismatch> {
+    let entry = archive.by_name(&format!("{}.class", main.replace('.', "/")))
+        .ok_or(ManifestMismatch::MissingMainClass(main.to_string()))?;
+    let bad = |reason| ManifestMismatch::BadMainClass(main.to_string(), reason);
+    let data = archive.read(entry).map_err(|e| bad(e.reason))?;
+    let class = Class::parse(&data).map_err(|e| bad(ArchiveFault::Class(e)))?;
+    let pool = class.get_constants();
+    let wanted = MethodAccessFlags::Public as u16 | MethodAccessFlags::Static as u16;
+    let found = (0..class.get_methods_count())
+        .map(|i| class.get_method(i).unwrap())
+        .filter(|m| m.access_flags & wanted == wanted)
+        .any(|m| {
+            pool.get_utf8(m.name_index).is_some_and(|x| x == "main") &&
+            pool.get_utf8(m.descriptor_index).is_some_and(|x| x == "([Ljava/lang/String;)V")
+        });
+    match found {
+        true => Ok(()),
+        false => Err(ManifestMismatch::NoMainMethod(main.to_string()))
+    }
+}
+
+/// Lines with their numbers, split at CR LF, LF or CR
+///
+/// A last line without a line break is left out.
+fn lines(text: &str) -> Vec<(usize, &str)> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while let Option::Some(end) = rest.find(['\r', '\n']) {
+        lines.push((lines.len() + 1, &rest[..end]));
+        let skip = if rest[end..].starts_with("\r\n") { 2 } else { 1 };
+        rest = &rest[end + skip..];
+    }
+    lines
+}
+
+fn valid_name(name: &str) -> bool {
+    !name.is_empty() && name.len() <= NAME_LENGTH &&
+        name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-' || b == b'_')
+}
+
+/// Java identifiers joined by `separator`, none of them a keyword
+fn valid_qualified_name(name: &str, separator: char) -> bool {
+    name.split(separator).all(|part| {
+        let mut chars = part.chars();
+        match chars.next() {
+            Option::Some(c) if c.is_alphabetic() || c == '_' || c == '$' => {},
+            _ => return false
+        };
+        chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$') && !KEYWORDS.contains(&part)
+    })
+}
+
+/// Write `name: value`, continuing after 72 bytes without splitting a
+/// character
+fn write_attribute(out: &mut Vec<u8>, name: &str, value: &str) {
+    let line = format!("{}: {}", name, value);
+    let mut rest = line.as_str();
+    let mut room = LINE_LENGTH;
+    loop {
+        let mut end = rest.len().min(room);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.extend_from_slice(&rest.as_bytes()[..end]);
+        out.extend_from_slice(b"\r\n");
+        rest = &rest[end..];
+        if rest.is_empty() {
+            break;
+        }
+        out.push(b' ');
+        room = LINE_LENGTH - 1;
+    }
+}
+
+impl<'a> Archive<'a> {
+
+    /// The parsed `META-INF/MANIFEST.MF`, if there is one
+    pub fn manifest(&self) -> Result<Option<Manifest>, ManifestError> {
+        let entry = match self.by_name(MANIFEST) {
+            Option::Some(x) => x,
+            Option::None => return Ok(None)
+        };
+        let data = self.read(entry).map_err(|e| ManifestError { line: 0, reason: ManifestFault::Entry(e.reason) })?;
+        Manifest::parse(&data).map(Some)
+    }
+}
+
+#[test]
+fn test_manifest() {
+    use super::super::bytecode::assemble_class;
+    use super::build_zip;
+
+    let text = "Manifest-Version: 1.0\r\nMain-Class: demo.App\nClass-Path: lib/a.jar \r\n lib/b.jar\rCreated-By: test\r\n\
+        Automatic-Module-Name: demo.app\r\nAdd-Opens: java.base/java.lang bad\r\n\r\n\r\n\
+        Name: demo/App.class\r\nSHA-256-Digest: abc\r\n\r\nName: demo/\r\nSealed: true\r\n\r\n\
+        name: demo/App.class\r\nX-Extra: 1\r\n\r\nName: gone.txt\r\nX: y\r\nignored: no line break";
+    let manifest = Manifest::parse(text.as_bytes()).unwrap();
+    assert_eq!(manifest.main.len(), 6);
+    assert_eq!(manifest.main_class(), Some("demo.App"));
+    assert_eq!(manifest.class_path(), ["lib/a.jar", "lib/b.jar"]);
+    assert_eq!(manifest.automatic_module_name(), Some("demo.app"));
+    assert_eq!(manifest.add_opens(), ["java.base/java.lang", "bad"]);
+    assert!(!manifest.is_multi_release());
+    assert_eq!(manifest.entries().iter().map(|x| x.0.as_str()).collect::<Vec<_>>(), ["demo/App.class", "demo/", "gone.txt"]);
+    let app = manifest.entry("demo/App.class").unwrap();
+    assert_eq!((app.get("sha-256-digest"), app.get("X-Extra")), (Some("abc"), Some("1")));
+    assert_eq!(manifest.entry("gone.txt").unwrap().get("ignored"), None);
+
+    // written with the version first, and read back the same
+    let mut long = manifest.clone();
+    long.main.remove(MANIFEST_VERSION);
+    long.main.set(CLASS_PATH, &"lib/é.jar ".repeat(12));
+    long.main.set(MANIFEST_VERSION, "1.0");
+    let written = long.write();
+    let written_text = String::from_utf8(written.clone()).unwrap();
+    assert!(written_text.starts_with("Manifest-Version: 1.0\r\nMain-Class: demo.App\r\nClass-Path: lib/"));
+    assert!(written_text.split("\r\n").all(|l| l.len() <= LINE_LENGTH));
+    let read = Manifest::parse(&written).unwrap();
+    assert_eq!(read.main.iter().next(), Some((MANIFEST_VERSION, "1.0")));
+    assert_eq!((read.main.get(CLASS_PATH), read.entries()), (long.main.get(CLASS_PATH), long.entries()));
+    assert_eq!(read.write(), written);
+    long.entry_mut("gone.txt").set("X", "z");
+    long.entry_mut("new.txt").set("X", "1");
+    assert_eq!(long.entries().len(), 4);
+    assert_eq!((long.entry("gone.txt").unwrap().get("X"), long.entry("new.txt").unwrap().get("X")), (Some("z"), Some("1")));
+
+    let errors: [&[u8]; 5] = [b"Main-Class demo.App\n", b" continued\n", b"Bad Name: x\n", b"A: b\n\nSealed: true\n", b"A: \xff\n"];
+    let errors = errors.iter()
+        .map(|x| Manifest::parse(x).unwrap_err().to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(errors, [
+        "malformed attribute at line 1",
+        "continuation line without an attribute at line 1",
+        "malformed attribute at line 1",
+        "section without a Name attribute at line 3",
+        "invalid UTF-8 at line 1"
+    ]);
+
+    let app = assemble_class("\
+.class public demo/App
+.super java/lang/Object
+.method public static main([Ljava/lang/String;)V
+    return
+.end method
+").unwrap();
+    let jar = build_zip(&[("META-INF/MANIFEST.MF", text.as_bytes()), ("demo/App.class", &app)]);
+    let archive = Archive::parse(&jar).unwrap();
+    assert_eq!(archive.manifest().unwrap(), Some(manifest.clone()));
+    let mismatches = manifest.check(&archive).iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(mismatches, ["invalid Add-Opens target bad", "section for missing entry gone.txt"]);
+
+    let mut other = Manifest::new();
+    other.main = manifest.main.clone();
+    other.main.set(MAIN_CLASS, "demo.Missing");
+    other.main.set(AUTOMATIC_MODULE_NAME, "demo.int");
+    let mismatches = other.check(&archive).iter().map(|x| x.to_string()).collect::<Vec<_>>();
+    assert_eq!(mismatches, ["main class demo.Missing not found", "invalid automatic module name demo.int", "invalid Add-Opens target bad"]);
+}
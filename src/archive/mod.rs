@@ -0,0 +1,581 @@
+//! Reading classes out of jar and zip archives.
+//!
+//! `Archive::parse` reads the central directory of a zip held in memory.
+//! Entries are only decompressed when asked for, and a corrupt entry
+//! fails on its own without affecting the rest of the archive.
+//!
+//! Stored and deflated entries are supported, as are ZIP64 archives,
+//! entries written with data descriptors and archives with data in front
+//! of them, such as executable jars with a launcher script.
+//!
+//! The `for_each_class` readers here hand each class to their callback as
+//! it is parsed. The class borrows the entry's decompressed bytes, which
+//! only live for that call, so anything kept must be copied out of it.
+
+mod classpath;
+pub use self::classpath::{Classpath, ClasspathError, ClasspathFault};
+
+mod inflate;
+pub use self::inflate::{InflateFault, crc32, inflate};
+
+mod jimage;
+pub use self::jimage::{Image, ImageError, ImageFault, Resource, ResourceError};
+
+mod jmod;
+pub use self::jmod::{Jmod, Section};
+
+mod manifest;
+pub use self::manifest::{Attributes, Manifest, ManifestError, ManifestFault, ManifestMismatch};
+
+mod release;
+pub use self::release::{ReleaseError, ReleaseFault};
+use self::release::{find_releases, read_multi_release};
+
+use super::clazz::{Class, Fault};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP64_END_OF_DIRECTORY: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// Compression methods
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+
+/// General purpose flags
+const ENCRYPTED: u16 = 0x0001;
+
+/// Why an archive or entry could not be read
+#[derive(Clone,Copy,Debug)]
+pub enum ArchiveFault {
+    /// No end of central directory record
+    NotAnArchive,
+    /// A header is cut short or has the wrong signature
+    BadHeader,
+    /// Split across several disks
+    MultiDisk,
+    /// A compression method other than stored or deflate
+    UnsupportedMethod(u16),
+    Encrypted,
+    Inflate(InflateFault),
+    /// The data doesn't match the size in the central directory
+    BadSize,
+    /// The data doesn't match the CRC-32 in the central directory
+    BadChecksum,
+    /// The entry is not a valid class file
+    Class(Fault),
+    /// A JMOD without the `JM` magic number
+    NotAJmod,
+    /// Major and minor version of a JMOD format this doesn't know
+    UnsupportedVersion(u8, u8),
+    /// A required entry isn't in the archive
+    Missing
+}
+impl fmt::Display for ArchiveFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArchiveFault::NotAnArchive => write!(f, "not a zip archive"),
+            ArchiveFault::BadHeader => write!(f, "malformed header"),
+            ArchiveFault::MultiDisk => write!(f, "multi-disk archives are not supported"),
+            ArchiveFault::UnsupportedMethod(x) => write!(f, "unsupported compression method {}", x),
+            ArchiveFault::Encrypted => write!(f, "encrypted entry"),
+            ArchiveFault::Inflate(x) => write!(f, "{}", x),
+            ArchiveFault::BadSize => write!(f, "size mismatch"),
+            ArchiveFault::BadChecksum => write!(f, "CRC-32 mismatch"),
+            ArchiveFault::Class(x) => write!(f, "malformed class: {:?}", x),
+            ArchiveFault::NotAJmod => write!(f, "not a JMOD file"),
+            ArchiveFault::UnsupportedVersion(major, minor) => write!(f, "unsupported JMOD version {}.{}", major, minor),
+            ArchiveFault::Missing => write!(f, "missing entry")
+        }
+    }
+}
+
+/// A failure to read the archive as a whole
+#[derive(Clone,Copy,Debug)]
+pub struct ArchiveError {
+    /// Offset of the offending record in the archive
+    pub offset: usize,
+    pub reason: ArchiveFault
+}
+impl fmt::Display for ArchiveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at offset {}", self.reason, self.offset)
+    }
+}
+
+/// A failure to read one entry
+#[derive(Clone,Debug)]
+pub struct EntryError {
+    pub name: String,
+    pub reason: ArchiveFault
+}
+impl fmt::Display for EntryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in entry {}", self.reason, self.name)
+    }
+}
+
+/// An entry as described by the central directory
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Entry {
+    /// Path within the archive, directories end with `/`
+    pub name: String,
+    pub method: u16,
+    pub flags: u16,
+    pub crc32: u32,
+    pub compressed_size: u64,
+    pub size: u64,
+    /// Offset of the local header, relative to the start of the archive
+    pub header_offset: u64
+}
+impl Entry {
+
+    pub fn is_dir(&self) -> bool {
+        self.name.ends_with('/')
+    }
+
+    /// Does the name say it's a class file
+    pub fn is_class(&self) -> bool {
+        !self.is_dir() && self.name.ends_with(".class")
+    }
+}
+
+/// Little endian fields of a record
+struct Record<'a> {
+    data: &'a [u8]
+}
+impl<'a> Record<'a> {
+
+    fn at(data: &'a [u8], offset: usize, len: usize) -> Option<Record<'a>> {
+        data.get(offset..offset.checked_add(len)?).map(|data| Record { data })
+    }
+
+    fn u16(&self, at: usize) -> u16 {
+        u16::from_le_bytes([self.data[at], self.data[at + 1]])
+    }
+
+    fn u32(&self, at: usize) -> u32 {
+        u32::from_le_bytes([self.data[at], self.data[at + 1], self.data[at + 2], self.data[at + 3]])
+    }
+
+    fn u64(&self, at: usize) -> u64 {
+        self.u32(at) as u64 | (self.u32(at + 4) as u64) << 32
+    }
+}
+
+/// Where the central directory is, from the end records
+struct Directory {
+    entries: u64,
+    size: u64,
+    offset: u64,
+    /// Where the end records start, the directory ends here
+    end: usize
+}
+
+/// A zip or jar archive held in memory
+#[derive(Clone,Debug)]
+pub struct Archive<'a> {
+    data: &'a [u8],
+    /// Bytes in front of the archive proper, which offsets don't count
+    prefix: usize,
+    entries: Vec<Entry>,
+    /// Position of each entry by name, the first one when repeated
+    index: HashMap<String, usize>,
+    /// Whether the manifest says `Multi-Release: true`
+    multi_release: bool,
+    /// Releases with versioned entries, ascending
+    releases: Vec<u32>
+}
+
+impl<'a> Archive<'a> {
+
+    /// Read the central directory of the archive in `data`
+    pub fn parse(data: &'a [u8]) -> Result<Archive<'a>, ArchiveError> {
+        let err = |offset, reason| ArchiveError { offset, reason };
+        let dir = Archive::directory(data)?;
+        let prefix = (dir.end as u64).checked_sub(dir.offset.saturating_add(dir.size))
+            .ok_or(err(dir.end, ArchiveFault::BadHeader))? as usize;
+        let mut entries = Vec::with_capacity(dir.entries.min(data.len() as u64 / 46) as usize);
+        let mut pos = prefix + dir.offset as usize;
+        for _ in 0..dir.entries {
+            let r = Record::at(data, pos, 46)
+                .filter(|r| r.u32(0) == CENTRAL_HEADER)
+                .ok_or(err(pos, ArchiveFault::BadHeader))?;
+            let (name_len, extra_len, comment_len) = (r.u16(28) as usize, r.u16(30) as usize, r.u16(32) as usize);
+            let name = data.get(pos + 46..pos + 46 + name_len).ok_or(err(pos, ArchiveFault::BadHeader))?;
+            let extra = data.get(pos + 46 + name_len..pos + 46 + name_len + extra_len)
+                .ok_or(err(pos, ArchiveFault::BadHeader))?;
+            let mut entry = Entry {
+                name: String::from_utf8_lossy(name).into_owned(),
+                method: r.u16(10),
+                flags: r.u16(8),
+                crc32: r.u32(16),
+                compressed_size: r.u32(20) as u64,
+                size: r.u32(24) as u64,
+                header_offset: r.u32(42) as u64
+            };
+            Archive::zip64_extra(&mut entry, extra, r.u16(34)).ok_or(err(pos, ArchiveFault::BadHeader))?;
+            entries.push(entry);
+            pos += 46 + name_len + extra_len + comment_len;
+        }
+        let mut index = HashMap::with_capacity(entries.len());
+        for (i, entry) in entries.iter().enumerate() {
+            index.entry(entry.name.clone()).or_insert(i);
+        }
+        let releases = find_releases(&entries);
+        let mut archive = Archive { data, prefix, entries, index, multi_release: false, releases };
+        archive.multi_release = read_multi_release(&archive);
+        Ok(archive)
+    }
+
+    /// Find the end of central directory record, and its ZIP64 version
+    fn directory(data: &[u8]) -> Result<Directory, ArchiveError> {
+        let err = |offset, reason| ArchiveError { offset, reason };
+        // the record is 22 bytes followed by a comment of up to 64K
+        let lowest = data.len().saturating_sub(22 + 0xFFFF);
+        let end = (lowest..data.len().saturating_sub(21)).rev()
+            .find(|&i| {
+                let r = Record::at(data, i, 22).unwrap();
+                r.u32(0) == END_OF_DIRECTORY && i + 22 + r.u16(20) as usize == data.len()
+            })
+            .ok_or(err(data.len(), ArchiveFault::NotAnArchive))?;
+        let r = Record::at(data, end, 22).unwrap();
+        if r.u16(4) != 0 || r.u16(6) != 0 {
+            return Err(err(end, ArchiveFault::MultiDisk));
+        }
+        let mut dir = Directory {
+            entries: r.u16(10) as u64,
+            size: r.u32(12) as u64,
+            offset: r.u32(16) as u64,
+            end
+        };
+        let locator = match end.checked_sub(20).and_then(|i| Record::at(data, i, 20)) {
+            Option::Some(l) if l.u32(0) == ZIP64_LOCATOR => l,
+            _ => return Ok(dir)
+        };
+        // the record, whose size doesn't count its first 12 bytes, ends
+        // where the locator starts
+        let ends_at_locator = |i: usize| Record::at(data, i, 56)
+            .filter(|r| r.u32(0) == ZIP64_END_OF_DIRECTORY)
+            .filter(|r| {
+                let size = (i as u64).checked_add(12).and_then(|x| x.checked_add(r.u64(4)));
+                size == Option::Some(end as u64 - 20)
+            });
+        // the locator gives an offset that doesn't count any prefix, a
+        // prefixed archive is taken to have a record of the usual 56 bytes
+        let found = usize::try_from(locator.u64(8)).ok()
+            .and_then(|i| Some((i, ends_at_locator(i)?)))
+            .or_else(|| end.checked_sub(20 + 56).and_then(|i| Some((i, ends_at_locator(i)?))));
+        let (record, r) = found.ok_or(err(end - 20, ArchiveFault::BadHeader))?;
+        if locator.u32(4) != 0 || r.u32(16) != 0 {
+            return Err(err(record, ArchiveFault::MultiDisk));
+        }
+        dir.entries = r.u64(32);
+        dir.size = r.u64(40);
+        dir.offset = r.u64(48);
+        dir.end = record;
+        Ok(dir)
+    }
+
+    /// Replace saturated sizes and offsets with those of the ZIP64 extra field
+    fn zip64_extra(entry: &mut Entry, mut extra: &[u8], disk: u16) -> Option<()> {
+        let wanted = [entry.size == 0xFFFF_FFFF, entry.compressed_size == 0xFFFF_FFFF, entry.header_offset == 0xFFFF_FFFF];
+        if !wanted.iter().any(|&x| x) && disk != 0xFFFF {
+            return Some(());
+        }
+        while extra.len() >= 4 {
+            let r = Record::at(extra, 0, 4)?;
+            let len = r.u16(2) as usize;
+            let body = extra.get(4..4 + len)?;
+            if r.u16(0) == ZIP64_EXTRA {
+                let mut values = body.chunks_exact(8).map(|x| Record { data: x }.u64(0));
+                let mut fields = [&mut entry.size, &mut entry.compressed_size, &mut entry.header_offset];
+                for (field, wanted) in fields.iter_mut().zip(wanted.iter()) {
+                    if *wanted {
+                        **field = values.next()?;
+                    }
+                }
+                return Some(());
+            }
+            extra = &extra[4 + len..];
+        }
+        None
+    }
+
+    /// Entries in central directory order
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// The entry with exactly this name
+    pub fn by_name(&self, name: &str) -> Option<&Entry> {
+        self.index.get(name).map(|&i| &self.entries[i])
+    }
+
+    /// Entries that are neither directories nor classes
+    pub fn resources(&self) -> impl Iterator<Item = &Entry> + '_ {
+        self.entries.iter().filter(|e| !e.is_dir() && !e.is_class())
+    }
+
+    /// The uncompressed contents of an entry, borrowed when stored
+    ///
+    /// The size and CRC-32 are checked against the central directory.
+    pub fn read(&self, entry: &Entry) -> Result<Cow<'a, [u8]>, EntryError> {
+        let err = |reason| EntryError { name: entry.name.clone(), reason };
+        if entry.flags & ENCRYPTED != 0 {
+            return Err(err(ArchiveFault::Encrypted));
+        }
+        let pos = (self.prefix as u64).checked_add(entry.header_offset)
+            .filter(|&x| x <= usize::MAX as u64)
+            .ok_or(err(ArchiveFault::BadHeader))? as usize;
+        let r = Record::at(self.data, pos, 30)
+            .filter(|r| r.u32(0) == LOCAL_HEADER)
+            .ok_or(err(ArchiveFault::BadHeader))?;
+        // sizes in the local header may be zero when a data descriptor
+        // follows, the central directory is always right
+        let start = pos + 30 + r.u16(26) as usize + r.u16(28) as usize;
+        let compressed = start.checked_add(entry.compressed_size as usize)
+            .and_then(|end| self.data.get(start..end))
+            .ok_or(err(ArchiveFault::BadHeader))?;
+        let data = match entry.method {
+            STORED => Cow::Borrowed(compressed),
+            DEFLATED => {
+                let limit = usize::try_from(entry.size).map_err(|_| err(ArchiveFault::BadSize))?;
+                Cow::Owned(inflate(compressed, limit).map_err(|e| err(ArchiveFault::Inflate(e)))?.0)
+            },
+            x => return Err(err(ArchiveFault::UnsupportedMethod(x)))
+        };
+        if data.len() as u64 != entry.size {
+            return Err(err(ArchiveFault::BadSize));
+        }
+        if crc32(&data) != entry.crc32 {
+            return Err(err(ArchiveFault::BadChecksum));
+        }
+        Ok(data)
+    }
+
+    /// Read and parse every class entry in turn
+    pub fn for_each_class<F>(&self, mut f: F)
+        where F: FnMut(&Entry, Result<Class, EntryError>)
+    {
+        for entry in self.entries.iter().filter(|e| e.is_class()) {
+            match self.read(entry) {
+                Ok(data) => f(entry, parse_entry(entry, &data)),
+                Err(e) => f(entry, Err(e))
+            };
+        }
+    }
+
+    /// Read and parse every class entry on `threads` threads, returning
+    /// what `f` makes of each, in central directory order
+    pub fn map_classes<T, F>(&self, threads: usize, f: F) -> Vec<T>
+        where T: Send, F: Fn(&Entry, Result<Class, EntryError>) -> T + Sync
+    {
+        let classes = self.entries.iter().filter(|e| e.is_class()).collect::<Vec<_>>();
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(Vec::with_capacity(classes.len()));
+        thread::scope(|scope| {
+            for _ in 0..threads.max(1) {
+                scope.spawn(|| {
+                    let mut done = Vec::new();
+                    loop {
+                        let i = next.fetch_add(1, Ordering::Relaxed);
+                        let entry = match classes.get(i) {
+                            Option::Some(x) => *x,
+                            Option::None => break
+                        };
+                        let value = match self.read(entry) {
+                            Ok(data) => f(entry, parse_entry(entry, &data)),
+                            Err(e) => f(entry, Err(e))
+                        };
+                        done.push((i, value));
+                    }
+                    results.lock().unwrap().extend(done);
+                });
+            }
+        });
+        let mut results = results.into_inner().unwrap();
+        results.sort_by_key(|x| x.0);
+        results.into_iter().map(|x| x.1).collect()
+    }
+}
+
+fn parse_entry<'d>(entry: &Entry, data: &'d [u8]) -> Result<Class<'d>, EntryError> {
+    Class::parse(data).map_err(|e| EntryError { name: entry.name.clone(), reason: ArchiveFault::Class(e) })
+}
+
+#[cfg(test)]
+fn zip_entry(out: &mut Vec<u8>, directory: &mut Vec<u8>, name: &str, method: u16, size: usize, crc: u32, data: &[u8]) {
+    let offset = out.len() as u32;
+    let fields = |out: &mut Vec<u8>| {
+        out.extend_from_slice(&method.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&crc.to_le_bytes());
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(&(size as u32).to_le_bytes());
+        out.extend_from_slice(&(name.len() as u16).to_le_bytes());
+        out.extend_from_slice(&[0; 2]);
+    };
+    out.extend_from_slice(&LOCAL_HEADER.to_le_bytes());
+    out.extend_from_slice(&[20, 0, 0, 0]);
+    fields(out);
+    out.extend_from_slice(name.as_bytes());
+    out.extend_from_slice(data);
+    directory.extend_from_slice(&CENTRAL_HEADER.to_le_bytes());
+    directory.extend_from_slice(&[20, 0, 20, 0, 0, 0]);
+    fields(directory);
+    directory.extend_from_slice(&[0; 10]);
+    directory.extend_from_slice(&offset.to_le_bytes());
+    directory.extend_from_slice(name.as_bytes());
+}
+
+/// A zip of stored entries
+#[cfg(test)]
+fn build_zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    for &(name, data) in entries {
+        zip_entry(&mut out, &mut directory, name, STORED, data.len(), crc32(data), data);
+    }
+    let directory_offset = out.len();
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&[0; 4]);
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(directory_offset as u32).to_le_bytes());
+    out.extend_from_slice(&[0, 0]);
+    out
+}
+
+#[test]
+fn test_archive() {
+    use super::bytecode::assemble_class;
+    let class = assemble_class(".class public A\n").unwrap();
+    let deflated = b"\xcb\x48\xcd\xc9\xc9\x57\xc8\x40\x90\x8a\x00";
+    let text = b"hello hello hello!";
+
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    zip_entry(&mut out, &mut directory, "META-INF/", STORED, 0, 0, b"");
+    zip_entry(&mut out, &mut directory, "A.class", STORED, class.len(), crc32(&class), &class);
+    zip_entry(&mut out, &mut directory, "hello.txt", DEFLATED, text.len(), crc32(text), deflated);
+    zip_entry(&mut out, &mut directory, "Bad.class", DEFLATED, text.len(), crc32(text), &deflated[..6]);
+    zip_entry(&mut out, &mut directory, "Text.class", DEFLATED, text.len(), crc32(text), deflated);
+    // a constant_pool_count of 0
+    let empty = b"\xca\xfe\xba\xbe\x00\x00\x00\x34\x00\x00";
+    zip_entry(&mut out, &mut directory, "Empty.class", STORED, empty.len(), crc32(empty), empty);
+    let directory_offset = out.len();
+    out.extend_from_slice(&directory);
+    out.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+    out.extend_from_slice(&[0, 0, 0, 0, 6, 0, 6, 0]);
+    out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(directory_offset as u32).to_le_bytes());
+    out.extend_from_slice(&[0, 0]);
+    // as in an executable jar, offsets don't count the launcher script
+    let out = [&b"#!/bin/sh\nexec java -jar $0\n"[..], &out].concat();
+
+    let archive = Archive::parse(&out).unwrap();
+    assert_eq!(archive.entries().len(), 6);
+    assert_eq!(archive.resources().map(|e| e.name.as_str()).collect::<Vec<_>>(), ["hello.txt"]);
+    let hello = archive.read(archive.by_name("hello.txt").unwrap()).unwrap();
+    assert_eq!(&hello[..], &text[..]);
+
+    let mut seen = Vec::new();
+    archive.for_each_class(|_, class| seen.push(match class {
+        Ok(c) => c.get_this_class().unwrap().into_owned(),
+        Err(e) => format!("{}", e)
+    }));
+    assert_eq!(seen, [
+        "A",
+        "truncated deflate stream in entry Bad.class",
+        "malformed class: ParseError in entry Text.class",
+        "malformed class: ParseError in entry Empty.class"
+    ]);
+    let names = archive.map_classes(3, |entry, class| (entry.name.clone(), class.is_ok()));
+    assert_eq!(names, [
+        ("A.class".to_string(), true),
+        ("Bad.class".to_string(), false),
+        ("Text.class".to_string(), false),
+        ("Empty.class".to_string(), false)
+    ]);
+
+    assert!(matches!(Archive::parse(&out[..out.len() - 1]), Err(ArchiveError { reason: ArchiveFault::NotAnArchive, .. })));
+
+    // a ZIP64 archive, whose first entry has its sizes and offset in the
+    // extra field and whose second is followed by a data descriptor
+    let mut out = Vec::new();
+    let mut directory = Vec::new();
+    zip_entry(&mut out, &mut directory, "big.txt", STORED, text.len(), crc32(text), text);
+    let mut extra = Vec::new();
+    extra.extend_from_slice(&ZIP64_EXTRA.to_le_bytes());
+    extra.extend_from_slice(&24u16.to_le_bytes());
+    extra.extend_from_slice(&(text.len() as u64).to_le_bytes());
+    extra.extend_from_slice(&(text.len() as u64).to_le_bytes());
+    extra.extend_from_slice(&0u64.to_le_bytes());
+    directory[20..28].copy_from_slice(&[0xFF; 8]);
+    directory[30..32].copy_from_slice(&(extra.len() as u16).to_le_bytes());
+    directory[42..46].copy_from_slice(&[0xFF; 4]);
+    directory.extend_from_slice(&extra);
+    let (local, central) = (out.len(), directory.len());
+    zip_entry(&mut out, &mut directory, "streamed.txt", DEFLATED, text.len(), crc32(text), deflated);
+    // flag bit 3, with the CRC-32 and sizes zero in the local header
+    out[local + 6] = 8;
+    out[local + 14..local + 26].copy_from_slice(&[0; 12]);
+    directory[central + 8] = 8;
+    out.extend_from_slice(&0x0807_4b50u32.to_le_bytes());
+    out.extend_from_slice(&crc32(text).to_le_bytes());
+    out.extend_from_slice(&(deflated.len() as u32).to_le_bytes());
+    out.extend_from_slice(&(text.len() as u32).to_le_bytes());
+    let directory_offset = out.len();
+    out.extend_from_slice(&directory);
+    // the end records, the ZIP64 one with an extensible data sector
+    let end_records = |sector: &[u8]| {
+        let mut out = out.clone();
+        let record = out.len();
+        out.extend_from_slice(&ZIP64_END_OF_DIRECTORY.to_le_bytes());
+        out.extend_from_slice(&(44 + sector.len() as u64).to_le_bytes());
+        out.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        out.extend_from_slice(&2u64.to_le_bytes());
+        out.extend_from_slice(&2u64.to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u64).to_le_bytes());
+        out.extend_from_slice(&(directory_offset as u64).to_le_bytes());
+        out.extend_from_slice(sector);
+        out.extend_from_slice(&ZIP64_LOCATOR.to_le_bytes());
+        out.extend_from_slice(&[0; 4]);
+        out.extend_from_slice(&(record as u64).to_le_bytes());
+        out.extend_from_slice(&1u32.to_le_bytes());
+        out.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+        out.extend_from_slice(&[0, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF]);
+        out.extend_from_slice(&[0xFF; 8]);
+        out.extend_from_slice(&[0, 0]);
+        out
+    };
+    let (plain, extended) = (end_records(b""), end_records(b"\x99\x99\x04\x00data"));
+    let prefixed = [&b"#!/bin/sh\n"[..], &plain].concat();
+    for out in [&plain, &extended, &prefixed].iter() {
+        let archive = Archive::parse(out).unwrap();
+        assert_eq!(archive.entries().len(), 2);
+        for entry in archive.entries() {
+            assert_eq!(&archive.read(entry).unwrap()[..], &text[..]);
+        }
+    }
+
+    // a locator too close to the start to leave room for the ZIP64 record
+    let mut short = ZIP64_LOCATOR.to_le_bytes().to_vec();
+    short.extend_from_slice(&[0; 16]);
+    short.extend_from_slice(&END_OF_DIRECTORY.to_le_bytes());
+    short.extend_from_slice(&[0; 18]);
+    assert!(matches!(Archive::parse(&short), Err(ArchiveError { offset: 0, reason: ArchiveFault::BadHeader })));
+}
@@ -0,0 +1,313 @@
+//! Multi-release jars.
+//!
+//! A jar whose manifest says `Multi-Release: true` may hold entries for
+//! newer runtimes under `META-INF/versions/N/`. A runtime of release `R`
+//! loads an entry from the highest such directory with `9 <= N <= R`, or
+//! the base entry when there is none. Other jars are read as if the
+//! versioned entries were ordinary resources.
+//!
+//! Versioned classes must be loadable by their release and keep the
+//! public API of the class they stand in for.
+
+use super::super::clazz::{Class, ClassAccessFlags, Fault, FieldAccessFlags, MethodAccessFlags};
+use super::{Archive, ArchiveFault, Entry};
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+const VERSIONS: &str = "META-INF/versions/";
+
+/// The first release to read versioned entries
+const FIRST_RELEASE: u32 = 9;
+
+/// Release `N` reads class files up to major version `N + 44`
+const MAJOR_OFFSET: u32 = 44;
+
+/// Why a versioned class breaks the rules for multi-release jars
+#[derive(Clone,Debug)]
+pub enum ReleaseFault {
+    /// The class file's major version is too new for its release
+    TooNew(u16),
+    /// A public class that no earlier release has
+    NewPublicClass,
+    /// Public API the earlier class doesn't have
+    Added(String),
+    /// Public API of the earlier class that is gone
+    Removed(String),
+    /// The entry couldn't be read or parsed
+    Entry(ArchiveFault)
+}
+impl fmt::Display for ReleaseFault {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ReleaseFault::TooNew(x) => write!(f, "class file version {} is too new for its release", x),
+            ReleaseFault::NewPublicClass => write!(f, "public class not in an earlier release"),
+            ReleaseFault::Added(ref x) => write!(f, "adds {}", x),
+            ReleaseFault::Removed(ref x) => write!(f, "removes {}", x),
+            ReleaseFault::Entry(x) => write!(f, "{}", x)
+        }
+    }
+}
+
+/// A problem with one versioned entry
+#[derive(Clone,Debug)]
+pub struct ReleaseError {
+    /// Name of the entry, `META-INF/versions/` included
+    pub name: String,
+    pub release: u32,
+    pub reason: ReleaseFault
+}
+impl fmt::Display for ReleaseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} in entry {}", self.reason, self.name)
+    }
+}
+
+/// Split the name of a versioned entry into its release and base name
+pub fn versioned(name: &str) -> Option<(u32, &str)> {
+    let rest = name.strip_prefix(VERSIONS)?;
+    let slash = rest.find('/')?;
+    let release = rest[..slash].parse().ok()?;
+    Some((release, &rest[slash + 1..]))
+}
+
+/// Does the manifest say `Multi-Release: true`, a manifest that can't be
+/// read saying nothing
+pub fn read_multi_release(archive: &Archive) -> bool {
+    match archive.manifest() {
+        Ok(Option::Some(manifest)) => manifest.is_multi_release(),
+        _ => false
+    }
+}
+
+/// Releases with versioned entries, ascending
+pub fn find_releases(entries: &[Entry]) -> Vec<u32> {
+    let releases = entries.iter()
+        .filter_map(|e| versioned(&e.name))
+        .map(|x| x.0)
+        .filter(|&r| r >= FIRST_RELEASE)
+        .collect::<BTreeSet<_>>();
+    releases.into_iter().collect()
+}
+
+impl<'a> Archive<'a> {
+
+    /// Does the manifest say `Multi-Release: true`
+    pub fn is_multi_release(&self) -> bool {
+        self.multi_release
+    }
+
+    /// Releases with a `META-INF/versions/` directory, ascending
+    pub fn releases(&self) -> &[u32] {
+        &self.releases
+    }
+
+    /// The entry a runtime of `release` loads for `name`, such as
+    /// `com/example/Foo.class`
+    pub fn lookup(&self, name: &str, release: u32) -> Option<&Entry> {
+        if self.multi_release {
+            for &r in self.releases.iter().rev().filter(|&&r| r <= release) {
+                if let Option::Some(e) = self.by_name(&format!("{}{}/{}", VERSIONS, r, name)) {
+                    return Some(e);
+                }
+            }
+        }
+        self.by_name(name)
+    }
+
+    /// Check every versioned class of a multi-release jar
+    ///
+    /// A versioned class must have a class file version its release can
+    /// load and the same public API as the class earlier releases load.
+    /// A public class may not be new in a versioned directory.
+    pub fn check_releases(&self) -> Vec<ReleaseError> {
+        let mut errors = Vec::new();
+        if !self.multi_release {
+            return errors;
+        }
+        for entry in self.entries.iter().filter(|e| e.is_class()) {
+            let (release, name) = match versioned(&entry.name) {
+                Option::Some(x) if x.0 >= FIRST_RELEASE => x,
+                _ => continue
+            };
+            let err = |entry: &Entry, reason| ReleaseError { name: entry.name.clone(), release, reason };
+            let api = match self.public_api(entry) {
+                Ok((major, api)) => {
+                    if major as u32 > release + MAJOR_OFFSET {
+                        errors.push(err(entry, ReleaseFault::TooNew(major)));
+                    }
+                    api
+                },
+                Err(e) => {
+                    errors.push(err(entry, ReleaseFault::Entry(e)));
+                    continue;
+                }
+            };
+            let earlier = match self.lookup(name, release - 1) {
+                Option::Some(x) => x,
+                Option::None => {
+                    if !api.is_empty() {
+                        errors.push(err(entry, ReleaseFault::NewPublicClass));
+                    }
+                    continue;
+                }
+            };
+            let before = match self.public_api(earlier) {
+                Ok(x) => x.1,
+                // reported when checking the earlier entry, if versioned
+                Err(_) => continue
+            };
+            for x in before.difference(&api) {
+                errors.push(err(entry, ReleaseFault::Removed(x.clone())));
+            }
+            for x in api.difference(&before) {
+                errors.push(err(entry, ReleaseFault::Added(x.clone())));
+            }
+        }
+        errors
+    }
+
+    /// The major version of a class and a description of its public API,
+    /// empty when the class isn't public
+    fn public_api(&self, entry: &Entry) -> Result<(u16, BTreeSet<String>), ArchiveFault> {
+        let data = self.read(entry).map_err(|e| e.reason)?;
+        let class = Class::parse(&data).map_err(ArchiveFault::Class)?;
+        let api = describe_api(&class).map_err(ArchiveFault::Class)?;
+        Ok((class.get_major_version(), api))
+    }
+}
+
+fn describe_api(class: &Class) -> Result<BTreeSet<String>, Fault> {
+    let mut api = BTreeSet::new();
+    let flags = class.get_access_flags();
+    if flags & (ClassAccessFlags::Public as u16) == 0 {
+        return Ok(api);
+    }
+    let kind = if flags & (ClassAccessFlags::Annotation as u16) != 0 {
+        "@interface"
+    } else if flags & (ClassAccessFlags::Interface as u16) != 0 {
+        "interface"
+    } else if flags & (ClassAccessFlags::Enum as u16) != 0 {
+        "enum"
+    } else if flags & (ClassAccessFlags::Abstract as u16) != 0 {
+        "abstract class"
+    } else if flags & (ClassAccessFlags::Final as u16) != 0 {
+        "final class"
+    } else {
+        "class"
+    };
+    api.insert(format!("public {} {}", kind, class.get_this_class()?));
+    if class.get_super_class_index() != 0 {
+        api.insert(format!("superclass {}", class.get_super_class()?));
+    }
+    for name in class.get_interfaces()? {
+        api.insert(format!("interface {}", name));
+    }
+
+    let pool = class.get_constants();
+    let member = |flags: u16, what: &str, name: u16, descriptor: u16| -> Result<Option<String>, Fault> {
+        let visibility = if flags & (MethodAccessFlags::Public as u16) != 0 {
+            "public"
+        } else if flags & (MethodAccessFlags::Protected as u16) != 0 {
+            "protected"
+        } else {
+            return Ok(None);
+        };
+        let mut text = visibility.to_string();
+        let modifiers = [
+            (MethodAccessFlags::Static as u16, " static"),
+            (MethodAccessFlags::Final as u16, " final"),
+            (MethodAccessFlags::Abstract as u16, " abstract")
+        ];
+        for &(flag, word) in modifiers.iter() {
+            if flags & flag != 0 {
+                text.push_str(word);
+            }
+        }
+        let name = pool.get_utf8(name).ok_or(Fault::UTF8LookupFailure)?;
+        let descriptor = pool.get_utf8(descriptor).ok_or(Fault::UTF8LookupFailure)?;
+        Ok(Some(format!("{} {} {}:{}", text, what, name, descriptor)))
+    };
+    for i in 0..class.get_fields_count() {
+        let f = class.get_field(i).unwrap();
+        // fields have no abstract flag, the bit is unused
+        let flags = f.access_flags & !(FieldAccessFlags::Volatile as u16 | MethodAccessFlags::Abstract as u16);
+        api.extend(member(flags, "field", f.name_index, f.descriptor_index)?);
+    }
+    for i in 0..class.get_methods_count() {
+        let m = class.get_method(i).unwrap();
+        api.extend(member(m.access_flags, "method", m.name_index, m.descriptor_index)?);
+    }
+    Ok(api)
+}
+
+#[test]
+fn test_multi_release() {
+    use super::super::bytecode::assemble_class;
+    use super::build_zip;
+    let class = |source: &str| assemble_class(source).unwrap();
+    let base = class("\
+.class public demo/Api
+.super java/lang/Object
+.method public static run()V
+    return
+.end method
+");
+    let same = class("\
+.version 53
+.class public demo/Api
+.super java/lang/Object
+.method public static run()V
+    return
+.end method
+.method private helper()V
+    return
+.end method
+");
+    let changed = class("\
+.version 55
+.class public demo/Api
+.super java/lang/Object
+.method public static run(I)V
+    return
+.end method
+");
+    let util = class(".class demo/Util\n.super java/lang/Object\n");
+    let too_new = class(".version 61\n.class demo/Util\n.super java/lang/Object\n");
+    let new_public = class(".version 53\n.class public demo/Extra\n.super java/lang/Object\n");
+    let manifest = b"Manifest-Version: 1.0\r\nCreated-By: test\r\nMulti-Rel\r\n ease: true\r\n\r\nName: demo/Api.class\r\nX: y\r\n";
+
+    let jar = build_zip(&[
+        ("META-INF/MANIFEST.MF", manifest),
+        ("demo/Api.class", &base),
+        ("demo/Util.class", &util),
+        ("META-INF/versions/9/demo/Api.class", &same),
+        ("META-INF/versions/9/demo/Extra.class", &new_public),
+        ("META-INF/versions/11/demo/Api.class", &changed),
+        ("META-INF/versions/11/demo/Util.class", &too_new),
+        ("META-INF/versions/x/demo/Api.class", b"")
+    ]);
+    let archive = Archive::parse(&jar).unwrap();
+    assert!(archive.is_multi_release());
+    assert_eq!(archive.releases(), [9, 11]);
+    let loaded = |release| archive.lookup("demo/Api.class", release).map(|e| e.name.as_str());
+    assert_eq!(loaded(8), Some("demo/Api.class"));
+    assert_eq!(loaded(10), Some("META-INF/versions/9/demo/Api.class"));
+    assert_eq!(loaded(17), Some("META-INF/versions/11/demo/Api.class"));
+    assert_eq!(archive.lookup("demo/Extra.class", 8), None);
+
+    let errors = archive.check_releases().iter().map(|e| format!("{}", e)).collect::<Vec<_>>();
+    assert_eq!(errors, [
+        "public class not in an earlier release in entry META-INF/versions/9/demo/Extra.class",
+        "removes public static method run:()V in entry META-INF/versions/11/demo/Api.class",
+        "adds public static method run:(I)V in entry META-INF/versions/11/demo/Api.class",
+        "class file version 61 is too new for its release in entry META-INF/versions/11/demo/Util.class"
+    ]);
+
+    // without the attribute versioned entries are plain resources
+    let plain = build_zip(&[("demo/Api.class", &base), ("META-INF/versions/9/demo/Api.class", &same)]);
+    let archive = Archive::parse(&plain).unwrap();
+    assert!(!archive.is_multi_release());
+    assert_eq!(archive.lookup("demo/Api.class", 17).unwrap().name, "demo/Api.class");
+    assert!(archive.check_releases().is_empty());
+}
@@ -0,0 +1,1195 @@
+//! A Jasmin-like assembler for whole classes.
+//!
+//! Sources are line based. Directives start with a `.`, anything else
+//! inside a method is a label (`Loop:`) or an instruction written with
+//! its `javap` mnemonic. A `;` at the start of a token begins a comment.
+//!
+//! ```text
+//! .version 49 0
+//! .class public super Hello
+//! .super java/lang/Object
+//! .field static final K I = 5
+//!
+//! .method public static main([Ljava/lang/String;)V
+//!     .limit stack 2
+//!     getstatic java/lang/System.out:Ljava/io/PrintStream;
+//!     ldc "hello"
+//!     invokevirtual java/io/PrintStream.println:(Ljava/lang/String;)V
+//!     return
+//! .end method
+//! ```
+//!
+//! Members are written `owner.name:descriptor` as in `javap` comments, a
+//! missing owner means the class being assembled. Class level directives
+//! are `.version major [minor]`, `.class flags.. name`, `.interface
+//! flags.. name`, `.super`, `.implements`, `.source` and `.field flags..
+//! name descriptor [= value]`. Inside `.method flags.. name(desc)` and
+//! `.end method` there are
+//!
+//! * `.limit stack n` and `.limit locals n`, computed when left out
+//! * `.catch class|all from L1 to L2 using L3`
+//! * `.line n`, the line of the next instruction
+//! * `.var slot is name descriptor [from L1 to L2]`
+//! * `.throws class`
+//!
+//! Instructions are encoded exactly as written: `iload 1` is not narrowed
+//! to `iload_1`, `goto` is never widened and switch padding follows from
+//! the pc. `tableswitch low [high]` and `lookupswitch` are followed by
+//! one line per target, `label` or `key: label` respectively, ending with
+//! `default: label`. A `high` given must match the number of targets.
+//! Lookup keys may be written in any order. No `StackMapTable` is
+//! generated, which is why the class file version defaults to 49.0.
+
+use super::super::clazz::{
+    CodeAttribute,
+    ExceptionEntry,
+    FieldType,
+    MethodType,
+    JavaString,
+    ClassAccessFlags,
+    FieldAccessFlags,
+    MethodAccessFlags,
+    AttributeInfo,
+    LineNumber,
+    LocalVariable,
+    parse_constant_pool,
+    write_attribute,
+    write_line_number_table,
+    write_local_variable_table
+};
+use super::super::nom::IResult;
+use super::ops::JOp;
+use super::labels::Jump;
+use super::encoder::{encode_op, MAX_CODE_LEN};
+use super::cfg::Cfg;
+use super::stack::StackInfo;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// Why a class could not be assembled
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum AsmFault {
+    /// An unknown directive, or one used outside of where it belongs
+    BadDirective,
+    UnknownInstruction,
+    /// An operand is missing, malformed or out of range
+    BadOperand,
+    /// `invokedynamic`, which needs bootstrap methods
+    Unsupported,
+    UndefinedLabel,
+    DuplicateLabel,
+    /// A branch offset does not fit in its instruction
+    BranchTooFar,
+    /// There is no `.class` or `.interface` directive before it is needed
+    MissingClass,
+    /// A `.method` without its `.end method`
+    UnterminatedMethod,
+    /// The constant pool would hold more than 65535 slots
+    PoolOverflow,
+    CodeTooLarge,
+    /// `.limit stack` was left out and could not be computed
+    Limits
+}
+
+/// An assembly failure and the source line causing it
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct AsmError {
+    /// 1 based
+    pub line: usize,
+    pub reason: AsmFault
+}
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            AsmFault::BadDirective => write!(f, "invalid or misplaced directive"),
+            AsmFault::UnknownInstruction => write!(f, "unknown instruction"),
+            AsmFault::BadOperand => write!(f, "invalid operand"),
+            AsmFault::Unsupported => write!(f, "unsupported instruction"),
+            AsmFault::UndefinedLabel => write!(f, "undefined label"),
+            AsmFault::DuplicateLabel => write!(f, "label defined twice"),
+            AsmFault::BranchTooFar => write!(f, "branch offset out of range"),
+            AsmFault::MissingClass => write!(f, "no .class directive"),
+            AsmFault::UnterminatedMethod => write!(f, "missing .end method"),
+            AsmFault::PoolOverflow => write!(f, "constant pool overflow"),
+            AsmFault::CodeTooLarge => write!(f, "code array exceeds {} bytes", MAX_CODE_LEN),
+            AsmFault::Limits => write!(f, "cannot compute max_stack, add .limit stack")
+        }?;
+        write!(f, " at line {}", self.line)
+    }
+}
+
+/// Instructions without operands, looked up by mnemonic
+const SIMPLE_OPS: &[JOp] = &[
+    JOp::Nop, JOp::AConstNull, JOp::IConstM1, JOp::IConst0, JOp::IConst1, JOp::IConst2,
+    JOp::IConst3, JOp::IConst4, JOp::IConst5, JOp::LConst0, JOp::LConst1, JOp::FConst0,
+    JOp::FConst1, JOp::FConst2, JOp::DConst0, JOp::DConst1, JOp::ILoad0, JOp::ILoad1,
+    JOp::ILoad2, JOp::ILoad3, JOp::LLoad0, JOp::LLoad1, JOp::LLoad2, JOp::LLoad3, JOp::FLoad0,
+    JOp::FLoad1, JOp::FLoad2, JOp::FLoad3, JOp::DLoad0, JOp::DLoad1, JOp::DLoad2, JOp::DLoad3,
+    JOp::ALoad0, JOp::ALoad1, JOp::ALoad2, JOp::ALoad3, JOp::IALoad, JOp::LALoad, JOp::FALoad,
+    JOp::DALoad, JOp::AALoad, JOp::BaLoad, JOp::CaLoad, JOp::SALoad, JOp::IStore0, JOp::IStore1,
+    JOp::IStore2, JOp::IStore3, JOp::LStore0, JOp::LStore1, JOp::LStore2, JOp::LStore3,
+    JOp::FStore0, JOp::FStore1, JOp::FStore2, JOp::FStore3, JOp::DStore0, JOp::DStore1,
+    JOp::DStore2, JOp::DStore3, JOp::AStore0, JOp::AStore1, JOp::AStore2, JOp::AStore3,
+    JOp::IAStore, JOp::LAStore, JOp::FAStore, JOp::DAStore, JOp::AAStore, JOp::BaStore,
+    JOp::CaStore, JOp::SAStore, JOp::Pop, JOp::Pop2, JOp::Dup, JOp::Dupx1, JOp::Dupx2,
+    JOp::Dup2, JOp::Dup2x1, JOp::Dup2x2, JOp::Swap, JOp::IAdd, JOp::LAdd, JOp::FAdd, JOp::DAdd,
+    JOp::ISub, JOp::LSub, JOp::FSub, JOp::DSub, JOp::IMul, JOp::LMul, JOp::FMul, JOp::DMul,
+    JOp::IDiv, JOp::LDiv, JOp::FDiv, JOp::DDiv, JOp::IRem, JOp::LRem, JOp::FRem, JOp::DRem,
+    JOp::INeg, JOp::LNeg, JOp::FNeg, JOp::DNeg, JOp::ISHL, JOp::LSHL, JOp::ISHR, JOp::LSHR,
+    JOp::IUSHR, JOp::LUSHR, JOp::IAnd, JOp::LAnd, JOp::IOr, JOp::LOr, JOp::IXor, JOp::LXor,
+    JOp::I2L, JOp::I2F, JOp::I2D, JOp::L2I, JOp::L2F, JOp::L2D, JOp::F2I, JOp::F2L, JOp::F2D,
+    JOp::D2I, JOp::D2L, JOp::D2F, JOp::I2B, JOp::I2C, JOp::I2S, JOp::Lcmp, JOp::FcmpL,
+    JOp::FcmpG, JOp::DcmpL, JOp::DcmpG, JOp::IReturn, JOp::LReturn, JOp::FReturn, JOp::DReturn,
+    JOp::AReturn, JOp::Return, JOp::ArrayLength, JOp::AThrow, JOp::MonitorEnter,
+    JOp::MonitorExit, JOp::BreakPoint, JOp::ImpDep1, JOp::ImpDep2
+];
+
+/// Branches with a 16 bit offset
+const BRANCH_OPS: &[JOp] = &[
+    JOp::IFEQ(0), JOp::IFNE(0), JOp::IFLT(0), JOp::IFGE(0), JOp::IFGT(0), JOp::IFLE(0),
+    JOp::IFIcmpEQ(0), JOp::IFIcmpNE(0), JOp::IFIcmpLT(0), JOp::IfIcmpGE(0), JOp::IFIcmpGT(0),
+    JOp::IFIcmpLE(0), JOp::IFAcmpEQ(0), JOp::IFAcmpNE(0), JOp::IFNull(0), JOp::IFnonNull(0),
+    JOp::Goto(0), JOp::JSR(0)
+];
+
+/// Constant pool under construction, entries are shared by their encoding
+struct Pool {
+    bytes: Vec<u8>,
+    index: HashMap<Vec<u8>, u16>,
+    next: u32
+}
+impl Pool {
+
+    fn add(&mut self, entry: Vec<u8>) -> Result<u16, AsmFault> {
+        if let Option::Some(x) = self.index.get(&entry) {
+            return Ok(*x);
+        }
+        // longs and doubles take two slots
+        let slots = if entry[0] == 5 || entry[0] == 6 { 2 } else { 1 };
+        if self.next + slots > 0xFFFF {
+            return Err(AsmFault::PoolOverflow);
+        }
+        let x = self.next as u16;
+        self.next += slots;
+        self.bytes.extend_from_slice(&entry);
+        self.index.insert(entry, x);
+        Ok(x)
+    }
+
+    fn with_index(tag: u8, x: u16) -> Vec<u8> {
+        let mut entry = vec![tag];
+        entry.extend_from_slice(&x.to_be_bytes());
+        entry
+    }
+
+    fn with_pair(tag: u8, x: u16, y: u16) -> Vec<u8> {
+        let mut entry = Pool::with_index(tag, x);
+        entry.extend_from_slice(&y.to_be_bytes());
+        entry
+    }
+
+    fn java_string(&mut self, s: &JavaString) -> Result<u16, AsmFault> {
+        let bytes = s.encode();
+        if bytes.len() > 0xFFFF {
+            return Err(AsmFault::BadOperand);
+        }
+        let mut entry = Pool::with_index(1, bytes.len() as u16);
+        entry.extend_from_slice(&bytes);
+        self.add(entry)
+    }
+
+    fn utf8(&mut self, s: &str) -> Result<u16, AsmFault> {
+        self.java_string(&JavaString::from_utf16(s.encode_utf16().collect()))
+    }
+
+    fn class(&mut self, name: &str) -> Result<u16, AsmFault> {
+        let x = self.utf8(name)?;
+        self.add(Pool::with_index(7, x))
+    }
+
+    fn name_and_type(&mut self, name: &str, desc: &str) -> Result<u16, AsmFault> {
+        let n = self.utf8(name)?;
+        let d = self.utf8(desc)?;
+        self.add(Pool::with_pair(12, n, d))
+    }
+
+    /// `tag` is 9, 10 or 11 for field, method and interface method references
+    fn member(&mut self, tag: u8, owner: &str, name: &str, desc: &str) -> Result<u16, AsmFault> {
+        let c = self.class(owner)?;
+        let nat = self.name_and_type(name, desc)?;
+        self.add(Pool::with_pair(tag, c, nat))
+    }
+
+    fn number(&mut self, tag: u8, bits: u64) -> Result<u16, AsmFault> {
+        let mut entry = vec![tag];
+        if tag == 5 || tag == 6 {
+            entry.extend_from_slice(&bits.to_be_bytes());
+        } else {
+            entry.extend_from_slice(&(bits as u32).to_be_bytes());
+        }
+        self.add(entry)
+    }
+
+    /// The pool as it appears in a class file, count included
+    fn encode(&self) -> Vec<u8> {
+        let mut out = (self.next as u16).to_be_bytes().to_vec();
+        out.extend_from_slice(&self.bytes);
+        out
+    }
+}
+
+/// Split a line into tokens
+///
+/// Commas separate like whitespace, quoted strings are one token with
+/// their quotes kept.
+fn tokenize(line: &str) -> Vec<String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars();
+    while let Option::Some(c) = chars.next() {
+        if quoted {
+            current.push(c);
+            match c {
+                '\\' => current.extend(chars.next()),
+                '"' => quoted = false,
+                _ => {}
+            };
+            continue;
+        }
+        match c {
+            ';' if current.is_empty() => break,
+            '"' => {
+                quoted = true;
+                current.push(c);
+            },
+            c if c.is_whitespace() || c == ',' => {
+                if !current.is_empty() {
+                    tokens.push(current.clone());
+                    current.clear();
+                }
+            },
+            c => current.push(c)
+        };
+    }
+    if !current.is_empty() {
+        tokens.push(current);
+    }
+    tokens
+}
+
+/// Drop the quotes around a name such as `"<init>"`
+fn unquote(s: &str) -> &str {
+    if s.len() >= 2 && s.starts_with('"') && s.ends_with('"') {
+        &s[1..s.len() - 1]
+    } else {
+        s
+    }
+}
+
+/// The UTF-16 units of a quoted string literal, with escapes applied
+fn string_literal(s: &str) -> Result<Vec<u16>, AsmFault> {
+    if s.len() < 2 || !s.starts_with('"') || !s.ends_with('"') {
+        return Err(AsmFault::BadOperand);
+    }
+    let mut units = Vec::new();
+    let mut chars = s[1..s.len() - 1].chars();
+    while let Option::Some(c) = chars.next() {
+        if c != '\\' {
+            let mut buf = [0u16; 2];
+            units.extend_from_slice(c.encode_utf16(&mut buf));
+            continue;
+        }
+        let unit = match chars.next() {
+            Option::Some('n') => '\n' as u16,
+            Option::Some('t') => '\t' as u16,
+            Option::Some('r') => '\r' as u16,
+            Option::Some('b') => 8,
+            Option::Some('f') => 12,
+            Option::Some('"') => '"' as u16,
+            Option::Some('\'') => '\'' as u16,
+            Option::Some('\\') => '\\' as u16,
+            // may be half of a surrogate pair, or an unpaired one
+            Option::Some('u') => {
+                let hex: String = chars.by_ref().take(4).collect();
+                if hex.len() != 4 {
+                    return Err(AsmFault::BadOperand);
+                }
+                u16::from_str_radix(&hex, 16).map_err(|_| AsmFault::BadOperand)?
+            },
+            _ => return Err(AsmFault::BadOperand)
+        };
+        units.push(unit);
+    }
+    Ok(units)
+}
+
+fn number<T: ::std::str::FromStr>(s: &str) -> Result<T, AsmFault> {
+    s.parse().map_err(|_| AsmFault::BadOperand)
+}
+
+/// Parse a float or double literal, without its suffix
+fn float_literal(s: &str) -> Result<f64, AsmFault> {
+    match s {
+        "Infinity" => Ok(f64::INFINITY),
+        "-Infinity" => Ok(f64::NEG_INFINITY),
+        "NaN" => Ok(f64::NAN),
+        _ => number(s)
+    }
+}
+
+/// Access flags from their names, `public static`
+fn access_flags<T: fmt::Debug + Into<u16> + Copy>(words: &[String], all: Vec<T>) -> Result<u16, AsmFault> {
+    let mut flags = 0;
+    for word in words {
+        let word = if word == "strictfp" { "strict" } else { word.as_str() };
+        match all.iter().find(|f| format!("{:?}", f).to_lowercase() == word) {
+            Option::Some(f) => flags |= (*f).into(),
+            Option::None => return Err(AsmFault::BadOperand)
+        };
+    }
+    Ok(flags)
+}
+
+/// Attribute name indices and bodies
+type Attributes = Vec<(u16, Vec<u8>)>;
+
+/// Where the labels of a branching instruction point
+enum Targets {
+    None,
+    Branch(String),
+    Table(String, Vec<String>),
+    Lookup(String, Vec<(i32, String)>)
+}
+
+/// One instruction, operands other than branch offsets already final
+struct Item {
+    line: usize,
+    op: JOp,
+    targets: Targets
+}
+
+/// A switch whose target lines are being read
+struct PendingSwitch {
+    line: usize,
+    /// `Some(low)` for `tableswitch`
+    low: Option<i32>,
+    /// The `high` a `tableswitch` was written with, if any
+    high: Option<i32>,
+    targets: Vec<(i32, String)>
+}
+
+struct Catch {
+    line: usize,
+    start: String,
+    end: String,
+    handler: String,
+    catch_type: u16
+}
+
+struct Var {
+    line: usize,
+    slot: u16,
+    name: u16,
+    descriptor: u16,
+    /// `None` spans the whole method
+    range: Option<(String, String)>
+}
+
+/// A method between `.method` and `.end method`
+struct MethodSource {
+    line: usize,
+    flags: u16,
+    name: u16,
+    descriptor: u16,
+    arg_slots: u16,
+    items: Vec<Item>,
+    /// Index of the item each label precedes
+    labels: HashMap<String, usize>,
+    catches: Vec<Catch>,
+    lines: Vec<(usize, u16)>,
+    vars: Vec<Var>,
+    throws: Vec<u16>,
+    max_stack: Option<u16>,
+    max_locals: Option<u16>,
+    switch: Option<PendingSwitch>
+}
+
+/// A method laid out, its limits may still be unknown
+struct MethodOut {
+    line: usize,
+    flags: u16,
+    name: u16,
+    descriptor: u16,
+    arg_slots: u16,
+    /// `None` for methods without a `Code` attribute
+    code: Option<Vec<u8>>,
+    ops: Vec<(usize, JOp)>,
+    exceptions: Vec<ExceptionEntry>,
+    code_attributes: Attributes,
+    attributes: Attributes,
+    max_stack: Option<u16>,
+    max_locals: Option<u16>
+}
+
+struct Assembler {
+    pool: Pool,
+    version: (u16, u16),
+    flags: u16,
+    this: Option<String>,
+    this_index: u16,
+    super_index: Option<u16>,
+    interfaces: Vec<u16>,
+    /// flags, name, descriptor and attributes
+    fields: Vec<(u16, u16, u16, Attributes)>,
+    methods: Vec<MethodOut>,
+    attributes: Attributes,
+    method: Option<MethodSource>,
+    /// A method whose `.end method` was just read
+    ended: Option<MethodSource>,
+    /// The source line being read
+    line: usize
+}
+
+impl Assembler {
+
+    fn this(&self) -> Result<String, AsmFault> {
+        self.this.clone().ok_or(AsmFault::MissingClass)
+    }
+
+    /// `[kind] owner.name:descriptor`, kind being `Field`, `Method` or `InterfaceMethod`
+    fn member(&mut self, tokens: &[String], default: u8) -> Result<u16, AsmFault> {
+        let (tag, text) = match tokens {
+            [kind, text] => (match kind.as_str() {
+                "Field" => 9,
+                "Method" => 10,
+                "InterfaceMethod" => 11,
+                _ => return Err(AsmFault::BadOperand)
+            }, text),
+            [text] => (default, text),
+            _ => return Err(AsmFault::BadOperand)
+        };
+        // the first colon outside of quotes ends the name
+        let mut quoted = false;
+        let colon = text.char_indices()
+            .find(|&(_, c)| {
+                if c == '"' {
+                    quoted = !quoted;
+                }
+                c == ':' && !quoted
+            })
+            .map(|(i, _)| i)
+            .ok_or(AsmFault::BadOperand)?;
+        let (left, desc) = (&text[..colon], &text[colon + 1..]);
+        let (owner, name) = match left.rfind('.') {
+            Option::Some(dot) => (unquote(&left[..dot]).to_string(), unquote(&left[dot + 1..])),
+            Option::None => (self.this()?, unquote(left))
+        };
+        let valid = if tag == 9 {
+            FieldType::parse(desc).is_some()
+        } else {
+            MethodType::parse(desc).is_some()
+        };
+        if !valid || name.is_empty() || owner.is_empty() {
+            return Err(AsmFault::BadOperand);
+        }
+        self.pool.member(tag, &owner, name, desc)
+    }
+
+    fn class_operand(&mut self, tokens: &[String]) -> Result<u16, AsmFault> {
+        match tokens {
+            [name] => self.pool.class(unquote(name)),
+            _ => Err(AsmFault::BadOperand)
+        }
+    }
+
+    /// The operand of `ldc`, `ldc_w` and `ldc2_w`
+    ///
+    /// Either a kind and value as printed by `javap`, `int 5`, or a bare
+    /// literal: `"text"`, `5`, `1.5f`, `2l` and `2.0d`. Unsuffixed numbers
+    /// are `int` and `float` for `ldc`, `long` and `double` for `ldc2_w`.
+    fn constant(&mut self, tokens: &[String], wide: bool) -> Result<u16, AsmFault> {
+        let value = match tokens {
+            [kind, value] => {
+                let value = value.as_str();
+                return match kind.as_str() {
+                    "int" => self.pool.number(3, number::<i32>(value)? as u32 as u64),
+                    "float" => self.pool.number(4, (float_literal(value.trim_end_matches('f'))? as f32).to_bits() as u64),
+                    "long" => self.pool.number(5, number::<i64>(value.trim_end_matches('l'))? as u64),
+                    "double" => self.pool.number(6, float_literal(value.trim_end_matches('d'))?.to_bits()),
+                    "String" => self.constant(&tokens[1..], wide),
+                    "class" => self.pool.class(unquote(value)),
+                    "MethodType" => {
+                        MethodType::parse(value).ok_or(AsmFault::BadOperand)?;
+                        let x = self.pool.utf8(value)?;
+                        self.pool.add(Pool::with_index(16, x))
+                    },
+                    _ => Err(AsmFault::BadOperand)
+                };
+            },
+            [kind, handle, rest @ ..] if kind == "MethodHandle" => {
+                let kinds = ["REF_getField", "REF_getStatic", "REF_putField", "REF_putStatic",
+                    "REF_invokeVirtual", "REF_invokeStatic", "REF_invokeSpecial",
+                    "REF_newInvokeSpecial", "REF_invokeInterface"];
+                let k = kinds.iter().position(|x| x == handle).ok_or(AsmFault::BadOperand)? as u8 + 1;
+                let default = match k {
+                    1..=4 => 9,
+                    9 => 11,
+                    _ => 10
+                };
+                let x = self.member(rest, default)?;
+                let mut entry = vec![15, k];
+                entry.extend_from_slice(&x.to_be_bytes());
+                return self.pool.add(entry);
+            },
+            [value] => value.as_str(),
+            _ => return Err(AsmFault::BadOperand)
+        };
+        if value.starts_with('"') {
+            let units = string_literal(value)?;
+            let x = self.pool.java_string(&JavaString::from_utf16(units))?;
+            return self.pool.add(Pool::with_index(8, x));
+        }
+        let (body, suffix) = match value.chars().last() {
+            Option::Some(c @ 'f') | Option::Some(c @ 'l') | Option::Some(c @ 'd') if !value.ends_with("Infinity") => {
+                (&value[..value.len() - 1], Some(c))
+            },
+            _ => (value, None)
+        };
+        let floating = body.contains(['.', 'e', 'E']) || body.ends_with("Infinity") || body == "NaN";
+        match (suffix, wide, floating) {
+            (Option::Some('f'), _, _) |
+            (Option::None, false, true) => self.pool.number(4, (float_literal(body)? as f32).to_bits() as u64),
+            (Option::Some('l'), _, _) |
+            (Option::None, true, false) => self.pool.number(5, number::<i64>(body)? as u64),
+            (Option::Some('d'), _, _) |
+            (Option::None, true, true) => self.pool.number(6, float_literal(body)?.to_bits()),
+            _ => self.pool.number(3, number::<i32>(body)? as u32 as u64)
+        }
+    }
+
+    /// A `.field` value as the `ConstantValue` its descriptor calls for
+    fn constant_value(&mut self, desc: &str, value: &str) -> Result<u16, AsmFault> {
+        let token = vec![value.to_string()];
+        match desc {
+            "I" | "S" | "C" | "B" | "Z" => self.pool.number(3, number::<i32>(value)? as u32 as u64),
+            "F" => self.pool.number(4, (float_literal(value.trim_end_matches('f'))? as f32).to_bits() as u64),
+            "J" => self.pool.number(5, number::<i64>(value.trim_end_matches('l'))? as u64),
+            "D" => self.pool.number(6, float_literal(value.trim_end_matches('d'))?.to_bits()),
+            "Ljava/lang/String;" => self.constant(&token, false),
+            _ => Err(AsmFault::BadOperand)
+        }
+    }
+
+    fn directive(&mut self, tokens: &[String]) -> Result<(), AsmFault> {
+        let args = &tokens[1..];
+        if self.method.is_some() {
+            return self.method_directive(tokens);
+        }
+        match tokens[0].as_str() {
+            ".version" => {
+                self.version = match args {
+                    [major] => (number(major)?, 0),
+                    [major, minor] => (number(major)?, number(minor)?),
+                    _ => return Err(AsmFault::BadOperand)
+                };
+            },
+            x @ ".class" | x @ ".interface" => {
+                let (name, words) = args.split_last().ok_or(AsmFault::BadOperand)?;
+                if self.this.is_some() {
+                    return Err(AsmFault::BadDirective);
+                }
+                self.flags = access_flags(words, ClassAccessFlags::and_mask(0xFFFF))?;
+                if x == ".interface" {
+                    self.flags |= 0x0600;
+                }
+                self.this = Some(unquote(name).to_string());
+                self.this_index = self.pool.class(unquote(name))?;
+            },
+            ".super" => self.super_index = Some(self.class_operand(args)?),
+            ".implements" => {
+                let x = self.class_operand(args)?;
+                self.interfaces.push(x);
+            },
+            ".source" => {
+                let file = match args {
+                    [file] => unquote(file),
+                    _ => return Err(AsmFault::BadOperand)
+                };
+                let name = self.pool.utf8("SourceFile")?;
+                let x = self.pool.utf8(file)?;
+                self.attributes.push((name, x.to_be_bytes().to_vec()));
+            },
+            ".field" => {
+                let split = args.iter().position(|x| x == "=").unwrap_or(args.len());
+                if split < 2 || (split != args.len() && split + 2 != args.len()) {
+                    return Err(AsmFault::BadOperand);
+                }
+                let (name, desc) = (unquote(&args[split - 2]), &args[split - 1]);
+                FieldType::parse(desc).ok_or(AsmFault::BadOperand)?;
+                let flags = access_flags(&args[..split - 2], FieldAccessFlags::and_mask(0xFFFF))?;
+                let name = self.pool.utf8(name)?;
+                let descriptor = self.pool.utf8(desc)?;
+                let mut attributes = Vec::new();
+                if let Option::Some(value) = args.get(split + 1) {
+                    let attr = self.pool.utf8("ConstantValue")?;
+                    let x = self.constant_value(desc, value)?;
+                    attributes.push((attr, x.to_be_bytes().to_vec()));
+                }
+                self.fields.push((flags, name, descriptor, attributes));
+            },
+            ".method" => {
+                let (decl, words) = args.split_last().ok_or(AsmFault::BadOperand)?;
+                let paren = decl.find('(').ok_or(AsmFault::BadOperand)?;
+                let (name, desc) = (unquote(&decl[..paren]), &decl[paren..]);
+                let t = MethodType::parse(desc).ok_or(AsmFault::BadOperand)?;
+                let flags = access_flags(words, MethodAccessFlags::and_mask(0xFFFF))?;
+                self.method = Some(MethodSource {
+                    line: self.line,
+                    flags,
+                    name: self.pool.utf8(name)?,
+                    descriptor: self.pool.utf8(desc)?,
+                    arg_slots: t.param_slots() + if flags & 0x0008 != 0 { 0 } else { 1 },
+                    items: Vec::new(),
+                    labels: HashMap::new(),
+                    catches: Vec::new(),
+                    lines: Vec::new(),
+                    vars: Vec::new(),
+                    throws: Vec::new(),
+                    max_stack: None,
+                    max_locals: None,
+                    switch: None
+                });
+            },
+            _ => return Err(AsmFault::BadDirective)
+        };
+        Ok(())
+    }
+
+    fn method_directive(&mut self, tokens: &[String]) -> Result<(), AsmFault> {
+        let args = &tokens[1..];
+        let line = self.line;
+        match (tokens[0].as_str(), args) {
+            (".limit", [what, n]) => {
+                let n = Some(number(n)?);
+                let m = self.method.as_mut().unwrap();
+                match what.as_str() {
+                    "stack" => m.max_stack = n,
+                    "locals" => m.max_locals = n,
+                    _ => return Err(AsmFault::BadOperand)
+                };
+            },
+            (".catch", [class, from, start, to, end, using, handler])
+                if from == "from" && to == "to" && using == "using" => {
+                let catch_type = match class.as_str() {
+                    "all" => 0,
+                    x => self.pool.class(unquote(x))?
+                };
+                self.method.as_mut().unwrap().catches.push(Catch {
+                    line,
+                    start: start.clone(),
+                    end: end.clone(),
+                    handler: handler.clone(),
+                    catch_type
+                });
+            },
+            (".line", [n]) => {
+                let n = number(n)?;
+                let m = self.method.as_mut().unwrap();
+                let at = m.items.len();
+                m.lines.push((at, n));
+            },
+            (".var", [slot, is, name, desc, rest @ ..]) if is == "is" => {
+                let range = match rest {
+                    [] => None,
+                    [from, start, to, end] if from == "from" && to == "to" => Some((start.clone(), end.clone())),
+                    _ => return Err(AsmFault::BadOperand)
+                };
+                let var = Var {
+                    line,
+                    slot: number(slot)?,
+                    name: self.pool.utf8(unquote(name))?,
+                    descriptor: self.pool.utf8(desc)?,
+                    range
+                };
+                self.method.as_mut().unwrap().vars.push(var);
+            },
+            (".throws", _) => {
+                let x = self.class_operand(args)?;
+                self.method.as_mut().unwrap().throws.push(x);
+            },
+            (".end", [what]) if what == "method" => {
+                let m = self.method.take().unwrap();
+                if m.switch.is_some() {
+                    return Err(AsmFault::BadOperand);
+                }
+                self.ended = Some(m);
+            },
+            (".limit", _) | (".catch", _) | (".line", _) | (".var", _) => return Err(AsmFault::BadOperand),
+            _ => return Err(AsmFault::BadDirective)
+        };
+        Ok(())
+    }
+
+    /// One line of a `tableswitch` or `lookupswitch` body
+    fn switch_line(&mut self, tokens: &[String]) -> Result<(), AsmFault> {
+        let text = tokens.join(" ");
+        let parts: Vec<&str> = text.split(':').map(str::trim).collect();
+        let m = self.method.as_mut().unwrap();
+        let pending = m.switch.as_mut().unwrap();
+        match (pending.low, parts.as_slice()) {
+            (_, ["default", label]) => {
+                let pending = m.switch.take().unwrap();
+                let default = label.to_string();
+                let (op, targets) = match pending.low {
+                    Option::Some(low) => {
+                        if pending.targets.is_empty() {
+                            return Err(AsmFault::BadOperand);
+                        }
+                        let high = low.checked_add(pending.targets.len() as i32 - 1).ok_or(AsmFault::BadOperand)?;
+                        if pending.high.is_some_and(|h| h != high) {
+                            return Err(AsmFault::BadOperand);
+                        }
+                        let labels: Vec<String> = pending.targets.into_iter().map(|(_, l)| l).collect();
+                        (JOp::TableSwitch(0, low, high, vec![0; labels.len()]), Targets::Table(default, labels))
+                    },
+                    Option::None => {
+                        // the JVM wants the keys sorted
+                        let mut targets = pending.targets;
+                        targets.sort_by_key(|&(k, _)| k);
+                        if targets.windows(2).any(|w| w[0].0 == w[1].0) {
+                            return Err(AsmFault::BadOperand);
+                        }
+                        let keys = targets.iter().map(|&(k, _)| (k, 0)).collect();
+                        (JOp::LookUpSwitch(0, keys), Targets::Lookup(default, targets))
+                    }
+                };
+                m.items.push(Item { line: pending.line, op, targets });
+            },
+            (Option::Some(_), [label]) => pending.targets.push((0, label.to_string())),
+            (Option::None, [key, label]) => pending.targets.push((number(key)?, label.to_string())),
+            _ => return Err(AsmFault::BadOperand)
+        };
+        Ok(())
+    }
+
+    fn instruction(&mut self, tokens: &[String]) -> Result<(), AsmFault> {
+        let line = self.line;
+        let name = tokens[0].as_str();
+        let args = &tokens[1..];
+        if let Option::Some(op) = SIMPLE_OPS.iter().find(|op| op.mnemonic() == name) {
+            if !args.is_empty() {
+                return Err(AsmFault::BadOperand);
+            }
+            return self.push(line, op.clone(), Targets::None);
+        }
+        let one = || match args {
+            [x] => Ok(x.as_str()),
+            _ => Err(AsmFault::BadOperand)
+        };
+        let byte = || one().and_then(number::<u8>);
+        let wide = || one().and_then(number::<u16>);
+        let op = match name {
+            "bipush" => JOp::BiPush(one().and_then(number::<i8>)? as u8),
+            "sipush" => JOp::SIPush(one().and_then(number::<i16>)? as u16),
+            "iload" => JOp::ILoad(byte()?),
+            "lload" => JOp::LLoad(byte()?),
+            "fload" => JOp::FLoad(byte()?),
+            "dload" => JOp::DLoad(byte()?),
+            "aload" => JOp::ALoad(byte()?),
+            "istore" => JOp::IStore(byte()?),
+            "lstore" => JOp::LStore(byte()?),
+            "fstore" => JOp::FStore(byte()?),
+            "dstore" => JOp::DStore(byte()?),
+            "astore" => JOp::AStore(byte()?),
+            "ret" => JOp::Ret(byte()?),
+            "iload_w" => JOp::ILoadWide(wide()?),
+            "lload_w" => JOp::LLoadWide(wide()?),
+            "fload_w" => JOp::FLoadWide(wide()?),
+            "dload_w" => JOp::DLoadWide(wide()?),
+            "aload_w" => JOp::ALoadWide(wide()?),
+            "istore_w" => JOp::IStoreWide(wide()?),
+            "lstore_w" => JOp::LStoreWide(wide()?),
+            "fstore_w" => JOp::FStoreWide(wide()?),
+            "dstore_w" => JOp::DStoreWide(wide()?),
+            "astore_w" => JOp::AStoreWide(wide()?),
+            "ret_w" => JOp::RetWide(wide()?),
+            "iinc" | "iinc_w" => match args {
+                [slot, c] if name == "iinc" => JOp::IInc(number(slot)?, number(c)?),
+                [slot, c] => JOp::IIncWide(number(slot)?, number(c)?),
+                _ => return Err(AsmFault::BadOperand)
+            },
+            "ldc" => {
+                let x = self.constant(args, false)?;
+                if x > 0xFF {
+                    return Err(AsmFault::BadOperand);
+                }
+                JOp::LDC(x as u8)
+            },
+            "ldc_w" => JOp::LDCW(self.constant(args, false)?),
+            "ldc2_w" => JOp::LDC2W(self.constant(args, true)?),
+            "getstatic" => JOp::GetStatic(self.member(args, 9)?),
+            "putstatic" => JOp::PutStatic(self.member(args, 9)?),
+            "getfield" => JOp::GetField(self.member(args, 9)?),
+            "putfield" => JOp::PutField(self.member(args, 9)?),
+            "invokevirtual" => JOp::InvokeVirtual(self.member(args, 10)?),
+            "invokespecial" => JOp::InvokeSpecial(self.member(args, 10)?),
+            "invokestatic" => JOp::InvokeStatic(self.member(args, 10)?),
+            "invokeinterface" => {
+                // the count defaults to the argument slots plus the receiver
+                let (member, count) = match args.last().map(|x| x.parse::<u8>()) {
+                    Option::Some(Ok(n)) => (&args[..args.len() - 1], Some(n)),
+                    _ => (args, None)
+                };
+                let x = self.member(member, 11)?;
+                let count = match count {
+                    Option::Some(n) => n,
+                    Option::None => {
+                        let desc = member.last().and_then(|m| m.find(":(").map(|i| &m[i + 1..]));
+                        let t = desc.and_then(MethodType::parse).ok_or(AsmFault::BadOperand)?;
+                        (t.param_slots() + 1) as u8
+                    }
+                };
+                JOp::InvokedInterface(x, count)
+            },
+            "invokedynamic" => return Err(AsmFault::Unsupported),
+            "new" => JOp::New(self.class_operand(args)?),
+            "anewarray" => JOp::ANewArray(self.class_operand(args)?),
+            "checkcast" => JOp::CheckCast(self.class_operand(args)?),
+            "instanceof" => JOp::InstanceOf(self.class_operand(args)?),
+            "multianewarray" => match args {
+                [class, dims] => JOp::MultiAneWArray(self.pool.class(unquote(class))?, number(dims)?),
+                _ => return Err(AsmFault::BadOperand)
+            },
+            "newarray" => {
+                let types = ["boolean", "char", "float", "double", "byte", "short", "int", "long"];
+                let t = one()?;
+                JOp::NewArray(types.iter().position(|x| *x == t).ok_or(AsmFault::BadOperand)? as u8 + 4)
+            },
+            "tableswitch" | "lookupswitch" => {
+                let (low, high) = match (name, args) {
+                    ("tableswitch", [low]) => (Some(number(low)?), None),
+                    ("tableswitch", [low, high]) => (Some(number(low)?), Some(number(high)?)),
+                    ("lookupswitch", []) => (None, None),
+                    _ => return Err(AsmFault::BadOperand)
+                };
+                self.method.as_mut().unwrap().switch = Some(PendingSwitch { line, low, high, targets: Vec::new() });
+                return Ok(());
+            },
+            "goto_w" => return self.push(line, JOp::GotoW(0), Targets::Branch(one()?.to_string())),
+            "jsr_w" => return self.push(line, JOp::JSRW(0), Targets::Branch(one()?.to_string())),
+            _ => {
+                // every other branch has a 16 bit offset
+                let jump = BRANCH_OPS.iter()
+                    .find(|op| op.mnemonic() == name)
+                    .ok_or(AsmFault::UnknownInstruction)?;
+                return self.push(line, jump.clone(), Targets::Branch(one()?.to_string()));
+            }
+        };
+        self.push(line, op, Targets::None)
+    }
+
+    fn push(&mut self, line: usize, op: JOp, targets: Targets) -> Result<(), AsmFault> {
+        self.method.as_mut().unwrap().items.push(Item { line, op, targets });
+        Ok(())
+    }
+
+    /// Assign pcs, resolve labels and encode a finished method
+    fn lay_out(&mut self, m: MethodSource) -> Result<MethodOut, AsmError> {
+        let err = |line: usize, reason: AsmFault| AsmError { line, reason };
+        let mut pcs = Vec::with_capacity(m.items.len() + 1);
+        let mut pc = 0;
+        for item in m.items.iter() {
+            pcs.push(pc);
+            pc += item.op.encoded_len(pc);
+            if pc > MAX_CODE_LEN {
+                return Err(err(item.line, AsmFault::CodeTooLarge));
+            }
+        }
+        pcs.push(pc);
+        let label = |line: usize, name: &str| match m.labels.get(name) {
+            Option::Some(x) => Ok(pcs[*x]),
+            Option::None => Err(err(line, AsmFault::UndefinedLabel))
+        };
+
+        let mut code = Vec::with_capacity(pc);
+        let mut ops = Vec::with_capacity(m.items.len());
+        for (item, &pc) in m.items.iter().zip(pcs.iter()) {
+            let offset = |name: &str| label(item.line, name).map(|x| x as i64 - pc as i64);
+            let op = match item.targets {
+                Targets::None => item.op.clone(),
+                Targets::Branch(ref name) => {
+                    let x = offset(name)?;
+                    match item.op {
+                        JOp::GotoW(_) => JOp::GotoW(x as i32),
+                        JOp::JSRW(_) => JOp::JSRW(x as i32),
+                        ref op => {
+                            if x < i16::MIN as i64 || x > i16::MAX as i64 {
+                                return Err(err(item.line, AsmFault::BranchTooFar));
+                            }
+                            Jump::from_op(op).unwrap().0.to_op(x as i16)
+                        }
+                    }
+                },
+                Targets::Table(ref default, ref labels) => match item.op {
+                    JOp::TableSwitch(_, low, high, _) => {
+                        let offsets = labels.iter().map(|l| offset(l).map(|x| x as i32)).collect::<Result<_, _>>()?;
+                        JOp::TableSwitch(offset(default)? as i32, low, high, offsets)
+                    },
+                    _ => unreachable!()
+                },
+                Targets::Lookup(ref default, ref pairs) => {
+                    let pairs = pairs.iter().map(|&(k, ref l)| offset(l).map(|x| (k, x as i32))).collect::<Result<_, _>>()?;
+                    JOp::LookUpSwitch(offset(default)? as i32, pairs)
+                }
+            };
+            encode_op(&op, pc, &mut code);
+            ops.push((pc, op));
+        }
+
+        let mut exceptions = Vec::with_capacity(m.catches.len());
+        for c in m.catches.iter() {
+            exceptions.push(ExceptionEntry {
+                start_pc: label(c.line, &c.start)? as u16,
+                end_pc: label(c.line, &c.end)? as u16,
+                handler_pc: label(c.line, &c.handler)? as u16,
+                catch_type: c.catch_type
+            });
+        }
+        let pool_err = |reason| err(m.line, reason);
+        let mut code_attributes = Vec::new();
+        if !m.lines.is_empty() {
+            let lines: Vec<LineNumber> = m.lines.iter()
+                .map(|&(at, line_number)| LineNumber { start_pc: pcs[at] as u16, line_number })
+                .collect();
+            let name = self.pool.utf8("LineNumberTable").map_err(pool_err)?;
+            code_attributes.push((name, write_line_number_table(&lines)));
+        }
+        if !m.vars.is_empty() {
+            let mut vars = Vec::with_capacity(m.vars.len());
+            for v in m.vars.iter() {
+                let (start, end) = match v.range {
+                    Option::Some((ref start, ref end)) => (label(v.line, start)?, label(v.line, end)?),
+                    Option::None => (0, pc)
+                };
+                vars.push(LocalVariable {
+                    start_pc: start as u16,
+                    length: end.saturating_sub(start) as u16,
+                    name_index: v.name,
+                    descriptor_index: v.descriptor,
+                    index: v.slot
+                });
+            }
+            let name = self.pool.utf8("LocalVariableTable").map_err(pool_err)?;
+            code_attributes.push((name, write_local_variable_table(&vars)));
+        }
+        let mut attributes = Vec::new();
+        if !m.throws.is_empty() {
+            let mut data = (m.throws.len() as u16).to_be_bytes().to_vec();
+            for x in m.throws.iter() {
+                data.extend_from_slice(&x.to_be_bytes());
+            }
+            attributes.push((self.pool.utf8("Exceptions").map_err(pool_err)?, data));
+        }
+        let has_code = !m.items.is_empty() || m.max_stack.is_some() || m.max_locals.is_some();
+        if has_code {
+            self.pool.utf8("Code").map_err(pool_err)?;
+        }
+        Ok(MethodOut {
+            line: m.line,
+            flags: m.flags,
+            name: m.name,
+            descriptor: m.descriptor,
+            arg_slots: m.arg_slots,
+            code: if has_code { Some(code) } else { None },
+            ops,
+            exceptions,
+            code_attributes,
+            attributes,
+            max_stack: m.max_stack,
+            max_locals: m.max_locals
+        })
+    }
+
+    fn statement(&mut self, text: &str) -> Result<(), AsmFault> {
+        let mut tokens = tokenize(text);
+        if tokens.is_empty() {
+            return Ok(());
+        }
+        if tokens[0].starts_with('.') {
+            return self.directive(&tokens);
+        }
+        let m = self.method.as_mut().ok_or(AsmFault::BadDirective)?;
+        if m.switch.is_some() {
+            return self.switch_line(&tokens);
+        }
+        if tokens[0].len() > 1 && tokens[0].ends_with(':') && !tokens[0].starts_with('"') {
+            let mut name = tokens.remove(0);
+            name.pop();
+            let at = m.items.len();
+            if m.labels.insert(name, at).is_some() {
+                return Err(AsmFault::DuplicateLabel);
+            }
+            if tokens.is_empty() {
+                return Ok(());
+            }
+        }
+        self.instruction(&tokens)
+    }
+
+    /// Compute missing limits and write the class file
+    fn finish(mut self) -> Result<Vec<u8>, AsmError> {
+        let line = self.line;
+        let at_end = |reason| AsmError { line, reason };
+        let this = self.this_index;
+        if self.this.is_none() {
+            return Err(at_end(AsmFault::MissingClass));
+        }
+        let super_index = match self.super_index {
+            Option::Some(x) => x,
+            Option::None => self.pool.class("java/lang/Object").map_err(at_end)?
+        };
+        let code_name = self.pool.utf8("Code").map_err(at_end)?;
+        let pool_bytes = self.pool.encode();
+        let pool = match parse_constant_pool(&pool_bytes) {
+            IResult::Done(_, pool) => pool,
+            _ => unreachable!()
+        };
+
+        let mut out = b"\xCA\xFE\xBA\xBE".to_vec();
+        out.extend_from_slice(&self.version.1.to_be_bytes());
+        out.extend_from_slice(&self.version.0.to_be_bytes());
+        out.extend_from_slice(&pool_bytes);
+        for x in [self.flags, this, super_index, self.interfaces.len() as u16].iter() {
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+        for x in self.interfaces.iter() {
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+        let members = |out: &mut Vec<u8>, flags: u16, name: u16, desc: u16, attributes: &[(u16, Vec<u8>)]| {
+            for x in [flags, name, desc, attributes.len() as u16].iter() {
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+            for &(name, ref data) in attributes {
+                out.extend_from_slice(&write_attribute(name, data));
+            }
+        };
+        out.extend_from_slice(&(self.fields.len() as u16).to_be_bytes());
+        for &(flags, name, desc, ref attributes) in self.fields.iter() {
+            members(&mut out, flags, name, desc, attributes);
+        }
+        out.extend_from_slice(&(self.methods.len() as u16).to_be_bytes());
+        for m in self.methods.iter() {
+            let mut attributes = Vec::with_capacity(m.attributes.len() + 1);
+            if let Option::Some(ref code) = m.code {
+                let locals = m.ops.iter()
+                    .filter_map(|(_, op)| op.local_access())
+                    .map(|(access, slot)| slot as u32 + access.width() as u32)
+                    .fold(m.arg_slots as u32, u32::max)
+                    .min(0xFFFF) as u16;
+                let max_stack = match m.max_stack {
+                    Option::Some(x) => x,
+                    Option::None => Cfg::build(m.ops.clone(), code.len(), &m.exceptions).ok()
+                        .and_then(|cfg| StackInfo::compute(&cfg, &pool, m.arg_slots).ok())
+                        .map(|info| info.max_stack)
+                        .ok_or(AsmError { line: m.line, reason: AsmFault::Limits })?
+                };
+                let code_attributes: Vec<AttributeInfo> = m.code_attributes.iter()
+                    .map(|&(name_index, ref data)| AttributeInfo { name_index, data })
+                    .collect();
+                let body = CodeAttribute {
+                    max_stack,
+                    max_locals: m.max_locals.unwrap_or(locals),
+                    code,
+                    exception_table: m.exceptions.clone(),
+                    attributes: code_attributes
+                };
+                attributes.push((code_name, body.write()));
+            }
+            attributes.extend(m.attributes.iter().cloned());
+            members(&mut out, m.flags, m.name, m.descriptor, &attributes);
+        }
+        out.extend_from_slice(&(self.attributes.len() as u16).to_be_bytes());
+        for &(name, ref data) in self.attributes.iter() {
+            out.extend_from_slice(&write_attribute(name, data));
+        }
+        Ok(out)
+    }
+}
+
+/// Assemble a class from source, returning the class file
+pub fn assemble_class(source: &str) -> Result<Vec<u8>, AsmError> {
+    let mut asm = Assembler {
+        pool: Pool { bytes: Vec::new(), index: HashMap::new(), next: 1 },
+        version: (49, 0),
+        flags: 0,
+        this: None,
+        this_index: 0,
+        super_index: None,
+        interfaces: Vec::new(),
+        fields: Vec::new(),
+        methods: Vec::new(),
+        attributes: Vec::new(),
+        method: None,
+        ended: None,
+        line: 0
+    };
+    for (n, text) in source.lines().enumerate() {
+        asm.line = n + 1;
+        asm.statement(text).map_err(|reason| AsmError { line: n + 1, reason })?;
+        if let Option::Some(m) = asm.ended.take() {
+            let out = asm.lay_out(m)?;
+            asm.methods.push(out);
+        }
+    }
+    if asm.method.is_some() {
+        return Err(AsmError { line: asm.line, reason: AsmFault::UnterminatedMethod });
+    }
+    asm.finish()
+}
+
+#[test]
+fn test_assemble_class() {
+    use super::super::clazz::Class;
+    use super::read_all_ops;
+    let source = "\
+.class public Edge
+.field static final K J = -2
+.method static f(I)I
+    iload_0                 ; the switch is padded by two bytes
+    tableswitch 0
+        A
+        default: B
+A:  iinc_w 1, -2
+    jsr S
+B:  iconst_0
+    ireturn
+S:  astore_2
+    ret 2
+.end method
+.method abstract g()V
+.end method
+";
+    let bytes = assemble_class(source).unwrap();
+    let class = Class::parse(&bytes).unwrap();
+    assert_eq!(&bytes[4..8], b"\x00\x00\x00\x31");
+    let code = class.get_method_code(0).unwrap().unwrap();
+    assert_eq!((code.max_stack, code.max_locals), (1, 3));
+    assert_eq!(read_all_ops(code.code).unwrap(), vec![
+        JOp::ILoad0, JOp::TableSwitch(28, 0, 0, vec![19]), JOp::IIncWide(1, -2), JOp::JSR(5),
+        JOp::IConst0, JOp::IReturn, JOp::AStore2, JOp::Ret(2)
+    ]);
+    assert!(class.get_method_code(1).unwrap().is_none());
+
+    let error = |source: &str| assemble_class(source).unwrap_err();
+    assert_eq!(error(".class A\n.method static f()V\n goto L\n.end method"),
+        AsmError { line: 3, reason: AsmFault::UndefinedLabel });
+    assert_eq!(error(".class A\n.method static f()V\n return"),
+        AsmError { line: 3, reason: AsmFault::UnterminatedMethod });
+    assert_eq!(error(".class A\n.method static f()V\n iload 256\n.end method").reason, AsmFault::BadOperand);
+    let switch = |high: &str| assemble_class(&format!(
+        ".class A\n.method static f(I)V\n iload_0\n tableswitch 1 {}\n L\n L\n default: L\nL:\n return\n.end method", high));
+    assert!(switch("2").is_ok());
+    assert_eq!(switch("3").unwrap_err(), AsmError { line: 7, reason: AsmFault::BadOperand });
+}
@@ -0,0 +1,592 @@
+//! Call graphs over a set of classes.
+//!
+//! Every `invoke*` instruction becomes a `CallSite` listing the methods it
+//! may run. Virtual and interface calls dispatch to every override in the
+//! given classes whose class can be the receiver, which `Analysis`
+//! decides:
+//!
+//! * `Analysis::Cha` accepts every non abstract subtype of the
+//!   referenced class.
+//! * `Analysis::Rta` only accepts those created by a `new` instruction
+//!   somewhere in the given classes. Objects created by reflection,
+//!   deserialization or JDK code are missed.
+//!
+//! Classes outside the set are unknown. A call whose method is declared
+//! outside it, or may be, is reported with `Unresolved::External`, so
+//! impact analysis can find everything that relies on a library.
+//! `invokedynamic` sites made by `LambdaMetafactory` target the lambda's
+//! implementation method, other bootstrap methods are reported as is.
+
+use super::super::clazz::{
+    Class,
+    ClassAccessFlags,
+    MethodAccessFlags,
+    PoolMembers,
+    Fault,
+    find_attribute,
+    read_bootstrap_methods
+};
+use super::ops::JOp;
+use super::decoder::{Decoder, DecodeFault};
+use super::hierarchy::OBJECT;
+use super::ssa::InvokeKind;
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::fmt;
+
+/// Guards against cyclic hierarchies in malformed input
+const MAX_DEPTH: usize = 1024;
+
+const LAMBDA_FACTORY: &str = "java/lang/invoke/LambdaMetafactory";
+
+/// `MethodHandle` reference kinds, JVMS 5.4.3.5
+const REF_INVOKE_VIRTUAL: u8 = 5;
+const REF_INVOKE_INTERFACE: u8 = 9;
+
+/// A method by declaring class, name and descriptor
+#[derive(Clone,Debug,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub struct MethodId {
+    pub class: String,
+    pub name: String,
+    pub descriptor: String
+}
+impl MethodId {
+    pub fn new(class: &str, name: &str, descriptor: &str) -> MethodId {
+        MethodId {
+            class: class.to_string(),
+            name: name.to_string(),
+            descriptor: descriptor.to_string()
+        }
+    }
+}
+impl fmt::Display for MethodId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}.{}:{}", self.class, self.name, self.descriptor)
+    }
+}
+
+/// Which receiver classes virtual calls dispatch to
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Analysis {
+    /// Class hierarchy analysis
+    Cha,
+    /// Rapid type analysis
+    Rta
+}
+
+/// Why a call site may run code the graph doesn't know
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Unresolved {
+    /// Method lookup reached this class, which is not in the set
+    External(String),
+    /// No given class declares the method, and the hierarchy is complete
+    Missing,
+    /// An `invokedynamic` linked by this bootstrap method at run time
+    Bootstrap(MethodId)
+}
+
+/// An `invoke*` instruction and the methods it may call
+#[derive(Clone,Debug)]
+pub struct CallSite {
+    pub caller: MethodId,
+    pub pc: usize,
+    pub kind: InvokeKind,
+    /// The method named by the instruction. For `invokedynamic` the
+    /// class is the caller's.
+    pub reference: MethodId,
+    /// Methods in the given classes that may run, sorted
+    pub targets: Vec<MethodId>,
+    pub unresolved: Option<Unresolved>
+}
+
+#[derive(Clone,Debug)]
+pub enum CallGraphFault {
+    Class(Fault),
+    Decode(DecodeFault),
+    /// A constant pool operand is missing or of the wrong kind
+    BadConstant(u16),
+    /// An `invokedynamic` whose bootstrap method is missing or malformed
+    BadBootstrap(u16)
+}
+
+/// A failure to read a method
+#[derive(Clone,Debug)]
+pub struct CallGraphError {
+    /// `owner.name:descriptor`, or the class alone for class wide faults
+    pub method: String,
+    pub pc: usize,
+    pub reason: CallGraphFault
+}
+impl fmt::Display for CallGraphError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            CallGraphFault::Class(ref x) => write!(f, "malformed class: {:?}", x),
+            CallGraphFault::Decode(ref x) => write!(f, "undecodable instruction: {:?}", x),
+            CallGraphFault::BadConstant(x) => write!(f, "invalid constant #{}", x),
+            CallGraphFault::BadBootstrap(x) => write!(f, "invalid bootstrap method for #{}", x)
+        }?;
+        write!(f, " in {} at pc {}", self.method, self.pc)
+    }
+}
+
+/// What the graph needs to know of a class
+struct Node {
+    super_class: Option<String>,
+    interfaces: Vec<String>,
+    flags: u16,
+    /// Name, descriptor and access flags
+    methods: Vec<(String, String, u16)>
+}
+impl Node {
+    fn find(&self, name: &str, descriptor: &str) -> Option<u16> {
+        self.methods.iter().find(|m| m.0 == name && m.1 == descriptor).map(|m| m.2)
+    }
+}
+
+/// The answers to method lookups over the given classes
+struct Resolver {
+    nodes: HashMap<String, Node>,
+    /// Direct subtypes of each class or interface, named or not
+    subtypes: HashMap<String, Vec<String>>,
+    /// Classes created by `new`, for `Analysis::Rta`
+    instantiated: Option<HashSet<String>>,
+    /// Receiver classes of each referenced class
+    receivers: HashMap<String, Vec<String>>,
+    /// Results of `dispatch` by referenced method
+    dispatched: HashMap<MethodId, (Vec<MethodId>, Option<Unresolved>)>
+}
+impl Resolver {
+
+    fn is_subtype(&self, class: &str, of: &str) -> bool {
+        let mut stack = vec![class];
+        let mut seen = HashSet::new();
+        while let Option::Some(c) = stack.pop() {
+            if c == of {
+                return true;
+            }
+            if !seen.insert(c) {
+                continue;
+            }
+            if let Option::Some(n) = self.nodes.get(c) {
+                stack.extend(n.super_class.iter().map(String::as_str));
+                stack.extend(n.interfaces.iter().map(String::as_str));
+            }
+        }
+        false
+    }
+
+    /// Find the class declaring `name` and `descriptor` for `class`
+    ///
+    /// Resolution (`selecting` false) searches the super classes, then the
+    /// super interfaces, as in JVMS 5.4.3.3. Selection for a receiver of
+    /// class `class` skips static and abstract methods, as in JVMS 5.4.6.
+    /// A super interface method found after the super classes left the
+    /// set comes with `Unresolved::External`, as the missing class may
+    /// override it.
+    fn lookup(&self, class: &str, name: &str, descriptor: &str, selecting: bool) -> (Option<String>, Option<Unresolved>) {
+        let skip = MethodAccessFlags::Static as u16 | MethodAccessFlags::Abstract as u16;
+        let mut external = None;
+        let mut chain = Vec::new();
+        let mut current = if class.starts_with('[') { OBJECT } else { class };
+        for _ in 0..MAX_DEPTH {
+            let node = match self.nodes.get(current) {
+                Option::Some(n) => n,
+                Option::None => {
+                    external = Some(current.to_string());
+                    break;
+                }
+            };
+            if let Option::Some(flags) = node.find(name, descriptor) {
+                if !selecting || flags & skip == 0 {
+                    return (Some(current.to_string()), None);
+                }
+            }
+            chain.push(current);
+            current = match node.super_class {
+                Option::Some(ref x) => x,
+                Option::None => break
+            };
+        }
+        let outside = external.clone().map(Unresolved::External);
+
+        // maximally specific super interface methods
+        let mut queue = chain.iter()
+            .flat_map(|c| self.nodes[*c].interfaces.iter().map(String::as_str))
+            .collect::<VecDeque<_>>();
+        let mut seen = HashSet::new();
+        let mut candidates = Vec::new();
+        while let Option::Some(iface) = queue.pop_front() {
+            if !seen.insert(iface) {
+                continue;
+            }
+            let node = match self.nodes.get(iface) {
+                Option::Some(n) => n,
+                Option::None => {
+                    external = external.or_else(|| Some(iface.to_string()));
+                    continue;
+                }
+            };
+            match node.find(name, descriptor) {
+                Option::Some(flags) if flags & (MethodAccessFlags::Private as u16 | MethodAccessFlags::Static as u16) == 0 => {
+                    if !selecting || flags & (MethodAccessFlags::Abstract as u16) == 0 {
+                        candidates.push(iface);
+                    }
+                },
+                _ => queue.extend(node.interfaces.iter().map(String::as_str))
+            };
+        }
+        let specific = candidates.iter()
+            .filter(|&&a| !candidates.iter().any(|&b| b != a && self.is_subtype(b, a)))
+            .collect::<Vec<_>>();
+        match (specific.first(), external) {
+            (Option::Some(x), _) if specific.len() == 1 || !selecting => (Some(x.to_string()), outside),
+            (_, Option::Some(x)) => (None, Some(Unresolved::External(x))),
+            _ => (None, Some(Unresolved::Missing))
+        }
+    }
+
+    /// Classes whose instances may receive a call on `class`
+    fn receivers(&mut self, class: &str) -> Vec<String> {
+        let class = if class.starts_with('[') { OBJECT } else { class };
+        if let Option::Some(x) = self.receivers.get(class) {
+            return x.clone();
+        }
+        let abstract_flags = ClassAccessFlags::Interface as u16 | ClassAccessFlags::Abstract as u16;
+        let mut found = BTreeSet::new();
+        let mut seen = HashSet::new();
+        let mut queue = vec![class.to_string()];
+        while let Option::Some(c) = queue.pop() {
+            if !seen.insert(c.clone()) {
+                continue;
+            }
+            let concrete = self.nodes.get(&c).is_some_and(|n| n.flags & abstract_flags == 0);
+            let created = self.instantiated.as_ref().is_none_or(|x| x.contains(&c));
+            if concrete && created {
+                found.insert(c.clone());
+            }
+            if let Option::Some(x) = self.subtypes.get(&c) {
+                queue.extend(x.iter().cloned());
+            }
+        }
+        let found = found.into_iter().collect::<Vec<_>>();
+        self.receivers.insert(class.to_string(), found.clone());
+        found
+    }
+
+    /// The descriptor of the signature polymorphic method `name` of
+    /// `class`, see JVMS 2.9.3
+    fn polymorphic(&self, class: &str, name: &str) -> Option<String> {
+        if class != "java/lang/invoke/MethodHandle" && class != "java/lang/invoke/VarHandle" {
+            return None;
+        }
+        let flags = MethodAccessFlags::Native as u16 | MethodAccessFlags::Varargs as u16;
+        self.nodes.get(class)?.methods.iter()
+            .find(|m| m.0 == name && m.2 & flags == flags && m.1.starts_with("([Ljava/lang/Object;)"))
+            .map(|m| m.1.clone())
+    }
+
+    /// Targets of a call that runs the resolved method itself
+    fn direct(&self, class: &str, name: &str, descriptor: &str) -> (Vec<MethodId>, Option<Unresolved>) {
+        if let Option::Some(x) = self.polymorphic(class, name) {
+            return (vec![MethodId::new(class, name, &x)], None);
+        }
+        // constructors are not inherited, only the named class is searched
+        if name == "<init>" {
+            return match self.nodes.get(class) {
+                Option::Some(n) if n.find(name, descriptor).is_some() => (vec![MethodId::new(class, name, descriptor)], None),
+                Option::Some(_) => (Vec::new(), Some(Unresolved::Missing)),
+                Option::None => (Vec::new(), Some(Unresolved::External(class.to_string())))
+            };
+        }
+        let (found, unresolved) = self.lookup(class, name, descriptor, false);
+        (found.iter().map(|x| MethodId::new(x, name, descriptor)).collect(), unresolved)
+    }
+
+    /// Targets of a call that dispatches on the receiver's class
+    fn dispatch(&mut self, class: &str, name: &str, descriptor: &str) -> (Vec<MethodId>, Option<Unresolved>) {
+        let key = MethodId::new(class, name, descriptor);
+        if let Option::Some(x) = self.dispatched.get(&key) {
+            return x.clone();
+        }
+        let found = self.select_all(class, name, descriptor);
+        self.dispatched.insert(key, found.clone());
+        found
+    }
+
+    fn select_all(&mut self, class: &str, name: &str, descriptor: &str) -> (Vec<MethodId>, Option<Unresolved>) {
+        if let Option::Some(x) = self.polymorphic(class, name) {
+            return (vec![MethodId::new(class, name, &x)], None);
+        }
+        let (found, mut unresolved) = self.lookup(class, name, descriptor, false);
+        if let Option::Some(x) = found {
+            let flags = self.nodes[&x].find(name, descriptor).unwrap_or(0);
+            let fixed = MethodAccessFlags::Private as u16 | MethodAccessFlags::Static as u16 | MethodAccessFlags::Final as u16;
+            if flags & fixed != 0 {
+                return (vec![MethodId::new(&x, name, descriptor)], unresolved);
+            }
+        }
+        let mut targets = BTreeSet::new();
+        for receiver in self.receivers(class) {
+            let (found, e) = self.lookup(&receiver, name, descriptor, true);
+            if let Option::Some(x) = found {
+                targets.insert(MethodId::new(&x, name, descriptor));
+            }
+            unresolved = unresolved.or(e);
+        }
+        (targets.into_iter().collect(), unresolved)
+    }
+}
+
+/// An `invoke*` instruction waiting for the whole set to be read
+struct Pending {
+    caller: MethodId,
+    pc: usize,
+    op: JOp,
+    /// Index of the caller's class
+    class: usize
+}
+
+/// The calls made by a set of classes
+#[derive(Clone,Debug)]
+pub struct CallGraph {
+    /// In the order of the classes, their methods and pcs
+    pub sites: Vec<CallSite>,
+    by_caller: HashMap<MethodId, Vec<usize>>,
+    /// Sites by target, and by reference
+    by_callee: HashMap<MethodId, Vec<usize>>
+}
+
+impl CallGraph {
+
+    /// Build the call graph of `classes`
+    pub fn build(classes: &[&Class], analysis: Analysis) -> Result<CallGraph, CallGraphError> {
+        let class_error = |name: String, reason| CallGraphError { method: name, pc: 0, reason: CallGraphFault::Class(reason) };
+        let mut nodes = HashMap::new();
+        let mut names = Vec::with_capacity(classes.len());
+        for (i, class) in classes.iter().enumerate() {
+            let name = class.get_this_class()
+                .map_err(|e| class_error(format!("class #{}", i), e))?
+                .into_owned();
+            let pool = class.get_constants();
+            let fail = |e| class_error(name.clone(), e);
+            let super_class = match class.get_super_class_index() {
+                0 => None,
+                _ => Some(class.get_super_class().map_err(fail)?.into_owned())
+            };
+            let mut methods = Vec::with_capacity(class.get_methods_count());
+            for m in 0..class.get_methods_count() {
+                let m = class.get_method(m).unwrap();
+                let method_name = pool.get_utf8(m.name_index).ok_or_else(|| fail(Fault::ParseError))?;
+                let descriptor = pool.get_utf8(m.descriptor_index).ok_or_else(|| fail(Fault::ParseError))?;
+                methods.push((method_name.into_owned(), descriptor.into_owned(), m.access_flags));
+            }
+            nodes.insert(name.clone(), Node {
+                super_class,
+                interfaces: class.get_interfaces().map_err(fail)?.into_iter().map(|x| x.into_owned()).collect(),
+                flags: class.get_access_flags(),
+                methods
+            });
+            names.push(name);
+        }
+        let mut subtypes: HashMap<String, Vec<String>> = HashMap::new();
+        for (name, node) in nodes.iter() {
+            for parent in node.super_class.iter().chain(node.interfaces.iter()) {
+                subtypes.entry(parent.clone()).or_default().push(name.clone());
+            }
+        }
+
+        let mut created = HashSet::new();
+        let mut pending = Vec::new();
+        for (i, class) in classes.iter().enumerate() {
+            let pool = class.get_constants();
+            let node = &nodes[&names[i]];
+            for (m, (name, descriptor, _)) in node.methods.iter().enumerate() {
+                let caller = MethodId::new(&names[i], name, descriptor);
+                let error = |pc, reason| CallGraphError { method: caller.to_string(), pc, reason };
+                let code = match class.get_method_code(m).map_err(|e| error(0, CallGraphFault::Class(e)))? {
+                    Option::Some(x) => x,
+                    Option::None => continue
+                };
+                for x in Decoder::new(code.code) {
+                    let (pc, op) = x.map_err(|e| error(e.pc, CallGraphFault::Decode(e.reason)))?;
+                    match op {
+                        JOp::New(x) => {
+                            let name = pool.get_class_name(x).ok_or_else(|| error(pc, CallGraphFault::BadConstant(x)))?;
+                            created.insert(name.into_owned());
+                        },
+                        JOp::InvokeVirtual(_) | JOp::InvokeSpecial(_) | JOp::InvokeStatic(_) |
+                        JOp::InvokedInterface(..) | JOp::InvokedDynamic(_) => {
+                            pending.push(Pending { caller: caller.clone(), pc, op, class: i });
+                        },
+                        _ => {}
+                    };
+                }
+            }
+        }
+
+        let mut resolver = Resolver {
+            nodes,
+            subtypes,
+            instantiated: if analysis == Analysis::Rta { Some(created) } else { None },
+            receivers: HashMap::new(),
+            dispatched: HashMap::new()
+        };
+        let mut graph = CallGraph { sites: Vec::with_capacity(pending.len()), by_caller: HashMap::new(), by_callee: HashMap::new() };
+        for p in pending {
+            let site = CallGraph::resolve(&mut resolver, classes[p.class], p)?;
+            let index = graph.sites.len();
+            graph.by_caller.entry(site.caller.clone()).or_default().push(index);
+            let callees = site.targets.iter().chain(Some(&site.reference)).collect::<BTreeSet<_>>();
+            for callee in callees {
+                graph.by_callee.entry(callee.clone()).or_default().push(index);
+            }
+            graph.sites.push(site);
+        }
+        Ok(graph)
+    }
+
+    fn resolve(resolver: &mut Resolver, class: &Class, p: Pending) -> Result<CallSite, CallGraphError> {
+        let pool = class.get_constants();
+        let error = |reason| CallGraphError { method: p.caller.to_string(), pc: p.pc, reason };
+        let member = |x: u16| pool.get_member_ref(x)
+            .map(|m| MethodId::new(&m.class, &m.name, &m.descriptor))
+            .ok_or_else(|| error(CallGraphFault::BadConstant(x)));
+        let (kind, reference, (targets, unresolved)) = match p.op {
+            JOp::InvokeStatic(x) | JOp::InvokeSpecial(x) => {
+                let m = member(x)?;
+                let kind = if let JOp::InvokeStatic(_) = p.op { InvokeKind::Static } else { InvokeKind::Special };
+                let found = resolver.direct(&m.class, &m.name, &m.descriptor);
+                (kind, m, found)
+            },
+            JOp::InvokeVirtual(x) | JOp::InvokedInterface(x, _) => {
+                let m = member(x)?;
+                let kind = if let JOp::InvokeVirtual(_) = p.op { InvokeKind::Virtual } else { InvokeKind::Interface };
+                let found = resolver.dispatch(&m.class, &m.name, &m.descriptor);
+                (kind, m, found)
+            },
+            JOp::InvokedDynamic(x) => {
+                let (bootstrap, nat) = match pool.get(x) {
+                    Option::Some(&PoolMembers::InvokeDynamic(b, n)) => (b, n),
+                    _ => return Err(error(CallGraphFault::BadConstant(x)))
+                };
+                let (name, descriptor) = pool.get_name_and_type(nat).ok_or_else(|| error(CallGraphFault::BadConstant(x)))?;
+                let reference = MethodId::new(&p.caller.class, &name, &descriptor);
+                let bad = || error(CallGraphFault::BadBootstrap(x));
+                let methods = find_attribute(class.get_attributes(), pool, "BootstrapMethods")
+                    .and_then(|a| read_bootstrap_methods(a.data))
+                    .ok_or_else(bad)?;
+                let entry = methods.get(bootstrap as usize).ok_or_else(bad)?;
+                let handle = |x: u16| match pool.get(x) {
+                    Option::Some(&PoolMembers::MethodHandle(kind, m)) => member(m).ok().map(|m| (kind, m)),
+                    _ => None
+                };
+                let (_, bsm) = handle(entry.method_ref).ok_or_else(bad)?;
+                let implementation = match entry.arguments.get(1) {
+                    Option::Some(&x) if bsm.class == LAMBDA_FACTORY => handle(x),
+                    _ => None
+                };
+                let found = match implementation {
+                    Option::Some((REF_INVOKE_VIRTUAL, m)) |
+                    Option::Some((REF_INVOKE_INTERFACE, m)) => resolver.dispatch(&m.class, &m.name, &m.descriptor),
+                    Option::Some((_, m)) => resolver.direct(&m.class, &m.name, &m.descriptor),
+                    Option::None => (Vec::new(), Some(Unresolved::Bootstrap(bsm)))
+                };
+                (InvokeKind::Dynamic, reference, found)
+            },
+            _ => unreachable!()
+        };
+        Ok(CallSite { caller: p.caller, pc: p.pc, kind, reference, targets, unresolved })
+    }
+
+    /// Call sites in `method`
+    pub fn sites_in(&self, method: &MethodId) -> Vec<&CallSite> {
+        self.by_caller.get(method).map_or(Vec::new(), |x| x.iter().map(|&i| &self.sites[i]).collect())
+    }
+
+    /// Methods `method` may call directly
+    pub fn callees(&self, method: &MethodId) -> BTreeSet<&MethodId> {
+        self.sites_in(method).into_iter().flat_map(|s| s.targets.iter()).collect()
+    }
+
+    /// Call sites that may call `method`, or name it
+    ///
+    /// Methods outside the set are only found by name.
+    pub fn callers(&self, method: &MethodId) -> Vec<&CallSite> {
+        self.by_callee.get(method).map_or(Vec::new(), |x| x.iter().map(|&i| &self.sites[i]).collect())
+    }
+
+    /// Call sites with targets the graph doesn't know
+    pub fn unresolved(&self) -> Vec<&CallSite> {
+        self.sites.iter().filter(|s| s.unresolved.is_some()).collect()
+    }
+
+    /// Every method that may reach one of `changed` through calls
+    pub fn impacted(&self, changed: &[MethodId]) -> BTreeSet<MethodId> {
+        let mut found = BTreeSet::new();
+        let mut queue = changed.iter().collect::<Vec<_>>();
+        while let Option::Some(m) = queue.pop() {
+            for site in self.callers(m) {
+                if found.insert(site.caller.clone()) {
+                    queue.push(&site.caller);
+                }
+            }
+        }
+        found
+    }
+}
+
+#[test]
+fn test_call_graph() {
+    use super::asm::assemble_class;
+    let sources = [
+        ".interface public abstract Shape\n.method public abstract area()I\n.end method\n",
+        ".class public Square\n.implements Shape\n\
+         .method public <init>()V\n    aload_0\n    invokespecial java/lang/Object.\"<init>\":()V\n    return\n.end method\n\
+         .method public area()I\n    iconst_4\n    ireturn\n.end method\n",
+        ".class public Circle\n.implements Shape\n\
+         .method public area()I\n    iconst_3\n    ireturn\n.end method\n",
+        ".class public Unit\n.super Square\n",
+        ".interface public abstract I\n.method public m()V\n    return\n.end method\n",
+        ".class public Foo\n.super lib/Base\n.implements I\n",
+        ".class public Main\n\
+         .method public static main([Ljava/lang/String;)V\n\
+             new Unit\n    dup\n    invokespecial Unit.\"<init>\":()V\n\
+             dup\n    invokeinterface Shape.area:()I 1\n    pop\n\
+             dup\n    invokevirtual Square.area:()I\n    pop\n\
+             invokevirtual java/lang/Object.toString:()Ljava/lang/String;\n    pop\n\
+             invokestatic Main.helper:()V\n    return\n.end method\n\
+         .method static helper()V\n    invokestatic Main.gone:()V\n    return\n.end method\n\
+         .method static upgrade(LFoo;)V\n    aload_0\n    invokevirtual Foo.m:()V\n    return\n.end method\n"
+    ];
+    let bytes = sources.iter().map(|s| assemble_class(s).unwrap()).collect::<Vec<_>>();
+    let classes = bytes.iter().map(|b| Class::parse(b).unwrap()).collect::<Vec<_>>();
+    let refs = classes.iter().collect::<Vec<_>>();
+    let area = |c: &str| MethodId::new(c, "area", "()I");
+    let main = MethodId::new("Main", "main", "([Ljava/lang/String;)V");
+    let helper = MethodId::new("Main", "helper", "()V");
+    let upgrade = MethodId::new("Main", "upgrade", "(LFoo;)V");
+
+    let cha = CallGraph::build(&refs, Analysis::Cha).unwrap();
+    let sites = cha.sites_in(&main);
+    assert_eq!(sites.iter().map(|s| s.kind).collect::<Vec<_>>(),
+        [InvokeKind::Special, InvokeKind::Interface, InvokeKind::Virtual, InvokeKind::Virtual, InvokeKind::Static]);
+    // Unit inherits area from Square, but not its constructor
+    assert_eq!((sites[0].targets.len(), sites[0].unresolved.clone()), (0, Some(Unresolved::Missing)));
+    assert_eq!(sites[1].targets, [area("Circle"), area("Square")]);
+    assert_eq!(sites[2].targets, [area("Square")]);
+    assert_eq!(sites[3].unresolved, Some(Unresolved::External(OBJECT.to_string())));
+    // java/lang/Object is not given, so it might declare gone()
+    assert_eq!(cha.sites_in(&helper)[0].unresolved, Some(Unresolved::External(OBJECT.to_string())));
+    // the default method is called unless lib/Base overrides it
+    let site = &cha.sites_in(&upgrade)[0];
+    assert_eq!(site.targets, [MethodId::new("I", "m", "()V")]);
+    assert_eq!(site.unresolved, Some(Unresolved::External("lib/Base".to_string())));
+
+    let rta = CallGraph::build(&refs, Analysis::Rta).unwrap();
+    assert_eq!(rta.sites_in(&main)[1].targets, [area("Square")]);
+    assert_eq!(rta.callers(&area("Circle")).len(), 0);
+    assert_eq!(rta.callers(&area("Square")).len(), 2);
+    assert_eq!(rta.unresolved().len(), 5);
+    let impacted = rta.impacted(&[MethodId::new("Main", "gone", "()V")]);
+    assert_eq!(impacted.into_iter().collect::<Vec<_>>(), [helper, main]);
+}
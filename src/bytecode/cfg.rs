@@ -0,0 +1,416 @@
+//! Control flow graphs of method bodies.
+//!
+//! A method is split into basic blocks, maximal runs of instructions
+//! only entered at the top and only left at the bottom. Blocks are
+//! connected by edges for fallthrough, branches, switch cases, `jsr`
+//! and `ret`, and by exceptional edges from every block covered by an
+//! exception table entry to its handler.
+
+use super::super::clazz::{CodeAttribute, ExceptionEntry};
+use super::ops::JOp;
+use super::labels::Jump;
+use super::decoder::{Decoder, DecodeFault};
+
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::fmt;
+
+/// Index of a block within `Cfg::blocks`
+pub type BlockId = usize;
+
+/// Why control moves along an edge
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum EdgeKind {
+    /// Into the block that follows in the code array
+    Fallthrough,
+    /// `goto` and `goto_w`
+    Jump,
+    /// The taken side of a conditional branch
+    Taken,
+    /// A case, or the default, of a `TableSwitch` or `LookUpSwitch`
+    Case,
+    /// `jsr` into a subroutine
+    JsrCall,
+    /// `ret` back to the instruction following a `jsr`
+    JsrReturn,
+    /// Into an exception handler, holds the handler's `catch_type`
+    Exception(u16)
+}
+impl EdgeKind {
+
+    /// Is this an edge into an exception handler
+    pub fn is_exceptional(&self) -> bool {
+        matches!(*self, EdgeKind::Exception(_))
+    }
+}
+
+/// A directed edge between two blocks
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub struct Edge {
+    pub from: BlockId,
+    pub to: BlockId,
+    pub kind: EdgeKind
+}
+
+/// How a block leaves the method
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Exit {
+    /// Any of the `*Return` instructions
+    Return,
+    /// `AThrow`
+    Throw
+}
+
+/// A maximal straight line run of instructions
+#[derive(Clone,Debug)]
+pub struct BasicBlock {
+    /// pc of the first instruction
+    pub start: usize,
+    /// pc following the last instruction
+    pub end: usize,
+    /// Indices into `Cfg::ops`
+    pub ops: Range<usize>,
+    /// Set when the block ends the method
+    pub exit: Option<Exit>,
+    /// Is this the entry of an exception handler
+    pub handler: bool,
+    /// Can the block be reached from the method entry
+    pub reachable: bool,
+    succs: Vec<usize>,
+    preds: Vec<usize>
+}
+
+/// Why a control flow graph could not be built
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum CfgFault {
+    /// The code array failed to decode
+    Decode(DecodeFault),
+    /// A branch or switch targets a pc that is outside the code, or
+    /// not the start of an instruction
+    BadTarget(i64),
+    /// An exception table entry does not line up with instructions
+    BadExceptionRange,
+    /// Execution can run past the end of the code array
+    FallsOffEnd,
+    /// The code array is empty
+    Empty
+}
+
+/// A control flow failure and the pc it concerns
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct CfgError {
+    pub pc: usize,
+    pub reason: CfgFault
+}
+impl fmt::Display for CfgError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            CfgFault::Decode(ref x) => write!(f, "decoding failed ({:?})", x),
+            CfgFault::BadTarget(x) => write!(f, "invalid branch target {}", x),
+            CfgFault::BadExceptionRange => write!(f, "invalid exception table entry"),
+            CfgFault::FallsOffEnd => write!(f, "execution falls off the end of the code"),
+            CfgFault::Empty => write!(f, "empty code array")
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+/// The control flow graph of one method
+#[derive(Clone,Debug)]
+pub struct Cfg {
+    /// Every instruction of the method, with its pc
+    pub ops: Vec<(usize, JOp)>,
+    /// Blocks in code order, the entry block is `0`
+    pub blocks: Vec<BasicBlock>,
+    pub edges: Vec<Edge>
+}
+
+fn is_return(op: &JOp) -> bool {
+    matches!(*op,
+        JOp::IReturn | JOp::LReturn | JOp::FReturn |
+        JOp::DReturn | JOp::AReturn | JOp::Return)
+}
+
+fn is_ret(op: &JOp) -> bool {
+    matches!(*op, JOp::Ret(_) | JOp::RetWide(_))
+}
+
+/// Does the instruction end a basic block
+fn ends_block(op: &JOp) -> bool {
+    op.is_branch() || is_return(op) || is_ret(op) || *op == JOp::AThrow
+}
+
+impl Cfg {
+
+    /// Decode a `Code` attribute and build its graph
+    pub fn from_code(code: &CodeAttribute) -> Result<Cfg, CfgError> {
+        let mut ops = Vec::new();
+        for op in Decoder::new(code.code) {
+            match op {
+                Ok(x) => ops.push(x),
+                Err(e) => return Err(CfgError { pc: e.pc, reason: CfgFault::Decode(e.reason) })
+            };
+        }
+        Cfg::build(ops, code.code.len(), &code.exception_table)
+    }
+
+    /// Build the graph of a decoded method
+    ///
+    /// `ops` is the whole method in order, as produced by `Decoder`,
+    /// and `code_len` the length of its code array.
+    pub fn build(ops: Vec<(usize, JOp)>, code_len: usize, exceptions: &[ExceptionEntry]) -> Result<Cfg, CfgError> {
+        if ops.is_empty() {
+            return Err(CfgError { pc: 0, reason: CfgFault::Empty });
+        }
+        let index: HashMap<usize, usize> = ops.iter()
+            .enumerate()
+            .map(|(i, &(pc, _))| (pc, i))
+            .collect();
+
+        // find the leaders
+        let mut leaders = BTreeSet::new();
+        leaders.insert(0);
+        for &(pc, ref op) in ops.iter() {
+            for target in op.branch_targets(pc) {
+                if target < 0 || !index.contains_key(&(target as usize)) {
+                    return Err(CfgError { pc, reason: CfgFault::BadTarget(target) });
+                }
+                leaders.insert(target as usize);
+            }
+            let next = pc + op.encoded_len(pc);
+            if ends_block(op) && next < code_len {
+                leaders.insert(next);
+            }
+        }
+        for entry in exceptions {
+            let (start, end, handler) = (entry.start_pc as usize, entry.end_pc as usize, entry.handler_pc as usize);
+            let valid = start < end &&
+                index.contains_key(&start) &&
+                index.contains_key(&handler) &&
+                (end == code_len || index.contains_key(&end));
+            if !valid {
+                return Err(CfgError { pc: start, reason: CfgFault::BadExceptionRange });
+            }
+            leaders.insert(start);
+            leaders.insert(handler);
+            if end < code_len {
+                leaders.insert(end);
+            }
+        }
+
+        // carve out blocks
+        let mut blocks = Vec::with_capacity(leaders.len());
+        let mut first = 0;
+        for i in 0..ops.len() {
+            let last = i + 1 == ops.len() || leaders.contains(&ops[i + 1].0);
+            if !last {
+                continue;
+            }
+            let (pc, ref op) = ops[i];
+            let exit = if is_return(op) {
+                Some(Exit::Return)
+            } else if *op == JOp::AThrow {
+                Some(Exit::Throw)
+            } else {
+                None
+            };
+            blocks.push(BasicBlock {
+                start: ops[first].0,
+                end: pc + op.encoded_len(pc),
+                ops: first..i + 1,
+                exit,
+                handler: false,
+                reachable: false,
+                succs: Vec::new(),
+                preds: Vec::new()
+            });
+            first = i + 1;
+        }
+        let mut cfg = Cfg { ops, blocks, edges: Vec::new() };
+        for entry in exceptions {
+            let b = cfg.block_of(entry.handler_pc as usize).unwrap();
+            cfg.blocks[b].handler = true;
+        }
+
+        // normal edges
+        let mut return_sites = HashMap::new();
+        for b in 0..cfg.blocks.len() {
+            let (pc, op) = cfg.ops[cfg.blocks[b].ops.end - 1].clone();
+            let next = if b + 1 < cfg.blocks.len() { Some(b + 1) } else { None };
+            let falls = |cfg: &mut Cfg| match next {
+                Option::Some(n) => {
+                    cfg.add_edge(b, n, EdgeKind::Fallthrough);
+                    Ok(())
+                },
+                Option::None => Err(CfgError { pc, reason: CfgFault::FallsOffEnd })
+            };
+            let targets: Vec<BlockId> = op.branch_targets(pc)
+                .into_iter()
+                .map(|t| cfg.block_of(t as usize).unwrap())
+                .collect();
+            match Jump::from_op(&op) {
+                Option::Some((Jump::Goto, _)) => cfg.add_edge(b, targets[0], EdgeKind::Jump),
+                Option::Some((Jump::JSR, _)) => {
+                    cfg.add_edge(b, targets[0], EdgeKind::JsrCall);
+                    match next {
+                        Option::Some(n) => return_sites.insert(b, n),
+                        Option::None => return Err(CfgError { pc, reason: CfgFault::FallsOffEnd })
+                    };
+                },
+                Option::Some(_) => {
+                    cfg.add_edge(b, targets[0], EdgeKind::Taken);
+                    falls(&mut cfg)?;
+                },
+                Option::None => {
+                    if op.is_branch() {
+                        for t in targets {
+                            cfg.add_edge(b, t, EdgeKind::Case);
+                        }
+                    } else if !ends_block(&op) {
+                        falls(&mut cfg)?;
+                    }
+                }
+            };
+        }
+
+        // exceptional edges, blocks are split at every range boundary
+        // so a block is either wholly covered or not at all
+        for b in 0..cfg.blocks.len() {
+            let start = cfg.blocks[b].start;
+            for entry in exceptions {
+                if entry.start_pc as usize <= start && start < entry.end_pc as usize {
+                    let h = cfg.block_of(entry.handler_pc as usize).unwrap();
+                    cfg.add_edge(b, h, EdgeKind::Exception(entry.catch_type));
+                }
+            }
+        }
+
+        // connect each `ret` to the return sites of the `jsr`s calling
+        // its subroutine
+        let mut rets = Vec::new();
+        for (&call, &site) in return_sites.iter() {
+            let sub = cfg.edges[cfg.blocks[call].succs[0]].to;
+            let mut seen = vec![false; cfg.blocks.len()];
+            let mut stack = vec![sub];
+            while let Option::Some(b) = stack.pop() {
+                if seen[b] {
+                    continue;
+                }
+                seen[b] = true;
+                if is_ret(&cfg.ops[cfg.blocks[b].ops.end - 1].1) {
+                    rets.push((b, site));
+                }
+                if let Option::Some(&nested) = return_sites.get(&b) {
+                    stack.push(nested);
+                    continue;
+                }
+                stack.extend(cfg.successors(b).filter(|e| e.kind == EdgeKind::Fallthrough ||
+                    e.kind == EdgeKind::Jump ||
+                    e.kind == EdgeKind::Taken ||
+                    e.kind == EdgeKind::Case).map(|e| e.to));
+            }
+        }
+        rets.sort();
+        for (ret, site) in rets {
+            cfg.add_edge(ret, site, EdgeKind::JsrReturn);
+        }
+
+        // reachability
+        let mut stack = vec![0];
+        while let Option::Some(b) = stack.pop() {
+            if cfg.blocks[b].reachable {
+                continue;
+            }
+            cfg.blocks[b].reachable = true;
+            stack.extend(cfg.successors(b).map(|e| e.to));
+        }
+        Ok(cfg)
+    }
+
+    fn add_edge(&mut self, from: BlockId, to: BlockId, kind: EdgeKind) {
+        let edge = Edge { from, to, kind };
+        if self.successors(from).any(|e| *e == edge) {
+            return;
+        }
+        let id = self.edges.len();
+        self.edges.push(edge);
+        self.blocks[from].succs.push(id);
+        self.blocks[to].preds.push(id);
+    }
+
+    /// The entry block
+    pub fn entry(&self) -> BlockId {
+        0
+    }
+
+    /// Edges leaving a block
+    pub fn successors<'b>(&'b self, block: BlockId) -> impl Iterator<Item=&'b Edge> + 'b {
+        self.blocks[block].succs.iter().map(move |e| &self.edges[*e])
+    }
+
+    /// Edges entering a block
+    pub fn predecessors<'b>(&'b self, block: BlockId) -> impl Iterator<Item=&'b Edge> + 'b {
+        self.blocks[block].preds.iter().map(move |e| &self.edges[*e])
+    }
+
+    /// The instructions of a block
+    pub fn block_ops(&self, block: BlockId) -> &[(usize, JOp)] {
+        &self.ops[self.blocks[block].ops.clone()]
+    }
+
+    /// The block containing `pc`
+    pub fn block_of(&self, pc: usize) -> Option<BlockId> {
+        let b = match self.blocks.binary_search_by_key(&pc, |b| b.start) {
+            Ok(b) => b,
+            Err(0) => return None,
+            Err(b) => b - 1
+        };
+        if pc < self.blocks[b].end {
+            Some(b)
+        } else {
+            None
+        }
+    }
+
+    /// Blocks that can never execute
+    pub fn unreachable(&self) -> Vec<BlockId> {
+        (0..self.blocks.len()).filter(|b| !self.blocks[*b].reachable).collect()
+    }
+}
+
+#[test]
+fn test_cfg() {
+    // 0: iconst_0; istore_0
+    // 2: iload_0; bipush 10; if_icmpge 16
+    // 8: iinc 0 1; goto 2
+    // 14: iconst_1; ireturn (dead)
+    // 16: return
+    // 17: astore_1; return (handler for 2..16)
+    let code: &[u8] = &[
+        0x03, 0x3B, 0x1A, 0x10, 0x0A, 0xA2, 0x00, 0x0B, 0x84, 0x00,
+        0x01, 0xA7, 0xFF, 0xF7, 0x04, 0xAC, 0xB1, 0x4C, 0xB1
+    ];
+    let ex = [ExceptionEntry { start_pc: 2, end_pc: 16, handler_pc: 17, catch_type: 0 }];
+    let ops = Decoder::new(code).map(Result::unwrap).collect();
+    let cfg = Cfg::build(ops, code.len(), &ex).unwrap();
+
+    let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+    assert_eq!(starts, vec![0, 2, 8, 14, 16, 17]);
+    let succs = |b: BlockId| -> Vec<(BlockId, EdgeKind)> {
+        cfg.successors(b).map(|e| (e.to, e.kind)).collect()
+    };
+    assert_eq!(succs(0), vec![(1, EdgeKind::Fallthrough)]);
+    assert_eq!(succs(1), vec![(4, EdgeKind::Taken), (2, EdgeKind::Fallthrough), (5, EdgeKind::Exception(0))]);
+    assert_eq!(succs(2), vec![(1, EdgeKind::Jump), (5, EdgeKind::Exception(0))]);
+    assert_eq!(cfg.blocks[4].exit, Some(Exit::Return));
+    assert!(cfg.blocks[5].handler);
+    assert_eq!(cfg.unreachable(), vec![3]);
+    assert_eq!(cfg.block_of(12), Some(2));
+
+    // 0: jsr +4; 3: return; 4: astore_0 (sub); 5: ret 0
+    let code: &[u8] = &[0xA8, 0x00, 0x04, 0xB1, 0x4B, 0xA9, 0x00];
+    let ops = Decoder::new(code).map(Result::unwrap).collect();
+    let cfg = Cfg::build(ops, code.len(), &[]).unwrap();
+    assert_eq!(cfg.successors(2).map(|e| (e.to, e.kind)).collect::<Vec<_>>(), vec![(1, EdgeKind::JsrReturn)]);
+    assert!(cfg.unreachable().is_empty());
+}
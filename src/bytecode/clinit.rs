@@ -0,0 +1,248 @@
+//! Recovering static field values by running `<clinit>`.
+//!
+//! Lookup tables, `static final` arrays and computed strings are usually
+//! assigned by the static initializer rather than a `ConstantValue`
+//! attribute. `evaluate_statics` runs the initializer in the
+//! `Interpreter` and reads back every static field of the class.
+//!
+//! When the initializer cannot be run to completion, fields that no
+//! given method ever writes with `putstatic` still hold their default or
+//! `ConstantValue` value. Every other field is unknown.
+
+use super::super::clazz::{Class, FieldType, FieldAccessFlags, JavaString, Fault};
+use super::ops::JOp;
+use super::decoder::Decoder;
+use super::interp::{Interpreter, InterpError, Object, Value};
+
+use std::collections::HashSet;
+
+/// The value of a static field after initialization
+#[derive(Clone,Debug,PartialEq)]
+pub enum StaticValue {
+    /// Also `boolean`, `byte`, `char` and `short` fields
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    Str(JavaString<'static>),
+    /// A class literal, by internal name
+    Class(String),
+    /// Elements follow the component type, a `byte[]` holds `Int`s
+    Array(FieldType, Vec<StaticValue>)
+}
+
+/// Why the value of a static field could not be determined
+#[derive(Clone,Debug)]
+pub enum UnknownReason {
+    /// The initializer stopped before finishing and the field may have
+    /// been assigned
+    Failed(InterpError),
+    /// The field holds an object of this class, which has no
+    /// `StaticValue` form
+    Opaque(String),
+    /// The field holds an array that contains itself
+    Cyclic
+}
+
+/// A static field of the evaluated class
+#[derive(Clone,Debug)]
+pub struct StaticField {
+    pub name: String,
+    pub descriptor: String,
+    pub value: Result<StaticValue, UnknownReason>
+}
+
+/// Run the static initializer of `class` and report its static fields,
+/// in declaration order
+///
+/// `others` are classes the initializer may use, their static methods
+/// and fields are available to it.
+pub fn evaluate_statics(class: &Class, others: &[&Class]) -> Result<Vec<StaticField>, Fault> {
+    let name = class.get_this_class()?;
+    let mut classes = vec![class];
+    classes.extend_from_slice(others);
+    let mut interp = Interpreter::new(&classes)?;
+    let failure = interp.initialize(&name).err();
+    let written = match failure {
+        Option::Some(_) => assigned_fields(&classes, &name)?,
+        Option::None => HashSet::new()
+    };
+
+    let pool = class.get_constants();
+    let mut fields = Vec::new();
+    for i in 0..class.get_fields_count() {
+        let field = class.get_field(i).unwrap();
+        if field.access_flags & (FieldAccessFlags::Static as u16) == 0 {
+            continue;
+        }
+        let field_name = pool.get_utf8(field.name_index).ok_or(Fault::ParseError)?.into_owned();
+        let descriptor = pool.get_utf8(field.descriptor_index).ok_or(Fault::ParseError)?.into_owned();
+        let value = match failure {
+            Option::Some(ref e) if written.contains(&(field_name.clone(), descriptor.clone())) => {
+                Err(UnknownReason::Failed(e.clone()))
+            },
+            _ => match interp.get_static(&name, &field_name, &descriptor) {
+                Ok(v) => recover(&interp, v, &mut Vec::new()),
+                Err(e) => Err(UnknownReason::Failed(e))
+            }
+        };
+        fields.push(StaticField { name: field_name, descriptor, value });
+    }
+    Ok(fields)
+}
+
+/// Fields of `owner` the code of `classes` may store to, by name and descriptor
+///
+/// Methods that fail to decode are assumed to write every field.
+fn assigned_fields(classes: &[&Class], owner: &str) -> Result<HashSet<(String, String)>, Fault> {
+    let mut written = HashSet::new();
+    for class in classes {
+        let pool = class.get_constants();
+        for i in 0..class.get_methods_count() {
+            let code = match class.get_method_code(i)? {
+                Option::Some(x) => x,
+                Option::None => continue
+            };
+            for x in Decoder::new(code.code) {
+                let index = match x {
+                    Ok((_, JOp::PutStatic(index))) => index,
+                    Ok(_) => continue,
+                    Err(_) => return all_fields(classes[0])
+                };
+                match pool.get_member_ref(index) {
+                    Option::Some(m) if m.class == owner => {
+                        written.insert((m.name.into_owned(), m.descriptor.into_owned()));
+                    },
+                    Option::Some(_) => {},
+                    Option::None => return all_fields(classes[0])
+                };
+            }
+        }
+    }
+    Ok(written)
+}
+
+fn all_fields(class: &Class) -> Result<HashSet<(String, String)>, Fault> {
+    let pool = class.get_constants();
+    let mut all = HashSet::new();
+    for i in 0..class.get_fields_count() {
+        let field = class.get_field(i).unwrap();
+        let name = pool.get_utf8(field.name_index).ok_or(Fault::ParseError)?;
+        let descriptor = pool.get_utf8(field.descriptor_index).ok_or(Fault::ParseError)?;
+        all.insert((name.into_owned(), descriptor.into_owned()));
+    }
+    Ok(all)
+}
+
+/// Copy a value out of the interpreter, `path` holds the arrays being copied
+fn recover(interp: &Interpreter, v: Value, path: &mut Vec<usize>) -> Result<StaticValue, UnknownReason> {
+    let x = match v {
+        Value::Int(x) => return Ok(StaticValue::Int(x)),
+        Value::Long(x) => return Ok(StaticValue::Long(x)),
+        Value::Float(x) => return Ok(StaticValue::Float(x)),
+        Value::Double(x) => return Ok(StaticValue::Double(x)),
+        Value::Ref(x) => x,
+        Value::Null | Value::ReturnAddress(_) => return Ok(StaticValue::Null)
+    };
+    match interp.object(v) {
+        Option::Some(Object::Str(s)) => Ok(StaticValue::Str(JavaString::from_utf16(s.clone()))),
+        Option::Some(Object::Class(name)) => Ok(StaticValue::Class(name.clone())),
+        Option::Some(Object::Array { component, values }) => {
+            if path.contains(&x) {
+                return Err(UnknownReason::Cyclic);
+            }
+            path.push(x);
+            let elements = values.iter()
+                .map(|&e| recover(interp, e, path))
+                .collect::<Result<Vec<_>, _>>();
+            path.pop();
+            Ok(StaticValue::Array(component.clone(), elements?))
+        },
+        Option::Some(object) => Err(UnknownReason::Opaque(object.class_name().into_owned())),
+        Option::None => Ok(StaticValue::Null)
+    }
+}
+
+#[test]
+fn test_evaluate_statics() {
+    use super::asm::assemble_class;
+    let good = assemble_class("\
+.class public Good
+.field static final TABLE [I
+.field static final NAME Ljava/lang/String;
+.field static BUFFER Ljava/lang/StringBuilder;
+.field static SELF [Ljava/lang/Object;
+.method static \"<clinit>\"()V
+    iconst_3
+    newarray int
+    dup
+    iconst_1
+    iconst_5
+    invokestatic Help.twice:(I)I
+    iastore
+    putstatic TABLE:[I
+    new java/lang/StringBuilder
+    dup
+    invokespecial java/lang/StringBuilder.\"<init>\":()V
+    ldc \"ab\"
+    invokevirtual java/lang/StringBuilder.append:(Ljava/lang/String;)Ljava/lang/StringBuilder;
+    bipush 99
+    invokevirtual java/lang/StringBuilder.append:(C)Ljava/lang/StringBuilder;
+    dup
+    putstatic BUFFER:Ljava/lang/StringBuilder;
+    invokevirtual java/lang/StringBuilder.toString:()Ljava/lang/String;
+    putstatic NAME:Ljava/lang/String;
+    iconst_1
+    anewarray java/lang/Object
+    dup
+    dup
+    iconst_0
+    swap
+    aastore
+    putstatic SELF:[Ljava/lang/Object;
+    return
+.end method
+").unwrap();
+    let bad = assemble_class("\
+.class public Bad
+.field static final LIMIT I = 5
+.field static COUNT J
+.field static STAMP J
+.method static \"<clinit>\"()V
+    invokestatic java/lang/System.nanoTime:()J
+    putstatic STAMP:J
+    return
+.end method
+").unwrap();
+    let help = assemble_class("\
+.class public Help
+.method static twice(I)I
+    iload_0
+    iconst_2
+    imul
+    ireturn
+.end method
+").unwrap();
+    let good = Class::parse(&good).unwrap();
+    let bad = Class::parse(&bad).unwrap();
+    let help = Class::parse(&help).unwrap();
+
+    let fields = evaluate_statics(&good, &[&help]).unwrap();
+    let values = fields.iter().map(|f| f.value.clone()).collect::<Vec<_>>();
+    assert_eq!(fields[0].name, "TABLE");
+    assert_eq!(values[0].clone().unwrap(), StaticValue::Array(FieldType::Int,
+        vec![StaticValue::Int(0), StaticValue::Int(10), StaticValue::Int(0)]));
+    assert_eq!(values[1].clone().unwrap(), StaticValue::Str(JavaString::from_utf16("abc".encode_utf16().collect())));
+    assert!(matches!(values[2], Err(UnknownReason::Opaque(ref x)) if x == "java/lang/StringBuilder"));
+    assert!(matches!(values[3], Err(UnknownReason::Cyclic)));
+
+    let fields = evaluate_statics(&bad, &[]).unwrap();
+    assert_eq!(fields.iter().map(|f| f.name.as_str()).collect::<Vec<_>>(), ["LIMIT", "COUNT", "STAMP"]);
+    assert_eq!(fields[0].value.clone().unwrap(), StaticValue::Int(5));
+    assert_eq!(fields[1].value.clone().unwrap(), StaticValue::Long(0));
+    match fields[2].value {
+        Err(UnknownReason::Failed(ref e)) => assert_eq!((e.method.as_str(), e.pc), ("Bad.<clinit>:()V", 0)),
+        ref x => panic!("{:?}", x)
+    };
+}
@@ -0,0 +1,288 @@
+//! Local variable liveness and reaching definitions.
+//!
+//! Both analyses run over the blocks of a `Cfg` and report results per
+//! instruction. Locals are tracked slot by slot, so a long or double
+//! touches two slots. Every instruction inside a try range may transfer
+//! control to its handler, so handlers see the locals as they were
+//! before any instruction of a covered block.
+
+use super::cfg::{BlockId, Cfg};
+use super::locals::LocalAccess;
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+/// A set of local variable slots
+pub type Slots = BTreeSet<u16>;
+
+/// Slots an access reads and slots it writes
+fn access_slots(access: LocalAccess, index: u16) -> (Vec<u16>, Vec<u16>) {
+    let slots: Vec<u16> = (index..index.saturating_add(access.width())).collect();
+    let reads = if access.reads() { slots.clone() } else { Vec::new() };
+    let writes = if access.writes() { slots } else { Vec::new() };
+    (reads, writes)
+}
+
+fn code_len(cfg: &Cfg) -> usize {
+    cfg.blocks.last().map_or(0, |b| b.end)
+}
+
+/// Handlers a block can throw to, and its normal successors
+fn split_successors(cfg: &Cfg, block: BlockId) -> (Vec<BlockId>, Vec<BlockId>) {
+    let mut handlers = Vec::new();
+    let mut normal = Vec::new();
+    for edge in cfg.successors(block) {
+        if edge.kind.is_exceptional() {
+            handlers.push(edge.to);
+        } else {
+            normal.push(edge.to);
+        }
+    }
+    (handlers, normal)
+}
+
+/// Which local slots hold a value that may still be read
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Liveness {
+    /// Live before each instruction, indexed by pc
+    live_in: Vec<Option<Slots>>,
+    /// Live after each instruction, indexed by pc
+    live_out: Vec<Option<Slots>>
+}
+
+impl Liveness {
+
+    /// Compute liveness for every instruction of `cfg`
+    pub fn compute(cfg: &Cfg) -> Liveness {
+        let len = code_len(cfg);
+        let mut info = Liveness {
+            live_in: vec![None; len],
+            live_out: vec![None; len]
+        };
+        let mut block_in: Vec<Slots> = vec![Slots::new(); cfg.blocks.len()];
+        let mut work: Vec<BlockId> = (0..cfg.blocks.len()).collect();
+        let mut queued = vec![true; cfg.blocks.len()];
+        while let Option::Some(b) = work.pop() {
+            queued[b] = false;
+            let (handlers, normal) = split_successors(cfg, b);
+            let caught: Slots = handlers.iter().flat_map(|h| block_in[*h].iter().cloned()).collect();
+            let mut live: Slots = normal.iter().flat_map(|s| block_in[*s].iter().cloned()).collect();
+            for &(pc, ref op) in cfg.block_ops(b).iter().rev() {
+                info.live_out[pc] = Some(live.clone());
+                if let Option::Some((access, index)) = op.local_access() {
+                    let (reads, writes) = access_slots(access, index);
+                    for slot in writes {
+                        live.remove(&slot);
+                    }
+                    live.extend(reads);
+                }
+                live.extend(caught.iter().cloned());
+                info.live_in[pc] = Some(live.clone());
+            }
+            if live != block_in[b] {
+                block_in[b] = live;
+                for edge in cfg.predecessors(b) {
+                    if !queued[edge.from] {
+                        queued[edge.from] = true;
+                        work.push(edge.from);
+                    }
+                }
+            }
+        }
+        info
+    }
+
+    /// Slots live before the instruction at `pc`
+    pub fn live_in(&self, pc: usize) -> Option<&Slots> {
+        self.live_in.get(pc).and_then(Option::as_ref)
+    }
+
+    /// Slots live after the instruction at `pc`
+    pub fn live_out(&self, pc: usize) -> Option<&Slots> {
+        self.live_out.get(pc).and_then(Option::as_ref)
+    }
+
+    /// pcs of stores and increments whose value is never read
+    pub fn dead_stores(&self, cfg: &Cfg) -> Vec<usize> {
+        let mut dead = Vec::new();
+        for &(pc, ref op) in cfg.ops.iter() {
+            let (access, index) = match op.local_access() {
+                Option::Some(x) => x,
+                Option::None => continue
+            };
+            let (_, writes) = access_slots(access, index);
+            let live = match self.live_out(pc) {
+                Option::Some(x) => x,
+                Option::None => continue
+            };
+            if !writes.is_empty() && !writes.iter().any(|s| live.contains(s)) {
+                dead.push(pc);
+            }
+        }
+        dead
+    }
+}
+
+/// Where the value in a local slot came from
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub enum Definition {
+    /// A parameter slot, holding its value from method entry
+    Entry(u16),
+    /// The store or `iinc` at this pc
+    ///
+    /// Long and double stores define both of their slots.
+    Store(usize)
+}
+
+/// Definitions reaching each slot at one point
+type DefMap = BTreeMap<u16, BTreeSet<Definition>>;
+
+fn merge_defs(into: &mut DefMap, from: &DefMap) -> bool {
+    let mut changed = false;
+    for (slot, defs) in from.iter() {
+        let set = into.entry(*slot).or_default();
+        for d in defs.iter() {
+            changed |= set.insert(*d);
+        }
+    }
+    changed
+}
+
+/// Which definitions of each local slot reach each instruction
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ReachingDefs {
+    /// Definitions before each instruction, indexed by pc
+    before: Vec<Option<DefMap>>,
+    /// The slot read by each loading instruction
+    reads: HashMap<usize, u16>
+}
+
+impl ReachingDefs {
+
+    /// Compute reaching definitions for the reachable instructions of
+    /// `cfg`
+    ///
+    /// `arg_slots` is the number of local slots taken by the method's
+    /// parameters, including `this` for instance methods.
+    pub fn compute(cfg: &Cfg, arg_slots: u16) -> ReachingDefs {
+        let mut info = ReachingDefs {
+            before: vec![None; code_len(cfg)],
+            reads: HashMap::new()
+        };
+        if cfg.blocks.is_empty() {
+            return info;
+        }
+        let mut entry: Vec<Option<DefMap>> = vec![None; cfg.blocks.len()];
+        entry[cfg.entry()] = Some((0..arg_slots)
+            .map(|s| (s, Some(Definition::Entry(s)).into_iter().collect()))
+            .collect());
+        let mut work = vec![cfg.entry()];
+        while let Option::Some(b) = work.pop() {
+            let mut defs = entry[b].clone().unwrap();
+            let mut caught = DefMap::new();
+            for &(pc, ref op) in cfg.block_ops(b) {
+                info.before[pc] = Some(defs.clone());
+                merge_defs(&mut caught, &defs);
+                let (access, index) = match op.local_access() {
+                    Option::Some(x) => x,
+                    Option::None => continue
+                };
+                if access.reads() {
+                    info.reads.insert(pc, index);
+                }
+                let (_, writes) = access_slots(access, index);
+                for &slot in writes.iter() {
+                    // a two slot value loses its other half
+                    let old = defs.remove(&slot).unwrap_or_default();
+                    let neighbours = [slot.checked_sub(1), slot.checked_add(1)];
+                    for n in neighbours.iter().filter_map(|x| *x) {
+                        if writes.contains(&n) {
+                            continue;
+                        }
+                        if let Option::Some(set) = defs.get_mut(&n) {
+                            set.retain(|d| !old.contains(d));
+                        }
+                    }
+                }
+                for &slot in writes.iter() {
+                    defs.insert(slot, Some(Definition::Store(pc)).into_iter().collect());
+                }
+            }
+            for edge in cfg.successors(b) {
+                let from = if edge.kind.is_exceptional() { &caught } else { &defs };
+                let changed = match entry[edge.to] {
+                    Option::Some(ref mut x) => merge_defs(x, from),
+                    Option::None => {
+                        entry[edge.to] = Some(from.clone());
+                        true
+                    }
+                };
+                if changed {
+                    work.push(edge.to);
+                }
+            }
+        }
+        info
+    }
+
+    /// Definitions of `slot` reaching the instruction at `pc`
+    ///
+    /// `None` if `pc` is unreachable, empty if nothing was ever stored.
+    pub fn reaching(&self, pc: usize, slot: u16) -> Option<BTreeSet<Definition>> {
+        self.before.get(pc)
+            .and_then(Option::as_ref)
+            .map(|defs| defs.get(&slot).cloned().unwrap_or_default())
+    }
+
+    /// Definitions reaching the local read by the load, `iinc` or `ret`
+    /// at `pc`
+    ///
+    /// For longs and doubles this is the lower of the two slots.
+    pub fn sources(&self, pc: usize) -> Option<BTreeSet<Definition>> {
+        self.reads.get(&pc).and_then(|slot| self.reaching(pc, *slot))
+    }
+}
+
+#[test]
+fn test_dataflow() {
+    use super::super::clazz::ExceptionEntry;
+    use super::decoder::Decoder;
+
+    let build = |code: &[u8], ex: &[ExceptionEntry]| {
+        let ops = Decoder::new(code).map(Result::unwrap).collect();
+        Cfg::build(ops, code.len(), ex).unwrap()
+    };
+    let slots = |x: &[u16]| x.iter().cloned().collect::<Slots>();
+    let defs = |x: &[Definition]| x.iter().cloned().collect::<BTreeSet<Definition>>();
+
+    // static int m(int a)
+    // 0: iconst_5; istore_1; iload_0; ifeq 11
+    // 6: iconst_1; istore_1; goto 13
+    // 11: iconst_2; istore_1
+    // 13: iload_1; lconst_0; lstore_2; lload_2; l2i; iadd; ireturn
+    let cfg = build(&[
+        0x08, 0x3C, 0x1A, 0x99, 0x00, 0x08, 0x04, 0x3C, 0xA7, 0x00,
+        0x05, 0x05, 0x3C, 0x1B, 0x09, 0x41, 0x20, 0x88, 0x60, 0xAC
+    ], &[]);
+    let live = Liveness::compute(&cfg);
+    assert_eq!(live.live_in(2), Some(&slots(&[0])));
+    assert_eq!(live.live_in(13), Some(&slots(&[1])));
+    assert_eq!(live.live_out(15), Some(&slots(&[2, 3])));
+    assert_eq!(live.dead_stores(&cfg), vec![1]);
+
+    let reaching = ReachingDefs::compute(&cfg, 1);
+    assert_eq!(reaching.sources(2), Some(defs(&[Definition::Entry(0)])));
+    assert_eq!(reaching.sources(13), Some(defs(&[Definition::Store(7), Definition::Store(12)])));
+    assert_eq!(reaching.reaching(16, 3), Some(defs(&[Definition::Store(15)])));
+    assert_eq!(reaching.reaching(2, 2), Some(BTreeSet::new()));
+
+    // static void m()
+    // 0: iconst_1; istore_0; iconst_2; istore_0; aconst_null; athrow
+    // 6: astore_1; iload_0; pop; return (handler for 2..6)
+    let ex = [ExceptionEntry { start_pc: 2, end_pc: 6, handler_pc: 6, catch_type: 0 }];
+    let cfg = build(&[0x04, 0x3B, 0x05, 0x3B, 0x01, 0xBF, 0x4C, 0x1A, 0x57, 0xB1], &ex);
+    let live = Liveness::compute(&cfg);
+    assert_eq!(live.dead_stores(&cfg), vec![6]);
+    assert_eq!(live.live_out(1), Some(&slots(&[0])));
+    let reaching = ReachingDefs::compute(&cfg, 0);
+    assert_eq!(reaching.sources(7), Some(defs(&[Definition::Store(1), Definition::Store(3)])));
+}
@@ -0,0 +1,141 @@
+//! Stateless bytecode decoding.
+//!
+//! Everything is driven by an explicit offset into the method's code
+//! array, so decoding is reentrant and can begin at any instruction
+//! boundary.
+
+use super::super::nom::{IResult, ErrorKind};
+use super::ops::{JOp, parse_java_op, UNKNOWN_OPCODE};
+
+use std::fmt;
+
+/// Why an instruction could not be decoded
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum DecodeFault {
+    /// The opcode byte is not assigned by the JVM spec
+    UnknownOpcode,
+    /// The code array ends in the middle of the instruction
+    Truncated,
+    /// The opcode is known but its operands are not valid
+    Malformed
+}
+
+/// A decoding failure and where it happened
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct DecodeError {
+    /// Offset of the offending instruction within the code array
+    pub pc: usize,
+    pub opcode: u8,
+    pub reason: DecodeFault
+}
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let why = match self.reason {
+            DecodeFault::UnknownOpcode => "unknown opcode",
+            DecodeFault::Truncated => "truncated instruction",
+            DecodeFault::Malformed => "malformed operands"
+        };
+        write!(f, "{} 0x{:02X} at pc {}", why, self.opcode, self.pc)
+    }
+}
+
+/// Decode the single instruction starting at `pc` in `code`
+///
+/// Returns the instruction and the pc of the one following it.
+pub fn decode_op(code: &[u8], pc: usize) -> Result<(JOp, usize), DecodeError> {
+    let buffer = match code.get(pc..) {
+        Option::Some(x) if !x.is_empty() => x,
+        _ => return Err(DecodeError { pc, opcode: 0, reason: DecodeFault::Truncated })
+    };
+    let opcode = buffer[0];
+    match parse_java_op(buffer, pc) {
+        IResult::Done(rem, op) => Ok((op, code.len() - rem.len())),
+        IResult::Incomplete(_) => Err(DecodeError { pc, opcode, reason: DecodeFault::Truncated }),
+        IResult::Error(ErrorKind::Custom(UNKNOWN_OPCODE)) => {
+            Err(DecodeError { pc, opcode, reason: DecodeFault::UnknownOpcode })
+        },
+        IResult::Error(_) => Err(DecodeError { pc, opcode, reason: DecodeFault::Malformed })
+    }
+}
+
+/// Lazily decodes a code array into `(pc, instruction)` pairs
+///
+/// Stops after the first error.
+#[derive(Clone,Debug)]
+pub struct Decoder<'a> {
+    code: &'a [u8],
+    pc: usize,
+    failed: bool
+}
+impl<'a> Decoder<'a> {
+
+    /// Decode a whole code array
+    pub fn new(code: &'a [u8]) -> Decoder<'a> {
+        Decoder::starting_at(code, 0)
+    }
+
+    /// Decode from `pc` onwards, `pc` must be an instruction boundary
+    pub fn starting_at(code: &'a [u8], pc: usize) -> Decoder<'a> {
+        Decoder {
+            code,
+            pc,
+            failed: false
+        }
+    }
+
+    /// The pc of the next instruction to be decoded
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+}
+impl<'a> Iterator for Decoder<'a> {
+    type Item = Result<(usize, JOp), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed || self.pc >= self.code.len() {
+            return None;
+        }
+        let pc = self.pc;
+        match decode_op(self.code, pc) {
+            Ok((op, next)) => {
+                self.pc = next;
+                Some(Ok((pc, op)))
+            },
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[test]
+fn test_decoder() {
+    let code: &[u8] = &[
+        0x00,                           // 0: nop
+        0xAA, 0x00, 0x00,               // 1: tableswitch, 2 bytes of padding
+        0x00, 0x00, 0x00, 0x13,         //    default +19
+        0x00, 0x00, 0x00, 0x01,         //    low 1
+        0x00, 0x00, 0x00, 0x01,         //    high 1
+        0x00, 0x00, 0x00, 0x13,         //    1 => +19
+        0x11, 0x01, 0x00,               // 20: sipush 256
+        0xB1,                           // 23: return
+    ];
+    let ops: Vec<(usize, JOp)> = Decoder::new(code).map(Result::unwrap).collect();
+    assert_eq!(ops, vec![
+        (0, JOp::Nop),
+        (1, JOp::TableSwitch(19, 1, 1, vec![19])),
+        (20, JOp::SIPush(256)),
+        (23, JOp::Return)
+    ]);
+    // resume mid method, padding depends on the absolute pc
+    assert_eq!(Decoder::starting_at(code, 1).count(), 3);
+
+    let err = Decoder::new(&[0x00, 0xB6, 0x00]).last().unwrap().unwrap_err();
+    assert_eq!(err, DecodeError { pc: 1, opcode: 0xB6, reason: DecodeFault::Truncated });
+    let err = decode_op(&[0xE0], 0).unwrap_err();
+    assert_eq!(err.reason, DecodeFault::UnknownOpcode);
+    // `wide` only applies to local variable instructions
+    let err = decode_op(&[0xC4, 0x60, 0x00, 0x01], 0).unwrap_err();
+    assert_eq!(err.reason, DecodeFault::Malformed);
+}
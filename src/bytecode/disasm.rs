@@ -0,0 +1,633 @@
+//! Listings of whole classes in the format of `javap -c -v -p`.
+//!
+//! Constant pool operands are resolved into trailing comments, branch
+//! offsets are shown as absolute pcs. The `Classfile`, modification time
+//! and checksum lines are left out since only the parsed class is known,
+//! and generic signatures are not folded into the declarations.
+
+use super::super::clazz::{
+    Class,
+    ClassAccessFlags,
+    CodeAttribute,
+    ConstantsPool,
+    Fault,
+    FieldAccessFlags,
+    FieldType,
+    MethodAccessFlags,
+    MethodType,
+    PoolMembers,
+    AttributeInfo,
+    find_attribute,
+    read_line_number_table,
+    read_local_variable_table
+};
+use super::decoder::{Decoder, DecodeError};
+use super::ops::JOp;
+use super::labels::Jump;
+
+use std::fmt;
+
+/// Why a class could not be listed
+#[derive(Clone,Copy,Debug)]
+pub enum DisasmFault {
+    /// A name, descriptor or attribute of the class is malformed
+    Class(Fault),
+    Decode(DecodeError)
+}
+
+/// A listing failure, and the method it happened in
+#[derive(Clone,Copy,Debug)]
+pub struct DisasmError {
+    /// Index of the method, `None` outside of methods
+    pub method: Option<usize>,
+    pub reason: DisasmFault
+}
+impl fmt::Display for DisasmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            DisasmFault::Class(Fault::ParseError) => write!(f, "malformed class file")?,
+            DisasmFault::Class(Fault::ClassLookUpFailure) => write!(f, "invalid class constant")?,
+            DisasmFault::Class(Fault::UTF8LookupFailure) => write!(f, "invalid name or descriptor")?,
+            DisasmFault::Class(Fault::MethodLookUpFailure) => write!(f, "no such method")?,
+            DisasmFault::Class(Fault::AttributeParseError) => write!(f, "malformed attribute")?,
+            DisasmFault::Decode(ref e) => write!(f, "{}", e)?
+        };
+        match self.method {
+            Option::Some(x) => write!(f, " in method {}", x),
+            Option::None => Ok(())
+        }
+    }
+}
+
+/// Pad `text` to `width` columns and append a `//` comment
+fn commented(text: String, width: usize, comment: Option<String>) -> String {
+    match comment {
+        Option::Some(c) => format!("{:<w$} // {}", text, c, w = width).trim_end().to_string(),
+        Option::None => text
+    }
+}
+
+/// Escape a constant for display, the way `javap` does
+fn escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '\t' => out.push_str("\\t"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\u{8}' => out.push_str("\\b"),
+            '\u{c}' => out.push_str("\\f"),
+            '"' => out.push_str("\\\""),
+            '\'' => out.push_str("\\'"),
+            '\\' => out.push_str("\\\\"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out
+}
+
+/// Quote names that are not plain identifiers, `"<init>"` and `"[I"`
+fn quote(name: &str) -> String {
+    if name.starts_with('<') || name.starts_with('[') {
+        format!("\"{}\"", name)
+    } else {
+        name.to_string()
+    }
+}
+
+/// Java's `Double.toString` layout, `2.0`, `1.0E20`
+fn float_text(x: f64) -> String {
+    let abs = x.abs();
+    if x.is_infinite() {
+        if x > 0.0 { "Infinity" } else { "-Infinity" }.to_string()
+    } else if x.is_nan() || abs == 0.0 || (1e-3..1e7).contains(&abs) {
+        format!("{:?}", x)
+    } else {
+        let text = format!("{:e}", x);
+        let (mantissa, exp) = text.split_at(text.find('e').unwrap());
+        let dot = if mantissa.contains('.') { "" } else { ".0" };
+        format!("{}{}E{}", mantissa, dot, &exp[1..])
+    }
+}
+
+fn handle_kind(kind: u8) -> &'static str {
+    match kind {
+        1 => "REF_getField",
+        2 => "REF_getStatic",
+        3 => "REF_putField",
+        4 => "REF_putStatic",
+        5 => "REF_invokeVirtual",
+        6 => "REF_invokeStatic",
+        7 => "REF_invokeSpecial",
+        8 => "REF_newInvokeSpecial",
+        9 => "REF_invokeInterface",
+        _ => "REF_???"
+    }
+}
+
+/// Source form of a field type, `java.lang.String[]`
+fn java_type(t: &FieldType) -> String {
+    match *t {
+        FieldType::Byte => "byte".to_string(),
+        FieldType::Char => "char".to_string(),
+        FieldType::Double => "double".to_string(),
+        FieldType::Float => "float".to_string(),
+        FieldType::Int => "int".to_string(),
+        FieldType::Long => "long".to_string(),
+        FieldType::Short => "short".to_string(),
+        FieldType::Boolean => "boolean".to_string(),
+        FieldType::Object(ref name) => name.replace('/', "."),
+        FieldType::Array(ref inner) => format!("{}[]", java_type(inner))
+    }
+}
+
+/// `ACC_` names of the set bits, `(0x0021) ACC_PUBLIC, ACC_SUPER`
+fn flag_text<T: fmt::Debug>(raw: u16, flags: Vec<T>) -> String {
+    let names: Vec<String> = flags.iter()
+        .map(|f| format!("ACC_{:?}", f).to_uppercase())
+        .collect();
+    format!("(0x{:04x}) {}", raw, names.join(", ")).trim_end().to_string()
+}
+
+/// Resolves constants the way `javap` prints them in comments
+struct Resolver<'p, 'a: 'p> {
+    pool: &'p ConstantsPool<'a>,
+    /// Owner left out of member references, the class being listed
+    this: Option<&'p str>
+}
+impl<'p, 'a> Resolver<'p, 'a> {
+
+    /// `owner.name:descriptor` of a member reference
+    fn member(&self, index: u16, this: Option<&str>) -> Option<String> {
+        let m = self.pool.get_member_ref(index)?;
+        Some(match this {
+            Option::Some(x) if x == m.class => format!("{}:{}", quote(&m.name), m.descriptor),
+            _ => format!("{}.{}:{}", quote(&m.class), quote(&m.name), m.descriptor)
+        })
+    }
+
+    fn name_and_type(&self, index: u16) -> Option<String> {
+        let (name, desc) = self.pool.get_name_and_type(index)?;
+        Some(format!("{}:{}", quote(&name), desc))
+    }
+
+    /// The value of a constant, without its kind
+    ///
+    /// Member references only leave out their owner inside code.
+    fn value(&self, index: u16, in_code: bool) -> Option<String> {
+        let this = if in_code { self.this } else { None };
+        Some(match *self.pool.get(index)? {
+            PoolMembers::ClassInfo(_) => quote(&self.pool.get_class_name(index)?),
+            PoolMembers::FieldRef(..) |
+            PoolMembers::MethodRef(..) |
+            PoolMembers::InterfaceMethodRef(..) => self.member(index, this)?,
+            PoolMembers::Str(x) => escape(&self.pool.get_utf8(x)?),
+            PoolMembers::Integer(x) => x.to_string(),
+            PoolMembers::Float(x) => format!("{}f", float_text(x as f64)),
+            PoolMembers::Long(x) => format!("{}l", x),
+            PoolMembers::Double(x) => format!("{}d", float_text(x)),
+            PoolMembers::NameAndType(..) => self.name_and_type(index)?,
+            PoolMembers::Utf8(ref s) => escape(&s.to_string_lossy()),
+            PoolMembers::MethodHandle(kind, x) => {
+                format!("{} {}", handle_kind(kind), self.member(x, None)?)
+            },
+            PoolMembers::MethodType(x) => self.pool.get_utf8(x)?.into_owned(),
+            PoolMembers::InvokeDynamic(bsm, nat) => format!("#{}:{}", bsm, self.name_and_type(nat)?),
+            PoolMembers::Module(x) |
+            PoolMembers::Package(x) => self.pool.get_utf8(x)?.into_owned()
+        })
+    }
+
+    /// A constant prefixed with its kind, `Method java/lang/Object."<init>":()V`
+    fn operand(&self, index: u16) -> String {
+        let kind = match self.pool.get(index) {
+            Option::Some(&PoolMembers::ClassInfo(_)) => "class",
+            Option::Some(&PoolMembers::FieldRef(..)) => "Field",
+            Option::Some(&PoolMembers::MethodRef(..)) => "Method",
+            Option::Some(&PoolMembers::InterfaceMethodRef(..)) => "InterfaceMethod",
+            Option::Some(&PoolMembers::Str(_)) => "String",
+            Option::Some(&PoolMembers::Integer(_)) => "int",
+            Option::Some(&PoolMembers::Float(_)) => "float",
+            Option::Some(&PoolMembers::Long(_)) => "long",
+            Option::Some(&PoolMembers::Double(_)) => "double",
+            Option::Some(&PoolMembers::NameAndType(..)) => "NameAndType",
+            Option::Some(&PoolMembers::Utf8(_)) => "Utf8",
+            Option::Some(&PoolMembers::MethodHandle(..)) => "MethodHandle",
+            Option::Some(&PoolMembers::MethodType(_)) => "MethodType",
+            Option::Some(&PoolMembers::InvokeDynamic(..)) => "InvokeDynamic",
+            Option::Some(&PoolMembers::Module(_)) => "Module",
+            Option::Some(&PoolMembers::Package(_)) => "Package",
+            Option::None => return "invalid constant".to_string()
+        };
+        match self.value(index, true) {
+            Option::Some(v) => format!("{} {}", kind, v),
+            Option::None => format!("{} ???", kind)
+        }
+    }
+}
+
+/// The `#n = Kind args // value` line of one constant
+fn pool_line(r: &Resolver, index: u16, width: usize) -> String {
+    let (kind, args) = match *r.pool.get(index).unwrap() {
+        PoolMembers::ClassInfo(x) => ("Class", format!("#{}", x)),
+        PoolMembers::FieldRef(c, n) => ("Fieldref", format!("#{}.#{}", c, n)),
+        PoolMembers::MethodRef(c, n) => ("Methodref", format!("#{}.#{}", c, n)),
+        PoolMembers::InterfaceMethodRef(c, n) => ("InterfaceMethodref", format!("#{}.#{}", c, n)),
+        PoolMembers::Str(x) => ("String", format!("#{}", x)),
+        PoolMembers::Integer(_) => ("Integer", String::new()),
+        PoolMembers::Float(_) => ("Float", String::new()),
+        PoolMembers::Long(_) => ("Long", String::new()),
+        PoolMembers::Double(_) => ("Double", String::new()),
+        PoolMembers::NameAndType(n, d) => ("NameAndType", format!("#{}:#{}", n, d)),
+        PoolMembers::Utf8(_) => ("Utf8", String::new()),
+        PoolMembers::MethodHandle(kind, x) => ("MethodHandle", format!("{}:#{}", kind, x)),
+        PoolMembers::MethodType(x) => ("MethodType", format!("#{}", x)),
+        PoolMembers::InvokeDynamic(b, n) => ("InvokeDynamic", format!("#{}:#{}", b, n)),
+        PoolMembers::Module(x) => ("Module", format!("#{}", x)),
+        PoolMembers::Package(x) => ("Package", format!("#{}", x))
+    };
+    let head = format!("{:>w$} = {:<18} ", format!("#{}", index), kind, w = width);
+    if args.is_empty() {
+        // literals are printed in place of their operands
+        return format!("{}{}", head, r.value(index, false).unwrap_or_default()).trim_end().to_string();
+    }
+    let mut comment = r.value(index, false).unwrap_or_else(|| "???".to_string());
+    if kind == "MethodType" {
+        // javap leaves room for a kind here
+        comment.insert(0, ' ');
+    }
+    commented(format!("{}{}", head, args), 41, Some(comment))
+}
+
+fn array_type(code: u8) -> &'static str {
+    match code {
+        4 => "boolean",
+        5 => "char",
+        6 => "float",
+        7 => "double",
+        8 => "byte",
+        9 => "short",
+        10 => "int",
+        11 => "long",
+        _ => "???"
+    }
+}
+
+/// Listing of one instruction, which spans several lines for switches
+fn instruction(r: &Resolver, pc: usize, op: &JOp) -> String {
+    let head = format!("{:>10}: ", pc);
+    let name = op.mnemonic();
+    let cp = |index: u16, extra: Option<u8>| {
+        let args = match extra {
+            Option::Some(x) => format!("#{},  {}", index, x),
+            Option::None => format!("#{}", index)
+        };
+        commented(format!("{}{:<13} {}", head, name, args), 45, Some(r.operand(index)))
+    };
+    let switch = |header: String, default: i32, cases: Vec<(i32, i32)>| {
+        let mut lines = vec![format!("{}{:<13} {{ // {}", head, name, header)];
+        for (key, offset) in cases {
+            lines.push(format!("{:>24}: {}", key, pc as i64 + offset as i64));
+        }
+        lines.push(format!("{:>24}: {}", "default", pc as i64 + default as i64));
+        lines.push(format!("{:>13}", "}"));
+        lines.join("\n")
+    };
+    if let Option::Some((_, offset)) = Jump::from_op(op) {
+        return format!("{}{:<13} {}", head, name, pc as i64 + offset as i64);
+    }
+    if let Option::Some((access, slot)) = op.local_access() {
+        // `iload_0` and friends carry the slot in their name
+        return match access {
+            _ if name.ends_with(|c: char| c.is_ascii_digit()) => format!("{}{}", head, name),
+            super::LocalAccess::Increment(c) => format!("{}{:<13} {}, {}", head, name, slot, c),
+            _ => format!("{}{:<13} {}", head, name, slot)
+        };
+    }
+    match *op {
+        JOp::LDC(x) => cp(x as u16, None),
+        JOp::LDCW(x) |
+        JOp::LDC2W(x) |
+        JOp::GetField(x) |
+        JOp::GetStatic(x) |
+        JOp::PutField(x) |
+        JOp::PutStatic(x) |
+        JOp::InvokeVirtual(x) |
+        JOp::InvokeSpecial(x) |
+        JOp::InvokeStatic(x) |
+        JOp::New(x) |
+        JOp::ANewArray(x) |
+        JOp::CheckCast(x) |
+        JOp::InstanceOf(x) => cp(x, None),
+        JOp::InvokedInterface(x, n) => cp(x, Some(n)),
+        JOp::InvokedDynamic(x) => cp(x, Some(0)),
+        JOp::MultiAneWArray(x, d) => cp(x, Some(d)),
+        JOp::BiPush(x) => format!("{}{:<13} {}", head, name, x as i8),
+        JOp::SIPush(x) => format!("{}{:<13} {}", head, name, x as i16),
+        JOp::NewArray(t) => format!("{}{:<14} {}", head, name, array_type(t)),
+        JOp::TableSwitch(default, low, high, ref offsets) => {
+            let cases = offsets.iter().enumerate().map(|(i, o)| (low + i as i32, *o)).collect();
+            switch(format!("{} to {}", low, high), default, cases)
+        },
+        JOp::LookUpSwitch(default, ref pairs) => switch(pairs.len().to_string(), default, pairs.clone()),
+        _ => format!("{}{}", head, name)
+    }
+}
+
+/// List the instructions and tables of a `Code` attribute
+///
+/// The output is indented as under a method in `javap -c -v`. Member
+/// references owned by `this_class` are printed without their owner.
+pub fn disassemble_code(code: &CodeAttribute, pool: &ConstantsPool, this_class: &str) -> Result<String, DecodeError> {
+    let r = Resolver { pool, this: Some(this_class) };
+    let mut lines = Vec::new();
+    for item in Decoder::new(code.code) {
+        let (pc, op) = item?;
+        lines.push(instruction(&r, pc, &op));
+    }
+    if !code.exception_table.is_empty() {
+        lines.push("      Exception table:".to_string());
+        lines.push("         from    to  target type".to_string());
+        for e in code.exception_table.iter() {
+            let kind = match e.catch_type {
+                0 => "any".to_string(),
+                x => format!("Class {}", r.value(x, false).unwrap_or_else(|| "???".to_string()))
+            };
+            lines.push(format!("{:>14}{:>6}{:>6}   {}", e.start_pc, e.end_pc, e.handler_pc, kind));
+        }
+    }
+    for attr in code.attributes.iter() {
+        match pool.get_utf8(attr.name_index).as_ref().map(|x| x.as_ref()) {
+            Option::Some("LineNumberTable") => {
+                lines.push("      LineNumberTable:".to_string());
+                for l in read_line_number_table(attr.data).unwrap_or_default() {
+                    lines.push(format!("        line {}: {}", l.line_number, l.start_pc));
+                }
+            },
+            Option::Some(name @ "LocalVariableTable") |
+            Option::Some(name @ "LocalVariableTypeTable") => {
+                lines.push(format!("      {}:", name));
+                lines.push("        Start  Length  Slot  Name   Signature".to_string());
+                for v in read_local_variable_table(attr.data).unwrap_or_default() {
+                    lines.push(format!("{:>13}{:>8}{:>6} {:>5}   {}", v.start_pc, v.length, v.index,
+                        pool.get_utf8(v.name_index).unwrap_or_default(),
+                        pool.get_utf8(v.descriptor_index).unwrap_or_default()));
+                }
+            },
+            _ => {}
+        };
+    }
+    Ok(lines.join("\n"))
+}
+
+/// `name:` and the UTF8 behind the `Signature` attribute, if there is one
+fn signature(attributes: &[AttributeInfo], r: &Resolver, indent: &str) -> Option<String> {
+    let attr = find_attribute(attributes, r.pool, "Signature")?;
+    let index = ((*attr.data.first()? as u16) << 8) | *attr.data.get(1)? as u16;
+    let text = format!("{}Signature: #{}", indent, index);
+    Some(commented(text, indent.len() + 39, r.value(index, false)))
+}
+
+/// Names of the classes in an `Exceptions` attribute
+fn throws(attributes: &[AttributeInfo], pool: &ConstantsPool) -> Vec<String> {
+    let data = match find_attribute(attributes, pool, "Exceptions") {
+        Option::Some(attr) => attr.data,
+        Option::None => return Vec::new()
+    };
+    data.chunks(2)
+        .skip(1)
+        .filter(|x| x.len() == 2)
+        .map(|x| pool.get_class_name(((x[0] as u16) << 8) | x[1] as u16).unwrap_or_default().replace('/', "."))
+        .collect()
+}
+
+/// Join access flags that are Java modifiers, `public static `
+fn modifiers<T: fmt::Debug>(flags: Vec<T>, keep: &[&str]) -> String {
+    flags.iter()
+        .map(|f| format!("{:?}", f).to_lowercase())
+        .filter(|f| keep.contains(&f.as_str()))
+        .map(|f| if f == "strict" { "strictfp ".to_string() } else { f + " " })
+        .collect()
+}
+
+/// List a whole class, the way `javap -c -v -p` does
+pub fn disassemble(class: &Class) -> Result<String, DisasmError> {
+    let class_fault = |method| move |reason| DisasmError { method, reason: DisasmFault::Class(reason) };
+    let pool = class.get_constants();
+    let this = class.get_this_class().map_err(class_fault(None))?;
+    let r = Resolver { pool, this: Some(&this) };
+    let mut lines = Vec::new();
+
+    let source = find_attribute(class.get_attributes(), pool, "SourceFile")
+        .and_then(|a| pool.get_utf8(((*a.data.first()? as u16) << 8) | *a.data.get(1)? as u16));
+    if let Option::Some(ref x) = source {
+        lines.push(format!("Compiled from \"{}\"", x));
+    }
+    let flags = class.get_access_flags();
+    let is_interface = flags & 0x0200 != 0;
+    let mut head = modifiers(ClassAccessFlags::and_mask(flags),
+        if is_interface { &["public"] } else { &["public", "final", "abstract"] });
+    head.push_str(if is_interface { "interface " } else { "class " });
+    head.push_str(&this.replace('/', "."));
+    let interfaces = class.get_interfaces().map_err(class_fault(None))?;
+    let interfaces: Vec<String> = interfaces.iter().map(|x| x.replace('/', ".")).collect();
+    match class.get_super_class() {
+        Ok(ref x) if x != "java/lang/Object" => head.push_str(&format!(" extends {}", x.replace('/', "."))),
+        _ => {}
+    };
+    if !interfaces.is_empty() {
+        head.push_str(if is_interface { " extends " } else { " implements " });
+        head.push_str(&interfaces.join(","));
+    }
+    lines.push(head);
+    lines.push(format!("  minor version: {}", class.get_minor_version()));
+    lines.push(format!("  major version: {}", class.get_major_version()));
+    lines.push(format!("  flags: {}", flag_text(flags, ClassAccessFlags::and_mask(flags))));
+    let this_index = class.get_this_class_index();
+    lines.push(commented(format!("  this_class: #{}", this_index), 41, r.value(this_index, false)));
+    let super_index = class.get_super_class_index();
+    lines.push(commented(format!("  super_class: #{}", super_index), 41, r.value(super_index, false)));
+    lines.push(format!("  interfaces: {}, fields: {}, methods: {}, attributes: {}",
+        class.get_interfaces_count(), class.get_fields_count(), class.get_methods_count(),
+        class.get_attributes().len()));
+
+    lines.push("Constant pool:".to_string());
+    let width = pool.count().to_string().len() + 3;
+    let mut index = 1;
+    while index < pool.count() {
+        lines.push(pool_line(&r, index as u16, width));
+        index += match pool.get(index as u16) {
+            Option::Some(&PoolMembers::Long(_)) |
+            Option::Some(&PoolMembers::Double(_)) => 2,
+            _ => 1
+        };
+    }
+
+    lines.push("{".to_string());
+    // every field is followed by a blank line, methods are only separated by one
+    let mut methods = Vec::new();
+    for i in 0..class.get_fields_count() {
+        let field = class.get_field(i).unwrap();
+        let utf8 = |x| pool.get_utf8(x).ok_or(Fault::UTF8LookupFailure).map_err(class_fault(None));
+        let name = utf8(field.name_index)?;
+        let desc = utf8(field.descriptor_index)?;
+        let t = FieldType::parse(&desc).ok_or(Fault::ParseError).map_err(class_fault(None))?;
+        let f = field.access_flags;
+        let mut m = vec![
+            format!("  {}{} {};", modifiers(FieldAccessFlags::and_mask(f),
+                &["public", "private", "protected", "static", "final", "volatile", "transient"]), java_type(&t), name),
+            format!("    descriptor: {}", desc),
+            format!("    flags: {}", flag_text(f, FieldAccessFlags::and_mask(f)))
+        ];
+        if let Option::Some(attr) = find_attribute(&field.attributes, pool, "ConstantValue") {
+            let index = ((*attr.data.first().unwrap_or(&0) as u16) << 8) | *attr.data.get(1).unwrap_or(&0) as u16;
+            m.push(format!("    ConstantValue: {}", r.operand(index)));
+        }
+        m.extend(signature(&field.attributes, &r, "    "));
+        lines.push(m.join("\n"));
+        lines.push(String::new());
+    }
+    for i in 0..class.get_methods_count() {
+        let method = class.get_method(i).unwrap();
+        let utf8 = |x| pool.get_utf8(x).ok_or(Fault::UTF8LookupFailure).map_err(class_fault(Some(i)));
+        let name = utf8(method.name_index)?;
+        let desc = utf8(method.descriptor_index)?;
+        let t = MethodType::parse(&desc).ok_or(Fault::ParseError).map_err(class_fault(Some(i)))?;
+        let f = method.access_flags;
+        let mut params: Vec<String> = t.params.iter().map(java_type).collect();
+        if f & 0x0080 != 0 {
+            if let Option::Some(last) = params.last_mut() {
+                if last.ends_with("[]") {
+                    let len = last.len() - 2;
+                    last.truncate(len);
+                    last.push_str("...");
+                }
+            }
+        }
+        let mods = modifiers(MethodAccessFlags::and_mask(f), &["public", "private", "protected", "static",
+            "final", "synchronized", "native", "abstract", "strict"]);
+        let thrown = throws(&method.attributes, pool);
+        let mut decl = match name.as_ref() {
+            "<clinit>" => "  static {};".to_string(),
+            "<init>" => format!("  {}{}({});", mods, this.replace('/', "."), params.join(", ")),
+            _ => {
+                let ret = t.ret.as_ref().map_or("void".to_string(), java_type);
+                format!("  {}{} {}({});", mods, ret, name, params.join(", "))
+            }
+        };
+        if !thrown.is_empty() {
+            decl.pop();
+            decl.push_str(&format!(" throws {};", thrown.join(", ")));
+        }
+        let mut m = vec![
+            decl,
+            format!("    descriptor: {}", desc),
+            format!("    flags: {}", flag_text(f, MethodAccessFlags::and_mask(f)))
+        ];
+        if let Option::Some(code) = class.get_method_code(i).map_err(class_fault(Some(i)))? {
+            let args = t.params.len() + if f & 0x0008 != 0 { 0 } else { 1 };
+            m.push("    Code:".to_string());
+            m.push(format!("      stack={}, locals={}, args_size={}", code.max_stack, code.max_locals, args));
+            let body = disassemble_code(&code, pool, &this)
+                .map_err(|e| DisasmError { method: Some(i), reason: DisasmFault::Decode(e) })?;
+            m.push(body);
+        }
+        if !thrown.is_empty() {
+            m.push("    Exceptions:".to_string());
+            m.push(format!("      throws {}", thrown.join(", ")));
+        }
+        m.extend(signature(&method.attributes, &r, "    "));
+        methods.push(m.join("\n"));
+    }
+    if !methods.is_empty() {
+        lines.push(methods.join("\n\n"));
+    }
+    lines.push("}".to_string());
+    lines.extend(signature(class.get_attributes(), &r, ""));
+    if let Option::Some(x) = source {
+        lines.push(format!("SourceFile: \"{}\"", x));
+    }
+    Ok(lines.join("\n") + "\n")
+}
+
+#[test]
+fn test_disassemble() {
+    use super::super::clazz::Class;
+    // class T { static int f(int x) { return x > 0 ? x : -x; } } compiled with -g, trimmed to f
+    let mut bytes = b"\xCA\xFE\xBA\xBE\x00\x00\x00\x34\x00\x0F".to_vec();
+    let pool: Vec<&[u8]> = vec![
+        b"\x07\x00\x02", b"\x01\x00\x01T",
+        b"\x07\x00\x04", b"\x01\x00\x10java/lang/Object",
+        b"\x01\x00\x01f", b"\x01\x00\x04(I)I", b"\x01\x00\x04Code",
+        b"\x01\x00\x0FLineNumberTable", b"\x01\x00\x12LocalVariableTable",
+        b"\x01\x00\x01x", b"\x01\x00\x01I",
+        b"\x0A\x00\x03\x00\x0D", b"\x0C\x00\x0E\x00\x06", b"\x01\x00\x03abs"
+    ];
+    for c in pool {
+        bytes.extend_from_slice(c);
+    }
+    // flags, this, super, interfaces, fields, one method
+    bytes.extend_from_slice(b"\x00\x20\x00\x01\x00\x03\x00\x00\x00\x00\x00\x01");
+    bytes.extend_from_slice(b"\x00\x08\x00\x05\x00\x06\x00\x01");
+    let code = b"\x1A\x9E\x00\x07\x1A\xA7\x00\x05\x1A\x74\xAC";
+    let mut body = b"\x00\x01\x00\x01".to_vec();
+    body.extend_from_slice(&(code.len() as u32).to_be_bytes());
+    body.extend_from_slice(code);
+    body.extend_from_slice(b"\x00\x01\x00\x00\x00\x04\x00\x08\x00\x00\x00\x02");
+    body.extend_from_slice(b"\x00\x08\x00\x00\x00\x06\x00\x01\x00\x00\x00\x03");
+    body.extend_from_slice(b"\x00\x09\x00\x00\x00\x0C\x00\x01\x00\x00\x00\x0B\x00\x0A\x00\x0B\x00\x00");
+    bytes.extend_from_slice(b"\x00\x07");
+    bytes.extend_from_slice(&(body.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(&body);
+    bytes.extend_from_slice(b"\x00\x00");
+    let class = Class::parse(&bytes).unwrap();
+    let text = disassemble(&class).unwrap();
+    let expected = "\
+class T
+  minor version: 0
+  major version: 52
+  flags: (0x0020) ACC_SUPER
+  this_class: #1                          // T
+  super_class: #3                         // java/lang/Object
+  interfaces: 0, fields: 0, methods: 1, attributes: 0
+Constant pool:
+   #1 = Class              #2             // T
+   #2 = Utf8               T
+   #3 = Class              #4             // java/lang/Object
+   #4 = Utf8               java/lang/Object
+   #5 = Utf8               f
+   #6 = Utf8               (I)I
+   #7 = Utf8               Code
+   #8 = Utf8               LineNumberTable
+   #9 = Utf8               LocalVariableTable
+  #10 = Utf8               x
+  #11 = Utf8               I
+  #12 = Methodref          #3.#13         // java/lang/Object.abs:(I)I
+  #13 = NameAndType        #14:#6         // abs:(I)I
+  #14 = Utf8               abs
+{
+  static int f(int);
+    descriptor: (I)I
+    flags: (0x0008) ACC_STATIC
+    Code:
+      stack=1, locals=1, args_size=1
+         0: iload_0
+         1: ifle          8
+         4: iload_0
+         5: goto          10
+         8: iload_0
+         9: ineg
+        10: ireturn
+      Exception table:
+         from    to  target type
+             0     4     8   any
+      LineNumberTable:
+        line 3: 0
+      LocalVariableTable:
+        Start  Length  Slot  Name   Signature
+            0      11     0     x   I
+}
+";
+    assert_eq!(text, expected);
+}
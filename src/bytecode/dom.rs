@@ -0,0 +1,428 @@
+//! Dominance and loop structure of a control flow graph.
+//!
+//! Dominators are computed with the Cooper, Harvey, Kennedy iterative
+//! algorithm over every edge of the `Cfg`, exceptional ones included.
+//! Blocks that cannot be reached play no part in either tree.
+
+use super::cfg::{Cfg, BlockId, Edge};
+
+/// A dominator or post-dominator tree
+#[derive(Clone,Debug)]
+pub struct DomTree {
+    idom: Vec<Option<BlockId>>,
+    children: Vec<Vec<BlockId>>,
+    roots: Vec<BlockId>,
+    pre: Vec<usize>,
+    post: Vec<usize>,
+    frontiers: Vec<Vec<BlockId>>
+}
+
+/// Solve dominance over `succs`, a graph of `n` nodes rooted at a
+/// virtual node `n` whose successors are `roots`
+fn solve(succs: &[Vec<usize>], roots: &[usize]) -> DomTree {
+    let n = succs.len();
+    let virt = n;
+    let succ_of = |b: usize| -> &[usize] {
+        if b == virt { roots } else { &succs[b] }
+    };
+
+    // reverse postorder from the virtual root
+    let mut order = Vec::with_capacity(n + 1);
+    let mut seen = vec![false; n + 1];
+    let mut stack = vec![(virt, 0)];
+    seen[virt] = true;
+    while let Option::Some(&mut (b, ref mut i)) = stack.last_mut() {
+        match succ_of(b).get(*i) {
+            Option::Some(&s) => {
+                *i += 1;
+                if !seen[s] {
+                    seen[s] = true;
+                    stack.push((s, 0));
+                }
+            },
+            Option::None => {
+                order.push(b);
+                stack.pop();
+            }
+        };
+    }
+    order.reverse();
+    let mut rpo = vec![usize::MAX; n + 1];
+    for (i, &b) in order.iter().enumerate() {
+        rpo[b] = i;
+    }
+    let mut preds = vec![Vec::new(); n + 1];
+    for &b in order.iter() {
+        for &s in succ_of(b) {
+            preds[s].push(b);
+        }
+    }
+
+    let mut idom = vec![usize::MAX; n + 1];
+    idom[virt] = virt;
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for &b in order.iter().skip(1) {
+            let mut new = usize::MAX;
+            for &p in preds[b].iter() {
+                if idom[p] == usize::MAX {
+                    continue;
+                }
+                new = if new == usize::MAX {
+                    p
+                } else {
+                    let (mut x, mut y) = (p, new);
+                    while x != y {
+                        while rpo[x] > rpo[y] {
+                            x = idom[x];
+                        }
+                        while rpo[y] > rpo[x] {
+                            y = idom[y];
+                        }
+                    }
+                    x
+                };
+            }
+            if idom[b] != new {
+                idom[b] = new;
+                changed = true;
+            }
+        }
+    }
+
+    let mut frontiers = vec![Vec::new(); n];
+    for &b in order.iter().skip(1) {
+        if preds[b].len() < 2 {
+            continue;
+        }
+        for &p in preds[b].iter() {
+            let mut runner = p;
+            while runner != idom[b] && runner != virt {
+                if !frontiers[runner].contains(&b) {
+                    frontiers[runner].push(b);
+                }
+                runner = idom[runner];
+            }
+        }
+    }
+    for f in frontiers.iter_mut() {
+        f.sort();
+    }
+
+    let mut tree = DomTree {
+        idom: vec![None; n],
+        children: vec![Vec::new(); n],
+        roots: Vec::new(),
+        pre: vec![usize::MAX; n],
+        post: vec![usize::MAX; n],
+        frontiers
+    };
+    for &b in order.iter().skip(1) {
+        if idom[b] == virt {
+            tree.roots.push(b);
+        } else {
+            tree.idom[b] = Some(idom[b]);
+            tree.children[idom[b]].push(b);
+        }
+    }
+    tree.roots.sort();
+    for c in tree.children.iter_mut() {
+        c.sort();
+    }
+
+    // number the tree so dominance queries are constant time
+    let mut counter = 0;
+    for r in tree.roots.clone() {
+        let mut stack = vec![(r, 0)];
+        tree.pre[r] = counter;
+        counter += 1;
+        while let Option::Some(&mut (b, ref mut i)) = stack.last_mut() {
+            match tree.children[b].get(*i) {
+                Option::Some(&c) => {
+                    *i += 1;
+                    tree.pre[c] = counter;
+                    counter += 1;
+                    stack.push((c, 0));
+                },
+                Option::None => {
+                    tree.post[b] = counter;
+                    counter += 1;
+                    stack.pop();
+                }
+            };
+        }
+    }
+    tree
+}
+
+impl DomTree {
+
+    /// The dominator tree, rooted at the entry block
+    pub fn dominators(cfg: &Cfg) -> DomTree {
+        let succs = (0..cfg.blocks.len())
+            .map(|b| cfg.successors(b).map(|e| e.to).collect())
+            .collect::<Vec<Vec<BlockId>>>();
+        solve(&succs, &[cfg.entry()])
+    }
+
+    /// The post-dominator tree
+    ///
+    /// Every reachable block that returns or throws is a root. Blocks
+    /// that are stuck in an infinite loop never reach an exit, so
+    /// they are left out of the tree.
+    pub fn post_dominators(cfg: &Cfg) -> DomTree {
+        let mut succs = vec![Vec::new(); cfg.blocks.len()];
+        let mut exits = Vec::new();
+        for (b, block) in cfg.blocks.iter().enumerate() {
+            if !block.reachable {
+                continue;
+            }
+            if block.exit.is_some() {
+                exits.push(b);
+            }
+            for e in cfg.predecessors(b) {
+                if cfg.blocks[e.from].reachable {
+                    succs[b].push(e.from);
+                }
+            }
+        }
+        solve(&succs, &exits)
+    }
+
+    /// The dominator tree of any graph, given each node's successors
+    /// and the nodes it is entered at
+    pub fn from_successors(succs: &[Vec<BlockId>], roots: &[BlockId]) -> DomTree {
+        solve(succs, roots)
+    }
+
+    /// The immediate dominator, `None` for roots and blocks outside
+    /// the tree
+    pub fn idom(&self, block: BlockId) -> Option<BlockId> {
+        self.idom[block]
+    }
+
+    /// Blocks immediately dominated by `block`
+    pub fn children(&self, block: BlockId) -> &[BlockId] {
+        &self.children[block]
+    }
+
+    /// The entry for dominators, the exits for post-dominators
+    pub fn roots(&self) -> &[BlockId] {
+        &self.roots
+    }
+
+    /// Is the block part of the tree
+    pub fn contains(&self, block: BlockId) -> bool {
+        self.pre[block] != usize::MAX
+    }
+
+    /// Does `a` dominate `b`, every block dominates itself
+    pub fn dominates(&self, a: BlockId, b: BlockId) -> bool {
+        self.contains(a) && self.contains(b) &&
+            self.pre[a] <= self.pre[b] && self.post[b] <= self.post[a]
+    }
+
+    /// Does `a` dominate `b` and differ from it
+    pub fn strictly_dominates(&self, a: BlockId, b: BlockId) -> bool {
+        a != b && self.dominates(a, b)
+    }
+
+    /// The dominance frontier of `block`
+    ///
+    /// For a post-dominator tree this is the set of blocks `block` is
+    /// control dependent on.
+    pub fn frontier(&self, block: BlockId) -> &[BlockId] {
+        &self.frontiers[block]
+    }
+}
+
+/// A natural loop
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Loop {
+    /// The single entry of the loop, it dominates every block in it
+    pub header: BlockId,
+    /// Every block of the loop in order, including the header and the
+    /// blocks of nested loops
+    pub blocks: Vec<BlockId>,
+    /// Sources of the back edges into the header
+    pub latches: Vec<BlockId>,
+    /// Index of the innermost enclosing loop
+    pub parent: Option<usize>,
+    /// `1` for outermost loops
+    pub depth: usize
+}
+
+/// Every natural loop of a method and its nesting
+#[derive(Clone,Debug)]
+pub struct Loops {
+    /// Loops with outer loops ahead of those nested in them
+    pub loops: Vec<Loop>,
+    /// Retreating edges whose target does not dominate their source,
+    /// each one marks a cycle with more than one entry
+    pub irreducible: Vec<Edge>,
+    innermost: Vec<Option<usize>>
+}
+
+impl Loops {
+
+    /// Find the loops of `cfg`, `dom` must be its dominator tree
+    pub fn build(cfg: &Cfg, dom: &DomTree) -> Loops {
+        let n = cfg.blocks.len();
+
+        // classify edges by a depth first walk
+        let mut irreducible = Vec::new();
+        let mut back = Vec::new();
+        let mut state = vec![0u8; n];
+        let mut stack = vec![(cfg.entry(), 0)];
+        state[cfg.entry()] = 1;
+        while let Option::Some(&mut (b, ref mut i)) = stack.last_mut() {
+            match cfg.successors(b).nth(*i) {
+                Option::Some(e) => {
+                    *i += 1;
+                    match state[e.to] {
+                        0 => {
+                            state[e.to] = 1;
+                            stack.push((e.to, 0));
+                        },
+                        1 if dom.dominates(e.to, b) => back.push(*e),
+                        1 => irreducible.push(*e),
+                        _ => if dom.dominates(e.to, b) {
+                            back.push(*e);
+                        }
+                    };
+                },
+                Option::None => {
+                    state[b] = 2;
+                    stack.pop();
+                }
+            };
+        }
+
+        // one loop per header, growing backwards from each latch
+        let mut loops: Vec<Loop> = Vec::new();
+        for e in back {
+            let index = match loops.iter().position(|l| l.header == e.to) {
+                Option::Some(i) => i,
+                Option::None => {
+                    loops.push(Loop {
+                        header: e.to,
+                        blocks: vec![e.to],
+                        latches: Vec::new(),
+                        parent: None,
+                        depth: 0
+                    });
+                    loops.len() - 1
+                }
+            };
+            let lp = &mut loops[index];
+            if !lp.latches.contains(&e.from) {
+                lp.latches.push(e.from);
+            }
+            let mut work = vec![e.from];
+            while let Option::Some(b) = work.pop() {
+                if lp.blocks.contains(&b) {
+                    continue;
+                }
+                lp.blocks.push(b);
+                work.extend(cfg.predecessors(b)
+                    .map(|p| p.from)
+                    .filter(|p| cfg.blocks[*p].reachable));
+            }
+        }
+        for lp in loops.iter_mut() {
+            lp.blocks.sort();
+            lp.latches.sort();
+        }
+
+        // natural loops are either nested or disjoint
+        loops.sort_by(|a, b| b.blocks.len().cmp(&a.blocks.len()).then(a.header.cmp(&b.header)));
+        let mut innermost = vec![None; n];
+        for i in 0..loops.len() {
+            let parent = (0..i).rev().find(|&j| loops[j].blocks.binary_search(&loops[i].header).is_ok());
+            loops[i].parent = parent;
+            loops[i].depth = parent.map_or(1, |p| loops[p].depth + 1);
+            for &b in loops[i].blocks.iter() {
+                innermost[b] = Some(i);
+            }
+        }
+        Loops { loops, irreducible, innermost }
+    }
+
+    /// Index of the innermost loop containing `block`
+    pub fn innermost(&self, block: BlockId) -> Option<usize> {
+        self.innermost[block]
+    }
+
+    /// Number of loops containing `block`
+    pub fn depth(&self, block: BlockId) -> usize {
+        self.innermost[block].map_or(0, |l| self.loops[l].depth)
+    }
+
+    /// Is `block` the header of a loop
+    pub fn is_header(&self, block: BlockId) -> bool {
+        self.loops.iter().any(|l| l.header == block)
+    }
+
+    /// Is every cycle of the method a natural loop
+    pub fn is_reducible(&self) -> bool {
+        self.irreducible.is_empty()
+    }
+}
+
+#[test]
+fn test_dominators() {
+    use super::decoder::Decoder;
+    let build = |code: &[u8]| {
+        let ops = Decoder::new(code).map(Result::unwrap).collect();
+        Cfg::build(ops, code.len(), &[]).unwrap()
+    };
+
+    // 0: iconst_0; istore_0
+    // 2: iload_0; bipush 10; if_icmpge 23      outer loop
+    // 8: iconst_0; istore_1
+    // 10: iload_1; ifne 20                     inner loop
+    // 14: iinc 1 1; goto 10
+    // 20: goto 2
+    // 23: return
+    let cfg = build(&[
+        0x03, 0x3B, 0x1A, 0x10, 0x0A, 0xA2, 0x00, 0x12, 0x03, 0x3C,
+        0x1B, 0x9A, 0x00, 0x09, 0x84, 0x01, 0x01, 0xA7, 0xFF, 0xF9,
+        0xA7, 0xFF, 0xEE, 0xB1
+    ]);
+    let starts: Vec<usize> = cfg.blocks.iter().map(|b| b.start).collect();
+    assert_eq!(starts, vec![0, 2, 8, 10, 14, 20, 23]);
+    let dom = DomTree::dominators(&cfg);
+    assert_eq!(dom.idom(4), Some(3));
+    assert_eq!(dom.idom(6), Some(1));
+    assert!(dom.dominates(1, 5) && !dom.dominates(4, 5));
+    assert_eq!(dom.frontier(4), &[3]);
+    let pdom = DomTree::post_dominators(&cfg);
+    assert_eq!(pdom.roots(), &[6]);
+    assert_eq!(pdom.idom(0), Some(1));
+    assert_eq!(pdom.idom(4), Some(3));
+    let loops = Loops::build(&cfg, &dom);
+    assert!(loops.is_reducible());
+    assert_eq!(loops.loops.len(), 2);
+    assert_eq!(loops.loops[0].header, 1);
+    assert_eq!(loops.loops[0].blocks, vec![1, 2, 3, 4, 5]);
+    assert_eq!(loops.loops[1].latches, vec![4]);
+    assert_eq!(loops.loops[1].parent, Some(0));
+    assert_eq!((loops.depth(0), loops.depth(2), loops.depth(4)), (0, 1, 2));
+
+    // two entries into the cycle between 4 and 7
+    // 0: iload_0; ifeq 7
+    // 4: iinc 0 1
+    // 7: iload_0; ifeq 14
+    // 11: goto 4
+    // 14: return
+    let cfg = build(&[
+        0x1A, 0x99, 0x00, 0x06, 0x84, 0x00, 0x01, 0x1A, 0x99, 0x00,
+        0x06, 0xA7, 0xFF, 0xF9, 0xB1
+    ]);
+    let dom = DomTree::dominators(&cfg);
+    assert_eq!((dom.idom(1), dom.idom(2)), (Some(0), Some(0)));
+    let loops = Loops::build(&cfg, &dom);
+    assert!(loops.loops.is_empty());
+    assert_eq!(loops.irreducible.len(), 1);
+}
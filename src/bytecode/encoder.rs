@@ -0,0 +1,703 @@
+//! Encoding instructions back into a code array.
+//!
+//! `encode_op` is the exact inverse of `parse_java_op`. `assemble`
+//! lays out a method written with labels: it picks the shortest form
+//! of every instruction, widens branches whose offsets overflow 16 bits
+//! and reports where each instruction ended up.
+
+use super::ops::JOp;
+use super::labels::{Label, Jump, Instruction};
+use super::locals::local_op;
+
+use std::collections::HashMap;
+use std::fmt;
+
+/// The JVM limits a method's code array to less than 64KiB
+pub const MAX_CODE_LEN: usize = 65535;
+
+#[inline(always)]
+fn push_u16(out: &mut Vec<u8>, x: u16) {
+    out.extend_from_slice(&[(x >> 8) as u8, x as u8]);
+}
+
+#[inline(always)]
+fn push_u32(out: &mut Vec<u8>, x: u32) {
+    out.extend_from_slice(&[(x >> 24) as u8, (x >> 16) as u8, (x >> 8) as u8, x as u8]);
+}
+
+#[inline(always)]
+fn op_u8(out: &mut Vec<u8>, code: u8, x: u8) {
+    out.extend_from_slice(&[code, x]);
+}
+
+#[inline(always)]
+fn op_u16(out: &mut Vec<u8>, code: u8, x: u16) {
+    out.push(code);
+    push_u16(out, x);
+}
+
+#[inline(always)]
+fn op_u32(out: &mut Vec<u8>, code: u8, x: u32) {
+    out.push(code);
+    push_u32(out, x);
+}
+
+#[inline(always)]
+fn padding(pc: usize) -> usize {
+    (4 - ((pc + 1) & 0b11)) & 0b11
+}
+
+#[inline(always)]
+fn pad(out: &mut Vec<u8>, pc: usize) {
+    for _ in 0..padding(pc) {
+        out.push(0);
+    }
+}
+
+/// Append the encoding of `op` to `out`
+///
+/// `pc` is where the instruction sits in the code array, it decides
+/// the padding of `TableSwitch` and `LookUpSwitch`.
+pub fn encode_op(op: &JOp, pc: usize, out: &mut Vec<u8>) {
+    match *op {
+        JOp::Nop => out.push(0x00),
+        JOp::AConstNull => out.push(0x01),
+        JOp::IConstM1 => out.push(0x02),
+        JOp::IConst0 => out.push(0x03),
+        JOp::IConst1 => out.push(0x04),
+        JOp::IConst2 => out.push(0x05),
+        JOp::IConst3 => out.push(0x06),
+        JOp::IConst4 => out.push(0x07),
+        JOp::IConst5 => out.push(0x08),
+        JOp::LConst0 => out.push(0x09),
+        JOp::LConst1 => out.push(0x0A),
+        JOp::FConst0 => out.push(0x0B),
+        JOp::FConst1 => out.push(0x0C),
+        JOp::FConst2 => out.push(0x0D),
+        JOp::DConst0 => out.push(0x0E),
+        JOp::DConst1 => out.push(0x0F),
+        JOp::BiPush(x) => op_u8(out, 0x10, x),
+        JOp::SIPush(x) => op_u16(out, 0x11, x),
+        JOp::LDC(x) => op_u8(out, 0x12, x),
+        JOp::LDCW(x) => op_u16(out, 0x13, x),
+        JOp::LDC2W(x) => op_u16(out, 0x14, x),
+        JOp::ILoad(x) => op_u8(out, 0x15, x),
+        JOp::LLoad(x) => op_u8(out, 0x16, x),
+        JOp::FLoad(x) => op_u8(out, 0x17, x),
+        JOp::DLoad(x) => op_u8(out, 0x18, x),
+        JOp::ALoad(x) => op_u8(out, 0x19, x),
+        JOp::ILoad0 => out.push(0x1A),
+        JOp::ILoad1 => out.push(0x1B),
+        JOp::ILoad2 => out.push(0x1C),
+        JOp::ILoad3 => out.push(0x1D),
+        JOp::LLoad0 => out.push(0x1E),
+        JOp::LLoad1 => out.push(0x1F),
+        JOp::LLoad2 => out.push(0x20),
+        JOp::LLoad3 => out.push(0x21),
+        JOp::FLoad0 => out.push(0x22),
+        JOp::FLoad1 => out.push(0x23),
+        JOp::FLoad2 => out.push(0x24),
+        JOp::FLoad3 => out.push(0x25),
+        JOp::DLoad0 => out.push(0x26),
+        JOp::DLoad1 => out.push(0x27),
+        JOp::DLoad2 => out.push(0x28),
+        JOp::DLoad3 => out.push(0x29),
+        JOp::ALoad0 => out.push(0x2A),
+        JOp::ALoad1 => out.push(0x2B),
+        JOp::ALoad2 => out.push(0x2C),
+        JOp::ALoad3 => out.push(0x2D),
+        JOp::IALoad => out.push(0x2E),
+        JOp::LALoad => out.push(0x2F),
+        JOp::FALoad => out.push(0x30),
+        JOp::DALoad => out.push(0x31),
+        JOp::AALoad => out.push(0x32),
+        JOp::BaLoad => out.push(0x33),
+        JOp::CaLoad => out.push(0x34),
+        JOp::SALoad => out.push(0x35),
+        JOp::IStore(x) => op_u8(out, 0x36, x),
+        JOp::LStore(x) => op_u8(out, 0x37, x),
+        JOp::FStore(x) => op_u8(out, 0x38, x),
+        JOp::DStore(x) => op_u8(out, 0x39, x),
+        JOp::AStore(x) => op_u8(out, 0x3A, x),
+        JOp::IStore0 => out.push(0x3B),
+        JOp::IStore1 => out.push(0x3C),
+        JOp::IStore2 => out.push(0x3D),
+        JOp::IStore3 => out.push(0x3E),
+        JOp::LStore0 => out.push(0x3F),
+        JOp::LStore1 => out.push(0x40),
+        JOp::LStore2 => out.push(0x41),
+        JOp::LStore3 => out.push(0x42),
+        JOp::FStore0 => out.push(0x43),
+        JOp::FStore1 => out.push(0x44),
+        JOp::FStore2 => out.push(0x45),
+        JOp::FStore3 => out.push(0x46),
+        JOp::DStore0 => out.push(0x47),
+        JOp::DStore1 => out.push(0x48),
+        JOp::DStore2 => out.push(0x49),
+        JOp::DStore3 => out.push(0x4A),
+        JOp::AStore0 => out.push(0x4B),
+        JOp::AStore1 => out.push(0x4C),
+        JOp::AStore2 => out.push(0x4D),
+        JOp::AStore3 => out.push(0x4E),
+        JOp::IAStore => out.push(0x4F),
+        JOp::LAStore => out.push(0x50),
+        JOp::FAStore => out.push(0x51),
+        JOp::DAStore => out.push(0x52),
+        JOp::AAStore => out.push(0x53),
+        JOp::BaStore => out.push(0x54),
+        JOp::CaStore => out.push(0x55),
+        JOp::SAStore => out.push(0x56),
+        JOp::Pop => out.push(0x57),
+        JOp::Pop2 => out.push(0x58),
+        JOp::Dup => out.push(0x59),
+        JOp::Dupx1 => out.push(0x5A),
+        JOp::Dupx2 => out.push(0x5B),
+        JOp::Dup2 => out.push(0x5C),
+        JOp::Dup2x1 => out.push(0x5D),
+        JOp::Dup2x2 => out.push(0x5E),
+        JOp::Swap => out.push(0x5F),
+        JOp::IAdd => out.push(0x60),
+        JOp::LAdd => out.push(0x61),
+        JOp::FAdd => out.push(0x62),
+        JOp::DAdd => out.push(0x63),
+        JOp::ISub => out.push(0x64),
+        JOp::LSub => out.push(0x65),
+        JOp::FSub => out.push(0x66),
+        JOp::DSub => out.push(0x67),
+        JOp::IMul => out.push(0x68),
+        JOp::LMul => out.push(0x69),
+        JOp::FMul => out.push(0x6A),
+        JOp::DMul => out.push(0x6B),
+        JOp::IDiv => out.push(0x6C),
+        JOp::LDiv => out.push(0x6D),
+        JOp::FDiv => out.push(0x6E),
+        JOp::DDiv => out.push(0x6F),
+        JOp::IRem => out.push(0x70),
+        JOp::LRem => out.push(0x71),
+        JOp::FRem => out.push(0x72),
+        JOp::DRem => out.push(0x73),
+        JOp::INeg => out.push(0x74),
+        JOp::LNeg => out.push(0x75),
+        JOp::FNeg => out.push(0x76),
+        JOp::DNeg => out.push(0x77),
+        JOp::ISHL => out.push(0x78),
+        JOp::LSHL => out.push(0x79),
+        JOp::ISHR => out.push(0x7A),
+        JOp::LSHR => out.push(0x7B),
+        JOp::IUSHR => out.push(0x7C),
+        JOp::LUSHR => out.push(0x7D),
+        JOp::IAnd => out.push(0x7E),
+        JOp::LAnd => out.push(0x7F),
+        JOp::IOr => out.push(0x80),
+        JOp::LOr => out.push(0x81),
+        JOp::IXor => out.push(0x82),
+        JOp::LXor => out.push(0x83),
+        JOp::IInc(x, y) => {
+            op_u8(out, 0x84, x);
+            out.push(y as u8);
+        },
+        JOp::I2L => out.push(0x85),
+        JOp::I2F => out.push(0x86),
+        JOp::I2D => out.push(0x87),
+        JOp::L2I => out.push(0x88),
+        JOp::L2F => out.push(0x89),
+        JOp::L2D => out.push(0x8A),
+        JOp::F2I => out.push(0x8B),
+        JOp::F2L => out.push(0x8C),
+        JOp::F2D => out.push(0x8D),
+        JOp::D2I => out.push(0x8E),
+        JOp::D2L => out.push(0x8F),
+        JOp::D2F => out.push(0x90),
+        JOp::I2B => out.push(0x91),
+        JOp::I2C => out.push(0x92),
+        JOp::I2S => out.push(0x93),
+        JOp::Lcmp => out.push(0x94),
+        JOp::FcmpL => out.push(0x95),
+        JOp::FcmpG => out.push(0x96),
+        JOp::DcmpL => out.push(0x97),
+        JOp::DcmpG => out.push(0x98),
+        JOp::IFEQ(x) => op_u16(out, 0x99, x as u16),
+        JOp::IFNE(x) => op_u16(out, 0x9A, x as u16),
+        JOp::IFLT(x) => op_u16(out, 0x9B, x as u16),
+        JOp::IFGE(x) => op_u16(out, 0x9C, x as u16),
+        JOp::IFGT(x) => op_u16(out, 0x9D, x as u16),
+        JOp::IFLE(x) => op_u16(out, 0x9E, x as u16),
+        JOp::IFIcmpEQ(x) => op_u16(out, 0x9F, x as u16),
+        JOp::IFIcmpNE(x) => op_u16(out, 0xA0, x as u16),
+        JOp::IFIcmpLT(x) => op_u16(out, 0xA1, x as u16),
+        JOp::IfIcmpGE(x) => op_u16(out, 0xA2, x as u16),
+        JOp::IFIcmpGT(x) => op_u16(out, 0xA3, x as u16),
+        JOp::IFIcmpLE(x) => op_u16(out, 0xA4, x as u16),
+        JOp::IFAcmpEQ(x) => op_u16(out, 0xA5, x as u16),
+        JOp::IFAcmpNE(x) => op_u16(out, 0xA6, x as u16),
+        JOp::Goto(x) => op_u16(out, 0xA7, x as u16),
+        JOp::JSR(x) => op_u16(out, 0xA8, x as u16),
+        JOp::Ret(x) => op_u8(out, 0xA9, x),
+        JOp::TableSwitch(default, low, high, ref offsets) => {
+            out.push(0xAA);
+            pad(out, pc);
+            push_u32(out, default as u32);
+            push_u32(out, low as u32);
+            push_u32(out, high as u32);
+            for x in offsets {
+                push_u32(out, *x as u32);
+            }
+        },
+        JOp::LookUpSwitch(default, ref pairs) => {
+            out.push(0xAB);
+            pad(out, pc);
+            push_u32(out, default as u32);
+            push_u32(out, pairs.len() as u32);
+            for &(key, offset) in pairs {
+                push_u32(out, key as u32);
+                push_u32(out, offset as u32);
+            }
+        },
+        JOp::IReturn => out.push(0xAC),
+        JOp::LReturn => out.push(0xAD),
+        JOp::FReturn => out.push(0xAE),
+        JOp::DReturn => out.push(0xAF),
+        JOp::AReturn => out.push(0xB0),
+        JOp::Return => out.push(0xB1),
+        JOp::GetStatic(x) => op_u16(out, 0xB2, x),
+        JOp::PutStatic(x) => op_u16(out, 0xB3, x),
+        JOp::GetField(x) => op_u16(out, 0xB4, x),
+        JOp::PutField(x) => op_u16(out, 0xB5, x),
+        JOp::InvokeVirtual(x) => op_u16(out, 0xB6, x),
+        JOp::InvokeSpecial(x) => op_u16(out, 0xB7, x),
+        JOp::InvokeStatic(x) => op_u16(out, 0xB8, x),
+        JOp::InvokedInterface(x, y) => {
+            op_u16(out, 0xB9, x);
+            out.push(y);
+            out.push(0);
+        },
+        JOp::InvokedDynamic(x) => {
+            op_u16(out, 0xBA, x);
+            out.extend_from_slice(&[0, 0]);
+        },
+        JOp::New(x) => op_u16(out, 0xBB, x),
+        JOp::NewArray(x) => op_u8(out, 0xBC, x),
+        JOp::ANewArray(x) => op_u16(out, 0xBD, x),
+        JOp::ArrayLength => out.push(0xBE),
+        JOp::AThrow => out.push(0xBF),
+        JOp::CheckCast(x) => op_u16(out, 0xC0, x),
+        JOp::InstanceOf(x) => op_u16(out, 0xC1, x),
+        JOp::MonitorEnter => out.push(0xC2),
+        JOp::MonitorExit => out.push(0xC3),
+        JOp::MultiAneWArray(x, y) => {
+            op_u16(out, 0xC5, x);
+            out.push(y);
+        },
+        JOp::IFNull(x) => op_u16(out, 0xC6, x as u16),
+        JOp::IFnonNull(x) => op_u16(out, 0xC7, x as u16),
+        JOp::GotoW(x) => op_u32(out, 0xC8, x as u32),
+        JOp::JSRW(x) => op_u32(out, 0xC9, x as u32),
+        JOp::BreakPoint => out.push(0xCA),
+        JOp::ImpDep1 => out.push(0xFE),
+        JOp::ImpDep2 => out.push(0xFF),
+        JOp::ILoadWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x15, x);
+        },
+        JOp::LLoadWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x16, x);
+        },
+        JOp::FLoadWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x17, x);
+        },
+        JOp::DLoadWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x18, x);
+        },
+        JOp::ALoadWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x19, x);
+        },
+        JOp::IStoreWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x36, x);
+        },
+        JOp::LStoreWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x37, x);
+        },
+        JOp::FStoreWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x38, x);
+        },
+        JOp::DStoreWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x39, x);
+        },
+        JOp::AStoreWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0x3A, x);
+        },
+        JOp::IIncWide(x, y) => {
+            out.push(0xC4);
+            op_u16(out, 0x84, x);
+            push_u16(out, y as u16);
+        },
+        JOp::RetWide(x) => {
+            out.push(0xC4);
+            op_u16(out, 0xA9, x);
+        },
+    }
+}
+
+impl JOp {
+
+    /// Number of bytes this instruction takes when placed at `pc`
+    pub fn encoded_len(&self, pc: usize) -> usize {
+        match *self {
+            JOp::TableSwitch(_, _, _, ref offsets) => 13 + padding(pc) + 4 * offsets.len(),
+            JOp::LookUpSwitch(_, ref pairs) => 9 + padding(pc) + 8 * pairs.len(),
+            JOp::BiPush(..) |
+            JOp::LDC(..) |
+            JOp::ILoad(..) |
+            JOp::LLoad(..) |
+            JOp::FLoad(..) |
+            JOp::DLoad(..) |
+            JOp::ALoad(..) |
+            JOp::IStore(..) |
+            JOp::LStore(..) |
+            JOp::FStore(..) |
+            JOp::DStore(..) |
+            JOp::AStore(..) |
+            JOp::Ret(..) |
+            JOp::NewArray(..) => 2,
+            JOp::SIPush(..) |
+            JOp::LDCW(..) |
+            JOp::LDC2W(..) |
+            JOp::IInc(..) |
+            JOp::IFEQ(..) |
+            JOp::IFNE(..) |
+            JOp::IFLT(..) |
+            JOp::IFGE(..) |
+            JOp::IFGT(..) |
+            JOp::IFLE(..) |
+            JOp::IFIcmpEQ(..) |
+            JOp::IFIcmpNE(..) |
+            JOp::IFIcmpLT(..) |
+            JOp::IfIcmpGE(..) |
+            JOp::IFIcmpGT(..) |
+            JOp::IFIcmpLE(..) |
+            JOp::IFAcmpEQ(..) |
+            JOp::IFAcmpNE(..) |
+            JOp::Goto(..) |
+            JOp::JSR(..) |
+            JOp::GetStatic(..) |
+            JOp::PutStatic(..) |
+            JOp::GetField(..) |
+            JOp::PutField(..) |
+            JOp::InvokeVirtual(..) |
+            JOp::InvokeSpecial(..) |
+            JOp::InvokeStatic(..) |
+            JOp::New(..) |
+            JOp::ANewArray(..) |
+            JOp::CheckCast(..) |
+            JOp::InstanceOf(..) |
+            JOp::IFNull(..) |
+            JOp::IFnonNull(..) => 3,
+            JOp::MultiAneWArray(..) |
+            JOp::ILoadWide(..) |
+            JOp::LLoadWide(..) |
+            JOp::FLoadWide(..) |
+            JOp::DLoadWide(..) |
+            JOp::ALoadWide(..) |
+            JOp::IStoreWide(..) |
+            JOp::LStoreWide(..) |
+            JOp::FStoreWide(..) |
+            JOp::DStoreWide(..) |
+            JOp::AStoreWide(..) |
+            JOp::RetWide(..) => 4,
+            JOp::InvokedInterface(..) |
+            JOp::InvokedDynamic(..) |
+            JOp::GotoW(..) |
+            JOp::JSRW(..) => 5,
+            JOp::IIncWide(..) => 6,
+            _ => 1
+        }
+    }
+}
+
+/// The shortest instruction equivalent to `op`
+///
+/// Local variable accesses and `ldc_w` are narrowed, `wide` is only
+/// kept when the slot or increment needs it.
+pub fn shortest_form(op: &JOp) -> JOp {
+    if let Option::Some((access, slot)) = op.local_access() {
+        return local_op(access, slot);
+    }
+    match *op {
+        JOp::LDCW(x) if x <= 255 => JOp::LDC(x as u8),
+        ref op => op.clone()
+    }
+}
+
+/// Why a method could not be assembled
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum EncodeFault {
+    /// A branch refers to a label that is never placed
+    UndefinedLabel(Label),
+    /// The same label is placed twice
+    DuplicateLabel(Label),
+    /// `Instruction::Op` holds a branch, whose offset can't be relocated
+    RawBranch,
+    /// A `TableSwitch` without targets, or whose keys overflow an `i32`
+    BadSwitch,
+    /// A `LookUpSwitch` matches the same key twice
+    DuplicateKey(i32),
+    /// The code array would be larger than `MAX_CODE_LEN`
+    CodeTooLarge
+}
+
+/// An assembly failure and the index of the instruction causing it
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct EncodeError {
+    pub index: usize,
+    pub reason: EncodeFault
+}
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            EncodeFault::UndefinedLabel(l) => write!(f, "label {} is never placed", l.0),
+            EncodeFault::DuplicateLabel(l) => write!(f, "label {} is placed twice", l.0),
+            EncodeFault::RawBranch => write!(f, "branch with a raw offset"),
+            EncodeFault::BadSwitch => write!(f, "tableswitch has no valid key range"),
+            EncodeFault::DuplicateKey(k) => write!(f, "lookupswitch matches {} twice", k),
+            EncodeFault::CodeTooLarge => write!(f, "code array exceeds {} bytes", MAX_CODE_LEN)
+        }?;
+        write!(f, " (instruction {})", self.index)
+    }
+}
+
+/// An assembled code array
+#[derive(Clone,Debug)]
+pub struct Assembled {
+    pub code: Vec<u8>,
+    /// pc of every input instruction, `Instruction::Label`s included
+    pub pcs: Vec<usize>,
+    labels: HashMap<Label, usize>
+}
+impl Assembled {
+
+    /// Where a label ended up
+    ///
+    /// A label placed after the last instruction maps to the code length,
+    /// as exception table and local variable ranges expect.
+    pub fn label_pc(&self, label: Label) -> Option<usize> {
+        self.labels.get(&label).cloned()
+    }
+}
+
+/*
+ * How a `Instruction::Jump` is encoded
+ *
+ * A short jump is the 3 byte form. A wide `goto`/`jsr` is `goto_w`/`jsr_w`.
+ * A wide conditional branch is inverted to hop over a `goto_w`:
+ *
+ *     ifne L   =>   ifeq +8; goto_w L
+ */
+fn jump_len(jump: Jump, wide: bool) -> usize {
+    match (wide, jump.is_conditional()) {
+        (false, _) => 3,
+        (true, false) => 5,
+        (true, true) => 8
+    }
+}
+
+fn insn_len(insn: &Instruction, wide: bool, pc: usize) -> usize {
+    match *insn {
+        Instruction::Label(_) => 0,
+        Instruction::Op(ref op) => op.encoded_len(pc),
+        Instruction::Jump(jump, _) => jump_len(jump, wide),
+        Instruction::TableSwitch { ref targets, .. } => 13 + padding(pc) + 4 * targets.len(),
+        Instruction::LookUpSwitch { ref pairs, .. } => 9 + padding(pc) + 8 * pairs.len()
+    }
+}
+
+/// Lay out and encode a method body
+pub fn assemble(insns: &[Instruction]) -> Result<Assembled, EncodeError> {
+    let err = |index: usize, reason: EncodeFault| EncodeError { index, reason };
+
+    // normalize and validate before layout
+    let mut placed = HashMap::new();
+    let mut body = Vec::with_capacity(insns.len());
+    for (index, insn) in insns.iter().enumerate() {
+        let insn = match *insn {
+            Instruction::Label(l) => {
+                if placed.insert(l, index).is_some() {
+                    return Err(err(index, EncodeFault::DuplicateLabel(l)));
+                }
+                Instruction::Label(l)
+            },
+            Instruction::Op(ref op) => {
+                if op.is_branch() {
+                    return Err(err(index, EncodeFault::RawBranch));
+                }
+                Instruction::Op(shortest_form(op))
+            },
+            Instruction::TableSwitch { ref targets, low, .. } => {
+                if targets.is_empty() || (low as i64 + targets.len() as i64 - 1) > i32::MAX as i64 {
+                    return Err(err(index, EncodeFault::BadSwitch));
+                }
+                insn.clone()
+            },
+            Instruction::LookUpSwitch { default, ref pairs } => {
+                let mut pairs = pairs.clone();
+                pairs.sort_by_key(|&(key, _)| key);
+                if let Option::Some(w) = pairs.windows(2).find(|w| w[0].0 == w[1].0) {
+                    return Err(err(index, EncodeFault::DuplicateKey(w[0].0)));
+                }
+                Instruction::LookUpSwitch { default, pairs }
+            },
+            Instruction::Jump(_, _) => insn.clone()
+        };
+        body.push(insn);
+    }
+    for (index, insn) in body.iter().enumerate() {
+        let check = |l: &Label| if placed.contains_key(l) {
+            Ok(())
+        } else {
+            Err(err(index, EncodeFault::UndefinedLabel(*l)))
+        };
+        match *insn {
+            Instruction::Jump(_, ref l) => check(l)?,
+            Instruction::TableSwitch { ref default, ref targets, .. } => {
+                check(default)?;
+                for l in targets {
+                    check(l)?;
+                }
+            },
+            Instruction::LookUpSwitch { ref default, ref pairs } => {
+                check(default)?;
+                for &(_, l) in pairs {
+                    check(&l)?;
+                }
+            },
+            _ => { }
+        };
+    }
+
+    // widen branches until every offset fits, widening only ever
+    // grows the code so this settles
+    let mut wide = vec![false; body.len()];
+    let mut pcs = vec![0usize; body.len()];
+    loop {
+        let mut pc = 0;
+        for (index, insn) in body.iter().enumerate() {
+            pcs[index] = pc;
+            pc += insn_len(insn, wide[index], pc);
+        }
+        if pc > MAX_CODE_LEN {
+            return Err(err(body.len().saturating_sub(1), EncodeFault::CodeTooLarge));
+        }
+        let mut changed = false;
+        for (index, insn) in body.iter().enumerate() {
+            if let Instruction::Jump(_, l) = *insn {
+                let offset = pcs[placed[&l]] as i64 - pcs[index] as i64;
+                if !wide[index] && (offset < i16::MIN as i64 || offset > i16::MAX as i64) {
+                    wide[index] = true;
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+
+    let target = |l: &Label, pc: usize| (pcs[placed[l]] as i64 - pc as i64) as i32;
+    let mut code = Vec::new();
+    for (index, insn) in body.iter().enumerate() {
+        let pc = pcs[index];
+        match *insn {
+            Instruction::Label(_) => { },
+            Instruction::Op(ref op) => encode_op(op, pc, &mut code),
+            Instruction::Jump(jump, ref l) => {
+                if !wide[index] {
+                    encode_op(&jump.to_op(target(l, pc) as i16), pc, &mut code);
+                } else if let Option::Some(inverse) = jump.invert() {
+                    encode_op(&inverse.to_op(8), pc, &mut code);
+                    encode_op(&JOp::GotoW(target(l, pc + 3)), pc + 3, &mut code);
+                } else if jump == Jump::JSR {
+                    encode_op(&JOp::JSRW(target(l, pc)), pc, &mut code);
+                } else {
+                    encode_op(&JOp::GotoW(target(l, pc)), pc, &mut code);
+                }
+            },
+            Instruction::TableSwitch { ref default, low, ref targets } => {
+                let op = JOp::TableSwitch(
+                    target(default, pc),
+                    low,
+                    low + (targets.len() as i32 - 1),
+                    targets.iter().map(|l| target(l, pc)).collect());
+                encode_op(&op, pc, &mut code);
+            },
+            Instruction::LookUpSwitch { ref default, ref pairs } => {
+                let op = JOp::LookUpSwitch(
+                    target(default, pc),
+                    pairs.iter().map(|&(key, ref l)| (key, target(l, pc))).collect());
+                encode_op(&op, pc, &mut code);
+            }
+        };
+        debug_assert_eq!(code.len(), pc + insn_len(insn, wide[index], pc));
+    }
+
+    let labels = placed.iter().map(|(l, index)| (*l, pcs[*index])).collect();
+    Ok(Assembled { code, pcs, labels })
+}
+
+#[test]
+fn test_round_trip() {
+    use super::{Decoder, label_code};
+
+    // javac output for `static int sum(int[] a)`, with a tableswitch
+    // appended to exercise padding
+    let code: &[u8] = &[
+        0x03, 0x3C, 0x03, 0x3D, 0x1C, 0x2A, 0xBE, 0xA2, 0x00, 0x0F,
+        0x1B, 0x2A, 0x1C, 0x2E, 0x60, 0x3C, 0x84, 0x02, 0x01, 0xA7,
+        0xFF, 0xF1, 0x1B, 0x00, 0xAA, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0xFF, 0xFF, 0xFF, 0xFE, 0xAC
+    ];
+    let ops: Vec<(usize, JOp)> = Decoder::new(code).map(Result::unwrap).collect();
+    let mut bytes = Vec::new();
+    for &(pc, ref op) in ops.iter() {
+        assert_eq!(op.encoded_len(pc), { encode_op(op, pc, &mut bytes); bytes.len() - pc });
+    }
+    assert_eq!(bytes, code.to_vec());
+    let insns = label_code(&ops, code.len()).unwrap();
+    assert_eq!(assemble(&insns).unwrap().code, code.to_vec());
+}
+
+#[test]
+fn test_widening() {
+    let (top, end) = (Label(0), Label(1));
+    let mut insns = vec![
+        Instruction::Label(top),
+        Instruction::Op(JOp::ILoadWide(1)),
+        Instruction::Jump(Jump::IFEQ, end)
+    ];
+    for _ in 0..40000 {
+        insns.push(Instruction::Op(JOp::Nop));
+    }
+    insns.push(Instruction::Op(JOp::IIncWide(1, -1)));
+    insns.push(Instruction::Jump(Jump::Goto, top));
+    insns.push(Instruction::Label(end));
+    insns.push(Instruction::Op(JOp::Return));
+
+    let asm = assemble(&insns).unwrap();
+    // iload_1, then `ifne +8; goto_w end`
+    assert_eq!(&asm.code[..9], &[0x1B, 0x9A, 0x00, 0x08, 0xC8, 0x00, 0x00, 0x9C, 0x4D]);
+    // iinc 1 -1, then `goto_w top`
+    let pc = asm.pcs[insns.len() - 4];
+    assert_eq!(&asm.code[pc..pc + 8], &[0x84, 0x01, 0xFF, 0xC8, 0xFF, 0xFF, 0x63, 0xB4]);
+    assert_eq!(asm.label_pc(end), Some(asm.code.len() - 1));
+
+    let bad = [Instruction::Jump(Jump::Goto, Label(9))];
+    assert_eq!(assemble(&bad).unwrap_err().reason, EncodeFault::UndefinedLabel(Label(9)));
+}
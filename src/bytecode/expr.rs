@@ -0,0 +1,955 @@
+//! Expression trees rebuilt from stack code.
+//!
+//! Each basic block becomes a list of `Statement`s over `Expr` trees,
+//! with constant pool operands resolved to names and literals, so
+//! `aload_1; iload_2; iconst_1; iadd; invokevirtual; istore_3` reads as
+//! `local3 = local1.foo(local2 + 1)`.
+//!
+//! Operand stack entries stay unevaluated trees for as long as that
+//! keeps evaluation order. Before a statement runs, entries with side
+//! effects, or reading a local the statement writes, are assigned to
+//! temporaries `t0`, `t1`, .. instead. Values still on the stack when a
+//! block ends are assigned to `stack0`, `stack1`, .., counted from the
+//! bottom, which the successors read back.
+
+use super::super::clazz::{ConstantsPool, FieldType, MethodType, PoolMembers};
+use super::cfg::Cfg;
+use super::labels::Jump;
+use super::locals::{LocalAccess, LocalType};
+use super::ops::JOp;
+use super::ssa::{BinOp, Comparison, Cond, InvokeKind, Narrowing};
+
+use std::fmt;
+
+/// A field or method, as named by a constant
+///
+/// `owner` is empty for `invokedynamic` call sites.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Member {
+    pub owner: String,
+    pub name: String,
+    pub descriptor: String
+}
+
+/// A loadable constant
+#[derive(Clone,Debug,PartialEq)]
+pub enum Literal {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    Str(String),
+    /// Internal name or array descriptor
+    Class(String),
+    /// A method descriptor
+    MethodType(String),
+    /// Reference kind and the referenced member
+    MethodHandle(u8, Member)
+}
+
+/// A value computed without intervening statements
+#[derive(Clone,Debug,PartialEq)]
+pub enum Expr {
+    Local(u16),
+    Literal(Literal),
+    /// A temporary assigned by `Statement::Temp`
+    Temp(usize),
+    /// A value the block starts with on the operand stack
+    Stack(usize),
+    /// The exception entering a handler
+    Caught,
+    /// The operator, the type of its operands and result, and operands
+    Binary(BinOp, LocalType, Box<Expr>, Box<Expr>),
+    Neg(Box<Expr>),
+    /// Conversion to the given type
+    Convert(LocalType, Box<Expr>),
+    Narrow(Narrowing, Box<Expr>),
+    Compare(Comparison, Box<Expr>, Box<Expr>),
+    /// Array then index
+    ArrayLoad(Box<Expr>, Box<Expr>),
+    ArrayLength(Box<Expr>),
+    /// The array type created, and the lengths of its outer dimensions
+    NewArray(FieldType, Vec<Expr>),
+    /// An object not yet initialized
+    New(String),
+    /// `new` followed by its `<init>` call
+    Construct(Member, Vec<Expr>),
+    GetField(Member, Box<Expr>),
+    GetStatic(Member),
+    /// Arguments, with the receiver first
+    Invoke(InvokeKind, Member, Vec<Expr>),
+    CheckCast(String, Box<Expr>),
+    InstanceOf(String, Box<Expr>)
+}
+impl Expr {
+
+    /// Can the expression move past a statement that doesn't write
+    /// any local it reads
+    fn is_stable(&self) -> bool {
+        match *self {
+            Expr::Local(_) |
+            Expr::Literal(_) |
+            Expr::Temp(_) |
+            Expr::Stack(_) |
+            Expr::Caught |
+            Expr::New(_) => true,
+            Expr::Neg(ref a) |
+            Expr::Convert(_, ref a) |
+            Expr::Narrow(_, ref a) => a.is_stable(),
+            // integer division may throw `ArithmeticException`
+            Expr::Binary(BinOp::Div, t, _, _) |
+            Expr::Binary(BinOp::Rem, t, _, _) if t == LocalType::Int || t == LocalType::Long => false,
+            Expr::Binary(_, _, ref a, ref b) |
+            Expr::Compare(_, ref a, ref b) => a.is_stable() && b.is_stable(),
+            _ => false
+        }
+    }
+
+    /// Does the expression read `f` anywhere
+    fn reads(&self, f: &dyn Fn(&Expr) -> bool) -> bool {
+        if f(self) {
+            return true;
+        }
+        match *self {
+            Expr::Neg(ref a) |
+            Expr::Convert(_, ref a) |
+            Expr::Narrow(_, ref a) |
+            Expr::ArrayLength(ref a) |
+            Expr::GetField(_, ref a) |
+            Expr::CheckCast(_, ref a) |
+            Expr::InstanceOf(_, ref a) => a.reads(f),
+            Expr::Binary(_, _, ref a, ref b) |
+            Expr::Compare(_, ref a, ref b) |
+            Expr::ArrayLoad(ref a, ref b) => a.reads(f) || b.reads(f),
+            Expr::NewArray(_, ref args) |
+            Expr::Construct(_, ref args) |
+            Expr::Invoke(_, _, ref args) => args.iter().any(|a| a.reads(f)),
+            _ => false
+        }
+    }
+
+    /// Does the expression need parentheses as an operand
+    fn is_atom(&self) -> bool {
+        !matches!(*self,
+            Expr::Binary(..) | Expr::Neg(_) | Expr::Convert(..) | Expr::Narrow(..) |
+            Expr::Compare(..) | Expr::CheckCast(..) | Expr::InstanceOf(..))
+    }
+}
+
+/// An effect, or a transfer of control to the pc given
+#[derive(Clone,Debug,PartialEq)]
+pub enum Statement {
+    Store(u16, Expr),
+    Temp(usize, Expr),
+    /// A value left on the operand stack for the next block
+    Stack(usize, Expr),
+    Increment(u16, i16),
+    /// Array, index, then value
+    ArrayStore(Expr, Expr, Expr),
+    /// Object then value
+    PutField(Member, Expr, Expr),
+    PutStatic(Member, Expr),
+    /// An expression evaluated for its side effects
+    Expr(Expr),
+    MonitorEnter(Expr),
+    MonitorExit(Expr),
+    /// Comparisons against zero or `null` have a literal `rhs`
+    If { cond: Cond, lhs: Expr, rhs: Expr, target: usize },
+    Goto(usize),
+    Switch { value: Expr, default: usize, cases: Vec<(i32, usize)> },
+    Return(Option<Expr>),
+    Throw(Expr)
+}
+
+/// Why expressions could not be rebuilt
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum ExprFault {
+    StackUnderflow,
+    /// Predecessors leave different stack heights
+    StackHeight,
+    /// A long or double split by a stack instruction
+    SplitValue,
+    BadConstant(u16),
+    /// `jsr` and `ret` are not supported
+    Subroutine,
+    /// A reserved opcode
+    Reserved
+}
+
+/// A failure and the pc it concerns
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct ExprError {
+    pub pc: usize,
+    pub reason: ExprFault
+}
+impl fmt::Display for ExprError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            ExprFault::StackUnderflow => write!(f, "operand stack underflow"),
+            ExprFault::StackHeight => write!(f, "inconsistent stack height"),
+            ExprFault::SplitValue => write!(f, "long or double split in half"),
+            ExprFault::BadConstant(x) => write!(f, "invalid constant #{}", x),
+            ExprFault::Subroutine => write!(f, "jsr/ret is not supported"),
+            ExprFault::Reserved => write!(f, "reserved opcode")
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+type Res<T> = Result<T, ExprFault>;
+
+/// The type named by a `ClassInfo`, which holds an internal name or,
+/// for arrays, a descriptor
+fn class_type(name: &str) -> FieldType {
+    if name.starts_with('[') {
+        if let Option::Some(t) = FieldType::parse(name) {
+            return t;
+        }
+    }
+    FieldType::Object(name.to_string())
+}
+
+struct Builder<'p, 'a: 'p> {
+    pool: &'p ConstantsPool<'a>,
+    stack: Vec<(Expr, LocalType)>,
+    out: Vec<Statement>,
+    temps: usize
+}
+impl<'p, 'a> Builder<'p, 'a> {
+
+    fn push(&mut self, e: Expr, ty: LocalType) {
+        self.stack.push((e, ty));
+    }
+
+    fn pop(&mut self) -> Res<Expr> {
+        self.stack.pop().map(|x| x.0).ok_or(ExprFault::StackUnderflow)
+    }
+
+    fn pop_n(&mut self, n: usize) -> Res<Vec<Expr>> {
+        if n > self.stack.len() {
+            return Err(ExprFault::StackUnderflow);
+        }
+        let at = self.stack.len() - n;
+        Ok(self.stack.split_off(at).into_iter().map(|x| x.0).collect())
+    }
+
+    fn temp(&mut self, e: Expr) -> Expr {
+        let t = self.temps;
+        self.temps += 1;
+        self.out.push(Statement::Temp(t, e));
+        Expr::Temp(t)
+    }
+
+    /// Assign stack entries to temporaries ahead of a statement that
+    /// may write `slot`
+    fn spill(&mut self, slot: Option<u16>) {
+        for i in 0..self.stack.len() {
+            let e = &self.stack[i].0;
+            let clobbered = slot.is_some_and(|s| e.reads(&|x| *x == Expr::Local(s)));
+            if !e.is_stable() || clobbered {
+                let e = self.stack[i].0.clone();
+                self.stack[i].0 = self.temp(e);
+            }
+        }
+    }
+
+    fn emit(&mut self, s: Statement) {
+        self.spill(None);
+        self.out.push(s);
+    }
+
+    /// Number of entries at the top of `stack[..end]` making up `slots`
+    fn entries_in(&self, end: usize, slots: usize) -> Res<usize> {
+        let (mut n, mut total) = (0, 0);
+        while total < slots {
+            if n >= end {
+                return Err(ExprFault::StackUnderflow);
+            }
+            total += self.stack[end - 1 - n].1.width() as usize;
+            n += 1;
+        }
+        if total == slots {
+            Ok(n)
+        } else {
+            Err(ExprFault::SplitValue)
+        }
+    }
+
+    /// The `dup` family, copy the top `copy` slots beneath the `skip`
+    /// slots below them
+    fn duplicate(&mut self, copy: usize, skip: usize) -> Res<()> {
+        self.spill(None);
+        let copied = self.entries_in(self.stack.len(), copy)?;
+        let top = self.stack.len() - copied;
+        let at = top - self.entries_in(top, skip)?;
+        let entries = self.stack[top..].to_vec();
+        for (i, e) in entries.into_iter().enumerate() {
+            self.stack.insert(at + i, e);
+        }
+        Ok(())
+    }
+
+    fn discard(&mut self, slots: usize) -> Res<()> {
+        let n = self.entries_in(self.stack.len(), slots)?;
+        for e in self.pop_n(n)? {
+            if !e.is_stable() {
+                self.emit(Statement::Expr(e));
+            }
+        }
+        Ok(())
+    }
+
+    fn member(&self, index: u16) -> Res<Member> {
+        let m = self.pool.get_member_ref(index).ok_or(ExprFault::BadConstant(index))?;
+        Ok(Member {
+            owner: m.class.into_owned(),
+            name: m.name.into_owned(),
+            descriptor: m.descriptor.into_owned()
+        })
+    }
+
+    fn class(&self, index: u16) -> Res<String> {
+        self.pool.get_class_name(index)
+            .map(|x| x.into_owned())
+            .ok_or(ExprFault::BadConstant(index))
+    }
+
+    fn utf8(&self, index: u16, of: u16) -> Res<String> {
+        self.pool.get_utf8(index)
+            .map(|x| x.into_owned())
+            .ok_or(ExprFault::BadConstant(of))
+    }
+
+    fn literal(&self, index: u16) -> Res<(Literal, LocalType)> {
+        Ok(match self.pool.get(index) {
+            Option::Some(&PoolMembers::Integer(x)) => (Literal::Int(x), LocalType::Int),
+            Option::Some(&PoolMembers::Float(x)) => (Literal::Float(x), LocalType::Float),
+            Option::Some(&PoolMembers::Long(x)) => (Literal::Long(x), LocalType::Long),
+            Option::Some(&PoolMembers::Double(x)) => (Literal::Double(x), LocalType::Double),
+            Option::Some(&PoolMembers::Str(x)) => (Literal::Str(self.utf8(x, index)?), LocalType::Reference),
+            Option::Some(&PoolMembers::ClassInfo(_)) => (Literal::Class(self.class(index)?), LocalType::Reference),
+            Option::Some(&PoolMembers::MethodType(x)) => (Literal::MethodType(self.utf8(x, index)?), LocalType::Reference),
+            Option::Some(&PoolMembers::MethodHandle(kind, x)) => (Literal::MethodHandle(kind, self.member(x)?), LocalType::Reference),
+            _ => return Err(ExprFault::BadConstant(index))
+        })
+    }
+
+    fn invoke(&mut self, op: &JOp) -> Res<()> {
+        let (kind, index) = match *op {
+            JOp::InvokeVirtual(x) => (InvokeKind::Virtual, x),
+            JOp::InvokeSpecial(x) => (InvokeKind::Special, x),
+            JOp::InvokeStatic(x) => (InvokeKind::Static, x),
+            JOp::InvokedInterface(x, _) => (InvokeKind::Interface, x),
+            _ => (InvokeKind::Dynamic, match *op { JOp::InvokedDynamic(x) => x, _ => unreachable!() })
+        };
+        let member = match (kind, self.pool.get(index)) {
+            (InvokeKind::Dynamic, Option::Some(&PoolMembers::InvokeDynamic(_, nat))) => {
+                let (name, descriptor) = self.pool.get_name_and_type(nat).ok_or(ExprFault::BadConstant(index))?;
+                Member { owner: String::new(), name: name.into_owned(), descriptor: descriptor.into_owned() }
+            },
+            (InvokeKind::Dynamic, _) => return Err(ExprFault::BadConstant(index)),
+            _ => self.member(index)?
+        };
+        let m = MethodType::parse(&member.descriptor).ok_or(ExprFault::BadConstant(index))?;
+        let receiver = !matches!(kind, InvokeKind::Static | InvokeKind::Dynamic);
+        let mut args = self.pop_n(m.params.len() + receiver as usize)?;
+
+        // fold `new C; dup; ..; invokespecial C.<init>` into one expression
+        if kind == InvokeKind::Special && member.name == "<init>" {
+            if let Expr::New(_) = args[0] {
+                let created = args.remove(0);
+                let copy = self.stack.iter().rposition(|x| x.0 == created);
+                let e = Expr::Construct(member, args);
+                match copy {
+                    Option::Some(i) => self.stack[i].0 = e,
+                    Option::None => self.emit(Statement::Expr(e))
+                };
+                return Ok(());
+            }
+        }
+        let e = Expr::Invoke(kind, member, args);
+        match m.ret {
+            Option::Some(ref t) => self.push(e, LocalType::computational(t)),
+            Option::None => self.emit(Statement::Expr(e))
+        };
+        Ok(())
+    }
+
+    /// Apply one instruction, returning the statement ending the block
+    /// if there is one
+    fn step(&mut self, pc: usize, op: &JOp) -> Res<Option<Statement>> {
+        if let Option::Some((access, index)) = op.local_access() {
+            match access {
+                LocalAccess::Load(t) => self.push(Expr::Local(index), t),
+                LocalAccess::Store(_) => {
+                    let e = self.pop()?;
+                    self.spill(Some(index));
+                    self.out.push(Statement::Store(index, e));
+                },
+                LocalAccess::Increment(c) => {
+                    self.spill(Some(index));
+                    self.out.push(Statement::Increment(index, c));
+                },
+                LocalAccess::Ret => return Err(ExprFault::Subroutine)
+            };
+            return Ok(None);
+        }
+        if let Option::Some((bin, ty)) = op.binary_op() {
+            let b = self.pop()?;
+            let a = self.pop()?;
+            self.push(Expr::Binary(bin, ty, Box::new(a), Box::new(b)), ty);
+            return Ok(None);
+        }
+        if let Option::Some(ty) = op.negation() {
+            let a = self.pop()?;
+            self.push(Expr::Neg(Box::new(a)), ty);
+            return Ok(None);
+        }
+        if let Option::Some((_, to)) = op.conversion() {
+            let a = self.pop()?;
+            self.push(Expr::Convert(to, Box::new(a)), to);
+            return Ok(None);
+        }
+        if let Option::Some(n) = op.narrowing() {
+            let a = self.pop()?;
+            self.push(Expr::Narrow(n, Box::new(a)), LocalType::Int);
+            return Ok(None);
+        }
+        if let Option::Some(c) = op.comparison() {
+            let b = self.pop()?;
+            let a = self.pop()?;
+            self.push(Expr::Compare(c, Box::new(a), Box::new(b)), LocalType::Int);
+            return Ok(None);
+        }
+        if let Option::Some(kind) = op.array_load() {
+            let index = self.pop()?;
+            let array = self.pop()?;
+            self.push(Expr::ArrayLoad(Box::new(array), Box::new(index)), kind.element());
+            return Ok(None);
+        }
+        if op.array_store().is_some() {
+            let value = self.pop()?;
+            let index = self.pop()?;
+            let array = self.pop()?;
+            self.emit(Statement::ArrayStore(array, index, value));
+            return Ok(None);
+        }
+        if let Option::Some((jump, _)) = Jump::from_op(op) {
+            let target = op.branch_targets(pc)[0] as usize;
+            let cond = match Cond::from_jump(jump) {
+                Option::Some(c) => c,
+                Option::None if jump == Jump::Goto => return Ok(Some(Statement::Goto(target))),
+                Option::None => return Err(ExprFault::Subroutine)
+            };
+            let rhs = match jump {
+                Jump::IFNull | Jump::IFnonNull => Expr::Literal(Literal::Null),
+                Jump::IFEQ | Jump::IFNE | Jump::IFLT | Jump::IFGE | Jump::IFGT | Jump::IFLE => Expr::Literal(Literal::Int(0)),
+                _ => self.pop()?
+            };
+            let lhs = self.pop()?;
+            return Ok(Some(Statement::If { cond, lhs, rhs, target }));
+        }
+        let int = |x: i32| Expr::Literal(Literal::Int(x));
+        match *op {
+            JOp::Nop => { },
+            JOp::AConstNull => self.push(Expr::Literal(Literal::Null), LocalType::Reference),
+            JOp::IConstM1 => self.push(int(-1), LocalType::Int),
+            JOp::IConst0 => self.push(int(0), LocalType::Int),
+            JOp::IConst1 => self.push(int(1), LocalType::Int),
+            JOp::IConst2 => self.push(int(2), LocalType::Int),
+            JOp::IConst3 => self.push(int(3), LocalType::Int),
+            JOp::IConst4 => self.push(int(4), LocalType::Int),
+            JOp::IConst5 => self.push(int(5), LocalType::Int),
+            JOp::BiPush(x) => self.push(int(x as i8 as i32), LocalType::Int),
+            JOp::SIPush(x) => self.push(int(x as i16 as i32), LocalType::Int),
+            JOp::LConst0 => self.push(Expr::Literal(Literal::Long(0)), LocalType::Long),
+            JOp::LConst1 => self.push(Expr::Literal(Literal::Long(1)), LocalType::Long),
+            JOp::FConst0 => self.push(Expr::Literal(Literal::Float(0.0)), LocalType::Float),
+            JOp::FConst1 => self.push(Expr::Literal(Literal::Float(1.0)), LocalType::Float),
+            JOp::FConst2 => self.push(Expr::Literal(Literal::Float(2.0)), LocalType::Float),
+            JOp::DConst0 => self.push(Expr::Literal(Literal::Double(0.0)), LocalType::Double),
+            JOp::DConst1 => self.push(Expr::Literal(Literal::Double(1.0)), LocalType::Double),
+            JOp::LDC(x) => {
+                let (l, ty) = self.literal(x as u16)?;
+                self.push(Expr::Literal(l), ty);
+            },
+            JOp::LDCW(x) |
+            JOp::LDC2W(x) => {
+                let (l, ty) = self.literal(x)?;
+                self.push(Expr::Literal(l), ty);
+            },
+            JOp::Pop => self.discard(1)?,
+            JOp::Pop2 => self.discard(2)?,
+            JOp::Dup => self.duplicate(1, 0)?,
+            JOp::Dupx1 => self.duplicate(1, 1)?,
+            JOp::Dupx2 => self.duplicate(1, 2)?,
+            JOp::Dup2 => self.duplicate(2, 0)?,
+            JOp::Dup2x1 => self.duplicate(2, 1)?,
+            JOp::Dup2x2 => self.duplicate(2, 2)?,
+            JOp::Swap => {
+                self.spill(None);
+                let a = self.stack.pop().ok_or(ExprFault::StackUnderflow)?;
+                let b = self.stack.pop().ok_or(ExprFault::StackUnderflow)?;
+                self.stack.push(a);
+                self.stack.push(b);
+            },
+            JOp::ArrayLength => {
+                let a = self.pop()?;
+                self.push(Expr::ArrayLength(Box::new(a)), LocalType::Int);
+            },
+            JOp::NewArray(t) => {
+                let element = match t {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    11 => FieldType::Long,
+                    _ => return Err(ExprFault::BadConstant(t as u16))
+                };
+                let n = self.pop()?;
+                self.push(Expr::NewArray(FieldType::Array(Box::new(element)), vec![n]), LocalType::Reference);
+            },
+            JOp::ANewArray(x) => {
+                let element = class_type(&self.class(x)?);
+                let n = self.pop()?;
+                self.push(Expr::NewArray(FieldType::Array(Box::new(element)), vec![n]), LocalType::Reference);
+            },
+            JOp::MultiAneWArray(x, dims) => {
+                let t = class_type(&self.class(x)?);
+                let dims = self.pop_n(dims as usize)?;
+                self.push(Expr::NewArray(t, dims), LocalType::Reference);
+            },
+            JOp::New(x) => {
+                let c = self.class(x)?;
+                self.push(Expr::New(c), LocalType::Reference);
+            },
+            JOp::CheckCast(x) => {
+                let c = self.class(x)?;
+                let a = self.pop()?;
+                self.push(Expr::CheckCast(c, Box::new(a)), LocalType::Reference);
+            },
+            JOp::InstanceOf(x) => {
+                let c = self.class(x)?;
+                let a = self.pop()?;
+                self.push(Expr::InstanceOf(c, Box::new(a)), LocalType::Int);
+            },
+            JOp::GetStatic(x) => {
+                let m = self.member(x)?;
+                let ty = FieldType::parse(&m.descriptor).ok_or(ExprFault::BadConstant(x))?;
+                self.push(Expr::GetStatic(m), LocalType::computational(&ty));
+            },
+            JOp::GetField(x) => {
+                let m = self.member(x)?;
+                let ty = FieldType::parse(&m.descriptor).ok_or(ExprFault::BadConstant(x))?;
+                let obj = self.pop()?;
+                self.push(Expr::GetField(m, Box::new(obj)), LocalType::computational(&ty));
+            },
+            JOp::PutStatic(x) => {
+                let m = self.member(x)?;
+                let v = self.pop()?;
+                self.emit(Statement::PutStatic(m, v));
+            },
+            JOp::PutField(x) => {
+                let m = self.member(x)?;
+                let v = self.pop()?;
+                let obj = self.pop()?;
+                self.emit(Statement::PutField(m, obj, v));
+            },
+            JOp::InvokeVirtual(_) |
+            JOp::InvokeSpecial(_) |
+            JOp::InvokeStatic(_) |
+            JOp::InvokedInterface(..) |
+            JOp::InvokedDynamic(_) => self.invoke(op)?,
+            JOp::MonitorEnter => {
+                let a = self.pop()?;
+                self.emit(Statement::MonitorEnter(a));
+            },
+            JOp::MonitorExit => {
+                let a = self.pop()?;
+                self.emit(Statement::MonitorExit(a));
+            },
+            JOp::AThrow => return Ok(Some(Statement::Throw(self.pop()?))),
+            JOp::Return => return Ok(Some(Statement::Return(None))),
+            JOp::IReturn |
+            JOp::LReturn |
+            JOp::FReturn |
+            JOp::DReturn |
+            JOp::AReturn => return Ok(Some(Statement::Return(Some(self.pop()?)))),
+            JOp::TableSwitch(..) |
+            JOp::LookUpSwitch(..) => {
+                let value = self.pop()?;
+                let targets = op.branch_targets(pc);
+                let keys: Vec<i32> = match *op {
+                    JOp::TableSwitch(_, low, _, ref offsets) => (0..offsets.len()).map(|i| low.wrapping_add(i as i32)).collect(),
+                    JOp::LookUpSwitch(_, ref pairs) => pairs.iter().map(|p| p.0).collect(),
+                    _ => unreachable!()
+                };
+                let cases = keys.into_iter().zip(targets[1..].iter().map(|t| *t as usize)).collect();
+                return Ok(Some(Statement::Switch { value, default: targets[0] as usize, cases }));
+            },
+            JOp::JSR(_) |
+            JOp::JSRW(_) => return Err(ExprFault::Subroutine),
+            _ => return Err(ExprFault::Reserved)
+        };
+        Ok(None)
+    }
+
+    /// Hand the remaining stack to the successors, ahead of the
+    /// block's final branch `last`
+    fn leave(&mut self, last: Option<&mut Statement>) {
+        let moved: Vec<usize> = (0..self.stack.len())
+            .filter(|i| self.stack[*i].0 != Expr::Stack(*i))
+            .collect();
+        // the branch runs after the stores, operands reading a slot they
+        // overwrite are evaluated first, keeping their order
+        let operands = match last {
+            Option::Some(&mut Statement::If { ref mut lhs, ref mut rhs, .. }) => vec![lhs, rhs],
+            Option::Some(&mut Statement::Switch { ref mut value, .. }) => vec![value],
+            _ => Vec::new()
+        };
+        let overwritten = |e: &Expr| e.reads(&|x| matches!(*x, Expr::Stack(i) if moved.contains(&i)));
+        if operands.iter().any(|e| overwritten(e)) {
+            self.spill(None);
+            for e in operands {
+                if !matches!(*e, Expr::Literal(_)) {
+                    *e = self.temp(e.clone());
+                }
+            }
+        }
+        let reads_stack = |e: &Expr| e.reads(&|x| matches!(*x, Expr::Stack(_)));
+        if moved.len() > 1 && moved.iter().any(|i| reads_stack(&self.stack[*i].0)) {
+            for &i in moved.iter() {
+                let e = self.stack[i].0.clone();
+                self.stack[i].0 = self.temp(e);
+            }
+        }
+        for i in moved {
+            let e = self.stack[i].0.clone();
+            self.out.push(Statement::Stack(i, e));
+        }
+    }
+}
+
+/// Rebuild the statements of every block of `cfg`
+///
+/// Indexed by block, unreachable blocks have no statements. A block
+/// ending by falling through has no final `Goto`.
+pub fn expressions(cfg: &Cfg, pool: &ConstantsPool) -> Result<Vec<Vec<Statement>>, ExprError> {
+    let mut out = vec![Vec::new(); cfg.blocks.len()];
+    if cfg.blocks.is_empty() {
+        return Ok(out);
+    }
+    let mut entry: Vec<Option<Vec<LocalType>>> = vec![None; cfg.blocks.len()];
+    entry[cfg.entry()] = Some(Vec::new());
+    let mut work = vec![cfg.entry()];
+    let mut temps = 0;
+    while let Option::Some(b) = work.pop() {
+        let types = entry[b].clone().unwrap();
+        let stack = if cfg.blocks[b].handler {
+            vec![(Expr::Caught, LocalType::Reference)]
+        } else {
+            types.iter().enumerate().map(|(i, t)| (Expr::Stack(i), *t)).collect()
+        };
+        let mut builder = Builder { pool, stack, out: Vec::new(), temps };
+        let mut last = None;
+        for &(pc, ref op) in cfg.block_ops(b) {
+            last = builder.step(pc, op).map_err(|reason| ExprError { pc, reason })?;
+        }
+        match last {
+            Option::Some(Statement::Return(_)) |
+            Option::Some(Statement::Throw(_)) => builder.spill(None),
+            _ => builder.leave(last.as_mut())
+        };
+        builder.out.extend(last);
+        let exit: Vec<LocalType> = builder.stack.iter().map(|x| x.1).collect();
+        temps = builder.temps;
+        out[b] = builder.out;
+
+        for edge in cfg.successors(b) {
+            let found = if edge.kind.is_exceptional() { vec![LocalType::Reference] } else { exit.clone() };
+            match entry[edge.to] {
+                Option::Some(ref t) if t.len() != found.len() => {
+                    return Err(ExprError { pc: cfg.blocks[edge.to].start, reason: ExprFault::StackHeight });
+                },
+                Option::Some(_) => { },
+                Option::None => {
+                    entry[edge.to] = Some(found);
+                    work.push(edge.to);
+                }
+            };
+        }
+    }
+    Ok(out)
+}
+
+/// Java style name of a type
+fn type_name(t: &FieldType) -> String {
+    match *t {
+        FieldType::Byte => "byte".to_string(),
+        FieldType::Char => "char".to_string(),
+        FieldType::Double => "double".to_string(),
+        FieldType::Float => "float".to_string(),
+        FieldType::Int => "int".to_string(),
+        FieldType::Long => "long".to_string(),
+        FieldType::Short => "short".to_string(),
+        FieldType::Boolean => "boolean".to_string(),
+        FieldType::Object(ref x) => x.replace('/', "."),
+        FieldType::Array(ref x) => format!("{}[]", type_name(x))
+    }
+}
+
+fn class_name(name: &str) -> String {
+    type_name(&class_type(name))
+}
+
+fn binary_symbol(op: BinOp) -> &'static str {
+    match op {
+        BinOp::Add => "+",
+        BinOp::Sub => "-",
+        BinOp::Mul => "*",
+        BinOp::Div => "/",
+        BinOp::Rem => "%",
+        BinOp::Shl => "<<",
+        BinOp::Shr => ">>",
+        BinOp::UShr => ">>>",
+        BinOp::And => "&",
+        BinOp::Or => "|",
+        BinOp::Xor => "^"
+    }
+}
+
+fn cond_symbol(cond: Cond) -> &'static str {
+    match cond {
+        Cond::Eq => "==",
+        Cond::Ne => "!=",
+        Cond::Lt => "<",
+        Cond::Ge => ">=",
+        Cond::Gt => ">",
+        Cond::Le => "<="
+    }
+}
+
+/// Wraps an operand in parentheses unless it is an atom
+struct Operand<'e>(&'e Expr);
+impl<'e> fmt::Display for Operand<'e> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.0.is_atom() {
+            write!(f, "{}", self.0)
+        } else {
+            write!(f, "({})", self.0)
+        }
+    }
+}
+
+fn write_args(f: &mut fmt::Formatter, args: &[Expr]) -> fmt::Result {
+    for (i, a) in args.iter().enumerate() {
+        if i > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", a)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Literal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Literal::Int(x) => write!(f, "{}", x),
+            Literal::Long(x) => write!(f, "{}L", x),
+            Literal::Float(x) => write!(f, "{:?}f", x),
+            Literal::Double(x) => write!(f, "{:?}", x),
+            Literal::Null => write!(f, "null"),
+            Literal::Str(ref x) => write!(f, "{:?}", x),
+            Literal::Class(ref x) => write!(f, "{}.class", class_name(x)),
+            Literal::MethodType(ref x) => write!(f, "MethodType {}", x),
+            Literal::MethodHandle(_, ref m) => write!(f, "{}::{}", class_name(&m.owner), m.name)
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Expr::Local(x) => write!(f, "local{}", x),
+            Expr::Literal(ref x) => write!(f, "{}", x),
+            Expr::Temp(x) => write!(f, "t{}", x),
+            Expr::Stack(x) => write!(f, "stack{}", x),
+            Expr::Caught => write!(f, "caught"),
+            Expr::Binary(op, _, ref a, ref b) => write!(f, "{} {} {}", Operand(a), binary_symbol(op), Operand(b)),
+            Expr::Neg(ref a) => write!(f, "-{}", Operand(a)),
+            Expr::Convert(t, ref a) => {
+                let name = match t {
+                    LocalType::Int => "int",
+                    LocalType::Long => "long",
+                    LocalType::Float => "float",
+                    LocalType::Double => "double",
+                    LocalType::Reference => "Object"
+                };
+                write!(f, "({}) {}", name, Operand(a))
+            },
+            Expr::Narrow(n, ref a) => {
+                let name = match n {
+                    Narrowing::Byte => "byte",
+                    Narrowing::Char => "char",
+                    Narrowing::Short => "short"
+                };
+                write!(f, "({}) {}", name, Operand(a))
+            },
+            Expr::Compare(c, ref a, ref b) => {
+                let name = match c {
+                    Comparison::Long => "cmp",
+                    Comparison::FloatL | Comparison::DoubleL => "cmpl",
+                    Comparison::FloatG | Comparison::DoubleG => "cmpg"
+                };
+                write!(f, "{} {} {}", Operand(a), name, Operand(b))
+            },
+            Expr::ArrayLoad(ref a, ref i) => write!(f, "{}[{}]", Operand(a), i),
+            Expr::ArrayLength(ref a) => write!(f, "{}.length", Operand(a)),
+            Expr::NewArray(ref t, ref dims) => {
+                let mut t = t;
+                let mut depth = 0;
+                while let FieldType::Array(ref inner) = *t {
+                    t = inner;
+                    depth += 1;
+                }
+                write!(f, "new {}", type_name(t))?;
+                for i in 0..depth {
+                    match dims.get(i) {
+                        Option::Some(d) => write!(f, "[{}]", d)?,
+                        Option::None => write!(f, "[]")?
+                    };
+                }
+                Ok(())
+            },
+            Expr::New(ref c) => write!(f, "new {}", class_name(c)),
+            Expr::Construct(ref m, ref args) => {
+                write!(f, "new {}(", class_name(&m.owner))?;
+                write_args(f, args)?;
+                write!(f, ")")
+            },
+            Expr::GetField(ref m, ref obj) => write!(f, "{}.{}", Operand(obj), m.name),
+            Expr::GetStatic(ref m) => write!(f, "{}.{}", class_name(&m.owner), m.name),
+            Expr::Invoke(kind, ref m, ref args) => {
+                let args = match kind {
+                    InvokeKind::Static => {
+                        write!(f, "{}.", class_name(&m.owner))?;
+                        &args[..]
+                    },
+                    InvokeKind::Dynamic => {
+                        write!(f, "invokedynamic ")?;
+                        &args[..]
+                    },
+                    _ => {
+                        write!(f, "{}.", Operand(&args[0]))?;
+                        &args[1..]
+                    }
+                };
+                write!(f, "{}(", m.name)?;
+                write_args(f, args)?;
+                write!(f, ")")
+            },
+            Expr::CheckCast(ref c, ref a) => write!(f, "({}) {}", class_name(c), Operand(a)),
+            Expr::InstanceOf(ref c, ref a) => write!(f, "{} instanceof {}", Operand(a), class_name(c))
+        }
+    }
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Statement::Store(x, ref e) => write!(f, "local{} = {}", x, e),
+            Statement::Temp(x, ref e) => write!(f, "t{} = {}", x, e),
+            Statement::Stack(x, ref e) => write!(f, "stack{} = {}", x, e),
+            Statement::Increment(x, c) => write!(f, "local{} += {}", x, c),
+            Statement::ArrayStore(ref a, ref i, ref v) => write!(f, "{}[{}] = {}", Operand(a), i, v),
+            Statement::PutField(ref m, ref obj, ref v) => write!(f, "{}.{} = {}", Operand(obj), m.name, v),
+            Statement::PutStatic(ref m, ref v) => write!(f, "{}.{} = {}", class_name(&m.owner), m.name, v),
+            Statement::Expr(ref e) => write!(f, "{}", e),
+            Statement::MonitorEnter(ref e) => write!(f, "monitorenter {}", e),
+            Statement::MonitorExit(ref e) => write!(f, "monitorexit {}", e),
+            Statement::If { cond, ref lhs, ref rhs, target } => {
+                write!(f, "if ({} {} {}) goto {}", Operand(lhs), cond_symbol(cond), Operand(rhs), target)
+            },
+            Statement::Goto(x) => write!(f, "goto {}", x),
+            Statement::Switch { ref value, default, ref cases } => {
+                write!(f, "switch ({}) {{ ", value)?;
+                for &(key, target) in cases.iter() {
+                    write!(f, "case {}: goto {}; ", key, target)?;
+                }
+                write!(f, "default: goto {} }}", default)
+            },
+            Statement::Return(Option::Some(ref e)) => write!(f, "return {}", e),
+            Statement::Return(Option::None) => write!(f, "return"),
+            Statement::Throw(ref e) => write!(f, "throw {}", e)
+        }
+    }
+}
+
+#[test]
+fn test_expressions() {
+    use super::super::clazz::parse_constant_pool;
+    use super::super::nom::IResult;
+    use super::decoder::Decoder;
+
+    // #2 class A, #6 A.foo(I)I, #10 A.<init>()V, #14 A.f:I
+    let pool = match parse_constant_pool(b"\x00\x0F\
+        \x01\x00\x01A\x07\x00\x01\x01\x00\x03foo\x01\x00\x04(I)I\x0C\x00\x03\x00\x04\
+        \x0A\x00\x02\x00\x05\x01\x00\x06<init>\x01\x00\x03()V\x0C\x00\x07\x00\x08\
+        \x0A\x00\x02\x00\x09\x01\x00\x01f\x01\x00\x01I\x0C\x00\x0B\x00\x0C\x09\x00\x02\x00\x0D") {
+        IResult::Done(_, pool) => pool,
+        _ => panic!()
+    };
+    let text = |code: &[u8]| -> Vec<Vec<String>> {
+        let ops = Decoder::new(code).map(Result::unwrap).collect();
+        let cfg = Cfg::build(ops, code.len(), &[]).unwrap();
+        expressions(&cfg, &pool).unwrap()
+            .iter()
+            .map(|b| b.iter().map(|s| s.to_string()).collect())
+            .collect()
+    };
+
+    // local3 = local1.foo(local2 + 1); local4 = new A();
+    // local5 = local3 == 0 ? 2 : 1; return
+    assert_eq!(text(&[
+        0x2B, 0x1C, 0x04, 0x60, 0xB6, 0x00, 0x06, 0x3E, 0xBB, 0x00,
+        0x02, 0x59, 0xB7, 0x00, 0x0A, 0x3A, 0x04, 0x1D, 0x99, 0x00,
+        0x07, 0x04, 0xA7, 0x00, 0x04, 0x05, 0x36, 0x05, 0xB1
+    ]), vec![
+        vec!["local3 = local1.foo(local2 + 1)", "local4 = new A()", "if (local3 == 0) goto 25"],
+        vec!["stack0 = 1", "goto 26"],
+        vec!["stack0 = 2"],
+        vec!["local5 = stack0", "return"]
+    ]);
+
+    // local0.f = local1 = 5; return local1 = local0.foo(0)
+    assert_eq!(text(&[
+        0x2A, 0x08, 0x5A, 0xB5, 0x00, 0x0E, 0x3C, 0x2A, 0x03, 0xB6,
+        0x00, 0x06, 0x59, 0x3C, 0xAC
+    ]), vec![
+        vec!["local0.f = 5", "local1 = 5", "t0 = local0.foo(0)", "local1 = t0", "return t0"]
+    ]);
+
+    // the branch reads stack0 before the swapped constant replaces it:
+    // local0 == 0 ? 1 : 2, then iconst_0; swap; ifeq
+    assert_eq!(text(&[
+        0x1A, 0x99, 0x00, 0x07, 0x04, 0xA7, 0x00, 0x04, 0x05, 0x03,
+        0x5F, 0x99, 0x00, 0x04, 0xAC, 0xAC
+    ]), vec![
+        vec!["if (local0 == 0) goto 8"],
+        vec!["stack0 = 1", "goto 9"],
+        vec!["stack0 = 2"],
+        vec!["t0 = stack0", "stack0 = 0", "if (t0 == 0) goto 15"],
+        vec!["return stack0"],
+        vec!["return stack0"]
+    ]);
+
+    // the division may throw, so it stays ahead of the store:
+    // iload_0; iload_1; idiv; aload_2; iconst_5; putfield f; ireturn
+    assert_eq!(text(&[0x1A, 0x1B, 0x6C, 0x2C, 0x08, 0xB5, 0x00, 0x0E, 0xAC]), vec![
+        vec!["t0 = local0 / local1", "local2.f = 5", "return t0"]
+    ]);
+}
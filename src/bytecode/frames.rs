@@ -0,0 +1,220 @@
+//! `StackMapTable` computation for generated or rewritten code.
+//!
+//! Frames come from type inference over the instructions and the
+//! exception table, then are compressed against each other into the
+//! smallest encoding.
+
+use super::super::clazz::{CodeAttribute, ConstantsPool, StackMapFrame, VerificationType, write_stack_map_table};
+use super::decoder::decode_op;
+use super::hierarchy::ClassHierarchy;
+use super::verify::{Frame, MethodContext, VType, VerifyError, VerifyFault, infer_frames};
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A `ClassHierarchy` that only answers `common_superclass`
+///
+/// Enough for frame computation, which never checks assignability.
+pub struct SuperclassOracle<F>(pub F);
+impl<F: Fn(&str, &str) -> String> ClassHierarchy for SuperclassOracle<F> {
+    fn super_class(&self, _: &str) -> Option<String> {
+        None
+    }
+    fn is_interface(&self, _: &str) -> bool {
+        false
+    }
+    fn common_superclass(&self, a: &str, b: &str) -> String {
+        (self.0)(a, b)
+    }
+}
+
+/// Why frames could not be computed
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum FrameFault {
+    /// Type inference failed
+    Infer(VerifyFault),
+    /// Unreachable code needs a frame, but has no types to put in it
+    DeadCode,
+    /// No `ClassInfo` constant for a class that appears in a frame
+    UnknownClass(String),
+    /// Return addresses cannot appear in a `StackMapTable`
+    ReturnAddress
+}
+
+/// A frame computation failure and the pc it concerns
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct FrameError {
+    pub pc: usize,
+    pub reason: FrameFault
+}
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            FrameFault::Infer(ref x) => {
+                return VerifyError { pc: self.pc, reason: x.clone() }.fmt(f);
+            },
+            FrameFault::DeadCode => write!(f, "unreachable code"),
+            FrameFault::UnknownClass(ref x) => write!(f, "no constant for class {}", x),
+            FrameFault::ReturnAddress => write!(f, "return address in a frame")
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+/// Infer the frames a `StackMapTable` must hold
+///
+/// These are at every branch target, exception handler, and
+/// instruction following an unconditional transfer of control.
+pub fn compute_frames(ctx: MethodContext, code: &CodeAttribute, pool: &ConstantsPool, oracle: &dyn ClassHierarchy) -> Result<Vec<(usize, Frame)>, FrameError> {
+    let inferred = infer_frames(ctx, code, pool, oracle)
+        .map_err(|e| FrameError { pc: e.pc, reason: FrameFault::Infer(e.reason) })?;
+    let mut needed = BTreeSet::new();
+    for entry in code.exception_table.iter() {
+        needed.insert(entry.handler_pc as usize);
+    }
+    let mut following = false;
+    for &(pc, _) in inferred.iter() {
+        if following {
+            needed.insert(pc);
+        }
+        let op = decode_op(code.code, pc).map_err(|e| FrameError { pc, reason: FrameFault::Infer(VerifyFault::Decode(e.reason)) })?.0;
+        needed.extend(op.branch_targets(pc).into_iter().map(|x| x as usize));
+        following = op.ends_flow();
+    }
+    let mut frames = Vec::with_capacity(needed.len());
+    for &(pc, ref frame) in inferred.iter() {
+        if !needed.contains(&pc) {
+            continue;
+        }
+        match *frame {
+            Option::Some(ref f) => frames.push((pc, f.clone())),
+            Option::None => return Err(FrameError { pc, reason: FrameFault::DeadCode })
+        };
+    }
+    Ok(frames)
+}
+
+fn to_stack_map(t: &VType, class_index: &mut dyn FnMut(&str) -> Option<u16>) -> Result<VerificationType, FrameFault> {
+    Ok(match *t {
+        VType::Top => VerificationType::Top,
+        VType::Int => VerificationType::Integer,
+        VType::Float => VerificationType::Float,
+        VType::Long => VerificationType::Long,
+        VType::Double => VerificationType::Double,
+        VType::Null => VerificationType::Null,
+        VType::UninitializedThis => VerificationType::UninitializedThis,
+        VType::Uninitialized(pc) => VerificationType::Uninitialized(pc),
+        VType::Reference(ref name) => {
+            VerificationType::Object(class_index(name).ok_or_else(|| FrameFault::UnknownClass(name.clone()))?)
+        },
+        VType::ReturnAddress => return Err(FrameFault::ReturnAddress)
+    })
+}
+
+/// Encode frames as compactly as possible
+///
+/// `frames` must be in pc order. `class_index` maps an internal class
+/// name (or array descriptor) to its `ClassInfo` constant.
+pub fn compress_frames(ctx: MethodContext, frames: &[(usize, Frame)], class_index: &mut dyn FnMut(&str) -> Option<u16>) -> Result<Vec<StackMapFrame>, FrameError> {
+    let mut previous = Frame::initial_entries(ctx)
+        .map_err(|reason| FrameError { pc: 0, reason: FrameFault::Infer(reason) })?;
+    let mut last_pc: Option<usize> = None;
+    let mut out = Vec::with_capacity(frames.len());
+    for &(pc, ref frame) in frames {
+        let fail = |reason| FrameError { pc, reason };
+        let offset_delta = match last_pc {
+            Option::Some(x) => pc - x - 1,
+            Option::None => pc
+        } as u16;
+        let locals = Frame::collapse(&frame.locals);
+        let stack = Frame::collapse(&frame.stack);
+        let convert = |list: &[VType], class_index: &mut dyn FnMut(&str) -> Option<u16>| {
+            list.iter()
+                .map(|t| to_stack_map(t, class_index))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(fail)
+        };
+        let same_prefix = previous.len().min(locals.len());
+        let prefix = previous[..same_prefix] == locals[..same_prefix];
+        let encoded = if locals == previous && stack.is_empty() {
+            StackMapFrame::Same { offset_delta }
+        } else if locals == previous && stack.len() == 1 {
+            StackMapFrame::SameLocals1StackItem { offset_delta, stack: to_stack_map(&stack[0], class_index).map_err(fail)? }
+        } else if prefix && stack.is_empty() && locals.len() < previous.len() && previous.len() - locals.len() <= 3 {
+            StackMapFrame::Chop { offset_delta, k: (previous.len() - locals.len()) as u8 }
+        } else if prefix && stack.is_empty() && locals.len() > previous.len() && locals.len() - previous.len() <= 3 {
+            StackMapFrame::Append { offset_delta, locals: convert(&locals[previous.len()..], class_index)? }
+        } else {
+            StackMapFrame::Full {
+                offset_delta,
+                locals: convert(&locals, class_index)?,
+                stack: convert(&stack, class_index)?
+            }
+        };
+        out.push(encoded);
+        previous = locals;
+        last_pc = Some(pc);
+    }
+    Ok(out)
+}
+
+/// Compute the body of a `StackMapTable` attribute for a method
+///
+/// Classes in frames must already have `ClassInfo` constants in `pool`,
+/// use `compute_frames` and `compress_frames` to allocate new ones.
+pub fn stack_map_table(ctx: MethodContext, code: &CodeAttribute, pool: &ConstantsPool, oracle: &dyn ClassHierarchy) -> Result<Vec<u8>, FrameError> {
+    let frames = compute_frames(ctx, code, pool, oracle)?;
+    let compressed = compress_frames(ctx, &frames, &mut |name| pool.find_class(name))?;
+    Ok(write_stack_map_table(&compressed))
+}
+
+#[test]
+fn test_frames() {
+    use super::super::clazz::{parse_constant_pool, AttributeInfo};
+    use super::super::nom::IResult;
+    use super::hierarchy::MapHierarchy;
+    use super::verify::verify_code;
+
+    // #2 A, #4 B, #6 C, #7 "StackMapTable"
+    let pool = match parse_constant_pool(b"\x00\x08\
+        \x01\x00\x01A\x07\x00\x01\x01\x00\x01B\x07\x00\x03\x01\x00\x01C\x07\x00\x05\
+        \x01\x00\x0DStackMapTable") {
+        IResult::Done(_, pool) => pool,
+        _ => panic!()
+    };
+    // Object f(boolean z) { C c = z ? (A) null : (B) null; return c; }
+    let body: &[u8] = &[
+        0x1A, 0x99, 0x00, 0x0B, 0x01, 0xC0, 0x00, 0x02, 0x4C, 0xA7,
+        0x00, 0x08, 0x01, 0xC0, 0x00, 0x04, 0x4C, 0x2B, 0xB0
+    ];
+    let code = CodeAttribute {
+        max_stack: 1,
+        max_locals: 2,
+        code: body,
+        exception_table: Vec::new(),
+        attributes: Vec::new()
+    };
+    let ctx = MethodContext {
+        class_name: "T",
+        name: "f",
+        descriptor: "(Z)Ljava/lang/Object;",
+        is_static: true,
+        major_version: 52
+    };
+    let oracle = SuperclassOracle(|_: &str, _: &str| "C".to_string());
+    let frames = compute_frames(ctx, &code, &pool, &oracle).unwrap();
+    assert_eq!(frames.iter().map(|x| x.0).collect::<Vec<_>>(), vec![12, 17]);
+    assert_eq!(frames[1].1.locals, vec![VType::Int, VType::Reference("C".to_string())]);
+
+    let table = stack_map_table(ctx, &code, &pool, &oracle).unwrap();
+    assert_eq!(table, b"\x00\x02\x0C\xFC\x00\x04\x07\x00\x06");
+
+    // the result passes the type checker
+    let mut h = MapHierarchy::new();
+    h.add_class("C", Some("java/lang/Object"));
+    h.add_class("A", Some("C"));
+    h.add_class("B", Some("C"));
+    let attributes = vec![AttributeInfo { name_index: 7, data: &table }];
+    let code = CodeAttribute { attributes, ..code };
+    assert_eq!(verify_code(ctx, &code, &pool, &h), Ok(()));
+}
@@ -0,0 +1,169 @@
+//! Class hierarchy queries for verification and frame computation.
+//!
+//! Nothing here loads classes. Callers describe the classes they know
+//! about through `ClassHierarchy`, so analysis runs without a JVM.
+
+use super::super::clazz::{Class, ClassAccessFlags, Fault};
+
+use std::collections::HashMap;
+
+/// Internal name of the root class
+pub const OBJECT: &str = "java/lang/Object";
+
+/// Guards against cyclic hierarchies in malformed input
+const MAX_DEPTH: usize = 1024;
+
+/// Answers questions about classes by internal name
+///
+/// Array classes are named by their descriptor, `[I` or
+/// `[Ljava/lang/String;`, and are never passed to `super_class` or
+/// `is_interface`.
+pub trait ClassHierarchy {
+
+    /// The direct super class, `None` for `java/lang/Object` and for
+    /// classes the provider knows nothing about
+    fn super_class(&self, class: &str) -> Option<String>;
+
+    /// Is `class` an interface
+    fn is_interface(&self, class: &str) -> bool;
+
+    /// Is `class` the same as `of`, or a subclass of it
+    fn is_subclass(&self, class: &str, of: &str) -> bool {
+        if of == OBJECT {
+            return true;
+        }
+        let mut current = class.to_string();
+        for _ in 0..MAX_DEPTH {
+            if current == of {
+                return true;
+            }
+            current = match self.super_class(&current) {
+                Option::Some(x) => x,
+                Option::None => return false
+            };
+        }
+        false
+    }
+
+    /// The closest class both `a` and `b` extend
+    ///
+    /// Interfaces have no useful common super class, so any interface
+    /// yields `java/lang/Object`.
+    fn common_superclass(&self, a: &str, b: &str) -> String {
+        if self.is_interface(a) || self.is_interface(b) {
+            return OBJECT.to_string();
+        }
+        let mut current = a.to_string();
+        for _ in 0..MAX_DEPTH {
+            if self.is_subclass(b, &current) {
+                return current;
+            }
+            current = match self.super_class(&current) {
+                Option::Some(x) => x,
+                Option::None => break
+            };
+        }
+        OBJECT.to_string()
+    }
+}
+
+/// Is a value of reference type `from` assignable to `to`
+///
+/// Follows JVMS 4.10.1.2, where every interface accepts every
+/// reference.
+pub fn is_ref_assignable(hierarchy: &dyn ClassHierarchy, from: &str, to: &str) -> bool {
+    if from == to || to == OBJECT {
+        return true;
+    }
+    let from_array = from.starts_with('[');
+    if let Option::Some(b) = to.strip_prefix('[') {
+        let a = match from.strip_prefix('[') {
+            Option::Some(a) => a,
+            Option::None => return false
+        };
+        return match (element_class(a), element_class(b)) {
+            (Option::Some(a), Option::Some(b)) => is_ref_assignable(hierarchy, a, b),
+            _ => a == b
+        };
+    }
+    if from_array {
+        return to == "java/lang/Cloneable" || to == "java/io/Serializable";
+    }
+    hierarchy.is_interface(to) || hierarchy.is_subclass(from, to)
+}
+
+/// The class named by a reference component descriptor, `None` for
+/// primitive components
+///
+/// `Ljava/lang/String;` gives `java/lang/String`, `[I` is returned as is.
+pub fn element_class(desc: &str) -> Option<&str> {
+    if desc.starts_with('L') && desc.ends_with(';') && desc.len() > 2 {
+        Some(&desc[1..desc.len() - 1])
+    } else if desc.starts_with('[') {
+        Some(desc)
+    } else {
+        None
+    }
+}
+
+/// A `ClassHierarchy` built up from explicit declarations
+///
+/// Unknown classes have no super class and are not interfaces.
+#[derive(Clone,Debug,Default)]
+pub struct MapHierarchy {
+    classes: HashMap<String, (Option<String>, bool)>
+}
+impl MapHierarchy {
+
+    pub fn new() -> MapHierarchy {
+        MapHierarchy::default()
+    }
+
+    /// Declare a class and its super class
+    pub fn add_class(&mut self, name: &str, super_class: Option<&str>) {
+        self.classes.insert(name.to_string(), (super_class.map(str::to_string), false));
+    }
+
+    /// Declare an interface
+    pub fn add_interface(&mut self, name: &str) {
+        self.classes.insert(name.to_string(), (Some(OBJECT.to_string()), true));
+    }
+
+    /// Declare a parsed class
+    pub fn add(&mut self, class: &Class) -> Result<(), Fault> {
+        let name = class.get_this_class()?;
+        let interface = class.get_access_flags() & (ClassAccessFlags::Interface as u16) != 0;
+        let super_class = if name == OBJECT {
+            None
+        } else {
+            Some(class.get_super_class()?.into_owned())
+        };
+        self.classes.insert(name.into_owned(), (super_class, interface));
+        Ok(())
+    }
+}
+impl ClassHierarchy for MapHierarchy {
+    fn super_class(&self, class: &str) -> Option<String> {
+        self.classes.get(class).and_then(|x| x.0.clone())
+    }
+    fn is_interface(&self, class: &str) -> bool {
+        self.classes.get(class).is_some_and(|x| x.1)
+    }
+}
+
+#[test]
+fn test_hierarchy() {
+    let mut h = MapHierarchy::new();
+    h.add_class("java/lang/Number", Some(OBJECT));
+    h.add_class("java/lang/Integer", Some("java/lang/Number"));
+    h.add_class("java/lang/Long", Some("java/lang/Number"));
+    h.add_interface("java/lang/Comparable");
+    assert!(h.is_subclass("java/lang/Integer", "java/lang/Number"));
+    assert!(!h.is_subclass("java/lang/Number", "java/lang/Integer"));
+    assert_eq!(h.common_superclass("java/lang/Integer", "java/lang/Long"), "java/lang/Number");
+    assert!(is_ref_assignable(&h, "java/lang/Long", "java/lang/Comparable"));
+    assert!(is_ref_assignable(&h, "[Ljava/lang/Integer;", "[Ljava/lang/Number;"));
+    assert!(is_ref_assignable(&h, "[[I", "[Ljava/lang/Object;"));
+    assert!(!is_ref_assignable(&h, "[I", "[J"));
+    assert!(!is_ref_assignable(&h, "[I", "java/lang/Number"));
+}
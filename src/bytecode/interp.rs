@@ -0,0 +1,1826 @@
+//! An interpreter for static methods.
+//!
+//! Runs code from a fixed set of parsed classes without a JVM. Values are
+//! primitives and references into a heap owned by the `Interpreter`,
+//! which holds arrays, strings and the few other objects modeled here.
+//! Static methods and static fields of the given classes work, and a
+//! class's `<clinit>` runs on first use as it would on a JVM.
+//!
+//! A handful of `java/lang/String`, `StringBuilder`, `Math`, `Integer`,
+//! `Long` and `System.arraycopy` calls are built in. Anything else, such
+//! as natives, instance methods of the given classes, reflection or
+//! threads, stops execution with `InterpFault::Unsupported`.
+//!
+//! Exceptions raised by the interpreter itself (division by zero, array
+//! bounds, null references, failed casts) and those created with `new`
+//! from the built in exception classes are thrown as on a JVM, so the
+//! code can catch them.
+
+use super::super::clazz::{
+    Class,
+    ConstantsPool,
+    PoolMembers,
+    FieldType,
+    MethodType,
+    ExceptionEntry,
+    FieldAccessFlags,
+    MethodAccessFlags,
+    Fault,
+    find_attribute
+};
+use super::ops::JOp;
+use super::decoder::{Decoder, DecodeFault};
+use super::labels::Jump;
+use super::locals::{LocalAccess, LocalType};
+use super::hierarchy::{ClassHierarchy, MapHierarchy, OBJECT, is_ref_assignable};
+
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::rc::Rc;
+use std::fmt;
+
+/// Instructions run before `InterpFault::StepLimit`, see `set_step_limit`
+pub const DEFAULT_STEP_LIMIT: u64 = 10_000_000;
+
+/// Array elements and characters allocated before
+/// `InterpFault::AllocationLimit`, see `set_allocation_limit`
+pub const DEFAULT_ALLOCATION_LIMIT: u64 = 1 << 24;
+
+/// Nested calls allowed before `InterpFault::TooDeep`
+const MAX_DEPTH: usize = 512;
+
+/// JDK classes with built in behaviour, and their super classes
+const BUILTIN_CLASSES: &[(&str, &str)] = &[
+    ("java/lang/String", OBJECT),
+    ("java/lang/StringBuilder", OBJECT),
+    ("java/lang/Class", OBJECT),
+    ("java/lang/Throwable", OBJECT),
+    ("java/lang/Exception", "java/lang/Throwable"),
+    ("java/lang/Error", "java/lang/Throwable"),
+    ("java/lang/RuntimeException", "java/lang/Exception"),
+    ("java/lang/ArithmeticException", "java/lang/RuntimeException"),
+    ("java/lang/ArrayStoreException", "java/lang/RuntimeException"),
+    ("java/lang/ClassCastException", "java/lang/RuntimeException"),
+    ("java/lang/IllegalArgumentException", "java/lang/RuntimeException"),
+    ("java/lang/IllegalStateException", "java/lang/RuntimeException"),
+    ("java/lang/NumberFormatException", "java/lang/IllegalArgumentException"),
+    ("java/lang/IndexOutOfBoundsException", "java/lang/RuntimeException"),
+    ("java/lang/ArrayIndexOutOfBoundsException", "java/lang/IndexOutOfBoundsException"),
+    ("java/lang/StringIndexOutOfBoundsException", "java/lang/IndexOutOfBoundsException"),
+    ("java/lang/NegativeArraySizeException", "java/lang/RuntimeException"),
+    ("java/lang/NullPointerException", "java/lang/RuntimeException"),
+    ("java/lang/UnsupportedOperationException", "java/lang/RuntimeException")
+];
+
+/// Interfaces of the built in classes
+const BUILTIN_INTERFACES: &[&str] = &[
+    "java/lang/CharSequence",
+    "java/lang/Comparable",
+    "java/lang/Appendable",
+    "java/lang/Cloneable",
+    "java/io/Serializable"
+];
+
+const ARITHMETIC: &str = "java/lang/ArithmeticException";
+const ARRAY_INDEX: &str = "java/lang/ArrayIndexOutOfBoundsException";
+const ARRAY_STORE: &str = "java/lang/ArrayStoreException";
+const CLASS_CAST: &str = "java/lang/ClassCastException";
+const NEGATIVE_SIZE: &str = "java/lang/NegativeArraySizeException";
+const NULL_POINTER: &str = "java/lang/NullPointerException";
+const STRING_INDEX: &str = "java/lang/StringIndexOutOfBoundsException";
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// A value on the operand stack, in a local, field or array element
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Value {
+    /// Also `boolean`, `byte`, `char` and `short`
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    /// Index of an object in the interpreter's heap
+    Ref(usize),
+    /// Left by `jsr` for `ret`
+    ReturnAddress(usize)
+}
+impl Value {
+
+    /// The initial value of a field or array element of type `t`
+    pub fn default_for(t: &FieldType) -> Value {
+        match *t {
+            FieldType::Long => Value::Long(0),
+            FieldType::Float => Value::Float(0.0),
+            FieldType::Double => Value::Double(0.0),
+            FieldType::Object(_) |
+            FieldType::Array(_) => Value::Null,
+            _ => Value::Int(0)
+        }
+    }
+
+    /// Is this a value of type `t`
+    pub fn fits(&self, t: &FieldType) -> bool {
+        match (*self, t) {
+            (Value::Long(_), &FieldType::Long) |
+            (Value::Float(_), &FieldType::Float) |
+            (Value::Double(_), &FieldType::Double) |
+            (Value::Null, &FieldType::Object(_)) |
+            (Value::Null, &FieldType::Array(_)) |
+            (Value::Ref(_), &FieldType::Object(_)) |
+            (Value::Ref(_), &FieldType::Array(_)) => true,
+            (Value::Int(_), t) => matches!(*t, FieldType::Byte | FieldType::Char | FieldType::Int |
+                FieldType::Short | FieldType::Boolean),
+            _ => false
+        }
+    }
+
+    /// Stack slots taken
+    fn width(&self) -> usize {
+        match *self {
+            Value::Long(_) | Value::Double(_) => 2,
+            _ => 1
+        }
+    }
+
+    fn fits_local(&self, t: LocalType) -> bool {
+        matches!((*self, t),
+            (Value::Int(_), LocalType::Int) |
+            (Value::Long(_), LocalType::Long) |
+            (Value::Float(_), LocalType::Float) |
+            (Value::Double(_), LocalType::Double) |
+            (Value::Null, LocalType::Reference) |
+            (Value::Ref(_), LocalType::Reference))
+    }
+}
+
+/// Something in the interpreter's heap
+#[derive(Clone,Debug,PartialEq)]
+pub enum Object {
+    /// Elements are kept as they are on the stack, a `byte[]` holds `Int`s
+    Array { component: FieldType, values: Vec<Value> },
+    /// A `java/lang/String`, as UTF-16 code units
+    Str(Vec<u16>),
+    /// A `java/lang/StringBuilder`
+    Builder(Vec<u16>),
+    /// A class literal, by internal name
+    Class(String),
+    /// An exception of the named class
+    Throwable { class: String, message: Option<Vec<u16>> }
+}
+impl Object {
+
+    /// Internal name of the object's class, arrays are named by descriptor
+    pub fn class_name(&self) -> Cow<'_, str> {
+        match *self {
+            Object::Array { ref component, .. } => Cow::Owned(format!("[{}", component)),
+            Object::Str(_) => Cow::Borrowed("java/lang/String"),
+            Object::Builder(_) => Cow::Borrowed("java/lang/StringBuilder"),
+            Object::Class(_) => Cow::Borrowed("java/lang/Class"),
+            Object::Throwable { ref class, .. } => Cow::Borrowed(class)
+        }
+    }
+}
+
+/// Why execution stopped
+#[derive(Clone,Debug)]
+pub enum InterpFault {
+    /// The code does something that is not modeled, such as calling a
+    /// native method or an instance method of a given class
+    Unsupported,
+    /// A class that was not given to the interpreter
+    UnknownClass(String),
+    /// A method or field missing from a given class, as `owner.name:descriptor`
+    UnknownMember(String),
+    /// The class file is malformed
+    Class(Fault),
+    Decode(DecodeFault),
+    /// A constant pool operand is missing or of the wrong kind
+    BadConstant(u16),
+    /// An operand or argument has the wrong type, or the stack is too
+    /// shallow. Code passing the verifier never does this.
+    TypeMismatch,
+    /// A local variable read before it was written, or out of range
+    BadLocal(u16),
+    /// A branch into the middle of an instruction
+    BadBranch,
+    /// Execution ran past the end of the code
+    FellOff,
+    /// More instructions ran than the step limit allows
+    StepLimit,
+    /// Calls nested deeper than the interpreter allows
+    TooDeep,
+    /// More array elements and characters were allocated than the
+    /// allocation limit allows
+    AllocationLimit,
+    /// An argument refers to no object of this interpreter's heap
+    BadReference(usize),
+    /// An exception nobody caught, by class name
+    Uncaught(String)
+}
+
+/// An execution failure and where it happened
+#[derive(Clone,Debug)]
+pub struct InterpError {
+    /// The method running, as `owner.name:descriptor`
+    pub method: String,
+    pub pc: usize,
+    pub reason: InterpFault
+}
+impl fmt::Display for InterpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            InterpFault::Unsupported => write!(f, "unsupported operation"),
+            InterpFault::UnknownClass(ref x) => write!(f, "unknown class {}", x),
+            InterpFault::UnknownMember(ref x) => write!(f, "unknown member {}", x),
+            InterpFault::Class(ref x) => write!(f, "malformed class: {:?}", x),
+            InterpFault::Decode(ref x) => write!(f, "undecodable instruction: {:?}", x),
+            InterpFault::BadConstant(x) => write!(f, "invalid constant #{}", x),
+            InterpFault::TypeMismatch => write!(f, "operand type mismatch"),
+            InterpFault::BadLocal(x) => write!(f, "invalid local {}", x),
+            InterpFault::BadBranch => write!(f, "branch to an invalid pc"),
+            InterpFault::FellOff => write!(f, "fell off the end of the code"),
+            InterpFault::StepLimit => write!(f, "step limit reached"),
+            InterpFault::TooDeep => write!(f, "calls nested too deep"),
+            InterpFault::AllocationLimit => write!(f, "allocation limit reached"),
+            InterpFault::BadReference(x) => write!(f, "no object at reference {}", x),
+            InterpFault::Uncaught(ref x) => write!(f, "uncaught {}", x)
+        }?;
+        write!(f, " in {} at pc {}", self.method, self.pc)
+    }
+}
+
+/// How an instruction leaves other than normally
+enum Trap {
+    /// Stop, the error belongs to the current instruction
+    Fault(InterpFault),
+    /// Stop, the error was located by a callee
+    Error(InterpError),
+    /// Throw a new exception of a built in class
+    Raise(&'static str),
+    /// Throw an existing throwable
+    Throw(usize)
+}
+impl From<InterpFault> for Trap {
+    fn from(x: InterpFault) -> Trap {
+        Trap::Fault(x)
+    }
+}
+
+fn mismatch() -> Trap {
+    Trap::Fault(InterpFault::TypeMismatch)
+}
+
+/// A class that is neither given nor built in
+fn missing_class(name: &str) -> InterpFault {
+    if name.starts_with("java/") {
+        InterpFault::Unsupported
+    } else {
+        InterpFault::UnknownClass(name.to_string())
+    }
+}
+
+/// Where execution continues after an instruction
+enum Flow {
+    Next,
+    Jump(usize),
+    Return(Option<Value>)
+}
+
+/// A decoded method body
+struct MethodCode {
+    /// `owner.name:descriptor`, for errors
+    name: String,
+    ops: Vec<(usize, JOp)>,
+    /// Index into `ops` of the instruction starting at each pc
+    at: HashMap<usize, usize>,
+    len: usize,
+    max_locals: usize,
+    exceptions: Vec<ExceptionEntry>
+}
+
+/// Operand stack and locals of a running method
+struct Activation {
+    stack: Vec<Value>,
+    locals: Vec<Option<Value>>
+}
+impl Activation {
+
+    fn push(&mut self, v: Value) {
+        self.stack.push(v);
+    }
+
+    fn pop(&mut self) -> Result<Value, Trap> {
+        self.stack.pop().ok_or_else(mismatch)
+    }
+
+    fn pop_int(&mut self) -> Result<i32, Trap> {
+        match self.pop()? {
+            Value::Int(x) => Ok(x),
+            _ => Err(mismatch())
+        }
+    }
+
+    fn pop_long(&mut self) -> Result<i64, Trap> {
+        match self.pop()? {
+            Value::Long(x) => Ok(x),
+            _ => Err(mismatch())
+        }
+    }
+
+    fn pop_float(&mut self) -> Result<f32, Trap> {
+        match self.pop()? {
+            Value::Float(x) => Ok(x),
+            _ => Err(mismatch())
+        }
+    }
+
+    fn pop_double(&mut self) -> Result<f64, Trap> {
+        match self.pop()? {
+            Value::Double(x) => Ok(x),
+            _ => Err(mismatch())
+        }
+    }
+
+    /// A reference, `None` for null
+    fn pop_ref(&mut self) -> Result<Option<usize>, Trap> {
+        match self.pop()? {
+            Value::Null => Ok(None),
+            Value::Ref(x) => Ok(Some(x)),
+            _ => Err(mismatch())
+        }
+    }
+
+    /// A reference that must not be null
+    fn pop_object(&mut self) -> Result<usize, Trap> {
+        self.pop_ref()?.ok_or(Trap::Raise(NULL_POINTER))
+    }
+
+    /// Pop values covering exactly `words` slots, in stack order
+    fn pop_words(&mut self, words: usize) -> Result<Vec<Value>, Trap> {
+        let mut out = Vec::with_capacity(words);
+        let mut count = 0;
+        while count < words {
+            let v = self.pop()?;
+            count += v.width();
+            out.push(v);
+        }
+        if count != words {
+            return Err(mismatch());
+        }
+        out.reverse();
+        Ok(out)
+    }
+
+    /// Copy the top `take` slots below the `skip` slots under them,
+    /// which covers every `dup` form
+    fn dup(&mut self, take: usize, skip: usize) -> Result<(), Trap> {
+        let top = self.pop_words(take)?;
+        let below = self.pop_words(skip)?;
+        self.stack.extend_from_slice(&top);
+        self.stack.extend(below);
+        self.stack.extend(top);
+        Ok(())
+    }
+
+    fn load(&self, slot: u16) -> Result<Value, Trap> {
+        match self.locals.get(slot as usize) {
+            Option::Some(&Option::Some(v)) => Ok(v),
+            _ => Err(Trap::Fault(InterpFault::BadLocal(slot)))
+        }
+    }
+
+    fn store(&mut self, slot: u16, v: Value) -> Result<(), Trap> {
+        let x = slot as usize;
+        if x + v.width() > self.locals.len() {
+            return Err(Trap::Fault(InterpFault::BadLocal(slot)));
+        }
+        // overwriting half of a long or double invalidates it
+        if x > 0 && self.locals[x - 1].is_some_and(|v| v.width() == 2) {
+            self.locals[x - 1] = None;
+        }
+        self.locals[x] = Some(v);
+        if v.width() == 2 {
+            self.locals[x + 1] = None;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! binary {
+    ($f:expr, $pop:ident, $wrap:path, |$a:ident, $b:ident| $e:expr) => {{
+        let $b = $f.$pop()?;
+        let $a = $f.$pop()?;
+        $f.push($wrap($e));
+    }};
+}
+
+macro_rules! unary {
+    ($f:expr, $pop:ident, $wrap:path, |$a:ident| $e:expr) => {{
+        let $a = $f.$pop()?;
+        $f.push($wrap($e));
+    }};
+}
+
+/// `fcmpl` and friends, `nan` is the result for unordered operands
+fn compare(a: f64, b: f64, nan: i32) -> i32 {
+    if a > b {
+        1
+    } else if a < b {
+        -1
+    } else if a == b {
+        0
+    } else {
+        nan
+    }
+}
+
+fn branch(pc: usize, offset: i32) -> Result<usize, Trap> {
+    let x = pc as i64 + offset as i64;
+    if x < 0 {
+        return Err(Trap::Fault(InterpFault::BadBranch));
+    }
+    Ok(x as usize)
+}
+
+/// Truncate an int stored into an array of `component`
+fn narrow(component: &FieldType, v: Value) -> Value {
+    match (component, v) {
+        (&FieldType::Boolean, Value::Int(x)) => Value::Int(x & 1),
+        (&FieldType::Byte, Value::Int(x)) => Value::Int(x as i8 as i32),
+        (&FieldType::Char, Value::Int(x)) => Value::Int(x as u16 as i32),
+        (&FieldType::Short, Value::Int(x)) => Value::Int(x as i16 as i32),
+        _ => v
+    }
+}
+
+/// Element type of an array created by `anewarray` or `multianewarray`
+fn component_of(name: &str) -> Option<FieldType> {
+    if name.starts_with('[') {
+        FieldType::parse(name)
+    } else {
+        Some(FieldType::Object(name.to_string()))
+    }
+}
+
+fn utf16(s: &str) -> Vec<u16> {
+    s.encode_utf16().collect()
+}
+
+fn find_method(class: &Class, name: &str, descriptor: &str) -> Option<usize> {
+    let pool = class.get_constants();
+    (0..class.get_methods_count()).find(|&i| {
+        let m = class.get_method(i).unwrap();
+        pool.get_utf8(m.name_index).is_some_and(|x| x == name) &&
+            pool.get_utf8(m.descriptor_index).is_some_and(|x| x == descriptor)
+    })
+}
+
+/// Runs static methods of a set of classes
+///
+/// The heap and static fields persist across calls, so a class is
+/// initialized once and objects returned by one call can be passed to
+/// the next.
+pub struct Interpreter<'c, 'a: 'c> {
+    classes: HashMap<String, &'c Class<'a>>,
+    hierarchy: MapHierarchy,
+    heap: Vec<Object>,
+    /// String literals, so equal literals are the same object
+    strings: HashMap<Vec<u16>, usize>,
+    class_objects: HashMap<String, usize>,
+    /// Static fields by declaring class, name and descriptor
+    statics: HashMap<(String, String, String), Value>,
+    /// `false` while `<clinit>` is running
+    initialized: HashMap<String, bool>,
+    code: HashMap<(String, usize), Rc<MethodCode>>,
+    /// Where the exception being propagated was thrown
+    unwinding: Option<(String, usize)>,
+    steps: u64,
+    step_limit: u64,
+    /// Array elements and characters allocated so far
+    allocated: u64,
+    allocation_limit: u64,
+    depth: usize
+}
+
+impl<'c, 'a> Interpreter<'c, 'a> {
+
+    /// An interpreter over `classes`, which may call each other
+    pub fn new(classes: &[&'c Class<'a>]) -> Result<Interpreter<'c, 'a>, Fault> {
+        let mut hierarchy = MapHierarchy::new();
+        for &(name, super_class) in BUILTIN_CLASSES {
+            hierarchy.add_class(name, Some(super_class));
+        }
+        for name in BUILTIN_INTERFACES {
+            hierarchy.add_interface(name);
+        }
+        let mut map = HashMap::new();
+        for class in classes {
+            hierarchy.add(class)?;
+            map.insert(class.get_this_class()?.into_owned(), *class);
+        }
+        Ok(Interpreter {
+            classes: map,
+            hierarchy,
+            heap: Vec::new(),
+            strings: HashMap::new(),
+            class_objects: HashMap::new(),
+            statics: HashMap::new(),
+            initialized: HashMap::new(),
+            code: HashMap::new(),
+            unwinding: None,
+            steps: 0,
+            step_limit: DEFAULT_STEP_LIMIT,
+            allocated: 0,
+            allocation_limit: DEFAULT_ALLOCATION_LIMIT,
+            depth: 0
+        })
+    }
+
+    /// Stop with `InterpFault::StepLimit` once `limit` instructions have
+    /// run in total
+    pub fn set_step_limit(&mut self, limit: u64) {
+        self.step_limit = limit;
+    }
+
+    /// Instructions run so far
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Stop with `InterpFault::AllocationLimit` before more than `limit`
+    /// array elements and characters have been allocated in total
+    pub fn set_allocation_limit(&mut self, limit: u64) {
+        self.allocation_limit = limit;
+    }
+
+    /// Call a static method, returning `None` for `void` ones
+    pub fn invoke(&mut self, class: &str, name: &str, descriptor: &str, args: &[Value]) -> Result<Option<Value>, InterpError> {
+        self.unwinding = None;
+        let bad = args.iter().find_map(|v| match *v {
+            Value::Ref(x) if x >= self.heap.len() => Some(x),
+            _ => None
+        });
+        let result = match bad {
+            Option::Some(x) => Err(InterpFault::BadReference(x).into()),
+            Option::None => self.invoke_static(class, name, descriptor, args.to_vec())
+        };
+        self.located(result, &format!("{}.{}:{}", class, name, descriptor))
+    }
+
+    /// Run the static initializer of `class`, unless it already ran
+    pub fn initialize(&mut self, class: &str) -> Result<(), InterpError> {
+        self.unwinding = None;
+        let result = self.initialize_class(class);
+        self.located(result, &format!("{}.<clinit>:()V", class))
+    }
+
+    /// The value of a static field, initializing its class first
+    ///
+    /// Fields inherited from super classes and interfaces are found too.
+    pub fn get_static(&mut self, class: &str, name: &str, descriptor: &str) -> Result<Value, InterpError> {
+        self.unwinding = None;
+        let result = self.static_field(class, name, descriptor).and_then(|key| self.static_value(&key));
+        self.located(result, &format!("{}.<clinit>:()V", class))
+    }
+
+    /// Put an object in the heap, such as an argument for `invoke`
+    pub fn alloc(&mut self, object: Object) -> Value {
+        Value::Ref(self.allocate(object))
+    }
+
+    /// A new `String` object
+    pub fn new_string(&mut self, s: &str) -> Value {
+        self.alloc(Object::Str(utf16(s)))
+    }
+
+    /// The object a reference points to
+    pub fn object(&self, value: Value) -> Option<&Object> {
+        match value {
+            Value::Ref(x) => self.heap.get(x),
+            _ => None
+        }
+    }
+
+    /// The contents of a `String` or `StringBuilder`
+    ///
+    /// Unpaired surrogates are replaced with `U+FFFD`.
+    pub fn string(&self, value: Value) -> Option<String> {
+        match self.object(value) {
+            Option::Some(&Object::Str(ref s)) |
+            Option::Some(&Object::Builder(ref s)) => Some(String::from_utf16_lossy(s)),
+            _ => None
+        }
+    }
+
+    /// Turn a trap that escaped every frame into an error
+    fn located<T>(&mut self, result: Result<T, Trap>, method: &str) -> Result<T, InterpError> {
+        let (method, pc, reason) = match result {
+            Ok(x) => return Ok(x),
+            Err(Trap::Error(e)) => return Err(e),
+            Err(Trap::Fault(reason)) => (method.to_string(), 0, reason),
+            Err(Trap::Raise(class)) => (method.to_string(), 0, InterpFault::Uncaught(class.to_string())),
+            Err(Trap::Throw(x)) => {
+                let (m, pc) = self.unwinding.take().unwrap_or_else(|| (method.to_string(), 0));
+                (m, pc, InterpFault::Uncaught(self.heap[x].class_name().into_owned()))
+            }
+        };
+        Err(InterpError { method, pc, reason })
+    }
+
+    /// Count `n` array elements or characters against the allocation
+    /// limit, before they are allocated
+    fn reserve(&mut self, n: u64) -> Result<(), Trap> {
+        let total = self.allocated.saturating_add(n);
+        if total > self.allocation_limit {
+            return Err(InterpFault::AllocationLimit.into());
+        }
+        self.allocated = total;
+        Ok(())
+    }
+
+    fn allocate(&mut self, object: Object) -> usize {
+        self.heap.push(object);
+        self.heap.len() - 1
+    }
+
+    /// The shared `String` for a literal
+    fn intern(&mut self, units: Vec<u16>) -> usize {
+        if let Option::Some(&x) = self.strings.get(&units) {
+            return x;
+        }
+        let x = self.allocate(Object::Str(units.clone()));
+        self.strings.insert(units, x);
+        x
+    }
+
+    fn class_object(&mut self, name: &str) -> usize {
+        if let Option::Some(&x) = self.class_objects.get(name) {
+            return x;
+        }
+        let x = self.allocate(Object::Class(name.to_string()));
+        self.class_objects.insert(name.to_string(), x);
+        x
+    }
+
+    /// The value of a loadable constant
+    fn constant(&mut self, pool: &ConstantsPool, index: u16) -> Result<Value, Trap> {
+        let bad = || Trap::Fault(InterpFault::BadConstant(index));
+        Ok(match pool.get(index) {
+            Option::Some(&PoolMembers::Integer(x)) => Value::Int(x),
+            Option::Some(&PoolMembers::Float(x)) => Value::Float(x),
+            Option::Some(&PoolMembers::Long(x)) => Value::Long(x),
+            Option::Some(&PoolMembers::Double(x)) => Value::Double(x),
+            Option::Some(&PoolMembers::Str(utf)) => {
+                let units = pool.get_java_string(utf).ok_or_else(bad)?.to_utf16();
+                Value::Ref(self.intern(units))
+            },
+            Option::Some(&PoolMembers::ClassInfo(_)) => {
+                let name = pool.get_class_name(index).ok_or_else(bad)?;
+                Value::Ref(self.class_object(&name))
+            },
+            Option::Some(&PoolMembers::MethodType(_)) |
+            Option::Some(&PoolMembers::MethodHandle(..)) => return Err(InterpFault::Unsupported.into()),
+            _ => return Err(bad())
+        })
+    }
+
+    /// Set default and `ConstantValue` values of static fields, then run `<clinit>`
+    ///
+    /// The super class is initialized after this class's static fields
+    /// are prepared and before its `<clinit>` runs, so a super class
+    /// initializer reading them finds their default values. A class
+    /// whose initializer is running counts as initialized, as it does for
+    /// the initializing thread on a JVM.
+    fn initialize_class(&mut self, name: &str) -> Result<(), Trap> {
+        if self.initialized.contains_key(name) {
+            return Ok(());
+        }
+        let class = match self.classes.get(name) {
+            Option::Some(&c) => c,
+            Option::None => return Err(missing_class(name).into())
+        };
+        self.initialized.insert(name.to_string(), false);
+        let pool = class.get_constants();
+        for i in 0..class.get_fields_count() {
+            let field = class.get_field(i).unwrap();
+            if field.access_flags & (FieldAccessFlags::Static as u16) == 0 {
+                continue;
+            }
+            let field_name = pool.get_utf8(field.name_index)
+                .ok_or(InterpFault::BadConstant(field.name_index))?;
+            let descriptor = pool.get_utf8(field.descriptor_index)
+                .ok_or(InterpFault::BadConstant(field.descriptor_index))?;
+            let t = FieldType::parse(&descriptor).ok_or(InterpFault::BadConstant(field.descriptor_index))?;
+            let value = match find_attribute(&field.attributes, pool, "ConstantValue") {
+                Option::Some(attr) if attr.data.len() == 2 => {
+                    self.constant(pool, u16::from_be_bytes([attr.data[0], attr.data[1]]))?
+                },
+                _ => Value::default_for(&t)
+            };
+            self.statics.insert((name.to_string(), field_name.into_owned(), descriptor.into_owned()), value);
+        }
+        if class.get_super_class_index() != 0 {
+            let super_class = class.get_super_class().map_err(InterpFault::Class)?;
+            if self.classes.contains_key(super_class.as_ref()) {
+                self.initialize_class(&super_class)?;
+            }
+        }
+        if let Option::Some(index) = find_method(class, "<clinit>", "()V") {
+            self.run(class, name, index, Vec::new())?;
+        }
+        self.initialized.insert(name.to_string(), true);
+        Ok(())
+    }
+
+    /// The class declaring a static field, searching as field resolution does
+    fn field_owner(&self, class: &str, name: &str, descriptor: &str, depth: usize) -> Option<String> {
+        let c = self.classes.get(class)?;
+        if depth > MAX_DEPTH {
+            return None;
+        }
+        let pool = c.get_constants();
+        let declared = (0..c.get_fields_count()).any(|i| {
+            let f = c.get_field(i).unwrap();
+            f.access_flags & (FieldAccessFlags::Static as u16) != 0 &&
+                pool.get_utf8(f.name_index).is_some_and(|x| x == name) &&
+                pool.get_utf8(f.descriptor_index).is_some_and(|x| x == descriptor)
+        });
+        if declared {
+            return Some(class.to_string());
+        }
+        for iface in c.get_interfaces().ok()? {
+            if let Option::Some(x) = self.field_owner(&iface, name, descriptor, depth + 1) {
+                return Some(x);
+            }
+        }
+        if c.get_super_class_index() == 0 {
+            return None;
+        }
+        self.field_owner(&c.get_super_class().ok()?, name, descriptor, depth + 1)
+    }
+
+    /// Resolve and initialize a static field, returning its key in `statics`
+    fn static_field(&mut self, class: &str, name: &str, descriptor: &str) -> Result<(String, String, String), Trap> {
+        let owner = match self.field_owner(class, name, descriptor, 0) {
+            Option::Some(x) => x,
+            Option::None if self.classes.contains_key(class) => {
+                return Err(InterpFault::UnknownMember(format!("{}.{}:{}", class, name, descriptor)).into());
+            },
+            Option::None => return Err(missing_class(class).into())
+        };
+        self.initialize_class(&owner)?;
+        Ok((owner, name.to_string(), descriptor.to_string()))
+    }
+
+    /// The value of a resolved static field
+    fn static_value(&self, key: &(String, String, String)) -> Result<Value, Trap> {
+        self.statics.get(key).cloned()
+            .ok_or_else(|| InterpFault::UnknownMember(format!("{}.{}:{}", key.0, key.1, key.2)).into())
+    }
+
+    fn method_code(&mut self, class: &'c Class<'a>, owner: &str, index: usize) -> Result<Rc<MethodCode>, Trap> {
+        let key = (owner.to_string(), index);
+        if let Option::Some(x) = self.code.get(&key) {
+            return Ok(x.clone());
+        }
+        let pool = class.get_constants();
+        let method = class.get_method(index).ok_or(InterpFault::Class(Fault::MethodLookUpFailure))?;
+        let name = format!("{}.{}:{}", owner,
+            pool.get_utf8(method.name_index).unwrap_or_default(),
+            pool.get_utf8(method.descriptor_index).unwrap_or_default());
+        let code = match class.get_method_code(index).map_err(InterpFault::Class)? {
+            Option::Some(x) => x,
+            Option::None => return Err(InterpFault::Unsupported.into())
+        };
+        let mut ops = Vec::new();
+        let mut at = HashMap::new();
+        for x in Decoder::new(code.code) {
+            let (pc, op) = x.map_err(|e| Trap::Error(InterpError {
+                method: name.clone(),
+                pc: e.pc,
+                reason: InterpFault::Decode(e.reason)
+            }))?;
+            at.insert(pc, ops.len());
+            ops.push((pc, op));
+        }
+        let decoded = Rc::new(MethodCode {
+            name,
+            ops,
+            at,
+            len: code.code.len(),
+            max_locals: code.max_locals as usize,
+            exceptions: code.exception_table
+        });
+        self.code.insert(key, decoded.clone());
+        Ok(decoded)
+    }
+
+    /// `invokestatic`, on a given class or a built in one
+    fn invoke_static(&mut self, owner: &str, name: &str, descriptor: &str, args: Vec<Value>) -> Result<Option<Value>, Trap> {
+        let unknown = || InterpFault::UnknownMember(format!("{}.{}:{}", owner, name, descriptor));
+        let t = MethodType::parse(descriptor).ok_or_else(unknown)?;
+        if args.len() != t.params.len() || !args.iter().zip(t.params.iter()).all(|(v, p)| v.fits(p)) {
+            return Err(mismatch());
+        }
+        let mut current = Cow::Borrowed(owner);
+        for _ in 0..MAX_DEPTH {
+            let class = match self.classes.get(current.as_ref()) {
+                Option::Some(&c) => c,
+                Option::None => break
+            };
+            if let Option::Some(index) = find_method(class, name, descriptor) {
+                let flags = class.get_method(index).unwrap().access_flags;
+                let native = MethodAccessFlags::Native as u16 | MethodAccessFlags::Abstract as u16;
+                if flags & (MethodAccessFlags::Static as u16) == 0 || flags & native != 0 {
+                    return Err(InterpFault::Unsupported.into());
+                }
+                self.initialize_class(&current)?;
+                return self.run(class, &current, index, args);
+            }
+            if class.get_super_class_index() == 0 {
+                break;
+            }
+            current = Cow::Owned(class.get_super_class().map_err(InterpFault::Class)?.into_owned());
+        }
+        if self.classes.contains_key(owner) {
+            return Err(unknown().into());
+        }
+        match self.builtin_static(owner, name, descriptor, &args) {
+            Err(Trap::Fault(InterpFault::Unsupported)) => Err(missing_class(owner).into()),
+            x => x
+        }
+    }
+
+    fn run(&mut self, class: &'c Class<'a>, owner: &str, index: usize, args: Vec<Value>) -> Result<Option<Value>, Trap> {
+        if self.depth >= MAX_DEPTH {
+            return Err(InterpFault::TooDeep.into());
+        }
+        let code = self.method_code(class, owner, index)?;
+        self.depth += 1;
+        let result = self.execute(class, &code, args);
+        self.depth -= 1;
+        result
+    }
+
+    fn execute(&mut self, class: &'c Class<'a>, code: &MethodCode, args: Vec<Value>) -> Result<Option<Value>, Trap> {
+        let slots = args.iter().map(Value::width).sum::<usize>();
+        let mut f = Activation {
+            stack: Vec::new(),
+            locals: vec![None; code.max_locals.max(slots)]
+        };
+        let mut slot = 0;
+        for v in args {
+            f.locals[slot] = Some(v);
+            slot += v.width();
+        }
+        let located = |pc, reason| Trap::Error(InterpError { method: code.name.clone(), pc, reason });
+        let mut i = 0;
+        loop {
+            let (pc, op) = match code.ops.get(i) {
+                Option::Some(&(pc, ref op)) => (pc, op),
+                Option::None => return Err(located(code.len, InterpFault::FellOff))
+            };
+            self.steps += 1;
+            if self.steps > self.step_limit {
+                return Err(located(pc, InterpFault::StepLimit));
+            }
+            let next = code.ops.get(i + 1).map_or(code.len, |x| x.0);
+            let thrown = match self.step(class, op, pc, next, &mut f) {
+                Ok(Flow::Next) => {
+                    i += 1;
+                    continue;
+                },
+                Ok(Flow::Jump(target)) => {
+                    i = *code.at.get(&target).ok_or_else(|| located(pc, InterpFault::BadBranch))?;
+                    continue;
+                },
+                Ok(Flow::Return(v)) => return Ok(v),
+                Err(Trap::Fault(reason)) => return Err(located(pc, reason)),
+                Err(Trap::Error(e)) => return Err(Trap::Error(e)),
+                Err(Trap::Raise(name)) => self.allocate(Object::Throwable { class: name.to_string(), message: None }),
+                Err(Trap::Throw(x)) => x
+            };
+            match self.handler(class, code, pc, thrown) {
+                Option::Some(target) => {
+                    self.unwinding = None;
+                    f.stack.clear();
+                    f.push(Value::Ref(thrown));
+                    i = *code.at.get(&target).ok_or_else(|| located(pc, InterpFault::BadBranch))?;
+                },
+                Option::None => {
+                    if self.unwinding.is_none() {
+                        self.unwinding = Some((code.name.clone(), pc));
+                    }
+                    return Err(Trap::Throw(thrown));
+                }
+            }
+        }
+    }
+
+    /// The handler catching `thrown` at `pc`
+    fn handler(&self, class: &Class, code: &MethodCode, pc: usize, thrown: usize) -> Option<usize> {
+        let name = self.heap[thrown].class_name();
+        let pool = class.get_constants();
+        code.exceptions.iter()
+            .filter(|e| e.start_pc as usize <= pc && pc < e.end_pc as usize)
+            .find(|e| e.catch_type == 0 || pool.get_class_name(e.catch_type)
+                .is_some_and(|c| self.hierarchy.is_subclass(&name, &c)))
+            .map(|e| e.handler_pc as usize)
+    }
+
+    /// Pop the arguments of a call, checked against its descriptor
+    fn pop_args(f: &mut Activation, descriptor: &str, index: u16) -> Result<Vec<Value>, Trap> {
+        let t = MethodType::parse(descriptor).ok_or(InterpFault::BadConstant(index))?;
+        let mut args = Vec::with_capacity(t.params.len());
+        for p in t.params.iter().rev() {
+            let v = f.pop()?;
+            if !v.fits(p) {
+                return Err(mismatch());
+            }
+            args.push(v);
+        }
+        args.reverse();
+        Ok(args)
+    }
+
+    fn array_get(&self, array: usize, index: i32) -> Result<Value, Trap> {
+        match self.heap[array] {
+            Object::Array { ref values, .. } => {
+                if index < 0 {
+                    return Err(Trap::Raise(ARRAY_INDEX));
+                }
+                values.get(index as usize).cloned().ok_or(Trap::Raise(ARRAY_INDEX))
+            },
+            _ => Err(mismatch())
+        }
+    }
+
+    fn array_set(&mut self, array: usize, index: i32, v: Value) -> Result<(), Trap> {
+        match self.heap[array] {
+            Object::Array { ref component, ref mut values } => {
+                if index < 0 || index as usize >= values.len() {
+                    return Err(Trap::Raise(ARRAY_INDEX));
+                }
+                values[index as usize] = narrow(component, v);
+                Ok(())
+            },
+            _ => Err(mismatch())
+        }
+    }
+
+    /// A possibly nested array for `multianewarray`
+    fn new_multi(&mut self, t: &FieldType, counts: &[i32]) -> Result<usize, Trap> {
+        let component = match *t {
+            FieldType::Array(ref inner) => inner.as_ref().clone(),
+            _ => return Err(mismatch())
+        };
+        self.reserve(counts[0] as u64)?;
+        let mut values = Vec::with_capacity(counts[0] as usize);
+        for _ in 0..counts[0] {
+            values.push(if counts.len() > 1 {
+                Value::Ref(self.new_multi(&component, &counts[1..])?)
+            } else {
+                Value::default_for(&component)
+            });
+        }
+        Ok(self.allocate(Object::Array { component, values }))
+    }
+
+    /// The characters of a `String` or `StringBuilder`
+    fn text(&self, v: Value) -> Result<Vec<u16>, Trap> {
+        match v {
+            Value::Null => Err(Trap::Raise(NULL_POINTER)),
+            Value::Ref(x) => match self.heap[x] {
+                Object::Str(ref s) | Object::Builder(ref s) => Ok(s.clone()),
+                _ => Err(mismatch())
+            },
+            _ => Err(mismatch())
+        }
+    }
+
+    /// `String.valueOf` of a reference, `toString` is only known for text
+    fn string_of(&self, v: Value) -> Result<Vec<u16>, Trap> {
+        match v {
+            Value::Null => Ok(utf16("null")),
+            Value::Ref(x) => match self.heap[x] {
+                Object::Str(ref s) | Object::Builder(ref s) => Ok(s.clone()),
+                _ => Err(InterpFault::Unsupported.into())
+            },
+            _ => Err(mismatch())
+        }
+    }
+
+    /// Characters `start..start + count` of a `char[]`, the whole array for `None`
+    fn chars(&self, v: Value, range: Option<(i32, i32)>) -> Result<Vec<u16>, Trap> {
+        let values = match v {
+            Value::Null => return Err(Trap::Raise(NULL_POINTER)),
+            Value::Ref(x) => match self.heap[x] {
+                Object::Array { component: FieldType::Char, ref values } => values,
+                _ => return Err(mismatch())
+            },
+            _ => return Err(mismatch())
+        };
+        let (start, count) = range.unwrap_or((0, values.len() as i32));
+        if start < 0 || count < 0 || start as usize + count as usize > values.len() {
+            return Err(Trap::Raise(STRING_INDEX));
+        }
+        Ok(values[start as usize..(start + count) as usize].iter()
+            .map(|v| match *v {
+                Value::Int(c) => c as u16,
+                _ => 0
+            })
+            .collect())
+    }
+
+    /// A new `String` value
+    fn string_value(&mut self, units: Vec<u16>) -> Result<Option<Value>, Trap> {
+        self.reserve(units.len() as u64)?;
+        Ok(Some(Value::Ref(self.allocate(Object::Str(units)))))
+    }
+
+    /// Static methods of JDK classes with a built in implementation
+    fn builtin_static(&mut self, owner: &str, name: &str, descriptor: &str, args: &[Value]) -> Result<Option<Value>, Trap> {
+        let int = |i: usize| match args.get(i) {
+            Option::Some(&Value::Int(x)) => x,
+            _ => 0
+        };
+        let long = |i: usize| match args.get(i) {
+            Option::Some(&Value::Long(x)) => x,
+            _ => 0
+        };
+        let result = match (owner, name, descriptor) {
+            ("java/lang/Math", "abs", "(I)I") => Value::Int(int(0).wrapping_abs()),
+            ("java/lang/Math", "abs", "(J)J") => Value::Long(long(0).wrapping_abs()),
+            ("java/lang/Math", "min", "(II)I") => Value::Int(int(0).min(int(1))),
+            ("java/lang/Math", "max", "(II)I") => Value::Int(int(0).max(int(1))),
+            ("java/lang/Math", "min", "(JJ)J") => Value::Long(long(0).min(long(1))),
+            ("java/lang/Math", "max", "(JJ)J") => Value::Long(long(0).max(long(1))),
+            ("java/lang/Integer", "rotateLeft", "(II)I") => Value::Int(int(0).rotate_left(int(1) as u32)),
+            ("java/lang/Integer", "rotateRight", "(II)I") => Value::Int(int(0).rotate_right(int(1) as u32)),
+            ("java/lang/Long", "rotateLeft", "(JI)J") => Value::Long(long(0).rotate_left(int(1) as u32)),
+            ("java/lang/Long", "rotateRight", "(JI)J") => Value::Long(long(0).rotate_right(int(1) as u32)),
+            ("java/lang/Integer", "toString", "(I)Ljava/lang/String;") |
+            ("java/lang/String", "valueOf", "(I)Ljava/lang/String;") => {
+                return self.string_value(utf16(&int(0).to_string()));
+            },
+            ("java/lang/Long", "toString", "(J)Ljava/lang/String;") |
+            ("java/lang/String", "valueOf", "(J)Ljava/lang/String;") => {
+                return self.string_value(utf16(&long(0).to_string()));
+            },
+            ("java/lang/Integer", "toHexString", "(I)Ljava/lang/String;") => {
+                return self.string_value(utf16(&format!("{:x}", int(0))));
+            },
+            ("java/lang/String", "valueOf", "(C)Ljava/lang/String;") => {
+                return self.string_value(vec![int(0) as u16]);
+            },
+            ("java/lang/String", "valueOf", "(Z)Ljava/lang/String;") => {
+                return self.string_value(utf16(if int(0) != 0 { "true" } else { "false" }));
+            },
+            ("java/lang/String", "valueOf", "([C)Ljava/lang/String;") => {
+                let units = self.chars(args[0], None)?;
+                return self.string_value(units);
+            },
+            ("java/lang/String", "valueOf", "(Ljava/lang/Object;)Ljava/lang/String;") => {
+                let units = self.string_of(args[0])?;
+                return self.string_value(units);
+            },
+            ("java/lang/System", "arraycopy", "(Ljava/lang/Object;ILjava/lang/Object;II)V") => {
+                self.array_copy(args)?;
+                return Ok(None);
+            },
+            _ => return Err(InterpFault::Unsupported.into())
+        };
+        Ok(Some(result))
+    }
+
+    /// `System.arraycopy`
+    fn array_copy(&mut self, args: &[Value]) -> Result<(), Trap> {
+        let (src, dest) = match (args[0], args[2]) {
+            (Value::Ref(a), Value::Ref(b)) => (a, b),
+            _ => return Err(Trap::Raise(NULL_POINTER))
+        };
+        let (from, to, count) = match (args[1], args[3], args[4]) {
+            (Value::Int(a), Value::Int(b), Value::Int(c)) => (a, b, c),
+            _ => return Err(mismatch())
+        };
+        let chunk = match (&self.heap[src], &self.heap[dest]) {
+            (Object::Array { component: a, values: x }, Object::Array { component: b, values: y }) => {
+                let primitive = |t: &FieldType| !matches!(*t, FieldType::Object(_) | FieldType::Array(_));
+                if (primitive(a) || primitive(b)) && a != b {
+                    return Err(Trap::Raise(ARRAY_STORE));
+                }
+                if from < 0 || to < 0 || count < 0 ||
+                    from as usize + count as usize > x.len() || to as usize + count as usize > y.len() {
+                    return Err(Trap::Raise(ARRAY_INDEX));
+                }
+                x[from as usize..(from + count) as usize].to_vec()
+            },
+            _ => return Err(Trap::Raise(ARRAY_STORE))
+        };
+        if let Object::Array { ref mut values, .. } = self.heap[dest] {
+            values[to as usize..(to + count) as usize].clone_from_slice(&chunk);
+        }
+        Ok(())
+    }
+
+    /// `invokespecial` of a constructor on a built in class
+    fn construct(&mut self, receiver: usize, descriptor: &str, args: &[Value]) -> Result<(), Trap> {
+        let object = match (&self.heap[receiver], descriptor) {
+            (Object::Str(_), "()V") => Object::Str(Vec::new()),
+            (Object::Str(_), "([C)V") => Object::Str(self.chars(args[0], None)?),
+            (Object::Str(_), "([CII)V") => match (args[1], args[2]) {
+                (Value::Int(start), Value::Int(count)) => Object::Str(self.chars(args[0], Some((start, count)))?),
+                _ => return Err(mismatch())
+            },
+            (Object::Str(_), "(Ljava/lang/String;)V") => Object::Str(self.text(args[0])?),
+            (Object::Builder(_), "()V") |
+            (Object::Builder(_), "(I)V") => Object::Builder(Vec::new()),
+            (Object::Builder(_), "(Ljava/lang/String;)V") |
+            (Object::Builder(_), "(Ljava/lang/CharSequence;)V") => Object::Builder(self.text(args[0])?),
+            (Object::Throwable { class, .. }, "()V") => Object::Throwable { class: class.clone(), message: None },
+            (Object::Throwable { class, .. }, "(Ljava/lang/String;)V") |
+            (Object::Throwable { class, .. }, "(Ljava/lang/String;Ljava/lang/Throwable;)V") => {
+                let message = match args[0] {
+                    Value::Null => None,
+                    v => Some(self.text(v)?)
+                };
+                Object::Throwable { class: class.clone(), message }
+            },
+            _ => return Err(InterpFault::Unsupported.into())
+        };
+        match object {
+            Object::Str(ref s) | Object::Builder(ref s) => self.reserve(s.len() as u64)?,
+            _ => ()
+        }
+        self.heap[receiver] = object;
+        Ok(())
+    }
+
+    /// `StringBuilder.append` and `setCharAt`
+    fn build(&mut self, receiver: usize, name: &str, descriptor: &str, args: &[Value]) -> Result<Option<Value>, Trap> {
+        let int = || match args.first() {
+            Option::Some(&Value::Int(x)) => x,
+            _ => 0
+        };
+        let units = match (name, descriptor) {
+            ("append", "(Ljava/lang/String;)Ljava/lang/StringBuilder;") |
+            ("append", "(Ljava/lang/CharSequence;)Ljava/lang/StringBuilder;") |
+            ("append", "(Ljava/lang/Object;)Ljava/lang/StringBuilder;") => self.string_of(args[0])?,
+            ("append", "(C)Ljava/lang/StringBuilder;") => vec![int() as u16],
+            ("append", "(I)Ljava/lang/StringBuilder;") => utf16(&int().to_string()),
+            ("append", "(Z)Ljava/lang/StringBuilder;") => utf16(if int() != 0 { "true" } else { "false" }),
+            ("append", "(J)Ljava/lang/StringBuilder;") => match args[0] {
+                Value::Long(x) => utf16(&x.to_string()),
+                _ => return Err(mismatch())
+            },
+            ("append", "([C)Ljava/lang/StringBuilder;") => self.chars(args[0], None)?,
+            ("setCharAt", "(IC)V") => {
+                let c = match args[1] {
+                    Value::Int(c) => c as u16,
+                    _ => return Err(mismatch())
+                };
+                if let Object::Builder(ref mut s) = self.heap[receiver] {
+                    let i = int();
+                    if i < 0 || i as usize >= s.len() {
+                        return Err(Trap::Raise(STRING_INDEX));
+                    }
+                    s[i as usize] = c;
+                }
+                return Ok(None);
+            },
+            _ => return Err(InterpFault::Unsupported.into())
+        };
+        self.reserve(units.len() as u64)?;
+        if let Object::Builder(ref mut s) = self.heap[receiver] {
+            s.extend(units);
+        }
+        Ok(Some(Value::Ref(receiver)))
+    }
+
+    /// Instance methods of built in objects
+    fn invoke_builtin(&mut self, receiver: usize, name: &str, descriptor: &str, args: &[Value]) -> Result<Option<Value>, Trap> {
+        if name == "<init>" {
+            return self.construct(receiver, descriptor, args).map(|_| None);
+        }
+        if let Object::Builder(_) = self.heap[receiver] {
+            if name == "append" || name == "setCharAt" {
+                return self.build(receiver, name, descriptor, args);
+            }
+        }
+        let int = |i: usize| match args.get(i) {
+            Option::Some(&Value::Int(x)) => x,
+            _ => 0
+        };
+        let range = |s: &[u16], start: i32, end: i32| {
+            if start < 0 || end < start || end as usize > s.len() {
+                Err(Trap::Raise(STRING_INDEX))
+            } else {
+                Ok(s[start as usize..end as usize].to_vec())
+            }
+        };
+        let (value, created) = match (&self.heap[receiver], name, descriptor) {
+            (Object::Str(s), "length", "()I") |
+            (Object::Builder(s), "length", "()I") => (Value::Int(s.len() as i32), None),
+            (Object::Str(s), "charAt", "(I)C") |
+            (Object::Builder(s), "charAt", "(I)C") => {
+                let c = range(s, int(0), int(0).saturating_add(1))?;
+                (Value::Int(c[0] as i32), None)
+            },
+            (Object::Str(s), "isEmpty", "()Z") => (Value::Int(s.is_empty() as i32), None),
+            (Object::Str(s), "hashCode", "()I") => {
+                (Value::Int(s.iter().fold(0i32, |h, &c| h.wrapping_mul(31).wrapping_add(c as i32))), None)
+            },
+            (Object::Str(s), "equals", "(Ljava/lang/Object;)Z") => {
+                let same = match args[0] {
+                    Value::Ref(x) => matches!(self.heap[x], Object::Str(ref t) if t == s),
+                    _ => false
+                };
+                (Value::Int(same as i32), None)
+            },
+            (Object::Str(s), "indexOf", "(I)I") => {
+                let c = int(0) as u32;
+                let needle = match ::std::char::from_u32(c) {
+                    Option::Some(ch) if c > 0xFFFF => utf16(&ch.to_string()),
+                    // not a code point, which no string holds
+                    Option::None if c > 0xFFFF => return Ok(Some(Value::Int(-1))),
+                    _ => vec![c as u16]
+                };
+                let at = s.windows(needle.len()).position(|w| w == needle.as_slice());
+                (Value::Int(at.map_or(-1, |x| x as i32)), None)
+            },
+            (Object::Str(_), "toString", "()Ljava/lang/String;") => (Value::Ref(receiver), None),
+            (Object::Str(s), "intern", "()Ljava/lang/String;") => {
+                let units = s.clone();
+                return Ok(Some(Value::Ref(self.intern(units))));
+            },
+            (Object::Str(s), "toCharArray", "()[C") => {
+                let values = s.iter().map(|&c| Value::Int(c as i32)).collect();
+                (Value::Null, Some(Object::Array { component: FieldType::Char, values }))
+            },
+            (Object::Str(s), "substring", "(I)Ljava/lang/String;") => {
+                (Value::Null, Some(Object::Str(range(s, int(0), s.len() as i32)?)))
+            },
+            (Object::Str(s), "substring", "(II)Ljava/lang/String;") => {
+                (Value::Null, Some(Object::Str(range(s, int(0), int(1))?)))
+            },
+            (Object::Str(s), "concat", "(Ljava/lang/String;)Ljava/lang/String;") => {
+                let mut units = s.clone();
+                units.extend(self.text(args[0])?);
+                (Value::Null, Some(Object::Str(units)))
+            },
+            (Object::Builder(s), "toString", "()Ljava/lang/String;") => (Value::Null, Some(Object::Str(s.clone()))),
+            (Object::Array { component, values }, "clone", "()Ljava/lang/Object;") => {
+                (Value::Null, Some(Object::Array { component: component.clone(), values: values.clone() }))
+            },
+            (Object::Throwable { message, .. }, "getMessage", "()Ljava/lang/String;") => {
+                (Value::Null, message.clone().map(Object::Str))
+            },
+            _ => return Err(InterpFault::Unsupported.into())
+        };
+        Ok(Some(match created {
+            Option::Some(object) => Value::Ref(self.allocate(object)),
+            Option::None => value
+        }))
+    }
+
+    /// Run one instruction
+    fn step(&mut self, class: &'c Class<'a>, op: &JOp, pc: usize, next: usize, f: &mut Activation) -> Result<Flow, Trap> {
+        let pool = class.get_constants();
+        if let Option::Some((access, slot)) = op.local_access() {
+            match access {
+                LocalAccess::Load(t) => {
+                    let v = f.load(slot)?;
+                    if !v.fits_local(t) {
+                        return Err(mismatch());
+                    }
+                    f.push(v);
+                },
+                LocalAccess::Store(t) => {
+                    let v = f.pop()?;
+                    let address = t == LocalType::Reference && matches!(v, Value::ReturnAddress(_));
+                    if !v.fits_local(t) && !address {
+                        return Err(mismatch());
+                    }
+                    f.store(slot, v)?;
+                },
+                LocalAccess::Increment(c) => match f.load(slot)? {
+                    Value::Int(x) => f.store(slot, Value::Int(x.wrapping_add(c as i32)))?,
+                    _ => return Err(mismatch())
+                },
+                LocalAccess::Ret => match f.load(slot)? {
+                    Value::ReturnAddress(x) => return Ok(Flow::Jump(x)),
+                    _ => return Err(mismatch())
+                }
+            };
+            return Ok(Flow::Next);
+        }
+        if let Option::Some((jump, offset)) = Jump::from_op(op) {
+            let taken = match jump {
+                Jump::IFEQ => f.pop_int()? == 0,
+                Jump::IFNE => f.pop_int()? != 0,
+                Jump::IFLT => f.pop_int()? < 0,
+                Jump::IFGE => f.pop_int()? >= 0,
+                Jump::IFGT => f.pop_int()? > 0,
+                Jump::IFLE => f.pop_int()? <= 0,
+                Jump::IFIcmpEQ | Jump::IFIcmpNE | Jump::IFIcmpLT |
+                Jump::IFIcmpGE | Jump::IFIcmpGT | Jump::IFIcmpLE => {
+                    let b = f.pop_int()?;
+                    let a = f.pop_int()?;
+                    match jump {
+                        Jump::IFIcmpEQ => a == b,
+                        Jump::IFIcmpNE => a != b,
+                        Jump::IFIcmpLT => a < b,
+                        Jump::IFIcmpGE => a >= b,
+                        Jump::IFIcmpGT => a > b,
+                        _ => a <= b
+                    }
+                },
+                Jump::IFAcmpEQ | Jump::IFAcmpNE => {
+                    let b = f.pop_ref()?;
+                    let a = f.pop_ref()?;
+                    (a == b) == (jump == Jump::IFAcmpEQ)
+                },
+                Jump::IFNull => f.pop_ref()?.is_none(),
+                Jump::IFnonNull => f.pop_ref()?.is_some(),
+                Jump::Goto => true,
+                Jump::JSR => {
+                    f.push(Value::ReturnAddress(next));
+                    true
+                }
+            };
+            return Ok(if taken { Flow::Jump(branch(pc, offset)?) } else { Flow::Next });
+        }
+        let member = |x: u16| pool.get_member_ref(x).ok_or(Trap::Fault(InterpFault::BadConstant(x)));
+        let class_name = |x: u16| pool.get_class_name(x).ok_or(Trap::Fault(InterpFault::BadConstant(x)));
+        match *op {
+            JOp::Nop => {},
+            JOp::AConstNull => f.push(Value::Null),
+            JOp::IConstM1 => f.push(Value::Int(-1)),
+            JOp::IConst0 => f.push(Value::Int(0)),
+            JOp::IConst1 => f.push(Value::Int(1)),
+            JOp::IConst2 => f.push(Value::Int(2)),
+            JOp::IConst3 => f.push(Value::Int(3)),
+            JOp::IConst4 => f.push(Value::Int(4)),
+            JOp::IConst5 => f.push(Value::Int(5)),
+            JOp::LConst0 => f.push(Value::Long(0)),
+            JOp::LConst1 => f.push(Value::Long(1)),
+            JOp::FConst0 => f.push(Value::Float(0.0)),
+            JOp::FConst1 => f.push(Value::Float(1.0)),
+            JOp::FConst2 => f.push(Value::Float(2.0)),
+            JOp::DConst0 => f.push(Value::Double(0.0)),
+            JOp::DConst1 => f.push(Value::Double(1.0)),
+            JOp::BiPush(x) => f.push(Value::Int(x as i8 as i32)),
+            JOp::SIPush(x) => f.push(Value::Int(x as i16 as i32)),
+            JOp::LDC(x) => {
+                let v = self.constant(pool, x as u16)?;
+                f.push(v);
+            },
+            JOp::LDCW(x) | JOp::LDC2W(x) => {
+                let v = self.constant(pool, x)?;
+                f.push(v);
+            },
+
+            JOp::IALoad | JOp::LALoad | JOp::FALoad | JOp::DALoad |
+            JOp::AALoad | JOp::BaLoad | JOp::CaLoad | JOp::SALoad => {
+                let i = f.pop_int()?;
+                let a = f.pop_object()?;
+                f.push(self.array_get(a, i)?);
+            },
+            JOp::IAStore | JOp::LAStore | JOp::FAStore | JOp::DAStore |
+            JOp::AAStore | JOp::BaStore | JOp::CaStore | JOp::SAStore => {
+                let v = f.pop()?;
+                let i = f.pop_int()?;
+                let a = f.pop_object()?;
+                self.array_set(a, i, v)?;
+            },
+
+            JOp::Pop => { f.pop_words(1)?; },
+            JOp::Pop2 => { f.pop_words(2)?; },
+            JOp::Dup => f.dup(1, 0)?,
+            JOp::Dupx1 => f.dup(1, 1)?,
+            JOp::Dupx2 => f.dup(1, 2)?,
+            JOp::Dup2 => f.dup(2, 0)?,
+            JOp::Dup2x1 => f.dup(2, 1)?,
+            JOp::Dup2x2 => f.dup(2, 2)?,
+            JOp::Swap => {
+                let b = f.pop_words(1)?;
+                let a = f.pop_words(1)?;
+                f.stack.extend(b);
+                f.stack.extend(a);
+            },
+
+            JOp::IAdd => binary!(f, pop_int, Value::Int, |a, b| a.wrapping_add(b)),
+            JOp::LAdd => binary!(f, pop_long, Value::Long, |a, b| a.wrapping_add(b)),
+            JOp::FAdd => binary!(f, pop_float, Value::Float, |a, b| a + b),
+            JOp::DAdd => binary!(f, pop_double, Value::Double, |a, b| a + b),
+            JOp::ISub => binary!(f, pop_int, Value::Int, |a, b| a.wrapping_sub(b)),
+            JOp::LSub => binary!(f, pop_long, Value::Long, |a, b| a.wrapping_sub(b)),
+            JOp::FSub => binary!(f, pop_float, Value::Float, |a, b| a - b),
+            JOp::DSub => binary!(f, pop_double, Value::Double, |a, b| a - b),
+            JOp::IMul => binary!(f, pop_int, Value::Int, |a, b| a.wrapping_mul(b)),
+            JOp::LMul => binary!(f, pop_long, Value::Long, |a, b| a.wrapping_mul(b)),
+            JOp::FMul => binary!(f, pop_float, Value::Float, |a, b| a * b),
+            JOp::DMul => binary!(f, pop_double, Value::Double, |a, b| a * b),
+            JOp::FDiv => binary!(f, pop_float, Value::Float, |a, b| a / b),
+            JOp::DDiv => binary!(f, pop_double, Value::Double, |a, b| a / b),
+            JOp::FRem => binary!(f, pop_float, Value::Float, |a, b| a % b),
+            JOp::DRem => binary!(f, pop_double, Value::Double, |a, b| a % b),
+            JOp::IDiv | JOp::IRem => {
+                let b = f.pop_int()?;
+                let a = f.pop_int()?;
+                if b == 0 {
+                    return Err(Trap::Raise(ARITHMETIC));
+                }
+                f.push(Value::Int(if *op == JOp::IDiv { a.wrapping_div(b) } else { a.wrapping_rem(b) }));
+            },
+            JOp::LDiv | JOp::LRem => {
+                let b = f.pop_long()?;
+                let a = f.pop_long()?;
+                if b == 0 {
+                    return Err(Trap::Raise(ARITHMETIC));
+                }
+                f.push(Value::Long(if *op == JOp::LDiv { a.wrapping_div(b) } else { a.wrapping_rem(b) }));
+            },
+            JOp::INeg => unary!(f, pop_int, Value::Int, |a| a.wrapping_neg()),
+            JOp::LNeg => unary!(f, pop_long, Value::Long, |a| a.wrapping_neg()),
+            JOp::FNeg => unary!(f, pop_float, Value::Float, |a| -a),
+            JOp::DNeg => unary!(f, pop_double, Value::Double, |a| -a),
+            JOp::ISHL => binary!(f, pop_int, Value::Int, |a, b| a.wrapping_shl(b as u32)),
+            JOp::ISHR => binary!(f, pop_int, Value::Int, |a, b| a.wrapping_shr(b as u32)),
+            JOp::IUSHR => binary!(f, pop_int, Value::Int, |a, b| (a as u32).wrapping_shr(b as u32) as i32),
+            JOp::LSHL | JOp::LSHR | JOp::LUSHR => {
+                let s = f.pop_int()? as u32;
+                let a = f.pop_long()?;
+                f.push(Value::Long(match *op {
+                    JOp::LSHL => a.wrapping_shl(s),
+                    JOp::LSHR => a.wrapping_shr(s),
+                    _ => (a as u64).wrapping_shr(s) as i64
+                }));
+            },
+            JOp::IAnd => binary!(f, pop_int, Value::Int, |a, b| a & b),
+            JOp::LAnd => binary!(f, pop_long, Value::Long, |a, b| a & b),
+            JOp::IOr => binary!(f, pop_int, Value::Int, |a, b| a | b),
+            JOp::LOr => binary!(f, pop_long, Value::Long, |a, b| a | b),
+            JOp::IXor => binary!(f, pop_int, Value::Int, |a, b| a ^ b),
+            JOp::LXor => binary!(f, pop_long, Value::Long, |a, b| a ^ b),
+
+            // float to int casts saturate and map NaN to zero, as in Java
+            JOp::I2L => unary!(f, pop_int, Value::Long, |a| a as i64),
+            JOp::I2F => unary!(f, pop_int, Value::Float, |a| a as f32),
+            JOp::I2D => unary!(f, pop_int, Value::Double, |a| a as f64),
+            JOp::L2I => unary!(f, pop_long, Value::Int, |a| a as i32),
+            JOp::L2F => unary!(f, pop_long, Value::Float, |a| a as f32),
+            JOp::L2D => unary!(f, pop_long, Value::Double, |a| a as f64),
+            JOp::F2I => unary!(f, pop_float, Value::Int, |a| a as i32),
+            JOp::F2L => unary!(f, pop_float, Value::Long, |a| a as i64),
+            JOp::F2D => unary!(f, pop_float, Value::Double, |a| a as f64),
+            JOp::D2I => unary!(f, pop_double, Value::Int, |a| a as i32),
+            JOp::D2L => unary!(f, pop_double, Value::Long, |a| a as i64),
+            JOp::D2F => unary!(f, pop_double, Value::Float, |a| a as f32),
+            JOp::I2B => unary!(f, pop_int, Value::Int, |a| a as i8 as i32),
+            JOp::I2C => unary!(f, pop_int, Value::Int, |a| a as u16 as i32),
+            JOp::I2S => unary!(f, pop_int, Value::Int, |a| a as i16 as i32),
+
+            JOp::Lcmp => binary!(f, pop_long, Value::Int, |a, b| a.cmp(&b) as i32),
+            JOp::FcmpL => binary!(f, pop_float, Value::Int, |a, b| compare(a as f64, b as f64, -1)),
+            JOp::FcmpG => binary!(f, pop_float, Value::Int, |a, b| compare(a as f64, b as f64, 1)),
+            JOp::DcmpL => binary!(f, pop_double, Value::Int, |a, b| compare(a, b, -1)),
+            JOp::DcmpG => binary!(f, pop_double, Value::Int, |a, b| compare(a, b, 1)),
+
+            JOp::TableSwitch(default, low, high, ref offsets) => {
+                let x = f.pop_int()?;
+                let offset = if x < low || x > high {
+                    default
+                } else {
+                    *offsets.get((x as i64 - low as i64) as usize).ok_or(InterpFault::BadBranch)?
+                };
+                return Ok(Flow::Jump(branch(pc, offset)?));
+            },
+            JOp::LookUpSwitch(default, ref pairs) => {
+                let x = f.pop_int()?;
+                let offset = pairs.iter().find(|p| p.0 == x).map_or(default, |p| p.1);
+                return Ok(Flow::Jump(branch(pc, offset)?));
+            },
+
+            JOp::IReturn => return Ok(Flow::Return(Some(Value::Int(f.pop_int()?)))),
+            JOp::LReturn => return Ok(Flow::Return(Some(Value::Long(f.pop_long()?)))),
+            JOp::FReturn => return Ok(Flow::Return(Some(Value::Float(f.pop_float()?)))),
+            JOp::DReturn => return Ok(Flow::Return(Some(Value::Double(f.pop_double()?)))),
+            JOp::AReturn => {
+                let v = f.pop_ref()?.map_or(Value::Null, Value::Ref);
+                return Ok(Flow::Return(Some(v)));
+            },
+            JOp::Return => return Ok(Flow::Return(None)),
+
+            JOp::GetStatic(x) => {
+                let m = member(x)?;
+                let key = self.static_field(&m.class, &m.name, &m.descriptor)?;
+                f.push(self.static_value(&key)?);
+            },
+            JOp::PutStatic(x) => {
+                let m = member(x)?;
+                let t = FieldType::parse(&m.descriptor).ok_or(InterpFault::BadConstant(x))?;
+                let v = f.pop()?;
+                if !v.fits(&t) {
+                    return Err(mismatch());
+                }
+                let key = self.static_field(&m.class, &m.name, &m.descriptor)?;
+                self.statics.insert(key, v);
+            },
+            JOp::InvokeStatic(x) => {
+                let m = member(x)?;
+                let args = Interpreter::pop_args(f, &m.descriptor, x)?;
+                if let Option::Some(v) = self.invoke_static(&m.class, &m.name, &m.descriptor, args)? {
+                    f.push(v);
+                }
+            },
+            JOp::InvokeVirtual(x) | JOp::InvokeSpecial(x) | JOp::InvokedInterface(x, _) => {
+                let m = member(x)?;
+                let args = Interpreter::pop_args(f, &m.descriptor, x)?;
+                let receiver = f.pop_object()?;
+                if let Option::Some(v) = self.invoke_builtin(receiver, &m.name, &m.descriptor, &args)? {
+                    f.push(v);
+                }
+            },
+            JOp::New(x) => {
+                let name = class_name(x)?;
+                let object = match name.as_ref() {
+                    "java/lang/String" => Object::Str(Vec::new()),
+                    "java/lang/StringBuilder" => Object::Builder(Vec::new()),
+                    x if BUILTIN_CLASSES.iter().any(|c| c.0 == x) && self.hierarchy.is_subclass(x, THROWABLE) => {
+                        Object::Throwable { class: x.to_string(), message: None }
+                    },
+                    x if self.classes.contains_key(x) => return Err(InterpFault::Unsupported.into()),
+                    x => return Err(missing_class(x).into())
+                };
+                f.push(Value::Ref(self.allocate(object)));
+            },
+            JOp::NewArray(t) => {
+                let component = match t {
+                    4 => FieldType::Boolean,
+                    5 => FieldType::Char,
+                    6 => FieldType::Float,
+                    7 => FieldType::Double,
+                    8 => FieldType::Byte,
+                    9 => FieldType::Short,
+                    10 => FieldType::Int,
+                    11 => FieldType::Long,
+                    _ => return Err(mismatch())
+                };
+                let n = f.pop_int()?;
+                if n < 0 {
+                    return Err(Trap::Raise(NEGATIVE_SIZE));
+                }
+                self.reserve(n as u64)?;
+                let values = vec![Value::default_for(&component); n as usize];
+                f.push(Value::Ref(self.allocate(Object::Array { component, values })));
+            },
+            JOp::ANewArray(x) => {
+                let component = component_of(&class_name(x)?).ok_or(InterpFault::BadConstant(x))?;
+                let n = f.pop_int()?;
+                if n < 0 {
+                    return Err(Trap::Raise(NEGATIVE_SIZE));
+                }
+                self.reserve(n as u64)?;
+                let values = vec![Value::Null; n as usize];
+                f.push(Value::Ref(self.allocate(Object::Array { component, values })));
+            },
+            JOp::MultiAneWArray(x, dims) => {
+                let t = FieldType::parse(&class_name(x)?).ok_or(InterpFault::BadConstant(x))?;
+                let mut counts = Vec::with_capacity(dims as usize);
+                for _ in 0..dims {
+                    counts.push(f.pop_int()?);
+                }
+                counts.reverse();
+                if counts.is_empty() {
+                    return Err(mismatch());
+                }
+                if counts.iter().any(|&n| n < 0) {
+                    return Err(Trap::Raise(NEGATIVE_SIZE));
+                }
+                let array = self.new_multi(&t, &counts)?;
+                f.push(Value::Ref(array));
+            },
+            JOp::ArrayLength => {
+                let a = f.pop_object()?;
+                match self.heap[a] {
+                    Object::Array { ref values, .. } => f.push(Value::Int(values.len() as i32)),
+                    _ => return Err(mismatch())
+                };
+            },
+            JOp::AThrow => {
+                let x = f.pop_object()?;
+                return match self.heap[x] {
+                    Object::Throwable { .. } => Err(Trap::Throw(x)),
+                    _ => Err(mismatch())
+                };
+            },
+            JOp::CheckCast(x) | JOp::InstanceOf(x) => {
+                let target = class_name(x)?;
+                let v = f.pop_ref()?;
+                let fits = v.is_some_and(|r| is_ref_assignable(&self.hierarchy, &self.heap[r].class_name(), &target));
+                if let JOp::InstanceOf(_) = *op {
+                    f.push(Value::Int(fits as i32));
+                } else if v.is_none() || fits {
+                    f.push(v.map_or(Value::Null, Value::Ref));
+                } else {
+                    return Err(Trap::Raise(CLASS_CAST));
+                }
+            },
+            // there is only one thread
+            JOp::MonitorEnter | JOp::MonitorExit => { f.pop_object()?; },
+
+            JOp::GetField(_) | JOp::PutField(_) | JOp::InvokedDynamic(_) |
+            JOp::BreakPoint | JOp::ImpDep1 | JOp::ImpDep2 => return Err(InterpFault::Unsupported.into()),
+            _ => return Err(mismatch())
+        };
+        Ok(Flow::Next)
+    }
+}
+
+#[test]
+fn test_interpreter() {
+    use super::asm::assemble_class;
+    let source = "\
+.class public Calc
+.field static TABLE [I
+.field static final SALT I = 7
+.field static a I
+.field static a Ljava/lang/String;
+.method static \"<clinit>\"()V
+    bipush 9
+    putstatic a:I
+    ldc \"nine\"
+    putstatic a:Ljava/lang/String;
+    iconst_4
+    newarray int
+    putstatic TABLE:[I
+    iconst_0
+    istore_0
+Fill:
+    iload_0
+    iconst_4
+    if_icmpge Done
+    getstatic TABLE:[I
+    iload_0
+    iload_0
+    iload_0
+    imul
+    getstatic SALT:I
+    iadd
+    iastore
+    iinc 0 1
+    goto Fill
+Done:
+    return
+.end method
+.method public static mix(Ljava/lang/String;)I
+    iconst_0
+    istore_1
+    iconst_0
+    istore_2
+Loop:
+    iload_2
+    getstatic TABLE:[I
+    arraylength
+    if_icmpge End
+    iload_1
+    getstatic TABLE:[I
+    iload_2
+    iaload
+    aload_0
+    iload_2
+    aload_0
+    invokevirtual java/lang/String.length:()I
+    irem
+    invokevirtual java/lang/String.charAt:(I)C
+    ixor
+    iadd
+    istore_1
+    iinc 2 1
+    goto Loop
+End:
+    iload_1
+    ireturn
+.end method
+.method public static safeDiv(II)I
+Try:
+    iload_0
+    iload_1
+    idiv
+    ireturn
+TryEnd:
+    pop
+    iconst_m1
+    ireturn
+    .catch java/lang/ArithmeticException from Try to TryEnd using TryEnd
+.end method
+.method public static greet(Ljava/lang/String;)Ljava/lang/String;
+    new java/lang/StringBuilder
+    dup
+    ldc \"hi \"
+    invokespecial java/lang/StringBuilder.\"<init>\":(Ljava/lang/String;)V
+    aload_0
+    invokevirtual java/lang/StringBuilder.append:(Ljava/lang/String;)Ljava/lang/StringBuilder;
+    bipush 33
+    invokevirtual java/lang/StringBuilder.append:(C)Ljava/lang/StringBuilder;
+    invokevirtual java/lang/StringBuilder.toString:()Ljava/lang/String;
+    areturn
+.end method
+.method public static spin()V
+Top:
+    goto Top
+.end method
+.method public static find(Ljava/lang/String;I)I
+    aload_0
+    iload_1
+    invokevirtual java/lang/String.indexOf:(I)I
+    ireturn
+.end method
+.method public static huge()[I
+    ldc 2147483647
+    newarray int
+    areturn
+.end method
+.method public static print()V
+    getstatic java/lang/System.out:Ljava/io/PrintStream;
+    pop
+    return
+.end method
+";
+    let bytes = assemble_class(source).unwrap();
+    let class = Class::parse(&bytes).unwrap();
+    let mut interp = Interpreter::new(&[&class]).unwrap();
+
+    // TABLE is {7, 8, 11, 16}, xored with "abab"
+    let s = interp.new_string("ab");
+    assert_eq!(interp.invoke("Calc", "mix", "(Ljava/lang/String;)I", &[s]).unwrap(), Some(Value::Int(428)));
+    let table = interp.get_static("Calc", "TABLE", "[I").unwrap();
+    match interp.object(table) {
+        Option::Some(Object::Array { values, .. }) => assert_eq!(values[3], Value::Int(16)),
+        x => panic!("{:?}", x)
+    };
+    let div = |interp: &mut Interpreter, a, b| interp.invoke("Calc", "safeDiv", "(II)I", &[Value::Int(a), Value::Int(b)]).unwrap();
+    assert_eq!(div(&mut interp, 7, 2), Some(Value::Int(3)));
+    assert_eq!(div(&mut interp, 7, 0), Some(Value::Int(-1)));
+    assert_eq!(div(&mut interp, i32::MIN, -1), Some(Value::Int(i32::MIN)));
+
+    let s = interp.new_string("bob");
+    let greeting = interp.invoke("Calc", "greet", "(Ljava/lang/String;)Ljava/lang/String;", &[s]).unwrap().unwrap();
+    assert_eq!(interp.string(greeting).unwrap(), "hi bob!");
+
+    let err = interp.invoke("Calc", "mix", "(Ljava/lang/String;)I", &[Value::Null]).unwrap_err();
+    assert_eq!((err.method.as_str(), err.pc), ("Calc.mix:(Ljava/lang/String;)I", 21));
+    assert!(matches!(err.reason, InterpFault::Uncaught(ref x) if x == NULL_POINTER));
+    let err = interp.invoke("Calc", "print", "()V", &[]).unwrap_err();
+    assert!(matches!(err.reason, InterpFault::Unsupported));
+    let err = interp.invoke("Calc", "mix", "(I)I", &[Value::Int(1)]).unwrap_err();
+    assert!(matches!(err.reason, InterpFault::UnknownMember(_)));
+    interp.set_step_limit(interp.steps() + 1000);
+    let err = interp.invoke("Calc", "spin", "()V", &[]).unwrap_err();
+    assert!(matches!(err.reason, InterpFault::StepLimit));
+    interp.set_step_limit(DEFAULT_STEP_LIMIT);
+
+    // fields sharing a name are told apart by their descriptor
+    assert_eq!(interp.get_static("Calc", "a", "I").unwrap(), Value::Int(9));
+    let a = interp.get_static("Calc", "a", "Ljava/lang/String;").unwrap();
+    assert_eq!(interp.string(a).unwrap(), "nine");
+    let err = interp.invoke("Calc", "huge", "()[I", &[]).unwrap_err();
+    assert!(matches!(err.reason, InterpFault::AllocationLimit));
+
+    // only code points that are not valid are never found
+    let s = interp.new_string("xA\u{10041}");
+    let found = [0x41, 0x10041, 0x110041, -65471].iter()
+        .map(|&c| interp.invoke("Calc", "find", "(Ljava/lang/String;I)I", &[s, Value::Int(c)]).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(found, [Some(Value::Int(1)), Some(Value::Int(2)), Some(Value::Int(-1)), Some(Value::Int(-1))]);
+    let err = interp.invoke("Calc", "find", "(Ljava/lang/String;I)I", &[Value::Ref(1 << 20), Value::Int(0)]).unwrap_err();
+    assert!(matches!(err.reason, InterpFault::BadReference(x) if x == 1 << 20));
+
+    // Base's initializer reads Derived.X while Derived is initializing,
+    // so X must already hold its default value
+    let base = "\
+.class public Base
+.field static SEEN I
+.method static \"<clinit>\"()V
+    getstatic Derived.X:I
+    putstatic SEEN:I
+    return
+.end method
+";
+    let derived = "\
+.class public Derived
+.super Base
+.field static X I
+.method static \"<clinit>\"()V
+    iconst_5
+    putstatic X:I
+    return
+.end method
+";
+    let (base, derived) = (assemble_class(base).unwrap(), assemble_class(derived).unwrap());
+    let (base, derived) = (Class::parse(&base).unwrap(), Class::parse(&derived).unwrap());
+    let mut interp = Interpreter::new(&[&base, &derived]).unwrap();
+    assert_eq!(interp.get_static("Derived", "X", "I").unwrap(), Value::Int(5));
+    assert_eq!(interp.get_static("Base", "SEEN", "I").unwrap(), Value::Int(0));
+}
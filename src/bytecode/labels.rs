@@ -0,0 +1,342 @@
+//! Branch targets resolved to labels.
+//!
+//! Branch operands in a code array are signed offsets relative to the
+//! branching instruction. `JOp::branch_targets` resolves them to absolute
+//! pcs, and `label_code` rewrites a decoded method into `Instruction`s
+//! where every destination is a `Label` placed in the instruction stream.
+
+use super::ops::JOp;
+
+use std::collections::BTreeSet;
+use std::fmt;
+
+/// A branch destination
+///
+/// Labels produced by `label_code` are numbered after the absolute pc
+/// they mark.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,PartialOrd,Ord,Hash)]
+pub struct Label(pub u32);
+
+/// The kinds of single target branch
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Jump {
+    IFEQ,
+    IFNE,
+    IFLT,
+    IFGE,
+    IFGT,
+    IFLE,
+    IFIcmpEQ,
+    IFIcmpNE,
+    IFIcmpLT,
+    IFIcmpGE,
+    IFIcmpGT,
+    IFIcmpLE,
+    IFAcmpEQ,
+    IFAcmpNE,
+    IFNull,
+    IFnonNull,
+    /// `goto` or `goto_w`
+    Goto,
+    /// `jsr` or `jsr_w`
+    JSR
+}
+impl Jump {
+
+    /// Is this a conditional branch
+    pub fn is_conditional(&self) -> bool {
+        !matches!(*self, Jump::Goto | Jump::JSR)
+    }
+
+    /// The branch taken when this one is not
+    ///
+    /// Returns `None` for `Goto` and `JSR`
+    pub fn invert(&self) -> Option<Jump> {
+        Some(match *self {
+            Jump::IFEQ => Jump::IFNE,
+            Jump::IFNE => Jump::IFEQ,
+            Jump::IFLT => Jump::IFGE,
+            Jump::IFGE => Jump::IFLT,
+            Jump::IFGT => Jump::IFLE,
+            Jump::IFLE => Jump::IFGT,
+            Jump::IFIcmpEQ => Jump::IFIcmpNE,
+            Jump::IFIcmpNE => Jump::IFIcmpEQ,
+            Jump::IFIcmpLT => Jump::IFIcmpGE,
+            Jump::IFIcmpGE => Jump::IFIcmpLT,
+            Jump::IFIcmpGT => Jump::IFIcmpLE,
+            Jump::IFIcmpLE => Jump::IFIcmpGT,
+            Jump::IFAcmpEQ => Jump::IFAcmpNE,
+            Jump::IFAcmpNE => Jump::IFAcmpEQ,
+            Jump::IFNull => Jump::IFnonNull,
+            Jump::IFnonNull => Jump::IFNull,
+            Jump::Goto |
+            Jump::JSR => return None
+        })
+    }
+
+    /// Split a branching `JOp` into its kind and relative offset
+    pub fn from_op(op: &JOp) -> Option<(Jump, i32)> {
+        let x = match *op {
+            JOp::IFEQ(x) => (Jump::IFEQ, x as i32),
+            JOp::IFNE(x) => (Jump::IFNE, x as i32),
+            JOp::IFLT(x) => (Jump::IFLT, x as i32),
+            JOp::IFGE(x) => (Jump::IFGE, x as i32),
+            JOp::IFGT(x) => (Jump::IFGT, x as i32),
+            JOp::IFLE(x) => (Jump::IFLE, x as i32),
+            JOp::IFIcmpEQ(x) => (Jump::IFIcmpEQ, x as i32),
+            JOp::IFIcmpNE(x) => (Jump::IFIcmpNE, x as i32),
+            JOp::IFIcmpLT(x) => (Jump::IFIcmpLT, x as i32),
+            JOp::IfIcmpGE(x) => (Jump::IFIcmpGE, x as i32),
+            JOp::IFIcmpGT(x) => (Jump::IFIcmpGT, x as i32),
+            JOp::IFIcmpLE(x) => (Jump::IFIcmpLE, x as i32),
+            JOp::IFAcmpEQ(x) => (Jump::IFAcmpEQ, x as i32),
+            JOp::IFAcmpNE(x) => (Jump::IFAcmpNE, x as i32),
+            JOp::IFNull(x) => (Jump::IFNull, x as i32),
+            JOp::IFnonNull(x) => (Jump::IFnonNull, x as i32),
+            JOp::Goto(x) => (Jump::Goto, x as i32),
+            JOp::GotoW(x) => (Jump::Goto, x),
+            JOp::JSR(x) => (Jump::JSR, x as i32),
+            JOp::JSRW(x) => (Jump::JSR, x),
+            _ => return None
+        };
+        Some(x)
+    }
+
+    /// Build the short (16bit offset) form of this branch
+    pub fn to_op(&self, offset: i16) -> JOp {
+        match *self {
+            Jump::IFEQ => JOp::IFEQ(offset),
+            Jump::IFNE => JOp::IFNE(offset),
+            Jump::IFLT => JOp::IFLT(offset),
+            Jump::IFGE => JOp::IFGE(offset),
+            Jump::IFGT => JOp::IFGT(offset),
+            Jump::IFLE => JOp::IFLE(offset),
+            Jump::IFIcmpEQ => JOp::IFIcmpEQ(offset),
+            Jump::IFIcmpNE => JOp::IFIcmpNE(offset),
+            Jump::IFIcmpLT => JOp::IFIcmpLT(offset),
+            Jump::IFIcmpGE => JOp::IfIcmpGE(offset),
+            Jump::IFIcmpGT => JOp::IFIcmpGT(offset),
+            Jump::IFIcmpLE => JOp::IFIcmpLE(offset),
+            Jump::IFAcmpEQ => JOp::IFAcmpEQ(offset),
+            Jump::IFAcmpNE => JOp::IFAcmpNE(offset),
+            Jump::IFNull => JOp::IFNull(offset),
+            Jump::IFnonNull => JOp::IFnonNull(offset),
+            Jump::Goto => JOp::Goto(offset),
+            Jump::JSR => JOp::JSR(offset)
+        }
+    }
+}
+
+/// An instruction whose destinations are labels rather than offsets
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Instruction {
+    /// Marks the position of a label, emits no code
+    Label(Label),
+    /// Any instruction that does not branch
+    Op(JOp),
+    Jump(Jump, Label),
+    TableSwitch {
+        default: Label,
+        low: i32,
+        /// Destinations for `low`, `low + 1`, ...
+        targets: Vec<Label>
+    },
+    LookUpSwitch {
+        default: Label,
+        pairs: Vec<(i32, Label)>
+    }
+}
+
+/// Why a branch could not be given a label
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum LabelFault {
+    /// The destination lies outside the code array
+    OutOfRange,
+    /// The destination is in the middle of an instruction
+    NotInstructionStart
+}
+
+/// A branch whose destination is invalid
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct LabelError {
+    /// pc of the branching instruction
+    pub pc: usize,
+    /// The destination it asked for
+    pub target: i64,
+    pub reason: LabelFault
+}
+impl fmt::Display for LabelError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let why = match self.reason {
+            LabelFault::OutOfRange => "outside the code array",
+            LabelFault::NotInstructionStart => "not the start of an instruction"
+        };
+        write!(f, "branch at pc {} targets {}, which is {}", self.pc, self.target, why)
+    }
+}
+
+#[inline(always)]
+fn absolute(pc: usize, offset: i32) -> i64 {
+    pc as i64 + offset as i64
+}
+
+impl JOp {
+
+    /// Does this instruction transfer control somewhere other than
+    /// the next instruction (returns and throws excluded)
+    pub fn is_branch(&self) -> bool {
+        match *self {
+            JOp::TableSwitch(..) |
+            JOp::LookUpSwitch(..) => true,
+            ref op => Jump::from_op(op).is_some()
+        }
+    }
+
+    /// Does control never continue to the next instruction
+    pub fn ends_flow(&self) -> bool {
+        matches!(*self,
+            JOp::Goto(_) | JOp::GotoW(_) | JOp::TableSwitch(..) | JOp::LookUpSwitch(..) |
+            JOp::IReturn | JOp::LReturn | JOp::FReturn | JOp::DReturn | JOp::AReturn |
+            JOp::Return | JOp::AThrow | JOp::Ret(_) | JOp::RetWide(_))
+    }
+
+    /// Absolute destinations of this instruction when it sits at `pc`
+    ///
+    /// Switches list their default first. Destinations are not checked
+    /// against any code array, so may be negative.
+    pub fn branch_targets(&self, pc: usize) -> Vec<i64> {
+        match *self {
+            JOp::TableSwitch(default, _, _, ref offsets) => {
+                let mut v = Vec::with_capacity(offsets.len() + 1);
+                v.push(absolute(pc, default));
+                v.extend(offsets.iter().map(|o| absolute(pc, *o)));
+                v
+            },
+            JOp::LookUpSwitch(default, ref pairs) => {
+                let mut v = Vec::with_capacity(pairs.len() + 1);
+                v.push(absolute(pc, default));
+                v.extend(pairs.iter().map(|&(_, o)| absolute(pc, o)));
+                v
+            },
+            ref op => match Jump::from_op(op) {
+                Option::Some((_, offset)) => vec![absolute(pc, offset)],
+                Option::None => Vec::new()
+            }
+        }
+    }
+}
+
+/// Replace the branch offsets of decoded code with labels
+///
+/// `ops` is a whole method as produced by `Decoder`, in order. A
+/// `Instruction::Label` is placed before every instruction something
+/// branches to, named after its pc. `code_len` is the length of the
+/// code array.
+pub fn label_code(ops: &[(usize, JOp)], code_len: usize) -> Result<Vec<Instruction>, LabelError> {
+    label_code_marking(ops, code_len, &[])
+}
+
+/// As `label_code`, but also places a label at each pc of `marks`
+///
+/// Use this for positions that aren't branch targets but must survive
+/// re-assembly, like exception table and line number entries. A mark
+/// equal to `code_len` places a label after the last instruction.
+/// Marks that are not instruction starts are ignored.
+pub fn label_code_marking(ops: &[(usize, JOp)], code_len: usize, marks: &[usize]) -> Result<Vec<Instruction>, LabelError> {
+    let starts: BTreeSet<usize> = ops.iter().map(|&(pc, _)| pc).collect();
+    let mut targets: BTreeSet<Label> = marks.iter().map(|pc| Label(*pc as u32)).collect();
+
+    let resolve = |pc: usize, target: i64| -> Result<Label, LabelError> {
+        if target < 0 || target >= code_len as i64 {
+            return Err(LabelError { pc, target, reason: LabelFault::OutOfRange });
+        }
+        if !starts.contains(&(target as usize)) {
+            return Err(LabelError { pc, target, reason: LabelFault::NotInstructionStart });
+        }
+        Ok(Label(target as u32))
+    };
+
+    let mut body = Vec::with_capacity(ops.len());
+    for &(pc, ref op) in ops {
+        let insn = match *op {
+            JOp::TableSwitch(default, low, _, ref offsets) => {
+                let mut labels = Vec::with_capacity(offsets.len());
+                for offset in offsets {
+                    labels.push(resolve(pc, absolute(pc, *offset))?);
+                }
+                Instruction::TableSwitch {
+                    default: resolve(pc, absolute(pc, default))?,
+                    low,
+                    targets: labels
+                }
+            },
+            JOp::LookUpSwitch(default, ref offsets) => {
+                let mut pairs = Vec::with_capacity(offsets.len());
+                for &(key, offset) in offsets {
+                    pairs.push((key, resolve(pc, absolute(pc, offset))?));
+                }
+                Instruction::LookUpSwitch {
+                    default: resolve(pc, absolute(pc, default))?,
+                    pairs
+                }
+            },
+            ref op => match Jump::from_op(op) {
+                Option::Some((jump, offset)) => Instruction::Jump(jump, resolve(pc, absolute(pc, offset))?),
+                Option::None => Instruction::Op(op.clone())
+            }
+        };
+        match insn {
+            Instruction::Jump(_, l) => {
+                targets.insert(l);
+            },
+            Instruction::TableSwitch { default, targets: ref t, .. } => {
+                targets.insert(default);
+                targets.extend(t.iter().cloned());
+            },
+            Instruction::LookUpSwitch { default, ref pairs, .. } => {
+                targets.insert(default);
+                targets.extend(pairs.iter().map(|&(_, l)| l));
+            },
+            _ => { }
+        };
+        body.push((pc, insn));
+    }
+
+    let mut ret = Vec::with_capacity(body.len() + targets.len());
+    for (pc, insn) in body {
+        let label = Label(pc as u32);
+        if targets.contains(&label) {
+            ret.push(Instruction::Label(label));
+        }
+        ret.push(insn);
+    }
+    let end = Label(code_len as u32);
+    if targets.contains(&end) {
+        ret.push(Instruction::Label(end));
+    }
+    Ok(ret)
+}
+
+#[test]
+fn test_label_code() {
+    use super::Decoder;
+
+    // 0: iload_0; 1: ifle +9; 4: iinc 0,-1; 7: goto -7; 10: return
+    let code: &[u8] = &[0x1A, 0x9E, 0x00, 0x09, 0x84, 0x00, 0xFF, 0xA7, 0xFF, 0xF9, 0xB1];
+    let ops: Vec<(usize, JOp)> = Decoder::new(code).map(Result::unwrap).collect();
+    assert_eq!(ops[3].1.branch_targets(ops[3].0), vec![0]);
+    assert_eq!(label_code(&ops, code.len()).unwrap(), vec![
+        Instruction::Label(Label(0)),
+        Instruction::Op(JOp::ILoad0),
+        Instruction::Jump(Jump::IFLE, Label(10)),
+        Instruction::Op(JOp::IInc(0, -1)),
+        Instruction::Jump(Jump::Goto, Label(0)),
+        Instruction::Label(Label(10)),
+        Instruction::Op(JOp::Return)
+    ]);
+
+    let bad = [(0, JOp::Goto(2)), (3, JOp::Return)];
+    assert_eq!(label_code(&bad, 4).unwrap_err().reason, LabelFault::NotInstructionStart);
+    let bad = [(0, JOp::Goto(-1))];
+    assert_eq!(label_code(&bad, 3).unwrap_err().reason, LabelFault::OutOfRange);
+}
@@ -0,0 +1,249 @@
+//! Uniform view of local variable instructions.
+//!
+//! The JVM has up to six encodings of each local variable access
+//! (`iload_0` .. `iload_3`, `iload`, `wide iload`). `JOp::local_access`
+//! folds them together, `local_op` picks the shortest encoding back.
+
+use super::super::clazz::FieldType;
+use super::ops::JOp;
+
+/// What lives in a local variable slot
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum LocalType {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference
+}
+impl LocalType {
+
+    /// Number of slots a value occupies, longs and doubles take two
+    pub fn width(&self) -> u16 {
+        match *self {
+            LocalType::Long | LocalType::Double => 2,
+            _ => 1
+        }
+    }
+
+    /// The type a value of field type `t` has on the stack, the ones
+    /// narrower than `int` widening to it
+    pub fn computational(t: &FieldType) -> LocalType {
+        match *t {
+            FieldType::Long => LocalType::Long,
+            FieldType::Float => LocalType::Float,
+            FieldType::Double => LocalType::Double,
+            FieldType::Object(_) |
+            FieldType::Array(_) => LocalType::Reference,
+            _ => LocalType::Int
+        }
+    }
+}
+
+/// How an instruction touches a local variable
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum LocalAccess {
+    Load(LocalType),
+    Store(LocalType),
+    /// `iinc`, reads and writes an int
+    Increment(i16),
+    /// `ret`, reads a return address
+    Ret
+}
+impl LocalAccess {
+
+    /// Does the instruction read the slot
+    pub fn reads(&self) -> bool {
+        !matches!(*self, LocalAccess::Store(_))
+    }
+
+    /// Does the instruction write the slot
+    pub fn writes(&self) -> bool {
+        matches!(*self, LocalAccess::Store(_) | LocalAccess::Increment(_))
+    }
+
+    /// Number of slots touched
+    pub fn width(&self) -> u16 {
+        match *self {
+            LocalAccess::Load(t) |
+            LocalAccess::Store(t) => t.width(),
+            LocalAccess::Increment(_) |
+            LocalAccess::Ret => 1
+        }
+    }
+}
+
+impl JOp {
+
+    /// The local variable slot this instruction accesses, and how
+    pub fn local_access(&self) -> Option<(LocalAccess, u16)> {
+        let x = match *self {
+            JOp::ILoad(x) => (LocalAccess::Load(LocalType::Int), x as u16),
+            JOp::ILoadWide(x) => (LocalAccess::Load(LocalType::Int), x),
+            JOp::ILoad0 => (LocalAccess::Load(LocalType::Int), 0),
+            JOp::ILoad1 => (LocalAccess::Load(LocalType::Int), 1),
+            JOp::ILoad2 => (LocalAccess::Load(LocalType::Int), 2),
+            JOp::ILoad3 => (LocalAccess::Load(LocalType::Int), 3),
+            JOp::IStore(x) => (LocalAccess::Store(LocalType::Int), x as u16),
+            JOp::IStoreWide(x) => (LocalAccess::Store(LocalType::Int), x),
+            JOp::IStore0 => (LocalAccess::Store(LocalType::Int), 0),
+            JOp::IStore1 => (LocalAccess::Store(LocalType::Int), 1),
+            JOp::IStore2 => (LocalAccess::Store(LocalType::Int), 2),
+            JOp::IStore3 => (LocalAccess::Store(LocalType::Int), 3),
+            JOp::LLoad(x) => (LocalAccess::Load(LocalType::Long), x as u16),
+            JOp::LLoadWide(x) => (LocalAccess::Load(LocalType::Long), x),
+            JOp::LLoad0 => (LocalAccess::Load(LocalType::Long), 0),
+            JOp::LLoad1 => (LocalAccess::Load(LocalType::Long), 1),
+            JOp::LLoad2 => (LocalAccess::Load(LocalType::Long), 2),
+            JOp::LLoad3 => (LocalAccess::Load(LocalType::Long), 3),
+            JOp::LStore(x) => (LocalAccess::Store(LocalType::Long), x as u16),
+            JOp::LStoreWide(x) => (LocalAccess::Store(LocalType::Long), x),
+            JOp::LStore0 => (LocalAccess::Store(LocalType::Long), 0),
+            JOp::LStore1 => (LocalAccess::Store(LocalType::Long), 1),
+            JOp::LStore2 => (LocalAccess::Store(LocalType::Long), 2),
+            JOp::LStore3 => (LocalAccess::Store(LocalType::Long), 3),
+            JOp::FLoad(x) => (LocalAccess::Load(LocalType::Float), x as u16),
+            JOp::FLoadWide(x) => (LocalAccess::Load(LocalType::Float), x),
+            JOp::FLoad0 => (LocalAccess::Load(LocalType::Float), 0),
+            JOp::FLoad1 => (LocalAccess::Load(LocalType::Float), 1),
+            JOp::FLoad2 => (LocalAccess::Load(LocalType::Float), 2),
+            JOp::FLoad3 => (LocalAccess::Load(LocalType::Float), 3),
+            JOp::FStore(x) => (LocalAccess::Store(LocalType::Float), x as u16),
+            JOp::FStoreWide(x) => (LocalAccess::Store(LocalType::Float), x),
+            JOp::FStore0 => (LocalAccess::Store(LocalType::Float), 0),
+            JOp::FStore1 => (LocalAccess::Store(LocalType::Float), 1),
+            JOp::FStore2 => (LocalAccess::Store(LocalType::Float), 2),
+            JOp::FStore3 => (LocalAccess::Store(LocalType::Float), 3),
+            JOp::DLoad(x) => (LocalAccess::Load(LocalType::Double), x as u16),
+            JOp::DLoadWide(x) => (LocalAccess::Load(LocalType::Double), x),
+            JOp::DLoad0 => (LocalAccess::Load(LocalType::Double), 0),
+            JOp::DLoad1 => (LocalAccess::Load(LocalType::Double), 1),
+            JOp::DLoad2 => (LocalAccess::Load(LocalType::Double), 2),
+            JOp::DLoad3 => (LocalAccess::Load(LocalType::Double), 3),
+            JOp::DStore(x) => (LocalAccess::Store(LocalType::Double), x as u16),
+            JOp::DStoreWide(x) => (LocalAccess::Store(LocalType::Double), x),
+            JOp::DStore0 => (LocalAccess::Store(LocalType::Double), 0),
+            JOp::DStore1 => (LocalAccess::Store(LocalType::Double), 1),
+            JOp::DStore2 => (LocalAccess::Store(LocalType::Double), 2),
+            JOp::DStore3 => (LocalAccess::Store(LocalType::Double), 3),
+            JOp::ALoad(x) => (LocalAccess::Load(LocalType::Reference), x as u16),
+            JOp::ALoadWide(x) => (LocalAccess::Load(LocalType::Reference), x),
+            JOp::ALoad0 => (LocalAccess::Load(LocalType::Reference), 0),
+            JOp::ALoad1 => (LocalAccess::Load(LocalType::Reference), 1),
+            JOp::ALoad2 => (LocalAccess::Load(LocalType::Reference), 2),
+            JOp::ALoad3 => (LocalAccess::Load(LocalType::Reference), 3),
+            JOp::AStore(x) => (LocalAccess::Store(LocalType::Reference), x as u16),
+            JOp::AStoreWide(x) => (LocalAccess::Store(LocalType::Reference), x),
+            JOp::AStore0 => (LocalAccess::Store(LocalType::Reference), 0),
+            JOp::AStore1 => (LocalAccess::Store(LocalType::Reference), 1),
+            JOp::AStore2 => (LocalAccess::Store(LocalType::Reference), 2),
+            JOp::AStore3 => (LocalAccess::Store(LocalType::Reference), 3),
+            JOp::IInc(x, c) => (LocalAccess::Increment(c as i16), x as u16),
+            JOp::IIncWide(x, c) => (LocalAccess::Increment(c), x),
+            JOp::Ret(x) => (LocalAccess::Ret, x as u16),
+            JOp::RetWide(x) => (LocalAccess::Ret, x),
+            _ => return None
+        };
+        Some(x)
+    }
+}
+
+/// Build the shortest encoding of a local variable access
+pub fn local_op(access: LocalAccess, slot: u16) -> JOp {
+    match access {
+        LocalAccess::Load(LocalType::Int) => match slot {
+            0 => JOp::ILoad0,
+            1 => JOp::ILoad1,
+            2 => JOp::ILoad2,
+            3 => JOp::ILoad3,
+            4..=255 => JOp::ILoad(slot as u8),
+            _ => JOp::ILoadWide(slot)
+        },
+        LocalAccess::Load(LocalType::Long) => match slot {
+            0 => JOp::LLoad0,
+            1 => JOp::LLoad1,
+            2 => JOp::LLoad2,
+            3 => JOp::LLoad3,
+            4..=255 => JOp::LLoad(slot as u8),
+            _ => JOp::LLoadWide(slot)
+        },
+        LocalAccess::Load(LocalType::Float) => match slot {
+            0 => JOp::FLoad0,
+            1 => JOp::FLoad1,
+            2 => JOp::FLoad2,
+            3 => JOp::FLoad3,
+            4..=255 => JOp::FLoad(slot as u8),
+            _ => JOp::FLoadWide(slot)
+        },
+        LocalAccess::Load(LocalType::Double) => match slot {
+            0 => JOp::DLoad0,
+            1 => JOp::DLoad1,
+            2 => JOp::DLoad2,
+            3 => JOp::DLoad3,
+            4..=255 => JOp::DLoad(slot as u8),
+            _ => JOp::DLoadWide(slot)
+        },
+        LocalAccess::Load(LocalType::Reference) => match slot {
+            0 => JOp::ALoad0,
+            1 => JOp::ALoad1,
+            2 => JOp::ALoad2,
+            3 => JOp::ALoad3,
+            4..=255 => JOp::ALoad(slot as u8),
+            _ => JOp::ALoadWide(slot)
+        },
+        LocalAccess::Store(LocalType::Int) => match slot {
+            0 => JOp::IStore0,
+            1 => JOp::IStore1,
+            2 => JOp::IStore2,
+            3 => JOp::IStore3,
+            4..=255 => JOp::IStore(slot as u8),
+            _ => JOp::IStoreWide(slot)
+        },
+        LocalAccess::Store(LocalType::Long) => match slot {
+            0 => JOp::LStore0,
+            1 => JOp::LStore1,
+            2 => JOp::LStore2,
+            3 => JOp::LStore3,
+            4..=255 => JOp::LStore(slot as u8),
+            _ => JOp::LStoreWide(slot)
+        },
+        LocalAccess::Store(LocalType::Float) => match slot {
+            0 => JOp::FStore0,
+            1 => JOp::FStore1,
+            2 => JOp::FStore2,
+            3 => JOp::FStore3,
+            4..=255 => JOp::FStore(slot as u8),
+            _ => JOp::FStoreWide(slot)
+        },
+        LocalAccess::Store(LocalType::Double) => match slot {
+            0 => JOp::DStore0,
+            1 => JOp::DStore1,
+            2 => JOp::DStore2,
+            3 => JOp::DStore3,
+            4..=255 => JOp::DStore(slot as u8),
+            _ => JOp::DStoreWide(slot)
+        },
+        LocalAccess::Store(LocalType::Reference) => match slot {
+            0 => JOp::AStore0,
+            1 => JOp::AStore1,
+            2 => JOp::AStore2,
+            3 => JOp::AStore3,
+            4..=255 => JOp::AStore(slot as u8),
+            _ => JOp::AStoreWide(slot)
+        },
+        LocalAccess::Increment(c) => {
+            if slot <= 255 && (-128..=127).contains(&c) {
+                JOp::IInc(slot as u8, c as i8)
+            } else {
+                JOp::IIncWide(slot, c)
+            }
+        },
+        LocalAccess::Ret => {
+            if slot <= 255 {
+                JOp::Ret(slot as u8)
+            } else {
+                JOp::RetWide(slot)
+            }
+        }
+    }
+}
@@ -1,32 +1,52 @@
-
 #![allow(dead_code)]
 
 mod ops;
 pub use self::ops::{JOp, parse_java_op};
-mod tls_pos;
-use self::tls_pos::set_pos_init;
+mod decoder;
+pub use self::decoder::{Decoder, DecodeError, DecodeFault, decode_op};
+mod labels;
+pub use self::labels::{Label, Jump, Instruction, LabelError, LabelFault, label_code, label_code_marking};
+mod locals;
+pub use self::locals::{LocalType, LocalAccess, local_op};
+mod encoder;
+pub use self::encoder::{Assembled, EncodeError, EncodeFault, MAX_CODE_LEN, assemble, encode_op, shortest_form};
+pub mod cfg;
+pub use self::cfg::{Cfg, CfgError, CfgFault};
+pub mod dom;
+pub use self::dom::{DomTree, Loop, Loops};
+mod stack;
+pub use self::stack::{StackInfo, StackError, StackFault};
+pub mod dataflow;
+pub use self::dataflow::{Liveness, ReachingDefs, Definition};
+pub mod ssa;
+pub use self::ssa::{Function, LiftError, LiftFault, LowerError, LowerFault, Lowered, lift, lower};
+pub mod structure;
+pub use self::structure::{Region, LoopKind, Structure, StructureError, StructureFault, structure};
+pub mod expr;
+pub use self::expr::{Expr, ExprError, ExprFault, Statement, expressions};
+pub mod disasm;
+pub use self::disasm::{DisasmError, DisasmFault, disassemble, disassemble_code};
+pub mod asm;
+pub use self::asm::{AsmError, AsmFault, assemble_class};
+pub mod interp;
+pub use self::interp::{Interpreter, InterpError, InterpFault, Object, Value};
+pub mod clinit;
+pub use self::clinit::{StaticField, StaticValue, UnknownReason, evaluate_statics};
+pub mod callgraph;
+pub use self::callgraph::{Analysis, CallGraph, CallGraphError, CallGraphFault, CallSite, MethodId, Unresolved};
+pub mod hierarchy;
+pub use self::hierarchy::{ClassHierarchy, MapHierarchy};
+pub mod verify;
+pub use self::verify::{Verifier, VerifyError, VerifyFault, VType, Frame, MethodContext, verify_code, infer_frames};
+pub mod frames;
+pub use self::frames::{FrameError, FrameFault, SuperclassOracle, compute_frames, compress_frames, stack_map_table};
 
 /// Attempts to read all opcodes in a buffer
 ///
-/// If this returns _none_ that signifies an error occured
-///
-/// Don't call this function on incomplete bytecode sections
-/// that start at an artibrary offset, java bytecode parsing
-/// as to have _some state_ about its offset into the file.
-pub fn read_all_ops<R: AsRef<[u8]>>(buffer: R) -> Vec<JOp> {
-    use super::nom::IResult;
-    set_pos_init(0);
-    let mut buffer: &[u8] = buffer.as_ref();
-    let mut ret = Vec::new();
-    loop {
-        buffer = match parse_java_op(buffer) {
-            IResult::Done(x,y) => {
-                ret.push(y);
-                x
-            },
-            IResult::Error(_) => return Vec::new(),
-            IResult::Incomplete(_) => break
-        };
-    }
-    ret
+/// `buffer` must be a complete code array, starting at pc 0.
+/// Use `Decoder` to decode lazily, or from an arbitrary pc.
+pub fn read_all_ops<R: AsRef<[u8]>>(buffer: R) -> Result<Vec<JOp>, DecodeError> {
+    Decoder::new(buffer.as_ref())
+        .map(|r| r.map(|(_, op)| op))
+        .collect()
 }
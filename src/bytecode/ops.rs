@@ -1,377 +1,413 @@
-
 #![allow(non_snake_case)]
 
-use super::super::nom::{le_u8, le_u32, le_u16, le_i32, IResult, ErrorKind, le_i16, Needed};
+use super::super::nom::{be_u8, be_i8, be_u16, be_i32, IResult, ErrorKind, be_i16, Needed};
+
+/// nom error code for an opcode the JVM spec does not assign
+pub const UNKNOWN_OPCODE: u32 = 1000;
 
-use super::tls_pos::{move_pos, align_code};
+/// nom error code for a known opcode with invalid operands
+pub const MALFORMED_OPERANDS: u32 = 1001;
 
-named!(parse_npairs<(i32,u32)>, do_parse!(
-    match_var: le_i32 >>
-    offset: le_u32 >>
+/*
+ * Decoding is table driven. Every opcode byte indexes straight into a
+ * 256 entry table of parsers, the `wide` prefix has a table of its own.
+ * Each parser receives the buffer starting at its opcode (so the opcode
+ * itself is never re-checked) and the pc of that opcode.
+ */
+type OpParser = fn(&[u8], usize) -> IResult<&[u8], JOp>;
+
+named!(parse_npairs<(i32,i32)>, do_parse!(
+    match_var: be_i32 >>
+    offset: be_i32 >>
     ( (match_var, offset) )
 ));
 
 fn slen(high: i32, low: i32) -> usize {
-    ((high - low) + 1) as usize
+    ((high as i64 - low as i64) + 1) as usize
+}
+
+/// Switch operands start on a 4 byte boundary relative to the
+/// start of the code array. `pc` is the position of the opcode.
+#[inline(always)]
+fn padding(pc: usize) -> usize {
+    (4 - ((pc + 1) & 0b11)) & 0b11
+}
+
+fn unknown_op(_: &[u8], _: usize) -> IResult<&[u8], JOp> {
+    IResult::Error(ErrorKind::Custom(UNKNOWN_OPCODE))
+}
+
+fn malformed_wide(_: &[u8], _: usize) -> IResult<&[u8], JOp> {
+    IResult::Error(ErrorKind::Custom(MALFORMED_OPERANDS))
 }
 
 macro_rules! build_it_all {
-    (@SIMP $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            ({
-                move_pos(1);
-                JOp::$b
-            })
-        ));
-    };
-    (@U8 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var: le_u8 >>
-            ({
-                move_pos(2);
-                JOp::$b(var)
-            })
-        ));
-    };
-    (@U8WIDE $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!(b"\xC4") >>
-            tag!($a) >>
-            var: le_u16 >>
-            ({
-                move_pos(4);
-                JOp::$b(var)
-            })
-        ));
-    };
-    (@U16 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var: le_u16 >>
-            ({
-                move_pos(3);
-                JOp::$b(var)
-            })
-        ));
-    };
-    (@U1600 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var: le_u16 >>
-            tag!(b"\x00\x00") >>
-            ({
-                move_pos(5);
-                JOp::$b(var)
-            })
-        ));
-    };
-    (@U32 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var: le_u32 >>
-            ({
-                move_pos(5);
-                JOp::$b(var)
-            })
-        ));
-    };
-    (@NPAIRS $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            call!(align_code) >>
-            default_code: le_u32 >>
-            pairs: le_u32 >>
-            data: many_m_n!(pairs as usize, pairs as usize, parse_npairs) >>
-            ({
-                move_pos(8 + (data.len() * 8));
-                JOp::$b(default_code, data)
-            })
-        ));
-    };
-    (@IINC $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!(b"\xC4") >>
-            tag!($a) >>
-            var0: le_u16 >>
-            var1: le_i16 >>
-            ({
-                move_pos(6);
-                JOp::$b(var0, var1)
-            })
-        ));
+    (@SIMP $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            IResult::Done(&buffer[1..], JOp::$b)
+        }
+    };
+    (@U8 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            map!(&buffer[1..], be_u8, JOp::$b)
+        }
+    };
+    (@U16 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            map!(&buffer[1..], be_u16, JOp::$b)
+        }
+    };
+    (@I16 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            map!(&buffer[1..], be_i16, JOp::$b)
+        }
+    };
+    (@I32 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            map!(&buffer[1..], be_i32, JOp::$b)
+        }
+    };
+    (@U1600 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            do_parse!(&buffer[1..],
+                var: be_u16 >>
+                tag!(b"\x00\x00") >>
+                (JOp::$b(var))
+            )
+        }
+    };
+    (@IINC $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            do_parse!(&buffer[1..],
+                var0: be_u8 >>
+                var1: be_i8 >>
+                (JOp::$b(var0, var1))
+            )
+        }
+    };
+    (@U16U80 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            do_parse!(&buffer[1..],
+                var0: be_u16 >>
+                var1: be_u8 >>
+                tag!(b"\x00") >>
+                (JOp::$b(var0, var1))
+            )
+        }
+    };
+    (@U16U8 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            do_parse!(&buffer[1..],
+                var0: be_u16 >>
+                var1: be_u8 >>
+                (JOp::$b(var0, var1))
+            )
+        }
+    };
+    (@NPAIRS $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], pc: usize) -> IResult<&[u8], JOp> {
+            let (rem, (default_code, pairs)) = try_parse!(&buffer[1..], do_parse!(
+                take!(padding(pc)) >>
+                default_code: be_i32 >>
+                pairs: be_i32 >>
+                ((default_code, pairs))
+            ));
+            if pairs < 0 {
+                return IResult::Error(ErrorKind::Custom(MALFORMED_OPERANDS));
+            }
+            let pairs = pairs as usize;
+            if rem.len() < pairs * 8 {
+                return IResult::Incomplete(Needed::Size(pairs * 8));
+            }
+            map!(rem, count!(parse_npairs, pairs), |data| JOp::$b(default_code, data))
+        }
+    };
+    (@TABLESWITCH $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], pc: usize) -> IResult<&[u8], JOp> {
+            let (rem, (default, low, high)) = try_parse!(&buffer[1..], do_parse!(
+                take!(padding(pc)) >>
+                default: be_i32 >>
+                low: be_i32 >>
+                high: be_i32 >>
+                ((default, low, high))
+            ));
+            if high < low {
+                return IResult::Error(ErrorKind::Custom(MALFORMED_OPERANDS));
+            }
+            let len = slen(high, low);
+            if rem.len() < len * 4 {
+                return IResult::Incomplete(Needed::Size(len * 4));
+            }
+            map!(rem, count!(be_i32, len), |data| JOp::$b(default, low, high, data))
+        }
     };
-    (@@U8 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var0: le_u8 >>
-            var1: le_u8 >>
-            ({
-                move_pos(3);
-                JOp::$b(var0, var1)
-            })
-        ));
-    };
-    (@@U16U80 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var0: le_u16 >>
-            var1: le_u8 >>
-            tag!(b"\x00") >>
-            ({
-                move_pos(5);
-                JOp::$b(var0, var1)
-            })
-        ));
-    };
-    (@@U16U8 $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            var0: le_u16 >>
-            var1: le_u8 >>
-            ({
-                move_pos(4);
-                JOp::$b(var0, var1)
-            })
-        ));
+
+    // the `wide` prefix, dispatches on the opcode it modifies
+    (@WIDE $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], pc: usize) -> IResult<&[u8], JOp> {
+            match buffer.get(1) {
+                Option::Some(op) => WIDE_OPCODES[*op as usize](buffer, pc),
+                Option::None => IResult::Incomplete(Needed::Size(1))
+            }
+        }
+    };
+    (@WIDE16 $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            map!(&buffer[2..], be_u16, JOp::$b)
+        }
+    };
+    (@WIDEIINC $b: ident, $c: ident) => {
+        fn $c(buffer: &[u8], _: usize) -> IResult<&[u8], JOp> {
+            do_parse!(&buffer[2..],
+                var0: be_u16 >>
+                var1: be_i16 >>
+                (JOp::$b(var0, var1))
+            )
+        }
     };
+}
 
-    //exit condition
-    (@TABLESWITCH $a: expr, $b: ident, $c: ident) => {
-        named!($c<JOp>, do_parse!(
-            tag!($a) >>
-            call!(align_code) >>
-            default: le_i32 >>
-            low: le_i32 >>
-            high: le_i32 >>
-            data: many_m_n!(slen(high,low),slen(high,low), le_u32) >>
-            ({
-                move_pos(12 + (data.len() * 4));
-                JOp::$b(default, low, high, data)
-            })
-        ));
+/*
+ * Declares the parser for every entry, and the table indexing them
+ */
+macro_rules! opcode_table {
+    ($TABLE: ident, $DEFAULT: ident;
+     $($CODE: expr => @$KIND: ident $VARIANT: ident, $PARSER: ident;)*) => {
+        $( build_it_all!(@$KIND $VARIANT, $PARSER); )*
+
+        static $TABLE: [OpParser; 256] = {
+            let mut table: [OpParser; 256] = [$DEFAULT; 256];
+            $( table[$CODE] = $PARSER; )*
+            table
+        };
     };
 }
 
+opcode_table! {OPCODES, unknown_op;
+    0x00 => @SIMP        Nop, parser_Nop;
+    0x01 => @SIMP        AConstNull, parser_AConstNull;
+    0x02 => @SIMP        IConstM1, parser_IConstM1;
+    0x03 => @SIMP        IConst0, parser_IConst0;
+    0x04 => @SIMP        IConst1, parser_IConst1;
+    0x05 => @SIMP        IConst2, parser_IConst2;
+    0x06 => @SIMP        IConst3, parser_IConst3;
+    0x07 => @SIMP        IConst4, parser_IConst4;
+    0x08 => @SIMP        IConst5, parser_IConst5;
+    0x09 => @SIMP        LConst0, parser_LConst0;
+    0x0A => @SIMP        LConst1, parser_LConst1;
+    0x0B => @SIMP        FConst0, parser_FConst0;
+    0x0C => @SIMP        FConst1, parser_FConst1;
+    0x0D => @SIMP        FConst2, parser_FConst2;
+    0x0E => @SIMP        DConst0, parser_DConst0;
+    0x0F => @SIMP        DConst1, parser_DConst1;
+    0x10 => @U8          BiPush, parser_BiPush;
+    0x11 => @U16         SIPush, parser_SIPush;
+    0x12 => @U8          LDC, parser_LDC;
+    0x13 => @U16         LDCW, parser_LDCW;
+    0x14 => @U16         LDC2W, parser_LDC2W;
+    0x15 => @U8          ILoad, parser_ILoad;
+    0x16 => @U8          LLoad, parser_LLoad;
+    0x17 => @U8          FLoad, parser_FLoad;
+    0x18 => @U8          DLoad, parser_DLoad;
+    0x19 => @U8          ALoad, parser_ALoad;
+    0x1A => @SIMP        ILoad0, parser_ILoad0;
+    0x1B => @SIMP        ILoad1, parser_ILoad1;
+    0x1C => @SIMP        ILoad2, parser_ILoad2;
+    0x1D => @SIMP        ILoad3, parser_ILoad3;
+    0x1E => @SIMP        LLoad0, parser_LLoad0;
+    0x1F => @SIMP        LLoad1, parser_LLoad1;
+    0x20 => @SIMP        LLoad2, parser_LLoad2;
+    0x21 => @SIMP        LLoad3, parser_LLoad3;
+    0x22 => @SIMP        FLoad0, parser_FLoad0;
+    0x23 => @SIMP        FLoad1, parser_FLoad1;
+    0x24 => @SIMP        FLoad2, parser_FLoad2;
+    0x25 => @SIMP        FLoad3, parser_FLoad3;
+    0x26 => @SIMP        DLoad0, parser_DLoad0;
+    0x27 => @SIMP        DLoad1, parser_DLoad1;
+    0x28 => @SIMP        DLoad2, parser_DLoad2;
+    0x29 => @SIMP        DLoad3, parser_DLoad3;
+    0x2A => @SIMP        ALoad0, parser_ALoad0;
+    0x2B => @SIMP        ALoad1, parser_ALoad1;
+    0x2C => @SIMP        ALoad2, parser_ALoad2;
+    0x2D => @SIMP        ALoad3, parser_ALoad3;
+    0x2E => @SIMP        IALoad, parser_IALoad;
+    0x2F => @SIMP        LALoad, parser_LALoad;
+    0x30 => @SIMP        FALoad, parser_FALoad;
+    0x31 => @SIMP        DALoad, parser_DALoad;
+    0x32 => @SIMP        AALoad, parser_AALoad;
+    0x33 => @SIMP        BaLoad, parser_BaLoad;
+    0x34 => @SIMP        CaLoad, parser_CaLoad;
+    0x35 => @SIMP        SALoad, parser_SALoad;
+    0x36 => @U8          IStore, parser_IStore;
+    0x37 => @U8          LStore, parser_LStore;
+    0x38 => @U8          FStore, parser_FStore;
+    0x39 => @U8          DStore, parser_DStore;
+    0x3A => @U8          AStore, parser_AStore;
+    0x3B => @SIMP        IStore0, parser_IStore0;
+    0x3C => @SIMP        IStore1, parser_IStore1;
+    0x3D => @SIMP        IStore2, parser_IStore2;
+    0x3E => @SIMP        IStore3, parser_IStore3;
+    0x3F => @SIMP        LStore0, parser_LStore0;
+    0x40 => @SIMP        LStore1, parser_LStore1;
+    0x41 => @SIMP        LStore2, parser_LStore2;
+    0x42 => @SIMP        LStore3, parser_LStore3;
+    0x43 => @SIMP        FStore0, parser_FStore0;
+    0x44 => @SIMP        FStore1, parser_FStore1;
+    0x45 => @SIMP        FStore2, parser_FStore2;
+    0x46 => @SIMP        FStore3, parser_FStore3;
+    0x47 => @SIMP        DStore0, parser_DStore0;
+    0x48 => @SIMP        DStore1, parser_DStore1;
+    0x49 => @SIMP        DStore2, parser_DStore2;
+    0x4A => @SIMP        DStore3, parser_DStore3;
+    0x4B => @SIMP        AStore0, parser_AStore0;
+    0x4C => @SIMP        AStore1, parser_AStore1;
+    0x4D => @SIMP        AStore2, parser_AStore2;
+    0x4E => @SIMP        AStore3, parser_AStore3;
+    0x4F => @SIMP        IAStore, parser_IAStore;
+    0x50 => @SIMP        LAStore, parser_LAStore;
+    0x51 => @SIMP        FAStore, parser_FAStore;
+    0x52 => @SIMP        DAStore, parser_DAStore;
+    0x53 => @SIMP        AAStore, parser_AAStore;
+    0x54 => @SIMP        BaStore, parser_BaStore;
+    0x55 => @SIMP        CaStore, parser_CaStore;
+    0x56 => @SIMP        SAStore, parser_SAStore;
+    0x57 => @SIMP        Pop, parser_Pop;
+    0x58 => @SIMP        Pop2, parser_Pop2;
+    0x59 => @SIMP        Dup, parser_Dup;
+    0x5A => @SIMP        Dupx1, parser_Dupx1;
+    0x5B => @SIMP        Dupx2, parser_Dupx2;
+    0x5C => @SIMP        Dup2, parser_Dup2;
+    0x5D => @SIMP        Dup2x1, parser_Dup2x1;
+    0x5E => @SIMP        Dup2x2, parser_Dup2x2;
+    0x5F => @SIMP        Swap, parser_Swap;
+    0x60 => @SIMP        IAdd, parser_IAdd;
+    0x61 => @SIMP        LAdd, parser_LAdd;
+    0x62 => @SIMP        FAdd, parser_FAdd;
+    0x63 => @SIMP        DAdd, parser_DAdd;
+    0x64 => @SIMP        ISub, parser_ISub;
+    0x65 => @SIMP        LSub, parser_LSub;
+    0x66 => @SIMP        FSub, parser_FSub;
+    0x67 => @SIMP        DSub, parser_DSub;
+    0x68 => @SIMP        IMul, parser_IMul;
+    0x69 => @SIMP        LMul, parser_LMul;
+    0x6A => @SIMP        FMul, parser_FMul;
+    0x6B => @SIMP        DMul, parser_DMul;
+    0x6C => @SIMP        IDiv, parser_IDiv;
+    0x6D => @SIMP        LDiv, parser_LDiv;
+    0x6E => @SIMP        FDiv, parser_FDiv;
+    0x6F => @SIMP        DDiv, parser_DDiv;
+    0x70 => @SIMP        IRem, parser_IRem;
+    0x71 => @SIMP        LRem, parser_LRem;
+    0x72 => @SIMP        FRem, parser_FRem;
+    0x73 => @SIMP        DRem, parser_DRem;
+    0x74 => @SIMP        INeg, parser_INeg;
+    0x75 => @SIMP        LNeg, parser_LNeg;
+    0x76 => @SIMP        FNeg, parser_FNeg;
+    0x77 => @SIMP        DNeg, parser_DNeg;
+    0x78 => @SIMP        ISHL, parser_ISHL;
+    0x79 => @SIMP        LSHL, parser_LSHL;
+    0x7A => @SIMP        ISHR, parser_ISHR;
+    0x7B => @SIMP        LSHR, parser_LSHR;
+    0x7C => @SIMP        IUSHR, parser_IUSHR;
+    0x7D => @SIMP        LUSHR, parser_LUSHR;
+    0x7E => @SIMP        IAnd, parser_IAnd;
+    0x7F => @SIMP        LAnd, parser_LAnd;
+    0x80 => @SIMP        IOr, parser_IOr;
+    0x81 => @SIMP        LOr, parser_LOr;
+    0x82 => @SIMP        IXor, parser_IXor;
+    0x83 => @SIMP        LXor, parser_LXor;
+    0x84 => @IINC        IInc, parser_IInc;
+    0x85 => @SIMP        I2L, parser_I2L;
+    0x86 => @SIMP        I2F, parser_I2F;
+    0x87 => @SIMP        I2D, parser_I2D;
+    0x88 => @SIMP        L2I, parser_L2I;
+    0x89 => @SIMP        L2F, parser_L2F;
+    0x8A => @SIMP        L2D, parser_L2D;
+    0x8B => @SIMP        F2I, parser_F2I;
+    0x8C => @SIMP        F2L, parser_F2L;
+    0x8D => @SIMP        F2D, parser_F2D;
+    0x8E => @SIMP        D2I, parser_D2I;
+    0x8F => @SIMP        D2L, parser_D2L;
+    0x90 => @SIMP        D2F, parser_D2F;
+    0x91 => @SIMP        I2B, parser_I2B;
+    0x92 => @SIMP        I2C, parser_I2C;
+    0x93 => @SIMP        I2S, parser_I2S;
+    0x94 => @SIMP        Lcmp, parser_Lcmp;
+    0x95 => @SIMP        FcmpL, parser_FcmpL;
+    0x96 => @SIMP        FcmpG, parser_FcmpG;
+    0x97 => @SIMP        DcmpL, parser_DcmpL;
+    0x98 => @SIMP        DcmpG, parser_DcmpG;
+    0x99 => @I16         IFEQ, parser_IFEQ;
+    0x9A => @I16         IFNE, parser_IFNE;
+    0x9B => @I16         IFLT, parser_IFLT;
+    0x9C => @I16         IFGE, parser_IFGE;
+    0x9D => @I16         IFGT, parser_IFGT;
+    0x9E => @I16         IFLE, parser_IFLE;
+    0x9F => @I16         IFIcmpEQ, parser_IFIcmpEQ;
+    0xA0 => @I16         IFIcmpNE, parser_IFIcmpNE;
+    0xA1 => @I16         IFIcmpLT, parser_IFIcmpLT;
+    0xA2 => @I16         IfIcmpGE, parser_IfIcmpGE;
+    0xA3 => @I16         IFIcmpGT, parser_IFIcmpGT;
+    0xA4 => @I16         IFIcmpLE, parser_IFIcmpLE;
+    0xA5 => @I16         IFAcmpEQ, parser_IFAcmpEQ;
+    0xA6 => @I16         IFAcmpNE, parser_IFAcmpNE;
+    0xA7 => @I16         Goto, parser_Goto;
+    0xA8 => @I16         JSR, parser_JSR;
+    0xA9 => @U8          Ret, parser_Ret;
+    0xAA => @TABLESWITCH TableSwitch, parser_TableSwitch;
+    0xAB => @NPAIRS      LookUpSwitch, parser_LookUpSwitch;
+    0xAC => @SIMP        IReturn, parser_IReturn;
+    0xAD => @SIMP        LReturn, parser_LReturn;
+    0xAE => @SIMP        FReturn, parser_FReturn;
+    0xAF => @SIMP        DReturn, parser_DReturn;
+    0xB0 => @SIMP        AReturn, parser_AReturn;
+    0xB1 => @SIMP        Return, parser_Return;
+    0xB2 => @U16         GetStatic, parser_GetStatic;
+    0xB3 => @U16         PutStatic, parser_PutStatic;
+    0xB4 => @U16         GetField, parser_GetField;
+    0xB5 => @U16         PutField, parser_PutField;
+    0xB6 => @U16         InvokeVirtual, parser_InvokeVirtual;
+    0xB7 => @U16         InvokeSpecial, parser_InvokeSpecial;
+    0xB8 => @U16         InvokeStatic, parser_InvokeStatic;
+    0xB9 => @U16U80      InvokedInterface, parser_InvokedInterface;
+    0xBA => @U1600       InvokedDynamic, parser_InvokedDynamic;
+    0xBB => @U16         New, parser_New;
+    0xBC => @U8          NewArray, parser_NewArray;
+    0xBD => @U16         ANewArray, parser_ANewArray;
+    0xBE => @SIMP        ArrayLength, parser_ArrayLength;
+    0xBF => @SIMP        AThrow, parser_AThrow;
+    0xC0 => @U16         CheckCast, parser_CheckCast;
+    0xC1 => @U16         InstanceOf, parser_InstanceOf;
+    0xC2 => @SIMP        MonitorEnter, parser_MonitorEnter;
+    0xC3 => @SIMP        MonitorExit, parser_MonitorExit;
+    0xC4 => @WIDE        Wide, parser_Wide;
+    0xC5 => @U16U8       MultiAneWArray, parser_MultiAneWArray;
+    0xC6 => @I16         IFNull, parser_IFNull;
+    0xC7 => @I16         IFnonNull, parser_IFnonNull;
+    0xC8 => @I32         GotoW, parser_GotoW;
+    0xC9 => @I32         JSRW, parser_JSRW;
+    0xCA => @SIMP        BreakPoint, parser_BreakPoint;
+    0xFE => @SIMP        ImpDep1, parser_ImpDep1;
+    0xFF => @SIMP        ImpDep2, parser_ImpDep2;
+}
 
-    build_it_all!(@SIMP       b"\x32", AALoad, parser_AALoad);
-    build_it_all!(@SIMP       b"\x53", AAStore, parser_AAStore);
-    build_it_all!(@SIMP       b"\x01", AConstNull, parser_AConstNull);
-    build_it_all!(@U8         b"\x19", ALoad, parser_ALoad);
-    build_it_all!(@U8WIDE     b"\x19", ALoadWide, parser_ALoadWide);
-    build_it_all!(@SIMP       b"\x2A", ALoad0, parser_ALoad0);
-    build_it_all!(@SIMP       b"\x2B", ALoad1, parser_ALoad1);
-    build_it_all!(@SIMP       b"\x2C", ALoad2, parser_ALoad2);
-    build_it_all!(@SIMP       b"\x2D", ALoad3, parser_ALoad3);
-    build_it_all!(@U16        b"\xBD", ANewArray, parser_ANewArray);
-    build_it_all!(@SIMP       b"\xB0", AReturn, parser_AReturn);
-    build_it_all!(@SIMP       b"\xBE", ArrayLength, parser_ArrayLength);
-    build_it_all!(@U8         b"\x3A", AStore, parser_AStore);
-    build_it_all!(@U8WIDE     b"\x3A", AStoreWide, parser_AStoreWide);
-    build_it_all!(@SIMP       b"\x4B", AStore0, parser_AStore0);
-    build_it_all!(@SIMP       b"\x4C", AStore1, parser_AStore1);
-    build_it_all!(@SIMP       b"\x4D", AStore2, parser_AStore2);
-    build_it_all!(@SIMP       b"\x4E", AStore3, parser_AStore3);
-    build_it_all!(@SIMP       b"\xBF", AThrow, parser_AThrow);
-    build_it_all!(@SIMP       b"\x33", BaLoad, parser_BaLoad);
-    build_it_all!(@SIMP       b"\x54", BaStore, parser_BaStore);
-    build_it_all!(@U8         b"\x10", BiPush, parser_BiPush);
-    build_it_all!(@SIMP       b"\xCA", BreakPoint, parser_BreakPoint);
-    build_it_all!(@SIMP       b"\x34", CaLoad, parser_CaLoad);
-    build_it_all!(@SIMP       b"\x55", CaStore, parser_CaStore);
-    build_it_all!(@U16        b"\xC0", CheckCast, parser_CheckCast);
-    build_it_all!(@SIMP       b"\x90", D2F, parser_D2F);
-    build_it_all!(@SIMP       b"\x8E", D2I, parser_D2I);
-    build_it_all!(@SIMP       b"\x8F", D2L, parser_D2L);
-    build_it_all!(@SIMP       b"\x63", DAdd, parser_DAdd);
-    build_it_all!(@SIMP       b"\x31", DALoad, parser_DALoad);
-    build_it_all!(@SIMP       b"\x52", DAStore, parser_DAStore);
-    build_it_all!(@SIMP       b"\x98", DcmpG, parser_DcmpG);
-    build_it_all!(@SIMP       b"\x97", DcmpL, parser_DcmpL);
-    build_it_all!(@SIMP       b"\x0E", DConst0, parser_DConst0);
-    build_it_all!(@SIMP       b"\x0F", DConst1, parser_DConst1);
-    build_it_all!(@SIMP       b"\x6F", DDiv, parser_DDiv);
-    build_it_all!(@U8         b"\x18", DLoad, parser_DLoad);
-    build_it_all!(@U8WIDE     b"\x18", DLoadWide, parser_DLoadWide);
-    build_it_all!(@SIMP       b"\x26", DLoad0, parser_DLoad0);
-    build_it_all!(@SIMP       b"\x27", DLoad1, parser_DLoad1);
-    build_it_all!(@SIMP       b"\x28", DLoad2, parser_DLoad2);
-    build_it_all!(@SIMP       b"\x29", DLoad3, parser_DLoad3);
-    build_it_all!(@SIMP       b"\x6B", DMul, parser_DMul);
-    build_it_all!(@SIMP       b"\x77", DNeg, parser_DNeg);
-    build_it_all!(@SIMP       b"\x73", DRem, parser_DRem);
-    build_it_all!(@U8         b"\xAF", DStore, parser_DStore);
-    build_it_all!(@U8WIDE     b"\xAF", DStoreWide, parser_DStoreWide);
-    build_it_all!(@SIMP       b"\x47", DStore0, parser_DStore0);
-    build_it_all!(@SIMP       b"\x48", DStore1, parser_DStore1);
-    build_it_all!(@SIMP       b"\x49", DStore2, parser_DStore2);
-    build_it_all!(@SIMP       b"\x4A", DStore3, parser_DStore3);
-    build_it_all!(@SIMP       b"\x67", DSub, parser_DSub);
-    build_it_all!(@SIMP       b"\x59", Dup, parser_Dup);
-    build_it_all!(@SIMP       b"\x5A", Dupx1, parser_Dupx1);
-    build_it_all!(@SIMP       b"\x5B", Dupx2, parser_Dupx2);
-    build_it_all!(@SIMP       b"\x5C", Dup2, parser_Dup2);
-    build_it_all!(@SIMP       b"\x5D", Dup2x1, parser_Dup2x1);
-    build_it_all!(@SIMP       b"\x5C", Dup2x2, parser_Dup2x2);
-    build_it_all!(@SIMP       b"\x8D", F2D, parser_F2D);
-    build_it_all!(@SIMP       b"\x8B", F2I, parser_F2I);
-    build_it_all!(@SIMP       b"\x8C", F2L, parser_F2L);
-    build_it_all!(@SIMP       b"\x62", FAdd, parser_FAdd);
-    build_it_all!(@SIMP       b"\x30", FALoad, parser_FALoad);
-    build_it_all!(@SIMP       b"\x51", FAStore, parser_FAStore);
-    build_it_all!(@SIMP       b"\x96", FcmpG, parser_FcmpG);
-    build_it_all!(@SIMP       b"\x95", FcmpL, parser_FcmpL);
-    build_it_all!(@SIMP       b"\x0B", FConst0, parser_FConst0);
-    build_it_all!(@SIMP       b"\x0C", FConst1, parser_FConst1);
-    build_it_all!(@SIMP       b"\x0D", FConst2, parser_FConst2);
-    build_it_all!(@SIMP       b"\x6E", FDiv, parser_FDiv);
-    build_it_all!(@U8         b"\x17", FLoad, parser_FLoad);
-    build_it_all!(@U8WIDE     b"\x17", FLoadWide, parser_FLoadWide);
-    build_it_all!(@SIMP       b"\x22", FLoad0, parser_FLoad0);
-    build_it_all!(@SIMP       b"\x23", FLoad1, parser_FLoad1);
-    build_it_all!(@SIMP       b"\x24", FLoad2, parser_FLoad2);
-    build_it_all!(@SIMP       b"\x25", FLoad3, parser_FLoad3);
-    build_it_all!(@SIMP       b"\x6A", FMul, parser_FMul);
-    build_it_all!(@SIMP       b"\x76", FNeg, parser_FNeg);
-    build_it_all!(@SIMP       b"\x72", FRem, parser_FRem);
-    build_it_all!(@SIMP       b"\xAE", FReturn, parser_FReturn);
-    build_it_all!(@U8         b"\x38", FStore, parser_FStore);
-    build_it_all!(@U8WIDE     b"\x38", FStoreWide, parser_FStoreWide);
-    build_it_all!(@SIMP       b"\x43", FStore0, parser_FStore0);
-    build_it_all!(@SIMP       b"\x44", FStore1, parser_FStore1);
-    build_it_all!(@SIMP       b"\x45", FStore2, parser_FStore2);
-    build_it_all!(@SIMP       b"\x46", FStore3, parser_FStore3);
-    build_it_all!(@SIMP       b"\x66", FSub, parser_FSub);
-    build_it_all!(@U16        b"\xB4", GetField, parser_GetField);
-    build_it_all!(@U16        b"\xB2", GetStatic, parser_GetStatic);
-    build_it_all!(@U16        b"\xA7", Goto, parser_Goto);
-    build_it_all!(@U32        b"\xC8", GotoW, parser_GotoW);
-    build_it_all!(@SIMP       b"\x91", I2B, parser_I2B);
-    build_it_all!(@SIMP       b"\x92", I2C, parser_I2C);
-    build_it_all!(@SIMP       b"\x87", I2D, parser_I2D);
-    build_it_all!(@SIMP       b"\x86", I2F, parser_I2F);
-    build_it_all!(@SIMP       b"\x85", I2L, parser_I2L);
-    build_it_all!(@SIMP       b"\x93", I2S, parser_I2S);
-    build_it_all!(@SIMP       b"\x60", IAdd, parser_IAdd);
-    build_it_all!(@SIMP       b"\x2E", IALoad, parser_IALoad);
-    build_it_all!(@SIMP       b"\x7E", IAnd, parser_IAnd);
-    build_it_all!(@SIMP       b"\x4F", IAStore, parser_IAStore);
-    build_it_all!(@SIMP       b"\x02", IConstM1, parser_IConstM1);
-    build_it_all!(@SIMP       b"\x03", IConst0, parser_IConst0);
-    build_it_all!(@SIMP       b"\x04", IConst1, parser_IConst1);
-    build_it_all!(@SIMP       b"\x05", IConst2, parser_IConst2);
-    build_it_all!(@SIMP       b"\x06", IConst3, parser_IConst3);
-    build_it_all!(@SIMP       b"\x07", IConst4, parser_IConst4);
-    build_it_all!(@SIMP       b"\x08", IConst5, parser_IConst5);
-    build_it_all!(@SIMP       b"\x6C", IDiv, parser_IDiv);
-    build_it_all!(@U16        b"\xA5", IFAcmpEQ, parser_IFAcmpEQ);
-    build_it_all!(@U16        b"\xA6", IFAcmpNE, parser_IFAcmpNE);
-    build_it_all!(@U16        b"\x9F", IFIcmpEQ, parser_IFIcmpEQ);
-    build_it_all!(@U16        b"\xA2", IfIcmpGE, parser_IfIcmpGE);
-    build_it_all!(@U16        b"\xA3", IFIcmpGT, parser_IFIcmpGT);
-    build_it_all!(@U16        b"\xA4", IFIcmpLE, parser_IFIcmpLE);
-    build_it_all!(@U16        b"\xA1", IFIcmpLT, parser_IFIcmpLT);
-    build_it_all!(@U16        b"\xA0", IFIcmpNE, parser_IFIcmpNE);
-    build_it_all!(@U16        b"\x99", IFEQ, parser_IFEQ);
-    build_it_all!(@U16        b"\x9C", IFGE, parser_IFGE);
-    build_it_all!(@U16        b"\x9D", IFGT, parser_IFGT);
-    build_it_all!(@U16        b"\x9B", IFLE, parser_IFLE);
-    build_it_all!(@U16        b"\x9A", IFNE, parser_IFNE);
-    build_it_all!(@U16        b"\xC7", IFnonNull, parser_IFnonNull);
-    build_it_all!(@U16        b"\xC6", IFNull, parser_IFNull);
-    build_it_all!(@@U8        b"\x84", IInc, parser_IInc);
-    build_it_all!(@IINC       b"\x84", IIncWide, parser_IIncWide);
-    build_it_all!(@U8WIDE     b"\x15", ILoad, parser_ILoad);
-    build_it_all!(@U8         b"\x15", ILoadWide, parser_ILoadWide);
-    build_it_all!(@SIMP       b"\x1A", ILoad0, parser_ILoad0);
-    build_it_all!(@SIMP       b"\x1B", ILoad1, parser_ILoad1);
-    build_it_all!(@SIMP       b"\x1C", ILoad2, parser_ILoad2);
-    build_it_all!(@SIMP       b"\x1D", ILoad3, parser_ILoad3);
-    build_it_all!(@SIMP       b"\xF3", ImpDep1, parser_ImpDep1);
-    build_it_all!(@SIMP       b"\xFF", ImpDep2, parser_ImpDep2);
-    build_it_all!(@SIMP       b"\x68", IMul, parser_IMul);
-    build_it_all!(@SIMP       b"\x74", INeg, parser_INeg);
-    build_it_all!(@U16        b"\xC1", InstanceOf, parser_InstanceOf);
-    build_it_all!(@U1600      b"\xBA", InvokedDynamic, parser_InvokedDynamic);
-    build_it_all!(@@U16U80    b"\xB9", InvokedInterface, parser_InvokedInterface);
-    build_it_all!(@U16        b"\xB7", InvokeSpecial, parser_InvokeSpecial);
-    build_it_all!(@U16        b"\xB8", InvokeStatic, parser_InvokeStatic);
-    build_it_all!(@U16        b"\xB6", InvokeVirtual, parser_InvokeVirtual);
-    build_it_all!(@SIMP       b"\x80", IOr, parser_IOr);
-    build_it_all!(@SIMP       b"\x70", IRem, parser_IRem);
-    build_it_all!(@SIMP       b"\xAC", IReturn, parser_IReturn);
-    build_it_all!(@SIMP       b"\x78", ISHL, parser_ISHL);
-    build_it_all!(@SIMP       b"\x7A", ISHR, parser_ISHR);
-    build_it_all!(@U8         b"\x36", IStore, parser_IStore);
-    build_it_all!(@U8WIDE     b"\x36", IStoreWide, parser_IStoreWide);
-    build_it_all!(@SIMP       b"\x3B", IStore0, parser_IStore0);
-    build_it_all!(@SIMP       b"\x3C", IStore1, parser_IStore1);
-    build_it_all!(@SIMP       b"\x3D", IStore2, parser_IStore2);
-    build_it_all!(@SIMP       b"\x3E", IStore3, parser_IStore3);
-    build_it_all!(@SIMP       b"\x64", ISub, parser_ISub);
-    build_it_all!(@SIMP       b"\x7C", IUSHR, parser_IUSHR);
-    build_it_all!(@SIMP       b"\x82", IXor, parser_IXor);
-    build_it_all!(@U16        b"\xA8", JSR, parser_JSR);
-    build_it_all!(@U16        b"\xC9", JSRW, parser_JSRW);
-    build_it_all!(@SIMP       b"\x8A", L2D, parser_L2D);
-    build_it_all!(@SIMP       b"\x89", L2F, parser_L2F);
-    build_it_all!(@SIMP       b"\x88", L2I, parser_L2I);
-    build_it_all!(@SIMP       b"\x61", LAdd, parser_LAdd);
-    build_it_all!(@SIMP       b"\x2F", LALoad, parser_LALoad);
-    build_it_all!(@SIMP       b"\x7F", LAnd, parser_LAnd);
-    build_it_all!(@SIMP       b"\x50", LAStore, parser_LAStore);
-    build_it_all!(@SIMP       b"\x94", Lcmp, parser_Lcmp);
-    build_it_all!(@SIMP       b"\x09", LConst0, parser_LConst0);
-    build_it_all!(@SIMP       b"\x0A", LConst1, parser_LConst1);
-    build_it_all!(@U8         b"\x12", LDC, parser_LDC);
-    build_it_all!(@U16        b"\x13", LDCW, parser_LDCW);
-    build_it_all!(@U16        b"\x14", LDC2W, parser_LDC2W);
-    build_it_all!(@SIMP       b"\x6D", LDiv, parser_LDiv);
-    build_it_all!(@U8         b"\x16", LLoad, parser_LLoad);
-    build_it_all!(@U8WIDE     b"\x16", LLoadWide, parser_LLoadWIDE);
-    build_it_all!(@SIMP       b"\x1E", LLoad0, parser_LLoad0);
-    build_it_all!(@SIMP       b"\x1F", LLoad1, parser_LLoad1);
-    build_it_all!(@SIMP       b"\x20", LLoad2, parser_LLoad2);
-    build_it_all!(@SIMP       b"\x21", LLoad3, parser_LLoad3);
-    build_it_all!(@SIMP       b"\x69", LMul, parser_LMul);
-    build_it_all!(@SIMP       b"\x75", LNeg, parser_LNeg);
-    build_it_all!(@NPAIRS     b"\xAB", LookUpSwitch, parser_LookUpSwitch);
-    build_it_all!(@SIMP       b"\x81", LOr, parser_LOr);
-    build_it_all!(@SIMP       b"\x71", LRem, parser_LRem);
-    build_it_all!(@SIMP       b"\xAD", LReturn, parser_LReturn);
-    build_it_all!(@SIMP       b"\x79", LSHL, parser_LSHL);
-    build_it_all!(@SIMP       b"\x7B", LSHR, parser_LSHR);
-    build_it_all!(@U8         b"\x37", LStore, parser_LStore);
-    build_it_all!(@U8WIDE     b"\x37", LStoreWide, parser_LStoreWide);
-    build_it_all!(@SIMP       b"\x3F", LStore0, parser_LStore0);
-    build_it_all!(@SIMP       b"\x40", LStore1, parser_LStore1);
-    build_it_all!(@SIMP       b"\x41", LStore2, parser_LStore2);
-    build_it_all!(@SIMP       b"\x42", LStore3, parser_LStore3);
-    build_it_all!(@SIMP       b"\x65", LSub, parser_LSub);
-    build_it_all!(@SIMP       b"\x7D", LUSHR, parser_LUSHR);
-    build_it_all!(@SIMP       b"\x83", LXor, parser_LXor);
-    build_it_all!(@SIMP       b"\xC2", MonitorEnter, parser_MonitorEnter);
-    build_it_all!(@SIMP       b"\xC3", MonitorExit, parser_MonitorExit);
-    build_it_all!(@@U16U8     b"\xC5", MultiAneWArray, parser_MultiAneWArray);
-    build_it_all!(@U16        b"\xBB", New, parser_New);
-    build_it_all!(@U8         b"\xBC", NewArray, parser_NewArray);
-    build_it_all!(@SIMP       b"\x00", Nop, parser_Nop);
-    build_it_all!(@SIMP       b"\x57", Pop, parser_Pop);
-    build_it_all!(@SIMP       b"\x58", Pop2, parser_Pop2);
-    build_it_all!(@U16        b"\xB5", PutField, parser_PutField);
-    build_it_all!(@U16        b"\xB3", PutStatic, parser_PutStatic);
-    build_it_all!(@U8         b"\xA9", Ret, parser_Ret);
-    build_it_all!(@U8WIDE     b"\xA9", RetWide, parser_RetWide);
-    build_it_all!(@SIMP       b"\xB1", Return, parser_Return);
-    build_it_all!(@SIMP       b"\x35", SALoad, parser_SALoad);
-    build_it_all!(@SIMP       b"\x56", SAStore, parser_SAStore);
-    build_it_all!(@U16        b"\x11", SIPush, parser_SIPush);
-    build_it_all!(@SIMP       b"\x5F", Swap, parser_Swap);
-    build_it_all!(@TABLESWITCH b"\xAA", TableSwitch, parser_TableSwitch);
+opcode_table! {WIDE_OPCODES, malformed_wide;
+    0x15 => @WIDE16      ILoadWide, parser_ILoadWide;
+    0x16 => @WIDE16      LLoadWide, parser_LLoadWide;
+    0x17 => @WIDE16      FLoadWide, parser_FLoadWide;
+    0x18 => @WIDE16      DLoadWide, parser_DLoadWide;
+    0x19 => @WIDE16      ALoadWide, parser_ALoadWide;
+    0x36 => @WIDE16      IStoreWide, parser_IStoreWide;
+    0x37 => @WIDE16      LStoreWide, parser_LStoreWide;
+    0x38 => @WIDE16      FStoreWide, parser_FStoreWide;
+    0x39 => @WIDE16      DStoreWide, parser_DStoreWide;
+    0x3A => @WIDE16      AStoreWide, parser_AStoreWide;
+    0x84 => @WIDEIINC    IIncWide, parser_IIncWide;
+    0xA9 => @WIDE16      RetWide, parser_RetWide;
+}
 
 /// This is a full list of all Java Enums
-#[derive(Clone,Debug)]
+#[derive(Clone,Debug,PartialEq,Eq)]
 pub enum JOp {
     AALoad,
     AAStore,
@@ -419,6 +455,7 @@ pub enum JOp {
     DMul,
     DNeg,
     DRem,
+    DReturn,
     DStore(u8),
     DStoreWide(u16),
     DStore0,
@@ -463,8 +500,8 @@ pub enum JOp {
     FSub,
     GetField(u16),
     GetStatic(u16),
-    Goto(u16),
-    GotoW(u32),
+    Goto(i16),
+    GotoW(i32),
     I2B,
     I2C,
     I2D,
@@ -483,25 +520,26 @@ pub enum JOp {
     IConst4,
     IConst5,
     IDiv,
-    IFAcmpEQ(u16),
-    IFAcmpNE(u16),
-    IFIcmpEQ(u16),
-    IfIcmpGE(u16),
-    IFIcmpGT(u16),
-    IFIcmpLE(u16),
-    IFIcmpLT(u16),
-    IFIcmpNE(u16),
-    IFEQ(u16),
-    IFGE(u16),
-    IFGT(u16),
-    IFLE(u16),
-    IFNE(u16),
-    IFnonNull(u16),
-    IFNull(u16),
-    IInc(u8, u8),
+    IFAcmpEQ(i16),
+    IFAcmpNE(i16),
+    IFIcmpEQ(i16),
+    IfIcmpGE(i16),
+    IFIcmpGT(i16),
+    IFIcmpLE(i16),
+    IFIcmpLT(i16),
+    IFIcmpNE(i16),
+    IFEQ(i16),
+    IFGE(i16),
+    IFGT(i16),
+    IFLE(i16),
+    IFLT(i16),
+    IFNE(i16),
+    IFnonNull(i16),
+    IFNull(i16),
+    IInc(u8, i8),
     IIncWide(u16,i16),
-    ILoad(u16),
-    ILoadWide(u8),
+    ILoad(u8),
+    ILoadWide(u16),
     ILoad0,
     ILoad1,
     ILoad2,
@@ -530,8 +568,8 @@ pub enum JOp {
     ISub,
     IUSHR,
     IXor,
-    JSR(u16),
-    JSRW(u16),
+    JSR(i16),
+    JSRW(i32),
     L2D,
     L2F,
     L2I,
@@ -554,7 +592,7 @@ pub enum JOp {
     LLoad3,
     LMul,
     LNeg,
-    LookUpSwitch(u32, Vec<(i32,u32)>),
+    LookUpSwitch(i32, Vec<(i32,i32)>),
     LOr,
     LRem,
     LReturn,
@@ -586,872 +624,268 @@ pub enum JOp {
     SAStore,
     SIPush(u16),
     Swap,
-    TableSwitch(i32, i32, i32, Vec<u32>)
+    TableSwitch(i32, i32, i32, Vec<i32>)
 }
 
-/// Parse a single JOP
-pub fn parse_java_op<'a>(buffer: &'a [u8]) -> IResult<&'a [u8], JOp> {
-    if buffer.len() == 0 {
-        return IResult::Incomplete(Needed::Unknown);
+impl JOp {
+
+    /// The name the JVM spec gives this instruction, `invokevirtual`
+    ///
+    /// Wide forms take a `_w` suffix, as `javap` prints them.
+    pub fn mnemonic(&self) -> &'static str {
+        match *self {
+            JOp::Nop => "nop",
+            JOp::AConstNull => "aconst_null",
+            JOp::IConstM1 => "iconst_m1",
+            JOp::IConst0 => "iconst_0",
+            JOp::IConst1 => "iconst_1",
+            JOp::IConst2 => "iconst_2",
+            JOp::IConst3 => "iconst_3",
+            JOp::IConst4 => "iconst_4",
+            JOp::IConst5 => "iconst_5",
+            JOp::LConst0 => "lconst_0",
+            JOp::LConst1 => "lconst_1",
+            JOp::FConst0 => "fconst_0",
+            JOp::FConst1 => "fconst_1",
+            JOp::FConst2 => "fconst_2",
+            JOp::DConst0 => "dconst_0",
+            JOp::DConst1 => "dconst_1",
+            JOp::BiPush(..) => "bipush",
+            JOp::SIPush(..) => "sipush",
+            JOp::LDC(..) => "ldc",
+            JOp::LDCW(..) => "ldc_w",
+            JOp::LDC2W(..) => "ldc2_w",
+            JOp::ILoad(..) => "iload",
+            JOp::LLoad(..) => "lload",
+            JOp::FLoad(..) => "fload",
+            JOp::DLoad(..) => "dload",
+            JOp::ALoad(..) => "aload",
+            JOp::ILoad0 => "iload_0",
+            JOp::ILoad1 => "iload_1",
+            JOp::ILoad2 => "iload_2",
+            JOp::ILoad3 => "iload_3",
+            JOp::LLoad0 => "lload_0",
+            JOp::LLoad1 => "lload_1",
+            JOp::LLoad2 => "lload_2",
+            JOp::LLoad3 => "lload_3",
+            JOp::FLoad0 => "fload_0",
+            JOp::FLoad1 => "fload_1",
+            JOp::FLoad2 => "fload_2",
+            JOp::FLoad3 => "fload_3",
+            JOp::DLoad0 => "dload_0",
+            JOp::DLoad1 => "dload_1",
+            JOp::DLoad2 => "dload_2",
+            JOp::DLoad3 => "dload_3",
+            JOp::ALoad0 => "aload_0",
+            JOp::ALoad1 => "aload_1",
+            JOp::ALoad2 => "aload_2",
+            JOp::ALoad3 => "aload_3",
+            JOp::IALoad => "iaload",
+            JOp::LALoad => "laload",
+            JOp::FALoad => "faload",
+            JOp::DALoad => "daload",
+            JOp::AALoad => "aaload",
+            JOp::BaLoad => "baload",
+            JOp::CaLoad => "caload",
+            JOp::SALoad => "saload",
+            JOp::IStore(..) => "istore",
+            JOp::LStore(..) => "lstore",
+            JOp::FStore(..) => "fstore",
+            JOp::DStore(..) => "dstore",
+            JOp::AStore(..) => "astore",
+            JOp::IStore0 => "istore_0",
+            JOp::IStore1 => "istore_1",
+            JOp::IStore2 => "istore_2",
+            JOp::IStore3 => "istore_3",
+            JOp::LStore0 => "lstore_0",
+            JOp::LStore1 => "lstore_1",
+            JOp::LStore2 => "lstore_2",
+            JOp::LStore3 => "lstore_3",
+            JOp::FStore0 => "fstore_0",
+            JOp::FStore1 => "fstore_1",
+            JOp::FStore2 => "fstore_2",
+            JOp::FStore3 => "fstore_3",
+            JOp::DStore0 => "dstore_0",
+            JOp::DStore1 => "dstore_1",
+            JOp::DStore2 => "dstore_2",
+            JOp::DStore3 => "dstore_3",
+            JOp::AStore0 => "astore_0",
+            JOp::AStore1 => "astore_1",
+            JOp::AStore2 => "astore_2",
+            JOp::AStore3 => "astore_3",
+            JOp::IAStore => "iastore",
+            JOp::LAStore => "lastore",
+            JOp::FAStore => "fastore",
+            JOp::DAStore => "dastore",
+            JOp::AAStore => "aastore",
+            JOp::BaStore => "bastore",
+            JOp::CaStore => "castore",
+            JOp::SAStore => "sastore",
+            JOp::Pop => "pop",
+            JOp::Pop2 => "pop2",
+            JOp::Dup => "dup",
+            JOp::Dupx1 => "dup_x1",
+            JOp::Dupx2 => "dup_x2",
+            JOp::Dup2 => "dup2",
+            JOp::Dup2x1 => "dup2_x1",
+            JOp::Dup2x2 => "dup2_x2",
+            JOp::Swap => "swap",
+            JOp::IAdd => "iadd",
+            JOp::LAdd => "ladd",
+            JOp::FAdd => "fadd",
+            JOp::DAdd => "dadd",
+            JOp::ISub => "isub",
+            JOp::LSub => "lsub",
+            JOp::FSub => "fsub",
+            JOp::DSub => "dsub",
+            JOp::IMul => "imul",
+            JOp::LMul => "lmul",
+            JOp::FMul => "fmul",
+            JOp::DMul => "dmul",
+            JOp::IDiv => "idiv",
+            JOp::LDiv => "ldiv",
+            JOp::FDiv => "fdiv",
+            JOp::DDiv => "ddiv",
+            JOp::IRem => "irem",
+            JOp::LRem => "lrem",
+            JOp::FRem => "frem",
+            JOp::DRem => "drem",
+            JOp::INeg => "ineg",
+            JOp::LNeg => "lneg",
+            JOp::FNeg => "fneg",
+            JOp::DNeg => "dneg",
+            JOp::ISHL => "ishl",
+            JOp::LSHL => "lshl",
+            JOp::ISHR => "ishr",
+            JOp::LSHR => "lshr",
+            JOp::IUSHR => "iushr",
+            JOp::LUSHR => "lushr",
+            JOp::IAnd => "iand",
+            JOp::LAnd => "land",
+            JOp::IOr => "ior",
+            JOp::LOr => "lor",
+            JOp::IXor => "ixor",
+            JOp::LXor => "lxor",
+            JOp::IInc(..) => "iinc",
+            JOp::I2L => "i2l",
+            JOp::I2F => "i2f",
+            JOp::I2D => "i2d",
+            JOp::L2I => "l2i",
+            JOp::L2F => "l2f",
+            JOp::L2D => "l2d",
+            JOp::F2I => "f2i",
+            JOp::F2L => "f2l",
+            JOp::F2D => "f2d",
+            JOp::D2I => "d2i",
+            JOp::D2L => "d2l",
+            JOp::D2F => "d2f",
+            JOp::I2B => "i2b",
+            JOp::I2C => "i2c",
+            JOp::I2S => "i2s",
+            JOp::Lcmp => "lcmp",
+            JOp::FcmpL => "fcmpl",
+            JOp::FcmpG => "fcmpg",
+            JOp::DcmpL => "dcmpl",
+            JOp::DcmpG => "dcmpg",
+            JOp::IFEQ(..) => "ifeq",
+            JOp::IFNE(..) => "ifne",
+            JOp::IFLT(..) => "iflt",
+            JOp::IFGE(..) => "ifge",
+            JOp::IFGT(..) => "ifgt",
+            JOp::IFLE(..) => "ifle",
+            JOp::IFIcmpEQ(..) => "if_icmpeq",
+            JOp::IFIcmpNE(..) => "if_icmpne",
+            JOp::IFIcmpLT(..) => "if_icmplt",
+            JOp::IfIcmpGE(..) => "if_icmpge",
+            JOp::IFIcmpGT(..) => "if_icmpgt",
+            JOp::IFIcmpLE(..) => "if_icmple",
+            JOp::IFAcmpEQ(..) => "if_acmpeq",
+            JOp::IFAcmpNE(..) => "if_acmpne",
+            JOp::Goto(..) => "goto",
+            JOp::JSR(..) => "jsr",
+            JOp::Ret(..) => "ret",
+            JOp::TableSwitch(..) => "tableswitch",
+            JOp::LookUpSwitch(..) => "lookupswitch",
+            JOp::IReturn => "ireturn",
+            JOp::LReturn => "lreturn",
+            JOp::FReturn => "freturn",
+            JOp::DReturn => "dreturn",
+            JOp::AReturn => "areturn",
+            JOp::Return => "return",
+            JOp::GetStatic(..) => "getstatic",
+            JOp::PutStatic(..) => "putstatic",
+            JOp::GetField(..) => "getfield",
+            JOp::PutField(..) => "putfield",
+            JOp::InvokeVirtual(..) => "invokevirtual",
+            JOp::InvokeSpecial(..) => "invokespecial",
+            JOp::InvokeStatic(..) => "invokestatic",
+            JOp::InvokedInterface(..) => "invokeinterface",
+            JOp::InvokedDynamic(..) => "invokedynamic",
+            JOp::New(..) => "new",
+            JOp::NewArray(..) => "newarray",
+            JOp::ANewArray(..) => "anewarray",
+            JOp::ArrayLength => "arraylength",
+            JOp::AThrow => "athrow",
+            JOp::CheckCast(..) => "checkcast",
+            JOp::InstanceOf(..) => "instanceof",
+            JOp::MonitorEnter => "monitorenter",
+            JOp::MonitorExit => "monitorexit",
+            JOp::MultiAneWArray(..) => "multianewarray",
+            JOp::IFNull(..) => "ifnull",
+            JOp::IFnonNull(..) => "ifnonnull",
+            JOp::GotoW(..) => "goto_w",
+            JOp::JSRW(..) => "jsr_w",
+            JOp::BreakPoint => "breakpoint",
+            JOp::ImpDep1 => "impdep1",
+            JOp::ImpDep2 => "impdep2",
+            JOp::ILoadWide(..) => "iload_w",
+            JOp::LLoadWide(..) => "lload_w",
+            JOp::FLoadWide(..) => "fload_w",
+            JOp::DLoadWide(..) => "dload_w",
+            JOp::ALoadWide(..) => "aload_w",
+            JOp::IStoreWide(..) => "istore_w",
+            JOp::LStoreWide(..) => "lstore_w",
+            JOp::FStoreWide(..) => "fstore_w",
+            JOp::DStoreWide(..) => "dstore_w",
+            JOp::AStoreWide(..) => "astore_w",
+            JOp::IIncWide(..) => "iinc_w",
+            JOp::RetWide(..) => "ret_w",
+        }
     }
-    match parser_AALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AAStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AConstNull(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ALoadWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ALoad0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ALoad1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ALoad2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ALoad3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ANewArray(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AReturn(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ArrayLength(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AStoreWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AStore0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AStore1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AStore2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AStore3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_AThrow(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_BaLoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_BaStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_BiPush(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_BreakPoint(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_CaLoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_CaStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_CheckCast(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_D2F(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_D2I(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_D2L(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DAdd(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DAStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DcmpG(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DcmpL(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DConst0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DConst1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DDiv(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DLoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DLoadWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DLoad0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DLoad1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DLoad2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DLoad3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DMul(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DNeg(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DRem(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DStoreWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DStore0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DStore1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DStore2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DStore3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_DSub(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Dup(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Dupx1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Dupx2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Dup2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Dup2x1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Dup2x2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_F2D(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_F2I(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_F2L(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FAdd(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FAStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FcmpG(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FcmpL(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FConst0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FConst1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FConst2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FDiv(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FLoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FLoadWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FLoad0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FLoad1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FLoad2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FLoad3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FMul(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FNeg(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FRem(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FReturn(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FStoreWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FStore0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FStore1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FStore2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FStore3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_FSub(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_GetField(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_GetStatic(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Goto(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_GotoW(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_I2B(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_I2C(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_I2D(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_I2F(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_I2L(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_I2S(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IAdd(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IAnd(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IAStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConstM1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConst0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConst1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConst2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConst3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConst4(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IConst5(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IDiv(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFAcmpEQ(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFAcmpNE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFIcmpEQ(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IfIcmpGE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFIcmpGT(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFIcmpLE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFIcmpLT(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFIcmpNE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFEQ(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFGE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFGT(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFLE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFNE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFnonNull(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IFNull(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IInc(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IIncWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ILoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ILoadWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ILoad0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ILoad1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ILoad2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ILoad3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ImpDep1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ImpDep2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IMul(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_INeg(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_InstanceOf(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_InvokedDynamic(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_InvokedInterface(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_InvokeSpecial(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_InvokeStatic(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_InvokeVirtual(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IOr(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IRem(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IReturn(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ISHL(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ISHR(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IStoreWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IStore0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IStore1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IStore2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IStore3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_ISub(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IUSHR(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_IXor(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_JSR(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_JSRW(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_L2D(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_L2F(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_L2I(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LAdd(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LAnd(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LAStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Lcmp(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LConst0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LConst1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LDC(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LDCW(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LDC2W(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LDiv(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LLoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LLoadWIDE(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LLoad0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LLoad1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LLoad2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LLoad3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LMul(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LNeg(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LookUpSwitch(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LOr(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LRem(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LReturn(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LSHL(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LSHR(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LStoreWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LStore0(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LStore1(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LStore2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LStore3(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LSub(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LUSHR(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_LXor(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_MonitorEnter(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_MonitorExit(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_MultiAneWArray(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_New(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_NewArray(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Nop(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Pop(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Pop2(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_PutField(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_PutStatic(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Ret(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_RetWide(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Return(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_SALoad(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_SAStore(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_SIPush(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_Swap(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    match parser_TableSwitch(buffer) {
-        IResult::Done(x,y) => return IResult::Done(x,y),
-        _ => { }
-    };
-    IResult::Error(ErrorKind::Custom(1000u32))
 }
 
+/// Parse a single JOP
+///
+/// `pc` is the offset of `buffer` within the method's code array,
+/// it is needed to skip the padding of `TableSwitch` and `LookUpSwitch`.
+#[inline]
+pub fn parse_java_op(buffer: &[u8], pc: usize) -> IResult<&[u8], JOp> {
+    match buffer.first() {
+        Option::Some(op) => OPCODES[*op as usize](buffer, pc),
+        Option::None => IResult::Incomplete(Needed::Size(1))
+    }
+}
 
-
+#[test]
+fn test_opcode_table() {
+    fn op(buffer: &[u8]) -> JOp {
+        match parse_java_op(buffer, 0) {
+            IResult::Done(rem, op) => {
+                assert!(rem.is_empty());
+                op
+            },
+            _ => panic!("failed to decode {:?}", buffer)
+        }
+    }
+    assert_eq!(op(b"\x5C"), JOp::Dup2);
+    assert_eq!(op(b"\x5E"), JOp::Dup2x2);
+    assert_eq!(op(b"\x39\x04"), JOp::DStore(4));
+    assert_eq!(op(b"\xAF"), JOp::DReturn);
+    assert_eq!(op(b"\x9B\x00\x03"), JOp::IFLT(3));
+    assert_eq!(op(b"\x9E\x00\x03"), JOp::IFLE(3));
+    assert_eq!(op(b"\x15\x07"), JOp::ILoad(7));
+    assert_eq!(op(b"\xC4\x15\x01\x00"), JOp::ILoadWide(256));
+    assert_eq!(op(b"\x84\x01\xFF"), JOp::IInc(1, -1));
+    assert_eq!(op(b"\xC4\x84\x01\x00\xFF\xFE"), JOp::IIncWide(256, -2));
+    assert_eq!(op(b"\xFE"), JOp::ImpDep1);
+}
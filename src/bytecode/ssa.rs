@@ -0,0 +1,1596 @@
+//! Register based SSA form.
+//!
+//! `lift` turns the stack code of a method into a `Function`: blocks of
+//! statements over numbered values, each assigned exactly once, with phi
+//! nodes where control flow merges. Operand stack shuffling and local
+//! variable traffic disappear, leaving computations and side effects in
+//! their original order. `lower` turns a `Function` back into a code
+//! array.
+//!
+//! Values carry their JVM computational type, so `byte`, `char`,
+//! `short` and `boolean` are all `LocalType::Int`. An exception handler
+//! may be entered from any instruction it covers, so its phis take the
+//! value a local has on entry to each covered block. Lifting splits
+//! covered blocks after every local store to keep that exact.
+
+use super::super::clazz::{CodeAttribute, ConstantsPool, ExceptionEntry, FieldType, MethodType, PoolMembers};
+use super::cfg::{Cfg, CfgFault};
+use super::decoder::Decoder;
+use super::encoder::{EncodeFault, assemble};
+use super::frames::SuperclassOracle;
+use super::hierarchy::OBJECT;
+use super::labels::{Instruction, Jump, Label};
+use super::locals::{LocalAccess, LocalType, local_op};
+use super::ops::JOp;
+use super::stack::{StackFault, StackInfo};
+use super::verify::{Frame, MethodContext, VType, VerifyFault, infer_frames};
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+
+/// Index of a value within `Function::values`
+pub type ValueId = usize;
+
+/// Index of a block within `Function::blocks`
+pub type BlockId = usize;
+
+/// A constant value
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub enum Const {
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    Null,
+    /// A loadable constant pool entry, as used by `ldc`
+    Pool(u16)
+}
+
+/// Two operand arithmetic and bitwise operations
+///
+/// Shifts of a long take an int shift distance.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+    Shl,
+    Shr,
+    UShr,
+    And,
+    Or,
+    Xor
+}
+
+/// `lcmp`, `fcmpl`, `fcmpg`, `dcmpl` and `dcmpg`
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Comparison {
+    Long,
+    FloatL,
+    FloatG,
+    DoubleL,
+    DoubleG
+}
+
+/// `i2b`, `i2c` and `i2s`
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Narrowing {
+    Byte,
+    Char,
+    Short
+}
+
+/// Element types of array loads and stores, `Byte` covers `boolean`
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum ArrayKind {
+    Int,
+    Long,
+    Float,
+    Double,
+    Reference,
+    Byte,
+    Char,
+    Short
+}
+impl ArrayKind {
+
+    /// The computational type of an element
+    pub fn element(&self) -> LocalType {
+        match *self {
+            ArrayKind::Long => LocalType::Long,
+            ArrayKind::Float => LocalType::Float,
+            ArrayKind::Double => LocalType::Double,
+            ArrayKind::Reference => LocalType::Reference,
+            _ => LocalType::Int
+        }
+    }
+}
+
+/// The five `invoke*` instructions
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum InvokeKind {
+    Virtual,
+    Special,
+    Static,
+    Interface,
+    Dynamic
+}
+
+/// Conditions of `Terminator::If`
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum Cond {
+    Eq,
+    Ne,
+    Lt,
+    Ge,
+    Gt,
+    Le
+}
+
+/// A computation or side effect
+///
+/// `u16` operands are constant pool indices.
+#[derive(Clone,Debug,PartialEq)]
+pub enum Inst {
+    Const(Const),
+    Binary(BinOp, LocalType, ValueId, ValueId),
+    Neg(LocalType, ValueId),
+    /// Conversion between two computational types
+    Convert(LocalType, LocalType, ValueId),
+    Narrow(Narrowing, ValueId),
+    Compare(Comparison, ValueId, ValueId),
+    /// Array then index
+    ArrayLoad(ArrayKind, ValueId, ValueId),
+    /// Array, index, then the stored value
+    ArrayStore(ArrayKind, ValueId, ValueId, ValueId),
+    ArrayLength(ValueId),
+    /// Primitive array with the `newarray` type code
+    NewArray(u8, ValueId),
+    ANewArray(u16, ValueId),
+    MultiANewArray(u16, Vec<ValueId>),
+    /// An uninitialized object, initialized by invoking `<init>` on it
+    New(u16),
+    GetField(u16, ValueId),
+    /// Object then the stored value
+    PutField(u16, ValueId, ValueId),
+    GetStatic(u16),
+    PutStatic(u16, ValueId),
+    /// Arguments, with the receiver first
+    Invoke(InvokeKind, u16, Vec<ValueId>),
+    CheckCast(u16, ValueId),
+    InstanceOf(u16, ValueId),
+    MonitorEnter(ValueId),
+    MonitorExit(ValueId)
+}
+impl Inst {
+
+    /// Values read by this instruction, in evaluation order
+    pub fn operands(&self) -> Vec<ValueId> {
+        let mut inst = self.clone();
+        inst.operands_mut().into_iter().map(|v| *v).collect()
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match *self {
+            Inst::Const(_) |
+            Inst::New(_) |
+            Inst::GetStatic(_) => Vec::new(),
+            Inst::Neg(_, ref mut a) |
+            Inst::Convert(_, _, ref mut a) |
+            Inst::Narrow(_, ref mut a) |
+            Inst::ArrayLength(ref mut a) |
+            Inst::NewArray(_, ref mut a) |
+            Inst::ANewArray(_, ref mut a) |
+            Inst::GetField(_, ref mut a) |
+            Inst::PutStatic(_, ref mut a) |
+            Inst::CheckCast(_, ref mut a) |
+            Inst::InstanceOf(_, ref mut a) |
+            Inst::MonitorEnter(ref mut a) |
+            Inst::MonitorExit(ref mut a) => vec![a],
+            Inst::Binary(_, _, ref mut a, ref mut b) |
+            Inst::Compare(_, ref mut a, ref mut b) |
+            Inst::ArrayLoad(_, ref mut a, ref mut b) |
+            Inst::PutField(_, ref mut a, ref mut b) => vec![a, b],
+            Inst::ArrayStore(_, ref mut a, ref mut b, ref mut c) => vec![a, b, c],
+            Inst::MultiANewArray(_, ref mut args) |
+            Inst::Invoke(_, _, ref mut args) => args.iter_mut().collect()
+        }
+    }
+
+    /// Can the instruction be removed when its result is unused
+    ///
+    /// Anything that may throw, or touches memory, is not pure.
+    pub fn is_pure(&self) -> bool {
+        match *self {
+            Inst::Binary(BinOp::Div, t, _, _) |
+            Inst::Binary(BinOp::Rem, t, _, _) => t == LocalType::Float || t == LocalType::Double,
+            Inst::Const(_) |
+            Inst::Binary(..) |
+            Inst::Neg(..) |
+            Inst::Convert(..) |
+            Inst::Narrow(..) |
+            Inst::Compare(..) |
+            Inst::InstanceOf(..) => true,
+            _ => false
+        }
+    }
+}
+
+/// One statement, `dest` is set exactly when `inst` produces a value
+#[derive(Clone,Debug,PartialEq)]
+pub struct Stmt {
+    pub dest: Option<ValueId>,
+    pub inst: Inst
+}
+
+/// Selects a value by the predecessor control arrived from
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Phi {
+    pub dest: ValueId,
+    pub args: Vec<(BlockId, ValueId)>
+}
+
+/// How control leaves a block
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Terminator {
+    Goto(BlockId),
+    /// Compares `lhs` against `rhs`, or against zero or `null` when
+    /// there is no `rhs`. References only support `Eq` and `Ne`.
+    If { cond: Cond, lhs: ValueId, rhs: Option<ValueId>, taken: BlockId, fallthrough: BlockId },
+    Switch { value: ValueId, default: BlockId, cases: Vec<(i32, BlockId)> },
+    Return(Option<ValueId>),
+    Throw(ValueId)
+}
+impl Terminator {
+
+    /// Blocks control may continue in, exception handlers excluded
+    pub fn successors(&self) -> Vec<BlockId> {
+        let mut term = self.clone();
+        term.targets_mut().into_iter().map(|b| *b).collect()
+    }
+
+    fn targets_mut(&mut self) -> Vec<&mut BlockId> {
+        match *self {
+            Terminator::Goto(ref mut b) => vec![b],
+            Terminator::If { ref mut taken, ref mut fallthrough, .. } => vec![taken, fallthrough],
+            Terminator::Switch { ref mut default, ref mut cases, .. } => {
+                let mut v = vec![default];
+                v.extend(cases.iter_mut().map(|c| &mut c.1));
+                v
+            },
+            Terminator::Return(_) |
+            Terminator::Throw(_) => Vec::new()
+        }
+    }
+
+    /// Values read by the terminator
+    pub fn operands(&self) -> Vec<ValueId> {
+        let mut term = self.clone();
+        term.operands_mut().into_iter().map(|v| *v).collect()
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut ValueId> {
+        match *self {
+            Terminator::Goto(_) |
+            Terminator::Return(None) => Vec::new(),
+            Terminator::If { ref mut lhs, rhs: None, .. } => vec![lhs],
+            Terminator::If { ref mut lhs, rhs: Some(ref mut rhs), .. } => vec![lhs, rhs],
+            Terminator::Switch { ref mut value, .. } |
+            Terminator::Return(Some(ref mut value)) |
+            Terminator::Throw(ref mut value) => vec![value]
+        }
+    }
+}
+
+/// A basic block
+#[derive(Clone,Debug,PartialEq)]
+pub struct Block {
+    pub phis: Vec<Phi>,
+    /// The caught exception, for exception handlers
+    pub exception: Option<ValueId>,
+    pub stmts: Vec<Stmt>,
+    pub term: Terminator,
+    /// Handlers covering every statement and the terminator, in the
+    /// order they are tried, with their `catch_type`
+    pub handlers: Vec<(u16, BlockId)>
+}
+
+/// A method in SSA form
+#[derive(Clone,Debug,PartialEq)]
+pub struct Function {
+    /// Parameters, with `this` first for instance methods
+    pub params: Vec<ValueId>,
+    /// Type of every value
+    pub values: Vec<LocalType>,
+    /// Blocks, the entry block is `0`
+    pub blocks: Vec<Block>
+}
+impl Function {
+
+    /// Allocate a value, to be defined by a new statement or phi
+    pub fn new_value(&mut self, ty: LocalType) -> ValueId {
+        self.values.push(ty);
+        self.values.len() - 1
+    }
+
+    /// Blocks that can pass control to `block`, including blocks it
+    /// is an exception handler for
+    pub fn predecessors(&self, block: BlockId) -> Vec<BlockId> {
+        (0..self.blocks.len())
+            .filter(|b| {
+                let b = &self.blocks[*b];
+                b.term.successors().contains(&block) || b.handlers.iter().any(|h| h.1 == block)
+            })
+            .collect()
+    }
+}
+
+/// Why a method could not be lifted
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum LiftFault {
+    /// The code failed type inference
+    Verify(VerifyFault),
+    Cfg(CfgFault),
+    /// `jsr` and `ret` are not supported
+    Subroutine,
+    /// An exception handler is also reached by normal control flow
+    HandlerJump,
+    BadConstant(u16),
+    /// The operand stack or locals do not agree with inferred types
+    Inconsistent
+}
+
+/// A lifting failure and the pc it concerns
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct LiftError {
+    pub pc: usize,
+    pub reason: LiftFault
+}
+impl fmt::Display for LiftError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            LiftFault::Verify(ref x) => write!(f, "type inference failed ({:?})", x),
+            LiftFault::Cfg(ref x) => write!(f, "invalid control flow ({:?})", x),
+            LiftFault::Subroutine => write!(f, "jsr/ret is not supported"),
+            LiftFault::HandlerJump => write!(f, "exception handler is a branch target"),
+            LiftFault::BadConstant(x) => write!(f, "invalid constant #{}", x),
+            LiftFault::Inconsistent => write!(f, "inconsistent operand stack or locals")
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+type Res<T> = Result<T, LiftFault>;
+
+const BINARY: &[(JOp, BinOp, LocalType)] = &[
+    (JOp::IAdd, BinOp::Add, LocalType::Int),
+    (JOp::LAdd, BinOp::Add, LocalType::Long),
+    (JOp::FAdd, BinOp::Add, LocalType::Float),
+    (JOp::DAdd, BinOp::Add, LocalType::Double),
+    (JOp::ISub, BinOp::Sub, LocalType::Int),
+    (JOp::LSub, BinOp::Sub, LocalType::Long),
+    (JOp::FSub, BinOp::Sub, LocalType::Float),
+    (JOp::DSub, BinOp::Sub, LocalType::Double),
+    (JOp::IMul, BinOp::Mul, LocalType::Int),
+    (JOp::LMul, BinOp::Mul, LocalType::Long),
+    (JOp::FMul, BinOp::Mul, LocalType::Float),
+    (JOp::DMul, BinOp::Mul, LocalType::Double),
+    (JOp::IDiv, BinOp::Div, LocalType::Int),
+    (JOp::LDiv, BinOp::Div, LocalType::Long),
+    (JOp::FDiv, BinOp::Div, LocalType::Float),
+    (JOp::DDiv, BinOp::Div, LocalType::Double),
+    (JOp::IRem, BinOp::Rem, LocalType::Int),
+    (JOp::LRem, BinOp::Rem, LocalType::Long),
+    (JOp::FRem, BinOp::Rem, LocalType::Float),
+    (JOp::DRem, BinOp::Rem, LocalType::Double),
+    (JOp::ISHL, BinOp::Shl, LocalType::Int),
+    (JOp::LSHL, BinOp::Shl, LocalType::Long),
+    (JOp::ISHR, BinOp::Shr, LocalType::Int),
+    (JOp::LSHR, BinOp::Shr, LocalType::Long),
+    (JOp::IUSHR, BinOp::UShr, LocalType::Int),
+    (JOp::LUSHR, BinOp::UShr, LocalType::Long),
+    (JOp::IAnd, BinOp::And, LocalType::Int),
+    (JOp::LAnd, BinOp::And, LocalType::Long),
+    (JOp::IOr, BinOp::Or, LocalType::Int),
+    (JOp::LOr, BinOp::Or, LocalType::Long),
+    (JOp::IXor, BinOp::Xor, LocalType::Int),
+    (JOp::LXor, BinOp::Xor, LocalType::Long)
+];
+
+const NEGATE: &[(JOp, LocalType)] = &[
+    (JOp::INeg, LocalType::Int),
+    (JOp::LNeg, LocalType::Long),
+    (JOp::FNeg, LocalType::Float),
+    (JOp::DNeg, LocalType::Double)
+];
+
+const CONVERT: &[(JOp, LocalType, LocalType)] = &[
+    (JOp::I2L, LocalType::Int, LocalType::Long),
+    (JOp::I2F, LocalType::Int, LocalType::Float),
+    (JOp::I2D, LocalType::Int, LocalType::Double),
+    (JOp::L2I, LocalType::Long, LocalType::Int),
+    (JOp::L2F, LocalType::Long, LocalType::Float),
+    (JOp::L2D, LocalType::Long, LocalType::Double),
+    (JOp::F2I, LocalType::Float, LocalType::Int),
+    (JOp::F2L, LocalType::Float, LocalType::Long),
+    (JOp::F2D, LocalType::Float, LocalType::Double),
+    (JOp::D2I, LocalType::Double, LocalType::Int),
+    (JOp::D2L, LocalType::Double, LocalType::Long),
+    (JOp::D2F, LocalType::Double, LocalType::Float)
+];
+
+const NARROW: &[(JOp, Narrowing)] = &[
+    (JOp::I2B, Narrowing::Byte),
+    (JOp::I2C, Narrowing::Char),
+    (JOp::I2S, Narrowing::Short)
+];
+
+const COMPARE: &[(JOp, Comparison)] = &[
+    (JOp::Lcmp, Comparison::Long),
+    (JOp::FcmpL, Comparison::FloatL),
+    (JOp::FcmpG, Comparison::FloatG),
+    (JOp::DcmpL, Comparison::DoubleL),
+    (JOp::DcmpG, Comparison::DoubleG)
+];
+
+const ARRAY_LOAD: &[(JOp, ArrayKind)] = &[
+    (JOp::IALoad, ArrayKind::Int),
+    (JOp::LALoad, ArrayKind::Long),
+    (JOp::FALoad, ArrayKind::Float),
+    (JOp::DALoad, ArrayKind::Double),
+    (JOp::AALoad, ArrayKind::Reference),
+    (JOp::BaLoad, ArrayKind::Byte),
+    (JOp::CaLoad, ArrayKind::Char),
+    (JOp::SALoad, ArrayKind::Short)
+];
+
+const ARRAY_STORE: &[(JOp, ArrayKind)] = &[
+    (JOp::IAStore, ArrayKind::Int),
+    (JOp::LAStore, ArrayKind::Long),
+    (JOp::FAStore, ArrayKind::Float),
+    (JOp::DAStore, ArrayKind::Double),
+    (JOp::AAStore, ArrayKind::Reference),
+    (JOp::BaStore, ArrayKind::Byte),
+    (JOp::CaStore, ArrayKind::Char),
+    (JOp::SAStore, ArrayKind::Short)
+];
+
+const CONDITIONS: &[(Jump, Cond)] = &[
+    (Jump::IFEQ, Cond::Eq),
+    (Jump::IFNE, Cond::Ne),
+    (Jump::IFLT, Cond::Lt),
+    (Jump::IFGE, Cond::Ge),
+    (Jump::IFGT, Cond::Gt),
+    (Jump::IFLE, Cond::Le),
+    (Jump::IFIcmpEQ, Cond::Eq),
+    (Jump::IFIcmpNE, Cond::Ne),
+    (Jump::IFIcmpLT, Cond::Lt),
+    (Jump::IFIcmpGE, Cond::Ge),
+    (Jump::IFIcmpGT, Cond::Gt),
+    (Jump::IFIcmpLE, Cond::Le),
+    (Jump::IFAcmpEQ, Cond::Eq),
+    (Jump::IFAcmpNE, Cond::Ne),
+    (Jump::IFNull, Cond::Eq),
+    (Jump::IFnonNull, Cond::Ne)
+];
+
+impl JOp {
+
+    /// The arithmetic or bitwise operation, and its operand type
+    pub fn binary_op(&self) -> Option<(BinOp, LocalType)> {
+        BINARY.iter().find(|x| x.0 == *self).map(|x| (x.1, x.2))
+    }
+
+    /// The operand type of a negation
+    pub fn negation(&self) -> Option<LocalType> {
+        NEGATE.iter().find(|x| x.0 == *self).map(|x| x.1)
+    }
+
+    /// The source and result types of a conversion
+    pub fn conversion(&self) -> Option<(LocalType, LocalType)> {
+        CONVERT.iter().find(|x| x.0 == *self).map(|x| (x.1, x.2))
+    }
+
+    pub fn narrowing(&self) -> Option<Narrowing> {
+        NARROW.iter().find(|x| x.0 == *self).map(|x| x.1)
+    }
+
+    pub fn comparison(&self) -> Option<Comparison> {
+        COMPARE.iter().find(|x| x.0 == *self).map(|x| x.1)
+    }
+
+    pub fn array_load(&self) -> Option<ArrayKind> {
+        ARRAY_LOAD.iter().find(|x| x.0 == *self).map(|x| x.1)
+    }
+
+    pub fn array_store(&self) -> Option<ArrayKind> {
+        ARRAY_STORE.iter().find(|x| x.0 == *self).map(|x| x.1)
+    }
+}
+
+impl Cond {
+
+    /// The condition a conditional branch tests, `None` for `Goto` and
+    /// `JSR`
+    pub fn from_jump(jump: Jump) -> Option<Cond> {
+        CONDITIONS.iter().find(|x| x.0 == jump).map(|x| x.1)
+    }
+}
+
+/// Does a conditional branch compare two operands
+fn compares_two(jump: Jump) -> bool {
+    matches!(jump,
+        Jump::IFIcmpEQ | Jump::IFIcmpNE | Jump::IFIcmpLT | Jump::IFIcmpGE |
+        Jump::IFIcmpGT | Jump::IFIcmpLE | Jump::IFAcmpEQ | Jump::IFAcmpNE)
+}
+
+/// The slots of a frame holding a value, with its type
+fn typed_slots(slots: &[VType]) -> Res<Vec<(usize, LocalType)>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < slots.len() {
+        let t = match slots[i] {
+            VType::Top => None,
+            VType::Int => Some(LocalType::Int),
+            VType::Float => Some(LocalType::Float),
+            VType::Long => Some(LocalType::Long),
+            VType::Double => Some(LocalType::Double),
+            VType::ReturnAddress => return Err(LiftFault::Subroutine),
+            _ => Some(LocalType::Reference)
+        };
+        if let Option::Some(t) = t {
+            out.push((i, t));
+        }
+        i += slots[i].width();
+    }
+    Ok(out)
+}
+
+/// Abstract machine state while lifting a block
+#[derive(Clone,Debug)]
+struct State {
+    /// Value of each local slot, set on the lower slot of wide values
+    locals: Vec<Option<ValueId>>,
+    /// One entry per value, wide values included
+    stack: Vec<ValueId>
+}
+
+/// Where a phi of the block entry state comes from
+#[derive(Clone,Copy,Debug)]
+enum PhiSlot {
+    Local(usize),
+    Stack(usize)
+}
+
+struct Lifter<'p, 'a: 'p> {
+    pool: &'p ConstantsPool<'a>,
+    values: Vec<LocalType>
+}
+impl<'p, 'a> Lifter<'p, 'a> {
+
+    fn value(&mut self, ty: LocalType) -> ValueId {
+        self.values.push(ty);
+        self.values.len() - 1
+    }
+
+    fn width(&self, v: ValueId) -> usize {
+        self.values[v].width() as usize
+    }
+
+    fn emit(&mut self, stmts: &mut Vec<Stmt>, ty: Option<LocalType>, inst: Inst) -> Option<ValueId> {
+        let dest = ty.map(|t| self.value(t));
+        stmts.push(Stmt { dest, inst });
+        dest
+    }
+
+    fn push(&mut self, st: &mut State, stmts: &mut Vec<Stmt>, ty: LocalType, inst: Inst) {
+        let v = self.emit(stmts, Some(ty), inst).unwrap();
+        st.stack.push(v);
+    }
+
+    fn pop(&self, st: &mut State) -> Res<ValueId> {
+        st.stack.pop().ok_or(LiftFault::Inconsistent)
+    }
+
+    fn pop_n(&self, st: &mut State, n: usize) -> Res<Vec<ValueId>> {
+        if n > st.stack.len() {
+            return Err(LiftFault::Inconsistent);
+        }
+        let at = st.stack.len() - n;
+        Ok(st.stack.split_off(at))
+    }
+
+    fn local(&self, st: &State, index: usize) -> Res<ValueId> {
+        st.locals.get(index).cloned().and_then(|x| x).ok_or(LiftFault::Inconsistent)
+    }
+
+    fn store(&self, st: &mut State, index: usize, v: ValueId) -> Res<()> {
+        let width = self.width(v);
+        if index + width > st.locals.len() {
+            return Err(LiftFault::Inconsistent);
+        }
+        if index > 0 && st.locals[index - 1].is_some_and(|x| self.width(x) == 2) {
+            st.locals[index - 1] = None;
+        }
+        st.locals[index] = Some(v);
+        if width == 2 {
+            st.locals[index + 1] = None;
+        }
+        Ok(())
+    }
+
+    /// Number of values at the top of `stack[..end]` making up `slots`
+    fn values_in(&self, stack: &[ValueId], end: usize, slots: usize) -> Res<usize> {
+        let (mut n, mut total) = (0, 0);
+        while total < slots {
+            if n >= end {
+                return Err(LiftFault::Inconsistent);
+            }
+            total += self.width(stack[end - 1 - n]);
+            n += 1;
+        }
+        if total == slots {
+            Ok(n)
+        } else {
+            Err(LiftFault::Inconsistent)
+        }
+    }
+
+    /// The `dup` family, copy the top `copy` slots beneath the `skip`
+    /// slots below them
+    fn duplicate(&self, st: &mut State, copy: usize, skip: usize) -> Res<()> {
+        let copied = self.values_in(&st.stack, st.stack.len(), copy)?;
+        let top = st.stack.len() - copied;
+        let at = top - self.values_in(&st.stack, top, skip)?;
+        let values = st.stack[top..].to_vec();
+        for (i, v) in values.into_iter().enumerate() {
+            st.stack.insert(at + i, v);
+        }
+        Ok(())
+    }
+
+    fn constant_type(&self, index: u16) -> Res<LocalType> {
+        Ok(match self.pool.get(index) {
+            Option::Some(&PoolMembers::Integer(_)) => LocalType::Int,
+            Option::Some(&PoolMembers::Float(_)) => LocalType::Float,
+            Option::Some(&PoolMembers::Long(_)) => LocalType::Long,
+            Option::Some(&PoolMembers::Double(_)) => LocalType::Double,
+            Option::Some(&PoolMembers::Str(_)) |
+            Option::Some(&PoolMembers::ClassInfo(_)) |
+            Option::Some(&PoolMembers::MethodType(_)) |
+            Option::Some(&PoolMembers::MethodHandle(..)) => LocalType::Reference,
+            _ => return Err(LiftFault::BadConstant(index))
+        })
+    }
+
+    fn field_type(&self, index: u16) -> Res<LocalType> {
+        self.pool.get_member_ref(index)
+            .and_then(|m| FieldType::parse(&m.descriptor))
+            .map(|t| LocalType::computational(&t))
+            .ok_or(LiftFault::BadConstant(index))
+    }
+
+    fn method_type(&self, kind: InvokeKind, index: u16) -> Res<MethodType> {
+        let desc = match (kind, self.pool.get(index)) {
+            (InvokeKind::Dynamic, Option::Some(&PoolMembers::InvokeDynamic(_, nat))) => {
+                self.pool.get_name_and_type(nat).map(|x| x.1.into_owned())
+            },
+            (InvokeKind::Dynamic, _) => None,
+            _ => self.pool.get_member_ref(index).map(|m| m.descriptor.into_owned())
+        };
+        desc.and_then(|d| MethodType::parse(&d)).ok_or(LiftFault::BadConstant(index))
+    }
+
+    /// Lift one instruction, returning the terminator if it ends the
+    /// block. Terminator targets are pcs.
+    fn step(&mut self, pc: usize, op: &JOp, st: &mut State, stmts: &mut Vec<Stmt>) -> Res<Option<Terminator>> {
+        if let Option::Some((access, index)) = op.local_access() {
+            let index = index as usize;
+            match access {
+                LocalAccess::Load(_) => {
+                    let v = self.local(st, index)?;
+                    st.stack.push(v);
+                },
+                LocalAccess::Store(_) => {
+                    let v = self.pop(st)?;
+                    self.store(st, index, v)?;
+                },
+                LocalAccess::Increment(c) => {
+                    let old = self.local(st, index)?;
+                    let c = self.emit(stmts, Some(LocalType::Int), Inst::Const(Const::Int(c as i32))).unwrap();
+                    let new = self.emit(stmts, Some(LocalType::Int), Inst::Binary(BinOp::Add, LocalType::Int, old, c)).unwrap();
+                    self.store(st, index, new)?;
+                },
+                LocalAccess::Ret => return Err(LiftFault::Subroutine)
+            };
+            return Ok(None);
+        }
+        if let Option::Some((bin, ty)) = op.binary_op() {
+            let b = self.pop(st)?;
+            let a = self.pop(st)?;
+            self.push(st, stmts, ty, Inst::Binary(bin, ty, a, b));
+            return Ok(None);
+        }
+        if let Option::Some(ty) = op.negation() {
+            let a = self.pop(st)?;
+            self.push(st, stmts, ty, Inst::Neg(ty, a));
+            return Ok(None);
+        }
+        if let Option::Some((from, to)) = op.conversion() {
+            let a = self.pop(st)?;
+            self.push(st, stmts, to, Inst::Convert(from, to, a));
+            return Ok(None);
+        }
+        if let Option::Some(n) = op.narrowing() {
+            let a = self.pop(st)?;
+            self.push(st, stmts, LocalType::Int, Inst::Narrow(n, a));
+            return Ok(None);
+        }
+        if let Option::Some(c) = op.comparison() {
+            let b = self.pop(st)?;
+            let a = self.pop(st)?;
+            self.push(st, stmts, LocalType::Int, Inst::Compare(c, a, b));
+            return Ok(None);
+        }
+        if let Option::Some(kind) = op.array_load() {
+            let index = self.pop(st)?;
+            let array = self.pop(st)?;
+            self.push(st, stmts, kind.element(), Inst::ArrayLoad(kind, array, index));
+            return Ok(None);
+        }
+        if let Option::Some(kind) = op.array_store() {
+            let value = self.pop(st)?;
+            let index = self.pop(st)?;
+            let array = self.pop(st)?;
+            self.emit(stmts, None, Inst::ArrayStore(kind, array, index, value));
+            return Ok(None);
+        }
+        if let Option::Some((jump, _)) = Jump::from_op(op) {
+            let target = op.branch_targets(pc)[0] as usize;
+            if jump == Jump::Goto {
+                return Ok(Some(Terminator::Goto(target)));
+            }
+            let cond = Cond::from_jump(jump).ok_or(LiftFault::Subroutine)?;
+            let rhs = if compares_two(jump) { Some(self.pop(st)?) } else { None };
+            let lhs = self.pop(st)?;
+            let fallthrough = pc + op.encoded_len(pc);
+            return Ok(Some(Terminator::If { cond, lhs, rhs, taken: target, fallthrough }));
+        }
+        let int = |x: i32| Inst::Const(Const::Int(x));
+        match *op {
+            JOp::Nop => { },
+            JOp::AConstNull => self.push(st, stmts, LocalType::Reference, Inst::Const(Const::Null)),
+            JOp::IConstM1 => self.push(st, stmts, LocalType::Int, int(-1)),
+            JOp::IConst0 => self.push(st, stmts, LocalType::Int, int(0)),
+            JOp::IConst1 => self.push(st, stmts, LocalType::Int, int(1)),
+            JOp::IConst2 => self.push(st, stmts, LocalType::Int, int(2)),
+            JOp::IConst3 => self.push(st, stmts, LocalType::Int, int(3)),
+            JOp::IConst4 => self.push(st, stmts, LocalType::Int, int(4)),
+            JOp::IConst5 => self.push(st, stmts, LocalType::Int, int(5)),
+            JOp::BiPush(x) => self.push(st, stmts, LocalType::Int, int(x as i8 as i32)),
+            JOp::SIPush(x) => self.push(st, stmts, LocalType::Int, int(x as i16 as i32)),
+            JOp::LConst0 => self.push(st, stmts, LocalType::Long, Inst::Const(Const::Long(0))),
+            JOp::LConst1 => self.push(st, stmts, LocalType::Long, Inst::Const(Const::Long(1))),
+            JOp::FConst0 => self.push(st, stmts, LocalType::Float, Inst::Const(Const::Float(0.0))),
+            JOp::FConst1 => self.push(st, stmts, LocalType::Float, Inst::Const(Const::Float(1.0))),
+            JOp::FConst2 => self.push(st, stmts, LocalType::Float, Inst::Const(Const::Float(2.0))),
+            JOp::DConst0 => self.push(st, stmts, LocalType::Double, Inst::Const(Const::Double(0.0))),
+            JOp::DConst1 => self.push(st, stmts, LocalType::Double, Inst::Const(Const::Double(1.0))),
+            JOp::LDC(x) => {
+                let ty = self.constant_type(x as u16)?;
+                self.push(st, stmts, ty, Inst::Const(Const::Pool(x as u16)));
+            },
+            JOp::LDCW(x) |
+            JOp::LDC2W(x) => {
+                let ty = self.constant_type(x)?;
+                self.push(st, stmts, ty, Inst::Const(Const::Pool(x)));
+            },
+            JOp::Pop => {
+                self.pop(st)?;
+            },
+            JOp::Pop2 => {
+                let n = self.values_in(&st.stack, st.stack.len(), 2)?;
+                self.pop_n(st, n)?;
+            },
+            JOp::Dup => self.duplicate(st, 1, 0)?,
+            JOp::Dupx1 => self.duplicate(st, 1, 1)?,
+            JOp::Dupx2 => self.duplicate(st, 1, 2)?,
+            JOp::Dup2 => self.duplicate(st, 2, 0)?,
+            JOp::Dup2x1 => self.duplicate(st, 2, 1)?,
+            JOp::Dup2x2 => self.duplicate(st, 2, 2)?,
+            JOp::Swap => {
+                let a = self.pop(st)?;
+                let b = self.pop(st)?;
+                st.stack.push(a);
+                st.stack.push(b);
+            },
+            JOp::ArrayLength => {
+                let a = self.pop(st)?;
+                self.push(st, stmts, LocalType::Int, Inst::ArrayLength(a));
+            },
+            JOp::NewArray(t) => {
+                let n = self.pop(st)?;
+                self.push(st, stmts, LocalType::Reference, Inst::NewArray(t, n));
+            },
+            JOp::ANewArray(x) => {
+                let n = self.pop(st)?;
+                self.push(st, stmts, LocalType::Reference, Inst::ANewArray(x, n));
+            },
+            JOp::MultiAneWArray(x, dims) => {
+                let dims = self.pop_n(st, dims as usize)?;
+                self.push(st, stmts, LocalType::Reference, Inst::MultiANewArray(x, dims));
+            },
+            JOp::New(x) => self.push(st, stmts, LocalType::Reference, Inst::New(x)),
+            JOp::CheckCast(x) => {
+                let a = self.pop(st)?;
+                self.push(st, stmts, LocalType::Reference, Inst::CheckCast(x, a));
+            },
+            JOp::InstanceOf(x) => {
+                let a = self.pop(st)?;
+                self.push(st, stmts, LocalType::Int, Inst::InstanceOf(x, a));
+            },
+            JOp::GetStatic(x) => {
+                let ty = self.field_type(x)?;
+                self.push(st, stmts, ty, Inst::GetStatic(x));
+            },
+            JOp::PutStatic(x) => {
+                let v = self.pop(st)?;
+                self.emit(stmts, None, Inst::PutStatic(x, v));
+            },
+            JOp::GetField(x) => {
+                let ty = self.field_type(x)?;
+                let obj = self.pop(st)?;
+                self.push(st, stmts, ty, Inst::GetField(x, obj));
+            },
+            JOp::PutField(x) => {
+                let v = self.pop(st)?;
+                let obj = self.pop(st)?;
+                self.emit(stmts, None, Inst::PutField(x, obj, v));
+            },
+            JOp::InvokeVirtual(x) |
+            JOp::InvokeSpecial(x) |
+            JOp::InvokeStatic(x) |
+            JOp::InvokedInterface(x, _) |
+            JOp::InvokedDynamic(x) => {
+                let kind = match *op {
+                    JOp::InvokeVirtual(_) => InvokeKind::Virtual,
+                    JOp::InvokeSpecial(_) => InvokeKind::Special,
+                    JOp::InvokeStatic(_) => InvokeKind::Static,
+                    JOp::InvokedInterface(..) => InvokeKind::Interface,
+                    _ => InvokeKind::Dynamic
+                };
+                let m = self.method_type(kind, x)?;
+                let receiver = !matches!(kind, InvokeKind::Static | InvokeKind::Dynamic);
+                let args = self.pop_n(st, m.params.len() + receiver as usize)?;
+                let ret = m.ret.as_ref().map(LocalType::computational);
+                if let Option::Some(v) = self.emit(stmts, ret, Inst::Invoke(kind, x, args)) {
+                    st.stack.push(v);
+                }
+            },
+            JOp::MonitorEnter => {
+                let a = self.pop(st)?;
+                self.emit(stmts, None, Inst::MonitorEnter(a));
+            },
+            JOp::MonitorExit => {
+                let a = self.pop(st)?;
+                self.emit(stmts, None, Inst::MonitorExit(a));
+            },
+            JOp::AThrow => return Ok(Some(Terminator::Throw(self.pop(st)?))),
+            JOp::Return => return Ok(Some(Terminator::Return(None))),
+            JOp::IReturn |
+            JOp::LReturn |
+            JOp::FReturn |
+            JOp::DReturn |
+            JOp::AReturn => return Ok(Some(Terminator::Return(Some(self.pop(st)?)))),
+            JOp::TableSwitch(..) |
+            JOp::LookUpSwitch(..) => {
+                let value = self.pop(st)?;
+                let targets = op.branch_targets(pc);
+                let keys: Vec<i32> = match *op {
+                    JOp::TableSwitch(_, low, _, ref offsets) => (0..offsets.len()).map(|i| low.wrapping_add(i as i32)).collect(),
+                    JOp::LookUpSwitch(_, ref pairs) => pairs.iter().map(|p| p.0).collect(),
+                    _ => unreachable!()
+                };
+                let cases = keys.into_iter().zip(targets[1..].iter().map(|t| *t as usize)).collect();
+                return Ok(Some(Terminator::Switch { value, default: targets[0] as usize, cases }));
+            },
+            _ => return Err(LiftFault::Inconsistent)
+        };
+        Ok(None)
+    }
+}
+
+/// Follow phi replacements to the surviving value
+fn resolve(replace: &[ValueId], mut v: ValueId) -> ValueId {
+    while replace[v] != v {
+        v = replace[v];
+    }
+    v
+}
+
+/// Remove phis whose arguments are all the same value, or the phi
+/// itself, then rewrite every use
+fn prune_phis(blocks: &mut [Block], values: usize) {
+    let mut replace: Vec<ValueId> = (0..values).collect();
+    loop {
+        let mut changed = false;
+        for block in blocks.iter_mut() {
+            let mut i = 0;
+            while i < block.phis.len() {
+                let dest = block.phis[i].dest;
+                let mut unique = None;
+                let mut trivial = true;
+                for &(_, arg) in block.phis[i].args.iter() {
+                    let arg = resolve(&replace, arg);
+                    if arg == dest || unique == Some(arg) {
+                        continue;
+                    }
+                    if unique.is_some() {
+                        trivial = false;
+                        break;
+                    }
+                    unique = Some(arg);
+                }
+                match unique {
+                    Option::Some(v) if trivial => {
+                        replace[dest] = v;
+                        block.phis.remove(i);
+                        changed = true;
+                    },
+                    _ => i += 1
+                };
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    for block in blocks.iter_mut() {
+        for phi in block.phis.iter_mut() {
+            for arg in phi.args.iter_mut() {
+                arg.1 = resolve(&replace, arg.1);
+            }
+        }
+        for stmt in block.stmts.iter_mut() {
+            for v in stmt.inst.operands_mut() {
+                *v = resolve(&replace, *v);
+            }
+        }
+        for v in block.term.operands_mut() {
+            *v = resolve(&replace, *v);
+        }
+    }
+}
+
+/// A lifted block before phi arguments are known
+struct Pending {
+    phis: Vec<PhiSlot>,
+    entry: Vec<Option<ValueId>>,
+    exit: State
+}
+
+fn dedup(blocks: Vec<BlockId>) -> Vec<BlockId> {
+    let mut seen = BTreeSet::new();
+    blocks.into_iter().filter(|b| seen.insert(*b)).collect()
+}
+
+/// Convert a method to SSA form
+///
+/// Unreachable code is dropped. Methods using `jsr` or `ret` are
+/// rejected.
+pub fn lift(ctx: MethodContext, code: &CodeAttribute, pool: &ConstantsPool) -> Result<Function, LiftError> {
+    let cfg = Cfg::from_code(code).map_err(|e| LiftError { pc: e.pc, reason: LiftFault::Cfg(e.reason) })?;
+    let subroutine = cfg.ops.iter()
+        .find(|x| matches!(x.1, JOp::JSR(_) | JOp::JSRW(_) | JOp::Ret(_) | JOp::RetWide(_)));
+    if let Option::Some(&(pc, _)) = subroutine {
+        return Err(LiftError { pc, reason: LiftFault::Subroutine });
+    }
+    let oracle = SuperclassOracle(|_: &str, _: &str| OBJECT.to_string());
+    let frames = infer_frames(ctx, code, pool, &oracle)
+        .map_err(|e| LiftError { pc: e.pc, reason: LiftFault::Verify(e.reason) })?;
+
+    // blocks of the cfg, split after local stores when covered by a handler
+    let mut segments = Vec::new();
+    for (b, block) in cfg.blocks.iter().enumerate() {
+        if !block.reachable {
+            continue;
+        }
+        let protected = cfg.successors(b).any(|e| e.kind.is_exceptional());
+        let mut start = block.ops.start;
+        for i in block.ops.clone() {
+            let writes = cfg.ops[i].1.local_access().is_some_and(|(a, _)| a.writes());
+            if protected && writes && i + 1 < block.ops.end {
+                segments.push((start..i + 1, b));
+                start = i + 1;
+            }
+        }
+        segments.push((start..block.ops.end, b));
+    }
+    let synthetic = cfg.predecessors(cfg.entry()).next().is_some() as usize;
+    let block_at: HashMap<usize, BlockId> = segments.iter()
+        .enumerate()
+        .map(|(i, s)| (cfg.ops[s.0.start].0, i + synthetic))
+        .collect();
+
+    let mut lifter = Lifter { pool, values: Vec::new() };
+    let max_locals = code.max_locals as usize;
+    let initial = Frame::initial(ctx, max_locals)
+        .map_err(|reason| LiftError { pc: 0, reason: LiftFault::Verify(reason) })?;
+    let mut params = Vec::new();
+    let mut entry_locals = vec![None; max_locals];
+    for (slot, ty) in typed_slots(&initial.locals).map_err(|reason| LiftError { pc: 0, reason })? {
+        let v = lifter.value(ty);
+        params.push(v);
+        entry_locals[slot] = Some(v);
+    }
+
+    let mut blocks = Vec::with_capacity(segments.len() + synthetic);
+    let mut pending = Vec::with_capacity(segments.len() + synthetic);
+    if synthetic == 1 {
+        blocks.push(Block {
+            phis: Vec::new(),
+            exception: None,
+            stmts: Vec::new(),
+            term: Terminator::Goto(1),
+            handlers: Vec::new()
+        });
+        pending.push(Pending {
+            phis: Vec::new(),
+            entry: entry_locals.clone(),
+            exit: State { locals: entry_locals.clone(), stack: Vec::new() }
+        });
+    }
+    for (k, &(ref ops, cfg_block)) in segments.iter().enumerate() {
+        let start_pc = cfg.ops[ops.start].0;
+        let at = |reason| LiftError { pc: start_pc, reason };
+        let frame = frames[ops.start].1.as_ref().ok_or(at(LiftFault::Inconsistent))?;
+        let handler = cfg.blocks[cfg_block].handler && ops.start == cfg.blocks[cfg_block].ops.start;
+        if handler && cfg.predecessors(cfg_block).any(|e| !e.kind.is_exceptional()) {
+            return Err(at(LiftFault::HandlerJump));
+        }
+
+        let mut block = Block {
+            phis: Vec::new(),
+            exception: None,
+            stmts: Vec::new(),
+            term: Terminator::Return(None),
+            handlers: Vec::new()
+        };
+        let mut slots = Vec::new();
+        let mut st = State { locals: vec![None; max_locals], stack: Vec::new() };
+        if k == 0 && synthetic == 0 {
+            st.locals = entry_locals.clone();
+        } else {
+            for (slot, ty) in typed_slots(&frame.locals).map_err(at)? {
+                let v = lifter.value(ty);
+                st.locals[slot] = Some(v);
+                block.phis.push(Phi { dest: v, args: Vec::new() });
+                slots.push(PhiSlot::Local(slot));
+            }
+            if handler {
+                let v = lifter.value(LocalType::Reference);
+                block.exception = Some(v);
+                st.stack.push(v);
+            } else {
+                for (_, ty) in typed_slots(&frame.stack).map_err(at)? {
+                    let v = lifter.value(ty);
+                    block.phis.push(Phi { dest: v, args: Vec::new() });
+                    slots.push(PhiSlot::Stack(st.stack.len()));
+                    st.stack.push(v);
+                }
+            }
+        }
+        let entry = st.locals.clone();
+
+        let mut term = None;
+        for &(pc, ref op) in cfg.ops[ops.clone()].iter() {
+            term = lifter.step(pc, op, &mut st, &mut block.stmts)
+                .map_err(|reason| LiftError { pc, reason })?;
+        }
+        let mut term = match term {
+            Option::Some(t) => t,
+            Option::None => {
+                let &(pc, ref op) = &cfg.ops[ops.end - 1];
+                Terminator::Goto(pc + op.encoded_len(pc))
+            }
+        };
+        for target in term.targets_mut() {
+            *target = *block_at.get(target).ok_or(at(LiftFault::Inconsistent))?;
+        }
+        block.term = term;
+        for entry in code.exception_table.iter() {
+            if (entry.start_pc as usize) <= start_pc && start_pc < entry.end_pc as usize {
+                let h = *block_at.get(&(entry.handler_pc as usize)).ok_or(at(LiftFault::Inconsistent))?;
+                block.handlers.push((entry.catch_type, h));
+            }
+        }
+        blocks.push(block);
+        pending.push(Pending { phis: slots, entry, exit: st });
+    }
+
+    // phi arguments from every predecessor
+    for b in 0..blocks.len() {
+        let pc = |s: BlockId| if s < synthetic { 0 } else { cfg.ops[segments[s - synthetic].0.start].0 };
+        for s in dedup(blocks[b].term.successors()) {
+            for (i, slot) in pending[s].phis.clone().into_iter().enumerate() {
+                let v = match slot {
+                    PhiSlot::Local(x) => pending[b].exit.locals[x],
+                    PhiSlot::Stack(x) => pending[b].exit.stack.get(x).cloned()
+                };
+                let v = v.ok_or(LiftError { pc: pc(s), reason: LiftFault::Inconsistent })?;
+                blocks[s].phis[i].args.push((b, v));
+            }
+        }
+        for h in dedup(blocks[b].handlers.iter().map(|x| x.1).collect()) {
+            for (i, slot) in pending[h].phis.clone().into_iter().enumerate() {
+                let v = match slot {
+                    PhiSlot::Local(x) => pending[b].entry[x],
+                    PhiSlot::Stack(_) => None
+                };
+                let v = v.ok_or(LiftError { pc: pc(h), reason: LiftFault::Inconsistent })?;
+                blocks[h].phis[i].args.push((b, v));
+            }
+        }
+    }
+    prune_phis(&mut blocks, lifter.values.len());
+    Ok(Function { params, values: lifter.values, blocks })
+}
+
+/// Why a `Function` could not be lowered
+#[derive(Clone,Debug,PartialEq)]
+pub enum LowerFault {
+    /// Only constants with a short encoding can be built without the
+    /// constant pool, others need `Const::Pool`
+    Constant(Const),
+    /// A phi has no argument for a predecessor
+    MissingArgument(ValueId),
+    /// A condition the operand types can't express
+    BadCondition,
+    /// More than 65535 local slots are needed
+    TooManyLocals,
+    Encode(EncodeFault),
+    Cfg(CfgFault),
+    Stack(StackFault)
+}
+
+/// A lowering failure, and the block causing it when known
+#[derive(Clone,Debug,PartialEq)]
+pub struct LowerError {
+    pub block: Option<BlockId>,
+    pub reason: LowerFault
+}
+impl fmt::Display for LowerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            LowerFault::Constant(ref x) => write!(f, "{:?} needs a constant pool entry", x),
+            LowerFault::MissingArgument(x) => write!(f, "phi v{} has no argument for a predecessor", x),
+            LowerFault::BadCondition => write!(f, "condition does not match its operand types"),
+            LowerFault::TooManyLocals => write!(f, "more than 65535 local slots"),
+            LowerFault::Encode(ref x) => write!(f, "assembly failed ({:?})", x),
+            LowerFault::Cfg(ref x) => write!(f, "invalid control flow ({:?})", x),
+            LowerFault::Stack(ref x) => write!(f, "invalid operand stack ({:?})", x)
+        }?;
+        match self.block {
+            Option::Some(b) => write!(f, " in block {}", b),
+            Option::None => Ok(())
+        }
+    }
+}
+
+/// A code array produced by `lower`
+///
+/// There is no `StackMapTable`, see `frames::stack_map_table`.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Lowered {
+    pub code: Vec<u8>,
+    pub exception_table: Vec<ExceptionEntry>,
+    pub max_stack: u16,
+    pub max_locals: u16
+}
+impl Lowered {
+
+    /// A `Code` attribute for the result, without attributes
+    pub fn code_attribute(&self) -> CodeAttribute<'_> {
+        CodeAttribute {
+            max_stack: self.max_stack,
+            max_locals: self.max_locals,
+            code: &self.code,
+            exception_table: self.exception_table.clone(),
+            attributes: Vec::new()
+        }
+    }
+}
+
+fn const_op(c: Const, ty: LocalType) -> Option<JOp> {
+    Some(match c {
+        Const::Null => JOp::AConstNull,
+        Const::Int(-1) => JOp::IConstM1,
+        Const::Int(0) => JOp::IConst0,
+        Const::Int(1) => JOp::IConst1,
+        Const::Int(2) => JOp::IConst2,
+        Const::Int(3) => JOp::IConst3,
+        Const::Int(4) => JOp::IConst4,
+        Const::Int(5) => JOp::IConst5,
+        Const::Int(x) if x as i8 as i32 == x => JOp::BiPush(x as u8),
+        Const::Int(x) if x as i16 as i32 == x => JOp::SIPush(x as u16),
+        Const::Long(0) => JOp::LConst0,
+        Const::Long(1) => JOp::LConst1,
+        Const::Float(x) if x.to_bits() == 0.0f32.to_bits() => JOp::FConst0,
+        Const::Float(1.0) => JOp::FConst1,
+        Const::Float(2.0) => JOp::FConst2,
+        Const::Double(x) if x.to_bits() == 0.0f64.to_bits() => JOp::DConst0,
+        Const::Double(1.0) => JOp::DConst1,
+        Const::Pool(x) if ty.width() == 2 => JOp::LDC2W(x),
+        Const::Pool(x) if x <= 0xFF => JOp::LDC(x as u8),
+        Const::Pool(x) => JOp::LDCW(x),
+        _ => return None
+    })
+}
+
+fn branch(cond: Cond, ty: LocalType, two: bool) -> Option<Jump> {
+    let reference = ty == LocalType::Reference;
+    if ty != LocalType::Int && !reference {
+        return None;
+    }
+    CONDITIONS.iter()
+        .find(|&&(jump, c)| {
+            let compares_refs = matches!(jump, Jump::IFNull | Jump::IFnonNull | Jump::IFAcmpEQ | Jump::IFAcmpNE);
+            c == cond && compares_two(jump) == two && compares_refs == reference
+        })
+        .map(|x| x.0)
+}
+
+struct Lowering<'f> {
+    func: &'f Function,
+    slots: Vec<u16>,
+    out: Vec<Instruction>,
+    next_label: u32
+}
+impl<'f> Lowering<'f> {
+
+    fn label(&mut self) -> Label {
+        self.next_label += 1;
+        Label(self.next_label - 1)
+    }
+
+    fn op(&mut self, op: JOp) {
+        self.out.push(Instruction::Op(op));
+    }
+
+    fn load(&mut self, v: ValueId) {
+        let op = local_op(LocalAccess::Load(self.func.values[v]), self.slots[v]);
+        self.op(op);
+    }
+
+    fn store(&mut self, v: ValueId) {
+        let op = local_op(LocalAccess::Store(self.func.values[v]), self.slots[v]);
+        self.op(op);
+    }
+
+    /// Moves into the phis of `to` when coming from `from`
+    fn moves(&self, from: BlockId, to: BlockId) -> Result<Vec<(ValueId, ValueId)>, LowerFault> {
+        let mut moves = Vec::new();
+        for phi in self.func.blocks[to].phis.iter() {
+            let arg = phi.args.iter()
+                .find(|a| a.0 == from)
+                .ok_or(LowerFault::MissingArgument(phi.dest))?
+                .1;
+            if arg != phi.dest {
+                moves.push((arg, phi.dest));
+            }
+        }
+        Ok(moves)
+    }
+
+    /// Assign phis as one parallel copy through the operand stack
+    fn copy(&mut self, from: BlockId, to: BlockId) -> Result<(), LowerFault> {
+        let moves = self.moves(from, to)?;
+        for &(src, _) in moves.iter() {
+            self.load(src);
+        }
+        for &(_, dest) in moves.iter().rev() {
+            self.store(dest);
+        }
+        Ok(())
+    }
+
+    /// Where to branch for an edge, a trampoline if phis need copies
+    fn edge(&mut self, from: BlockId, to: BlockId, trampolines: &mut Vec<(Label, BlockId)>) -> Result<Label, LowerFault> {
+        if self.moves(from, to)?.is_empty() {
+            return Ok(Label(to as u32));
+        }
+        if let Option::Some(t) = trampolines.iter().find(|t| t.1 == to) {
+            return Ok(t.0);
+        }
+        let l = self.label();
+        trampolines.push((l, to));
+        Ok(l)
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) -> Result<(), LowerFault> {
+        for v in stmt.inst.operands() {
+            self.load(v);
+        }
+        let ty = |v: ValueId| self.func.values[v];
+        let op = match stmt.inst {
+            Inst::Const(c) => {
+                let t = stmt.dest.map_or(LocalType::Int, ty);
+                const_op(c, t).ok_or(LowerFault::Constant(c))?
+            },
+            Inst::Binary(bin, t, _, _) => {
+                BINARY.iter().find(|x| x.1 == bin && x.2 == t).ok_or(LowerFault::BadCondition)?.0.clone()
+            },
+            Inst::Neg(t, _) => NEGATE.iter().find(|x| x.1 == t).ok_or(LowerFault::BadCondition)?.0.clone(),
+            Inst::Convert(from, to, _) => {
+                CONVERT.iter().find(|x| x.1 == from && x.2 == to).ok_or(LowerFault::BadCondition)?.0.clone()
+            },
+            Inst::Narrow(n, _) => NARROW.iter().find(|x| x.1 == n).unwrap().0.clone(),
+            Inst::Compare(c, _, _) => COMPARE.iter().find(|x| x.1 == c).unwrap().0.clone(),
+            Inst::ArrayLoad(k, _, _) => ARRAY_LOAD.iter().find(|x| x.1 == k).unwrap().0.clone(),
+            Inst::ArrayStore(k, _, _, _) => ARRAY_STORE.iter().find(|x| x.1 == k).unwrap().0.clone(),
+            Inst::ArrayLength(_) => JOp::ArrayLength,
+            Inst::NewArray(t, _) => JOp::NewArray(t),
+            Inst::ANewArray(x, _) => JOp::ANewArray(x),
+            Inst::MultiANewArray(x, ref dims) => JOp::MultiAneWArray(x, dims.len() as u8),
+            Inst::New(x) => JOp::New(x),
+            Inst::GetField(x, _) => JOp::GetField(x),
+            Inst::PutField(x, _, _) => JOp::PutField(x),
+            Inst::GetStatic(x) => JOp::GetStatic(x),
+            Inst::PutStatic(x, _) => JOp::PutStatic(x),
+            Inst::Invoke(kind, x, ref args) => match kind {
+                InvokeKind::Virtual => JOp::InvokeVirtual(x),
+                InvokeKind::Special => JOp::InvokeSpecial(x),
+                InvokeKind::Static => JOp::InvokeStatic(x),
+                InvokeKind::Interface => {
+                    let count: u16 = args.iter().map(|a| ty(*a).width()).sum();
+                    JOp::InvokedInterface(x, count as u8)
+                },
+                InvokeKind::Dynamic => JOp::InvokedDynamic(x)
+            },
+            Inst::CheckCast(x, _) => JOp::CheckCast(x),
+            Inst::InstanceOf(x, _) => JOp::InstanceOf(x),
+            Inst::MonitorEnter(_) => JOp::MonitorEnter,
+            Inst::MonitorExit(_) => JOp::MonitorExit
+        };
+        self.op(op);
+        if let Option::Some(dest) = stmt.dest {
+            self.store(dest);
+        }
+        Ok(())
+    }
+
+    fn block(&mut self, b: BlockId, ranges: &mut Vec<(Label, Label, BlockId)>) -> Result<(), LowerFault> {
+        let func = self.func;
+        let block = &func.blocks[b];
+        self.out.push(Instruction::Label(Label(b as u32)));
+        if let Option::Some(e) = block.exception {
+            self.store(e);
+        }
+        for h in dedup(block.handlers.iter().map(|x| x.1).collect()) {
+            self.copy(b, h)?;
+        }
+        let start = self.label();
+        self.out.push(Instruction::Label(start));
+        for stmt in block.stmts.iter() {
+            self.stmt(stmt)?;
+        }
+
+        let mut trampolines = Vec::new();
+        match block.term {
+            Terminator::Goto(to) => {
+                self.copy(b, to)?;
+                if to != b + 1 {
+                    self.out.push(Instruction::Jump(Jump::Goto, Label(to as u32)));
+                }
+            },
+            Terminator::If { cond, lhs, rhs, taken, fallthrough } => {
+                self.load(lhs);
+                if let Option::Some(rhs) = rhs {
+                    self.load(rhs);
+                }
+                let jump = branch(cond, self.func.values[lhs], rhs.is_some()).ok_or(LowerFault::BadCondition)?;
+                let target = self.edge(b, taken, &mut trampolines)?;
+                self.out.push(Instruction::Jump(jump, target));
+                self.copy(b, fallthrough)?;
+                if fallthrough != b + 1 || !trampolines.is_empty() {
+                    self.out.push(Instruction::Jump(Jump::Goto, Label(fallthrough as u32)));
+                }
+            },
+            Terminator::Switch { value, default, ref cases } => {
+                self.load(value);
+                let default = self.edge(b, default, &mut trampolines)?;
+                let mut pairs = Vec::with_capacity(cases.len());
+                for &(key, to) in cases.iter() {
+                    pairs.push((key, self.edge(b, to, &mut trampolines)?));
+                }
+                pairs.sort_by_key(|p| p.0);
+                let (low, high) = match (pairs.first(), pairs.last()) {
+                    (Option::Some(l), Option::Some(h)) => (l.0 as i64, h.0 as i64),
+                    _ => (0, -1)
+                };
+                // javac's choice between the two encodings
+                let table_cost = 4 + (high - low + 1) + 3 * 3;
+                let lookup_cost = 3 + 2 * pairs.len() as i64 + 3 * pairs.len() as i64;
+                if !pairs.is_empty() && table_cost <= lookup_cost {
+                    let mut targets = vec![default; (high - low + 1) as usize];
+                    for &(key, l) in pairs.iter() {
+                        targets[(key as i64 - low) as usize] = l;
+                    }
+                    self.out.push(Instruction::TableSwitch { default, low: low as i32, targets });
+                } else {
+                    self.out.push(Instruction::LookUpSwitch { default, pairs });
+                }
+            },
+            Terminator::Return(v) => {
+                let op = match v {
+                    Option::Some(v) => {
+                        self.load(v);
+                        match self.func.values[v] {
+                            LocalType::Int => JOp::IReturn,
+                            LocalType::Long => JOp::LReturn,
+                            LocalType::Float => JOp::FReturn,
+                            LocalType::Double => JOp::DReturn,
+                            LocalType::Reference => JOp::AReturn
+                        }
+                    },
+                    Option::None => JOp::Return
+                };
+                self.op(op);
+            },
+            Terminator::Throw(v) => {
+                self.load(v);
+                self.op(JOp::AThrow);
+            }
+        };
+        let end = self.label();
+        self.out.push(Instruction::Label(end));
+        if !block.handlers.is_empty() {
+            ranges.push((start, end, b));
+        }
+        for (l, to) in trampolines {
+            self.out.push(Instruction::Label(l));
+            self.copy(b, to)?;
+            self.out.push(Instruction::Jump(Jump::Goto, Label(to as u32)));
+        }
+        Ok(())
+    }
+}
+
+/// Convert a `Function` back to bytecode
+///
+/// Parameters keep their slots, every other value gets a local of its
+/// own. `pool` is the constant pool the function's indices refer to.
+pub fn lower(func: &Function, pool: &ConstantsPool) -> Result<Lowered, LowerError> {
+    let fail = |reason| LowerError { block: None, reason };
+    let mut defined = vec![false; func.values.len()];
+    for block in func.blocks.iter() {
+        let defs = block.phis.iter().map(|p| p.dest)
+            .chain(block.exception)
+            .chain(block.stmts.iter().filter_map(|s| s.dest));
+        for v in defs {
+            defined[v] = true;
+        }
+    }
+    for &p in func.params.iter() {
+        defined[p] = false;
+    }
+    let arg_slots: u16 = func.params.iter().map(|p| func.values[*p].width()).sum();
+    let order = func.params.iter().cloned().chain((0..func.values.len()).filter(|v| defined[*v]));
+    let mut slots = vec![0u16; func.values.len()];
+    let mut next = 0u32;
+    for v in order {
+        if next + func.values[v].width() as u32 > 0xFFFF {
+            return Err(fail(LowerFault::TooManyLocals));
+        }
+        slots[v] = next as u16;
+        next += func.values[v].width() as u32;
+    }
+    let max_locals = next as u16;
+
+    let mut lowering = Lowering {
+        func,
+        slots,
+        out: Vec::new(),
+        next_label: func.blocks.len() as u32
+    };
+    let mut ranges = Vec::new();
+    for b in 0..func.blocks.len() {
+        lowering.block(b, &mut ranges).map_err(|reason| LowerError { block: Some(b), reason })?;
+    }
+    let assembled = assemble(&lowering.out).map_err(|e| fail(LowerFault::Encode(e.reason)))?;
+
+    let mut exception_table = Vec::new();
+    for (start, end, b) in ranges {
+        let start_pc = assembled.label_pc(start).unwrap() as u16;
+        let end_pc = assembled.label_pc(end).unwrap() as u16;
+        if start_pc == end_pc {
+            continue;
+        }
+        for &(catch_type, h) in func.blocks[b].handlers.iter() {
+            let handler_pc = assembled.label_pc(Label(h as u32)).unwrap() as u16;
+            exception_table.push(ExceptionEntry { start_pc, end_pc, handler_pc, catch_type });
+        }
+    }
+    let ops = Decoder::new(&assembled.code)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| fail(LowerFault::Cfg(CfgFault::Decode(e.reason))))?;
+    let cfg = Cfg::build(ops, assembled.code.len(), &exception_table)
+        .map_err(|e| fail(LowerFault::Cfg(e.reason)))?;
+    let info = StackInfo::compute(&cfg, pool, arg_slots)
+        .map_err(|e| fail(LowerFault::Stack(e.reason)))?;
+    Ok(Lowered {
+        code: assembled.code,
+        exception_table,
+        max_stack: info.max_stack,
+        max_locals: max_locals.max(info.max_locals)
+    })
+}
+
+#[test]
+fn test_ssa() {
+    use super::super::clazz::parse_constant_pool;
+    use super::super::nom::IResult;
+    use super::hierarchy::MapHierarchy;
+    use super::verify::verify_code;
+
+    let pool = match parse_constant_pool(b"\x00\x01") {
+        IResult::Done(_, pool) => pool,
+        _ => panic!()
+    };
+    let ctx = MethodContext {
+        class_name: "T",
+        name: "f",
+        descriptor: "(I)I",
+        is_static: true,
+        major_version: 49
+    };
+    let code = |body: &'static [u8], exception_table: Vec<ExceptionEntry>| CodeAttribute {
+        max_stack: 2,
+        max_locals: 3,
+        code: body,
+        exception_table,
+        attributes: Vec::new()
+    };
+    let h = MapHierarchy::new();
+
+    // int s = 0; for (int i = 0; i < n; i++) s += i; return s;
+    let sum = code(&[
+        0x03, 0x3C, 0x03, 0x3D, 0x1C, 0x1A, 0xA2, 0x00, 0x0D, 0x1B,
+        0x1C, 0x60, 0x3C, 0x84, 0x02, 0x01, 0xA7, 0xFF, 0xF4, 0x1B, 0xAC
+    ], Vec::new());
+    let func = lift(ctx, &sum, &pool).unwrap();
+    assert_eq!(func.blocks.len(), 4);
+    assert_eq!(func.blocks.iter().map(|b| b.phis.len()).collect::<Vec<_>>(), vec![0, 2, 0, 0]);
+    let (s, i) = (func.blocks[1].phis[0].dest, func.blocks[1].phis[1].dest);
+    let n = func.params[0];
+    assert_eq!(func.blocks[1].term, Terminator::If { cond: Cond::Ge, lhs: i, rhs: Some(n), taken: 3, fallthrough: 2 });
+    assert_eq!(func.blocks[2].stmts[0].inst, Inst::Binary(BinOp::Add, LocalType::Int, s, i));
+    assert_eq!(func.blocks[3].term, Terminator::Return(Some(s)));
+    let lowered = lower(&func, &pool).unwrap();
+    assert_eq!(verify_code(ctx, &lowered.code_attribute(), &pool, &h), Ok(()));
+
+    // int x = 1; try { x = 2; a = a / a; x = 3; } catch (any) { return x; } return x;
+    let guarded = code(&[
+        0x04, 0x3C, 0x05, 0x3C, 0x1A, 0x1A, 0x6C, 0x3B, 0x06, 0x3C,
+        0xA7, 0x00, 0x06, 0x4D, 0x1B, 0xAC, 0x1B, 0xAC
+    ], vec![ExceptionEntry { start_pc: 2, end_pc: 10, handler_pc: 13, catch_type: 0 }]);
+    let func = lift(ctx, &guarded, &pool).unwrap();
+    let handler = func.blocks.iter().find(|b| b.exception.is_some()).unwrap();
+    let x = match handler.term {
+        Terminator::Return(Some(x)) => x,
+        _ => panic!()
+    };
+    let phi = handler.phis.iter().find(|p| p.dest == x).unwrap();
+    let args: BTreeSet<ValueId> = phi.args.iter().map(|a| a.1).collect();
+    let constants: BTreeSet<ValueId> = func.blocks.iter()
+        .flat_map(|b| b.stmts.iter())
+        .filter(|s| s.inst == Inst::Const(Const::Int(1)) || s.inst == Inst::Const(Const::Int(2)))
+        .filter_map(|s| s.dest)
+        .collect();
+    assert_eq!(args, constants);
+    let lowered = lower(&func, &pool).unwrap();
+    assert_eq!(lowered.exception_table.len(), 3);
+    assert_eq!(verify_code(ctx, &lowered.code_attribute(), &pool, &h), Ok(()));
+}
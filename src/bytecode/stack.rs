@@ -0,0 +1,280 @@
+//! Operand stack depth and local variable usage.
+//!
+//! Depths are counted in slots, like `max_stack`, so longs and doubles
+//! count twice.
+
+use super::super::clazz::{ConstantsPool, PoolMembers, FieldType, MethodType};
+use super::ops::JOp;
+use super::cfg::{Cfg, EdgeKind};
+use super::locals::LocalAccess;
+
+use std::fmt;
+
+/// Why stack depths could not be computed
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum StackFault {
+    /// The instruction pops more than the stack holds
+    Underflow { depth: u16, pops: u16 },
+    /// The stack would exceed 65535 slots
+    Overflow,
+    /// Paths reaching the same pc disagree on its depth
+    Inconsistent { expected: u16, found: u16 },
+    /// A constant pool operand is missing, of the wrong kind, or has
+    /// an invalid descriptor
+    BadConstant(u16)
+}
+
+/// A stack depth failure and the pc it concerns
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct StackError {
+    pub pc: usize,
+    pub reason: StackFault
+}
+impl fmt::Display for StackError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            StackFault::Underflow { depth, pops } => write!(f, "popping {} slots from a stack of {}", pops, depth),
+            StackFault::Overflow => write!(f, "operand stack overflow"),
+            StackFault::Inconsistent { expected, found } => write!(f, "stack depth {} merges with {}", found, expected),
+            StackFault::BadConstant(x) => write!(f, "invalid constant #{}", x)
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+fn member_type(pool: &ConstantsPool, index: u16) -> Result<FieldType, StackFault> {
+    pool.get_member_ref(index)
+        .and_then(|m| FieldType::parse(&m.descriptor))
+        .ok_or(StackFault::BadConstant(index))
+}
+
+fn method_type(pool: &ConstantsPool, index: u16) -> Result<MethodType, StackFault> {
+    let desc = match pool.get(index) {
+        Option::Some(&PoolMembers::InvokeDynamic(_, nat)) => pool.get_name_and_type(nat).map(|(_, d)| d),
+        _ => pool.get_member_ref(index).map(|m| m.descriptor)
+    };
+    desc.and_then(|d| MethodType::parse(&d))
+        .ok_or(StackFault::BadConstant(index))
+}
+
+impl JOp {
+
+    /// Slots popped and pushed by this instruction
+    ///
+    /// Field and method instructions consult `pool` for their
+    /// descriptors.
+    pub fn stack_effect(&self, pool: &ConstantsPool) -> Result<(u16, u16), StackFault> {
+        if let Option::Some((access, _)) = self.local_access() {
+            return Ok(match access {
+                LocalAccess::Load(t) => (0, t.width()),
+                LocalAccess::Store(t) => (t.width(), 0),
+                LocalAccess::Increment(_) | LocalAccess::Ret => (0, 0)
+            });
+        }
+        let effect = match *self {
+            JOp::Nop | JOp::BreakPoint | JOp::ImpDep1 | JOp::ImpDep2 |
+            JOp::Goto(_) | JOp::GotoW(_) | JOp::Return => (0, 0),
+
+            JOp::AConstNull | JOp::IConstM1 | JOp::IConst0 | JOp::IConst1 |
+            JOp::IConst2 | JOp::IConst3 | JOp::IConst4 | JOp::IConst5 |
+            JOp::FConst0 | JOp::FConst1 | JOp::FConst2 |
+            JOp::BiPush(_) | JOp::SIPush(_) | JOp::LDC(_) | JOp::LDCW(_) |
+            JOp::New(_) | JOp::JSR(_) | JOp::JSRW(_) => (0, 1),
+            JOp::LConst0 | JOp::LConst1 | JOp::DConst0 | JOp::DConst1 |
+            JOp::LDC2W(_) => (0, 2),
+
+            JOp::IALoad | JOp::FALoad | JOp::AALoad | JOp::BaLoad |
+            JOp::CaLoad | JOp::SALoad => (2, 1),
+            JOp::LALoad | JOp::DALoad => (2, 2),
+            JOp::IAStore | JOp::FAStore | JOp::AAStore | JOp::BaStore |
+            JOp::CaStore | JOp::SAStore => (3, 0),
+            JOp::LAStore | JOp::DAStore => (4, 0),
+
+            JOp::Pop => (1, 0),
+            JOp::Pop2 => (2, 0),
+            JOp::Dup => (1, 2),
+            JOp::Dupx1 => (2, 3),
+            JOp::Dupx2 => (3, 4),
+            JOp::Dup2 => (2, 4),
+            JOp::Dup2x1 => (3, 5),
+            JOp::Dup2x2 => (4, 6),
+            JOp::Swap => (2, 2),
+
+            JOp::IAdd | JOp::ISub | JOp::IMul | JOp::IDiv | JOp::IRem |
+            JOp::IAnd | JOp::IOr | JOp::IXor | JOp::ISHL | JOp::ISHR | JOp::IUSHR |
+            JOp::FAdd | JOp::FSub | JOp::FMul | JOp::FDiv | JOp::FRem |
+            JOp::FcmpL | JOp::FcmpG => (2, 1),
+            JOp::LAdd | JOp::LSub | JOp::LMul | JOp::LDiv | JOp::LRem |
+            JOp::LAnd | JOp::LOr | JOp::LXor |
+            JOp::DAdd | JOp::DSub | JOp::DMul | JOp::DDiv | JOp::DRem => (4, 2),
+            JOp::LSHL | JOp::LSHR | JOp::LUSHR => (3, 2),
+            JOp::Lcmp | JOp::DcmpL | JOp::DcmpG => (4, 1),
+            JOp::INeg | JOp::FNeg | JOp::I2F | JOp::F2I |
+            JOp::I2B | JOp::I2C | JOp::I2S => (1, 1),
+            JOp::LNeg | JOp::DNeg | JOp::L2D | JOp::D2L => (2, 2),
+            JOp::I2L | JOp::I2D | JOp::F2L | JOp::F2D => (1, 2),
+            JOp::L2I | JOp::L2F | JOp::D2I | JOp::D2F => (2, 1),
+
+            JOp::IFEQ(_) | JOp::IFNE(_) | JOp::IFLT(_) | JOp::IFGE(_) |
+            JOp::IFGT(_) | JOp::IFLE(_) | JOp::IFNull(_) | JOp::IFnonNull(_) |
+            JOp::TableSwitch(..) | JOp::LookUpSwitch(..) => (1, 0),
+            JOp::IFIcmpEQ(_) | JOp::IFIcmpNE(_) | JOp::IFIcmpLT(_) |
+            JOp::IfIcmpGE(_) | JOp::IFIcmpGT(_) | JOp::IFIcmpLE(_) |
+            JOp::IFAcmpEQ(_) | JOp::IFAcmpNE(_) => (2, 0),
+
+            JOp::IReturn | JOp::FReturn | JOp::AReturn | JOp::AThrow |
+            JOp::MonitorEnter | JOp::MonitorExit => (1, 0),
+            JOp::LReturn | JOp::DReturn => (2, 0),
+
+            JOp::NewArray(_) | JOp::ANewArray(_) | JOp::ArrayLength |
+            JOp::CheckCast(_) | JOp::InstanceOf(_) => (1, 1),
+            JOp::MultiAneWArray(_, dims) => (dims as u16, 1),
+
+            JOp::GetStatic(x) => (0, member_type(pool, x)?.slots()),
+            JOp::PutStatic(x) => (member_type(pool, x)?.slots(), 0),
+            JOp::GetField(x) => (1, member_type(pool, x)?.slots()),
+            JOp::PutField(x) => (1 + member_type(pool, x)?.slots(), 0),
+
+            JOp::InvokeStatic(x) |
+            JOp::InvokedDynamic(x) => {
+                let m = method_type(pool, x)?;
+                (m.param_slots(), m.ret_slots())
+            },
+            JOp::InvokeVirtual(x) |
+            JOp::InvokeSpecial(x) |
+            JOp::InvokedInterface(x, _) => {
+                let m = method_type(pool, x)?;
+                (m.param_slots() + 1, m.ret_slots())
+            },
+
+            // local variable instructions are handled above
+            _ => (0, 0)
+        };
+        Ok(effect)
+    }
+}
+
+/// Operand stack depths and frame size of a method
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct StackInfo {
+    /// Depth before each instruction, indexed by pc
+    depths: Vec<Option<u16>>,
+    pub max_stack: u16,
+    pub max_locals: u16
+}
+
+impl StackInfo {
+
+    /// Compute the depth at every reachable pc of `cfg`
+    ///
+    /// `arg_slots` is the number of local slots taken by the method's
+    /// parameters, including `this` for instance methods. Exception
+    /// handlers start with only the thrown exception on the stack.
+    pub fn compute(cfg: &Cfg, pool: &ConstantsPool, arg_slots: u16) -> Result<StackInfo, StackError> {
+        let code_len = cfg.blocks.last().map_or(0, |b| b.end);
+        let mut info = StackInfo {
+            depths: vec![None; code_len],
+            max_stack: 0,
+            max_locals: arg_slots
+        };
+        for &(pc, ref op) in cfg.ops.iter() {
+            if let Option::Some((access, index)) = op.local_access() {
+                let top = index as u32 + access.width() as u32;
+                if top > 0xFFFF {
+                    return Err(StackError { pc, reason: StackFault::Overflow });
+                }
+                info.max_locals = info.max_locals.max(top as u16);
+            }
+        }
+        if cfg.blocks.is_empty() {
+            return Ok(info);
+        }
+
+        let mut entry = vec![None; cfg.blocks.len()];
+        entry[cfg.entry()] = Some(0u16);
+        let mut work = vec![cfg.entry()];
+        while let Option::Some(b) = work.pop() {
+            let mut depth = entry[b].unwrap();
+            for &(pc, ref op) in cfg.block_ops(b) {
+                info.depths[pc] = Some(depth);
+                let (pops, pushes) = op.stack_effect(pool)
+                    .map_err(|reason| StackError { pc, reason })?;
+                if pops > depth {
+                    return Err(StackError { pc, reason: StackFault::Underflow { depth, pops } });
+                }
+                let after = depth as u32 - pops as u32 + pushes as u32;
+                if after > 0xFFFF {
+                    return Err(StackError { pc, reason: StackFault::Overflow });
+                }
+                info.max_stack = info.max_stack.max(after as u16);
+                depth = after as u16;
+            }
+            for edge in cfg.successors(b) {
+                let found = match edge.kind {
+                    EdgeKind::Exception(_) => 1,
+                    _ => depth
+                };
+                info.max_stack = info.max_stack.max(found);
+                match entry[edge.to] {
+                    Option::Some(expected) if expected != found => {
+                        let reason = StackFault::Inconsistent { expected, found };
+                        return Err(StackError { pc: cfg.blocks[edge.to].start, reason });
+                    },
+                    Option::Some(_) => { },
+                    Option::None => {
+                        entry[edge.to] = Some(found);
+                        work.push(edge.to);
+                    }
+                };
+            }
+        }
+        Ok(info)
+    }
+
+    /// Depth before the instruction at `pc`, `None` if `pc` is not a
+    /// reachable instruction
+    pub fn depth_at(&self, pc: usize) -> Option<u16> {
+        self.depths.get(pc).cloned().and_then(|x| x)
+    }
+}
+
+#[test]
+fn test_stack_depths() {
+    use super::super::clazz::ExceptionEntry;
+    use super::super::clazz::parse_constant_pool;
+    use super::decoder::Decoder;
+    use super::super::nom::IResult;
+
+    // #6 is the method T.m(JI)D
+    let pool = match parse_constant_pool(b"\x00\x07\
+        \x01\x00\x01T\x07\x00\x01\x01\x00\x01m\x01\x00\x05(JI)D\
+        \x0C\x00\x03\x00\x04\x0A\x00\x02\x00\x05") {
+        IResult::Done(_, pool) => pool,
+        _ => panic!()
+    };
+    let build = |code: &[u8], ex: &[ExceptionEntry]| {
+        let ops = Decoder::new(code).map(Result::unwrap).collect();
+        Cfg::build(ops, code.len(), ex).unwrap()
+    };
+
+    // lconst_1; iconst_2; invokestatic #6; dreturn; astore_1; return
+    let ex = [ExceptionEntry { start_pc: 0, end_pc: 6, handler_pc: 6, catch_type: 0 }];
+    let cfg = build(&[0x0A, 0x05, 0xB8, 0x00, 0x06, 0xAF, 0x4C, 0xB1], &ex);
+    let info = StackInfo::compute(&cfg, &pool, 0).unwrap();
+    assert_eq!((info.max_stack, info.max_locals), (3, 2));
+    assert_eq!(info.depth_at(2), Some(3));
+    assert_eq!(info.depth_at(5), Some(2));
+    assert_eq!(info.depth_at(6), Some(1));
+    assert_eq!(info.depth_at(3), None);
+
+    // iconst_0; iload_0; ifeq 6; iconst_1; return
+    let cfg = build(&[0x03, 0x1A, 0x99, 0x00, 0x04, 0x04, 0xB1], &[]);
+    let err = StackInfo::compute(&cfg, &pool, 1).unwrap_err();
+    assert_eq!(err.pc, 6);
+    assert!(matches!(err.reason, StackFault::Inconsistent { .. }));
+
+    // pop on an empty stack
+    let cfg = build(&[0x57, 0xB1], &[]);
+    let err = StackInfo::compute(&cfg, &pool, 0).unwrap_err();
+    assert_eq!(err, StackError { pc: 0, reason: StackFault::Underflow { depth: 0, pops: 1 } });
+}
@@ -0,0 +1,1177 @@
+//! Structured control flow recovered from a `Cfg`.
+//!
+//! Blocks are laid out along the dominator tree, following Ramsey's
+//! "Beyond Relooper". A block entered by several forward edges comes
+//! after a labeled statement those edges `break` out of, and a loop
+//! header wraps its loop in an endless loop that back edges `continue`.
+//! Each exception handler is a catch of a try statement entered at the
+//! handler's immediate dominator. Code following a loop or try is moved
+//! out of it, then loops are recognised as `while`, `do`/`while` or
+//! `for` where their shape allows.
+//!
+//! Every edge is kept: a jump is only left out where control falls
+//! through to its target anyway. Edges into a cycle with more than one
+//! entry cannot be expressed, those become `Region::Goto`.
+
+use super::cfg::{BlockId, Cfg, EdgeKind};
+use super::dom::DomTree;
+use super::labels::Jump;
+use super::locals::{LocalAccess, LocalType};
+use super::ops::JOp;
+
+use std::cmp::Reverse;
+use std::fmt;
+
+/// Numbers the loops and labeled statements of a method
+pub type LabelId = usize;
+
+/// A piece of structured code
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum Region {
+    /// The instructions of a block, less any branch ending it
+    Code(BlockId),
+    Seq(Vec<Region>),
+    /// On the conditional branch ending `block`, `then` runs when the
+    /// branch is taken
+    If { block: BlockId, then: Box<Region>, otherwise: Box<Region> },
+    /// On the switch ending `block`
+    Switch { block: BlockId, cases: Vec<Case>, default: Box<Region> },
+    /// Running off the end of `body` starts the next iteration
+    Loop { label: LabelId, kind: LoopKind, body: Box<Region> },
+    /// A statement `Break` can leave
+    Labeled { label: LabelId, body: Box<Region> },
+    /// Leave the loop or labeled statement
+    Break(LabelId),
+    /// Start the next iteration of the loop
+    Continue(LabelId),
+    Try { body: Box<Region>, catches: Vec<Catch>, finally: Option<Box<Region>> },
+    /// An edge that cannot be structured
+    Goto { from: BlockId, to: BlockId }
+}
+
+/// One arm of a `Switch`
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Case {
+    pub keys: Vec<i32>,
+    pub body: Region
+}
+
+/// One handler of a `Try`
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Catch {
+    pub handler: BlockId,
+    /// `catch_type` constants, `0` catches everything
+    pub types: Vec<u16>,
+    pub body: Region
+}
+
+/// How a loop is entered and left
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum LoopKind {
+    /// Left only by `Break`, return or throw
+    Endless,
+    /// Before each iteration the code of `block` runs, and the loop
+    /// ends unless whether its branch is taken equals `taken`
+    While { block: BlockId, taken: bool },
+    /// The body ends with `block`, and the loop repeats if whether its
+    /// branch is taken equals `taken`
+    DoWhile { block: BlockId, taken: bool },
+    /// A `While` whose body ends by updating a local the test reads,
+    /// with the instruction at pc `update`
+    For { block: BlockId, taken: bool, update: usize }
+}
+
+/// The structured form of a method
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Structure {
+    pub body: Region,
+    /// Edges kept as `Region::Goto`
+    pub irreducible: Vec<(BlockId, BlockId)>
+}
+
+/// Why control flow could not be structured
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum StructureFault {
+    /// `jsr` and `ret` are not supported
+    Subroutine,
+    /// An exception handler is entered by normal control flow, or the
+    /// code it protects has no single entry
+    Handler
+}
+
+/// A failure and the pc it concerns
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct StructureError {
+    pub pc: usize,
+    pub reason: StructureFault
+}
+impl fmt::Display for StructureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            StructureFault::Subroutine => write!(f, "jsr/ret is not supported"),
+            StructureFault::Handler => write!(f, "unstructured exception handler")
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+impl Region {
+
+    fn parts(&self) -> Vec<&Region> {
+        match *self {
+            Region::Seq(ref items) => items.iter().collect(),
+            Region::If { ref then, ref otherwise, .. } => vec![&**then, &**otherwise],
+            Region::Switch { ref cases, ref default, .. } => {
+                cases.iter().map(|c| &c.body).chain(Some(&**default)).collect()
+            },
+            Region::Loop { ref body, .. } |
+            Region::Labeled { ref body, .. } => vec![&**body],
+            Region::Try { ref body, ref catches, ref finally } => {
+                Some(&**body).into_iter()
+                    .chain(catches.iter().map(|c| &c.body))
+                    .chain(finally.as_ref().map(|x| &**x))
+                    .collect()
+            },
+            _ => Vec::new()
+        }
+    }
+
+    fn parts_mut(&mut self) -> Vec<&mut Region> {
+        match *self {
+            Region::Seq(ref mut items) => items.iter_mut().collect(),
+            Region::If { ref mut then, ref mut otherwise, .. } => vec![&mut **then, &mut **otherwise],
+            Region::Switch { ref mut cases, ref mut default, .. } => {
+                cases.iter_mut().map(|c| &mut c.body).chain(Some(&mut **default)).collect()
+            },
+            Region::Loop { ref mut body, .. } |
+            Region::Labeled { ref mut body, .. } => vec![&mut **body],
+            Region::Try { ref mut body, ref mut catches, ref mut finally } => {
+                Some(&mut **body).into_iter()
+                    .chain(catches.iter_mut().map(|c| &mut c.body))
+                    .chain(finally.as_mut().map(|x| &mut **x))
+                    .collect()
+            },
+            _ => Vec::new()
+        }
+    }
+
+    /// Does the region do nothing
+    pub fn is_empty(&self) -> bool {
+        match *self {
+            Region::Seq(ref items) => items.iter().all(Region::is_empty),
+            _ => false
+        }
+    }
+
+    /// Does `other` appear anywhere in the region
+    fn mentions(&self, other: &Region) -> bool {
+        self == other || self.parts().into_iter().any(|p| p.mentions(other))
+    }
+
+    fn take(&mut self) -> Region {
+        ::std::mem::replace(self, Region::Seq(Vec::new()))
+    }
+
+    /// Call `f` on the region and everything in it, outermost first
+    fn walk(&self, f: &mut dyn FnMut(&Region)) {
+        f(self);
+        for p in self.parts() {
+            p.walk(f);
+        }
+    }
+
+    /// Mark the blocks the region runs
+    fn blocks(&self, marks: &mut [bool]) {
+        self.walk(&mut |x| match *x {
+            Region::Code(b) |
+            Region::If { block: b, .. } |
+            Region::Switch { block: b, .. } => marks[b] = true,
+            Region::Loop { kind: LoopKind::While { block, .. }, .. } |
+            Region::Loop { kind: LoopKind::DoWhile { block, .. }, .. } |
+            Region::Loop { kind: LoopKind::For { block, .. }, .. } => marks[block] = true,
+            _ => { }
+        });
+    }
+}
+
+/// Flatten nested sequences, unwrapping those of a single region
+fn normalize(r: &mut Region) {
+    for p in r.parts_mut() {
+        normalize(p);
+    }
+    if let Region::Seq(ref mut items) = *r {
+        let mut flat = Vec::with_capacity(items.len());
+        for item in items.drain(..) {
+            match item {
+                Region::Seq(inner) => flat.extend(inner),
+                other => flat.push(other)
+            };
+        }
+        *items = flat;
+    }
+    let single = match *r {
+        Region::Seq(ref mut items) if items.len() == 1 => items.pop(),
+        _ => None
+    };
+    if let Option::Some(x) = single {
+        *r = x;
+    }
+}
+
+/// A labeled statement holding only a loop is left by `break`ing the
+/// loop
+fn loop_breaks(r: &mut Region) {
+    fn rename(r: &mut Region, from: LabelId, to: LabelId) {
+        if *r == Region::Break(from) {
+            *r = Region::Break(to);
+        }
+        for p in r.parts_mut() {
+            rename(p, from, to);
+        }
+    }
+    for p in r.parts_mut() {
+        loop_breaks(p);
+    }
+    let merged = match *r {
+        Region::Labeled { label, ref mut body } => match **body {
+            Region::Loop { label: inner, ref mut body, .. } => {
+                rename(body, label, inner);
+                true
+            },
+            _ => false
+        },
+        _ => false
+    };
+    if merged {
+        if let Region::Labeled { body, .. } = r.take() {
+            *r = *body;
+        }
+    }
+}
+
+/// Drop jumps to where control would fall through anyway, `fall` holds
+/// the jumps equivalent to running off the end of `r`
+fn trim(r: &mut Region, fall: &[Region]) {
+    match *r {
+        Region::Break(_) |
+        Region::Continue(_) => if fall.contains(r) {
+            *r = Region::Seq(Vec::new());
+        },
+        Region::Seq(ref mut items) => {
+            let mut tail = true;
+            for item in items.iter_mut().rev() {
+                trim(item, if tail { fall } else { &[] });
+                tail = tail && item.is_empty();
+            }
+            items.retain(|x| !x.is_empty());
+        },
+        Region::Labeled { label, ref mut body } => {
+            let mut inner = fall.to_vec();
+            inner.push(Region::Break(label));
+            trim(body, &inner);
+        },
+        Region::Loop { label, ref mut body, .. } => trim(body, &[Region::Continue(label)]),
+        _ => for p in r.parts_mut() {
+            trim(p, fall);
+        }
+    };
+    let pointless = match *r {
+        Region::If { ref then, ref otherwise, .. } => then.is_empty() && otherwise.is_empty(),
+        _ => false
+    };
+    if pointless {
+        *r = Region::Seq(Vec::new());
+    }
+}
+
+/// Remove labeled statements nothing `break`s out of
+fn unused_labels(r: &mut Region) {
+    for p in r.parts_mut() {
+        unused_labels(p);
+    }
+    let unused = match *r {
+        Region::Labeled { label, ref body } => !body.mentions(&Region::Break(label)),
+        _ => false
+    };
+    if unused {
+        if let Region::Labeled { body, .. } = r.take() {
+            *r = *body;
+        }
+    }
+}
+
+/// Does running the region always end in a jump, return or throw
+fn ends_abruptly(r: &Region, cfg: &Cfg) -> bool {
+    match *r {
+        Region::Break(_) |
+        Region::Continue(_) |
+        Region::Goto { .. } => true,
+        Region::Code(b) => cfg.blocks[b].exit.is_some(),
+        Region::Seq(ref items) => items.last().is_some_and(|x| ends_abruptly(x, cfg)),
+        _ => false
+    }
+}
+
+/// Move the arm of an `If` out after it when the other arm never
+/// completes
+fn straighten(r: &mut Region, cfg: &Cfg) {
+    for p in r.parts_mut() {
+        straighten(p, cfg);
+    }
+    let hoist_then = match *r {
+        Region::If { ref then, ref otherwise, .. } => {
+            match (ends_abruptly(then, cfg), ends_abruptly(otherwise, cfg)) {
+                (true, false) if !otherwise.is_empty() => Some(false),
+                (false, true) if !then.is_empty() => Some(true),
+                _ => None
+            }
+        },
+        _ => None
+    };
+    if let Option::Some(hoist_then) = hoist_then {
+        if let Region::If { block, mut then, mut otherwise } = r.take() {
+            let arm = if hoist_then { then.take() } else { otherwise.take() };
+            *r = Region::Seq(vec![Region::If { block, then, otherwise }, arm]);
+        }
+    }
+}
+
+/// The pc of the local update ending a `while` body, if the loop test
+/// in `header` reads that local
+fn for_update(cfg: &Cfg, header: BlockId, body: &Region) -> Option<usize> {
+    let last = match *body {
+        Region::Seq(ref items) => items.last(),
+        ref x => Some(x)
+    };
+    let block = match last {
+        Option::Some(&Region::Code(b)) if b != header => b,
+        _ => return None
+    };
+    let ops = cfg.block_ops(block);
+    let ops = match ops.last() {
+        Option::Some(&(_, JOp::Goto(_))) |
+        Option::Some(&(_, JOp::GotoW(_))) => &ops[..ops.len() - 1],
+        _ => ops
+    };
+    let &(pc, ref op) = ops.last()?;
+    let slot = match op.local_access() {
+        Option::Some((access, slot)) if access.writes() => slot,
+        _ => return None
+    };
+    let tested = cfg.block_ops(header).iter().any(|x| match x.1.local_access() {
+        Option::Some((access, s)) => s == slot && access.reads(),
+        Option::None => false
+    });
+    if tested { Some(pc) } else { None }
+}
+
+/// Recognise `while`, `do`/`while` and `for` loops
+fn loop_kinds(r: &mut Region, cfg: &Cfg) {
+    for p in r.parts_mut() {
+        loop_kinds(p, cfg);
+    }
+    let (label, body) = match *r {
+        Region::Loop { label, kind: LoopKind::Endless, ref mut body } => (label, body),
+        _ => return
+    };
+    let mut items = match body.take() {
+        Region::Seq(items) => items,
+        other => vec![other]
+    };
+    let exit = Region::Break(label);
+    let repeat = Region::Continue(label);
+    let n = items.len();
+
+    // the test comes first
+    if n >= 2 {
+        let test = match (&items[0], &items[1]) {
+            (&Region::Code(h), &Region::If { block, ref then, ref otherwise }) if h == block => {
+                if **otherwise == exit {
+                    Some((block, true))
+                } else if **then == exit {
+                    Some((block, false))
+                } else {
+                    None
+                }
+            },
+            _ => None
+        };
+        if let Option::Some((block, taken)) = test {
+            let mut tail = items.split_off(2);
+            let mut rest = match items.pop() {
+                Option::Some(Region::If { then, otherwise, .. }) => if taken { *then } else { *otherwise },
+                _ => unreachable!()
+            };
+            normalize(&mut rest);
+            let mut rest = match rest {
+                Region::Seq(mut x) => {
+                    x.append(&mut tail);
+                    Region::Seq(x)
+                },
+                x => {
+                    tail.insert(0, x);
+                    Region::Seq(tail)
+                }
+            };
+            normalize(&mut rest);
+            *r = if rest.is_empty() {
+                Region::Loop { label, kind: LoopKind::DoWhile { block, taken }, body: Box::new(Region::Code(block)) }
+            } else {
+                let kind = match for_update(cfg, block, &rest) {
+                    Option::Some(update) if !rest.mentions(&repeat) => LoopKind::For { block, taken, update },
+                    _ => LoopKind::While { block, taken }
+                };
+                Region::Loop { label, kind, body: Box::new(rest) }
+            };
+            return;
+        }
+    }
+
+    // the test comes last, nothing may `continue` past it
+    if n >= 2 {
+        let test = match (&items[n - 2], &items[n - 1]) {
+            (&Region::Code(l), &Region::If { block, ref then, ref otherwise }) if l == block => {
+                if **otherwise == exit && then.is_empty() {
+                    Some((block, true))
+                } else if **then == exit && otherwise.is_empty() {
+                    Some((block, false))
+                } else {
+                    None
+                }
+            },
+            _ => None
+        };
+        if let Option::Some((block, taken)) = test {
+            if !items.iter().any(|x| x.mentions(&repeat)) {
+                items.pop();
+                let mut rest = Region::Seq(items);
+                normalize(&mut rest);
+                *r = Region::Loop { label, kind: LoopKind::DoWhile { block, taken }, body: Box::new(rest) };
+                return;
+            }
+        }
+    }
+    let mut rest = Region::Seq(items);
+    normalize(&mut rest);
+    **body = rest;
+}
+
+/// A catch-all handler around a whole try statement becomes its
+/// `finally`, if javac's copy of it for the normal path is found
+fn finally_blocks(r: &mut Region, cfg: &Cfg) {
+    for p in r.parts_mut() {
+        finally_blocks(p, cfg);
+    }
+    let catch_all = match *r {
+        Region::Try { ref catches, finally: None, .. } => {
+            catches.len() == 1 && catches[0].types == [0] && has_copy(r, &catches[0].body, cfg)
+        },
+        _ => false
+    };
+    if !catch_all {
+        return;
+    }
+    if let Region::Try { body, mut catches, .. } = r.take() {
+        let handler = Some(Box::new(catches.pop().unwrap().body));
+        *r = match *body {
+            Region::Try { body, catches, finally: None } => Region::Try { body, catches, finally: handler },
+            body => Region::Try { body: Box::new(body), catches: Vec::new(), finally: handler }
+        };
+    }
+}
+
+/// Is the code of a straight line `handler`, between storing and
+/// rethrowing the exception, also run outside the try statement `r`
+fn has_copy(r: &Region, handler: &Region, cfg: &Cfg) -> bool {
+    let mut inside = vec![false; cfg.blocks.len()];
+    r.blocks(&mut inside);
+    let mut code = Vec::new();
+    let mut straight = true;
+    handler.walk(&mut |x| match *x {
+        Region::Code(b) => code.extend(cfg.block_ops(b).iter().map(|o| &o.1)),
+        Region::Seq(_) => { },
+        _ => straight = false
+    });
+    let slot = match code.first().and_then(|op| op.local_access()) {
+        Option::Some((LocalAccess::Store(LocalType::Reference), x)) if straight && code.len() >= 3 => x,
+        _ => return false
+    };
+    let n = code.len();
+    if code[n - 2].local_access() != Some((LocalAccess::Load(LocalType::Reference), slot)) || *code[n - 1] != JOp::AThrow {
+        return false;
+    }
+    let body = &code[1..n - 2];
+    body.is_empty() || (0..cfg.blocks.len()).filter(|&b| !inside[b]).any(|b| {
+        let ops = cfg.block_ops(b);
+        ops.len() >= body.len() && ops.windows(body.len()).any(|w| w.iter().zip(body.iter()).all(|(a, b)| a.1 == **b))
+    })
+}
+
+/// Number labels in the order their statements start
+fn relabel(r: &mut Region, seen: &mut Vec<LabelId>) {
+    let number = |label: LabelId, seen: &mut Vec<LabelId>| match seen.iter().position(|x| *x == label) {
+        Option::Some(i) => i + 1,
+        Option::None => {
+            seen.push(label);
+            seen.len()
+        }
+    };
+    match *r {
+        Region::Loop { ref mut label, .. } |
+        Region::Labeled { ref mut label, .. } |
+        Region::Break(ref mut label) |
+        Region::Continue(ref mut label) => *label = number(*label, seen),
+        _ => { }
+    };
+    for p in r.parts_mut() {
+        relabel(p, seen);
+    }
+}
+
+/// A statement branches can leave
+#[derive(Clone,Copy,Debug)]
+enum Scope {
+    /// The loop headed by the block
+    Loop(BlockId, LabelId),
+    /// The labeled statement followed by the block
+    Block(BlockId, LabelId)
+}
+
+/// Handler blocks and their `catch_type`s
+type Handlers = Vec<(BlockId, Vec<u16>)>;
+
+#[derive(Clone,Debug)]
+enum ConstructKind {
+    Loop,
+    Try(Handlers)
+}
+
+/// A loop, or one level of try statement, and the block entering it
+#[derive(Clone,Debug)]
+struct Construct {
+    owner: BlockId,
+    kind: ConstructKind,
+    /// Blocks of the loop, or blocks protected or handling exceptions
+    members: Vec<bool>,
+    /// Blocks in the loop, or blocks protected
+    size: usize
+}
+
+/// Where a block is placed relative to the block that owns it
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+enum Place {
+    /// Where its only forward edge is
+    Inline,
+    /// After a labeled statement holding the owner
+    Merge,
+    /// After a labeled statement holding a construct of the owner
+    Follow(usize),
+    /// In a catch of a try statement
+    Handler
+}
+
+struct Builder<'c> {
+    cfg: &'c Cfg,
+    /// Where an edge into each block really leads
+    thread: Vec<BlockId>,
+    rpo: Vec<usize>,
+    constructs: Vec<Construct>,
+    /// Constructs each block enters, outermost first
+    owned: Vec<Vec<usize>>,
+    children: Vec<Vec<BlockId>>,
+    place: Vec<Place>,
+    labels: usize,
+    irreducible: Vec<(BlockId, BlockId)>
+}
+
+impl<'c> Builder<'c> {
+
+    fn label(&mut self) -> LabelId {
+        self.labels += 1;
+        self.labels
+    }
+
+    fn tree(&mut self, x: BlockId, ctx: &mut Vec<Scope>) -> Region {
+        self.level(x, 0, ctx)
+    }
+
+    /// Lay out `x` within the constructs it enters from the `k`th on
+    fn level(&mut self, x: BlockId, k: usize, ctx: &mut Vec<Scope>) -> Region {
+        if k == self.owned[x].len() {
+            let merges: Vec<BlockId> = self.children[x].iter()
+                .cloned()
+                .filter(|y| self.place[*y] == Place::Merge)
+                .collect();
+            return self.wrap(&merges, ctx, &mut |s, ctx| s.exit(x, ctx));
+        }
+        let c = self.owned[x][k];
+        let follows: Vec<BlockId> = self.children[x].iter()
+            .cloned()
+            .filter(|y| self.place[*y] == Place::Follow(c))
+            .collect();
+        let kind = self.constructs[c].kind.clone();
+        self.wrap(&follows, ctx, &mut |s, ctx| match kind {
+            ConstructKind::Loop => {
+                let label = s.label();
+                ctx.push(Scope::Loop(x, label));
+                let body = s.level(x, k + 1, ctx);
+                ctx.pop();
+                Region::Loop { label, kind: LoopKind::Endless, body: Box::new(body) }
+            },
+            ConstructKind::Try(ref handlers) => {
+                let body = s.level(x, k + 1, ctx);
+                let catches = handlers.iter()
+                    .map(|&(handler, ref types)| Catch { handler, types: types.clone(), body: s.tree(handler, ctx) })
+                    .collect();
+                Region::Try { body: Box::new(body), catches, finally: None }
+            }
+        })
+    }
+
+    /// Place `inner` in labeled statements followed by each of `ys`,
+    /// given in reverse postorder
+    fn wrap(&mut self, ys: &[BlockId], ctx: &mut Vec<Scope>, inner: &mut dyn FnMut(&mut Builder<'c>, &mut Vec<Scope>) -> Region) -> Region {
+        let (y, rest) = match ys.split_last() {
+            Option::Some((y, rest)) => (*y, rest),
+            Option::None => return inner(self, ctx)
+        };
+        let label = self.label();
+        ctx.push(Scope::Block(y, label));
+        let body = self.wrap(rest, ctx, inner);
+        ctx.pop();
+        let after = self.tree(y, ctx);
+        Region::Seq(vec![Region::Labeled { label, body: Box::new(body) }, after])
+    }
+
+    /// The code of `x` and the branch ending it
+    fn exit(&mut self, x: BlockId, ctx: &mut Vec<Scope>) -> Region {
+        let cfg = self.cfg;
+        let (pc, ref op) = *cfg.block_ops(x).last().unwrap();
+        let mut taken = None;
+        let mut next = None;
+        for e in cfg.successors(x) {
+            match e.kind {
+                EdgeKind::Taken => taken = Some(self.thread[e.to]),
+                EdgeKind::Fallthrough | EdgeKind::Jump => next = Some(self.thread[e.to]),
+                _ => { }
+            };
+        }
+        let end = if op.is_branch() && Jump::from_op(op).is_none() {
+            let targets: Vec<BlockId> = op.branch_targets(pc).into_iter()
+                .map(|t| self.thread[cfg.block_of(t as usize).unwrap()])
+                .collect();
+            let keys: Vec<i32> = match *op {
+                JOp::TableSwitch(_, low, _, ref offsets) => (0..offsets.len()).map(|i| low.wrapping_add(i as i32)).collect(),
+                JOp::LookUpSwitch(_, ref pairs) => pairs.iter().map(|p| p.0).collect(),
+                _ => Vec::new()
+            };
+            let mut groups: Vec<(BlockId, Vec<i32>)> = Vec::new();
+            for (key, &t) in keys.into_iter().zip(targets[1..].iter()) {
+                if t == targets[0] {
+                    continue;
+                }
+                match groups.iter_mut().find(|g| g.0 == t) {
+                    Option::Some(g) => g.1.push(key),
+                    Option::None => groups.push((t, vec![key]))
+                };
+            }
+            let cases = groups.into_iter()
+                .map(|(t, keys)| Case { keys, body: self.branch(x, t, ctx) })
+                .collect();
+            let default = Box::new(self.branch(x, targets[0], ctx));
+            Region::Switch { block: x, cases, default }
+        } else if let Option::Some(t) = taken {
+            let then = Box::new(self.branch(x, t, ctx));
+            let otherwise = Box::new(self.branch(x, next.unwrap(), ctx));
+            Region::If { block: x, then, otherwise }
+        } else if let Option::Some(t) = next {
+            self.branch(x, t, ctx)
+        } else {
+            return Region::Code(x);
+        };
+        Region::Seq(vec![Region::Code(x), end])
+    }
+
+    /// Move along the edge from `x` to `y`
+    fn branch(&mut self, x: BlockId, y: BlockId, ctx: &mut Vec<Scope>) -> Region {
+        let found = if self.rpo[y] <= self.rpo[x] {
+            ctx.iter().rev().filter_map(|s| match *s {
+                Scope::Loop(h, label) if h == y => Some(Region::Continue(label)),
+                _ => None
+            }).next()
+        } else if self.place[y] == Place::Inline {
+            return self.tree(y, ctx);
+        } else {
+            ctx.iter().rev().filter_map(|s| match *s {
+                Scope::Block(b, label) if b == y => Some(Region::Break(label)),
+                _ => None
+            }).next()
+        };
+        found.unwrap_or_else(|| {
+            if !self.irreducible.contains(&(x, y)) {
+                self.irreducible.push((x, y));
+            }
+            Region::Goto { from: x, to: y }
+        })
+    }
+}
+
+/// Recover structured control flow from `cfg`
+///
+/// Only reachable blocks appear in the result.
+pub fn structure(cfg: &Cfg) -> Result<Structure, StructureError> {
+    let n = cfg.blocks.len();
+    for e in cfg.edges.iter() {
+        if e.kind == EdgeKind::JsrCall && cfg.blocks[e.from].reachable {
+            let pc = cfg.block_ops(e.from).last().unwrap().0;
+            return Err(StructureError { pc, reason: StructureFault::Subroutine });
+        }
+    }
+
+    // each handler belongs to a try entered at its immediate dominator
+    let dom = DomTree::dominators(cfg);
+    let mut handlers: Vec<(BlockId, Vec<u16>, BlockId, Vec<bool>)> = Vec::new();
+    for h in 0..n {
+        let block = &cfg.blocks[h];
+        if !block.handler || !block.reachable {
+            continue;
+        }
+        let fail = StructureError { pc: block.start, reason: StructureFault::Handler };
+        let mut protected = vec![false; n];
+        let mut types = Vec::new();
+        for e in cfg.predecessors(h) {
+            match e.kind {
+                EdgeKind::Exception(t) => {
+                    protected[e.from] = cfg.blocks[e.from].reachable;
+                    if !types.contains(&t) {
+                        types.push(t);
+                    }
+                },
+                _ => return Err(fail)
+            };
+        }
+        match dom.idom(h) {
+            Option::Some(entry) if protected[entry] => handlers.push((h, types, entry, protected)),
+            _ => return Err(fail)
+        };
+    }
+
+    // edges into blocks holding only a `goto` skip straight past them
+    let skip = |b: BlockId| {
+        b != cfg.entry() &&
+            !cfg.blocks[b].handler &&
+            !handlers.iter().any(|h| h.2 == b) &&
+            matches!(cfg.block_ops(b), [(_, JOp::Goto(_))] | [(_, JOp::GotoW(_))])
+    };
+    let mut thread: Vec<BlockId> = (0..n).collect();
+    for (b, target) in thread.iter_mut().enumerate() {
+        let mut t = b;
+        for _ in 0..n {
+            if !skip(t) {
+                *target = t;
+                break;
+            }
+            t = cfg.successors(t).next().unwrap().to;
+        }
+    }
+
+    // the graph to structure replaces exceptional edges with one edge
+    // from each try entry to its handlers
+    let mut succs: Vec<Vec<BlockId>> = vec![Vec::new(); n];
+    for (b, list) in succs.iter_mut().enumerate() {
+        if cfg.blocks[b].reachable {
+            *list = cfg.successors(b)
+                .filter(|e| !e.kind.is_exceptional())
+                .map(|e| thread[e.to])
+                .collect();
+        }
+    }
+    for &(h, _, entry, _) in handlers.iter() {
+        succs[entry].push(h);
+    }
+    let gdom = DomTree::from_successors(&succs, &[cfg.entry()]);
+
+    let mut order = Vec::with_capacity(n);
+    let mut seen = vec![false; n];
+    let mut stack = vec![(cfg.entry(), 0)];
+    seen[cfg.entry()] = true;
+    while let Option::Some(&mut (b, ref mut i)) = stack.last_mut() {
+        match succs[b].get(*i) {
+            Option::Some(&s) => {
+                *i += 1;
+                if !seen[s] {
+                    seen[s] = true;
+                    stack.push((s, 0));
+                }
+            },
+            Option::None => {
+                order.push(b);
+                stack.pop();
+            }
+        };
+    }
+    order.reverse();
+    let mut rpo = vec![usize::MAX; n];
+    for (i, &b) in order.iter().enumerate() {
+        rpo[b] = i;
+    }
+    let mut preds = vec![Vec::new(); n];
+    let mut forward = vec![0; n];
+    for &b in order.iter() {
+        for &s in succs[b].iter() {
+            preds[s].push(b);
+            if rpo[s] > rpo[b] {
+                forward[s] += 1;
+            }
+        }
+    }
+
+    // natural loops
+    let mut constructs = Vec::new();
+    let mut latches: Vec<Vec<BlockId>> = vec![Vec::new(); n];
+    let mut irreducible = Vec::new();
+    for &b in order.iter() {
+        for &s in succs[b].iter() {
+            if rpo[s] > rpo[b] {
+                continue;
+            }
+            if gdom.dominates(s, b) {
+                latches[s].push(b);
+            } else if !irreducible.contains(&(b, s)) {
+                irreducible.push((b, s));
+            }
+        }
+    }
+    for &header in order.iter() {
+        if latches[header].is_empty() {
+            continue;
+        }
+        let mut members = vec![false; n];
+        members[header] = true;
+        let mut work = latches[header].clone();
+        while let Option::Some(b) = work.pop() {
+            if !members[b] {
+                members[b] = true;
+                work.extend(preds[b].iter().cloned());
+            }
+        }
+        let size = members.iter().filter(|x| **x).count();
+        constructs.push(Construct { owner: header, kind: ConstructKind::Loop, members, size });
+    }
+
+    // try statements, one level per distinct set of protected blocks
+    let mut is_handler = vec![false; n];
+    let mut levels: Vec<(BlockId, Vec<bool>, Handlers)> = Vec::new();
+    for (h, types, entry, protected) in handlers {
+        is_handler[h] = true;
+        match levels.iter_mut().find(|l| l.0 == entry && l.1 == protected) {
+            Option::Some(l) => l.2.push((h, types)),
+            Option::None => levels.push((entry, protected, vec![(h, types)]))
+        };
+    }
+    for (owner, protected, caught) in levels {
+        let members: Vec<bool> = (0..n)
+            .map(|b| protected[b] || caught.iter().any(|&(h, _)| gdom.dominates(h, b)))
+            .collect();
+        let size = protected.iter().filter(|x| **x).count();
+        constructs.push(Construct { owner, kind: ConstructKind::Try(caught), members, size });
+    }
+    let mut owned = vec![Vec::new(); n];
+    for (i, c) in constructs.iter().enumerate() {
+        owned[c.owner].push(i);
+    }
+    // try levels nest by what they protect, a loop goes inside those
+    // holding all of it
+    for list in owned.iter_mut() {
+        let (loops, mut tries): (Vec<usize>, Vec<usize>) = list.iter()
+            .partition(|&&i| matches!(constructs[i].kind, ConstructKind::Loop));
+        tries.sort_by_key(|&i| Reverse(constructs[i].size));
+        for l in loops {
+            let within = |t: &usize| (0..n).all(|b| !constructs[l].members[b] || constructs[*t].members[b]);
+            let at = tries.iter().take_while(|t| within(t)).count();
+            tries.insert(at, l);
+        }
+        *list = tries;
+    }
+    let mut rank = vec![0; constructs.len()];
+    for list in owned.iter() {
+        for (i, &c) in list.iter().enumerate() {
+            rank[c] = i;
+        }
+    }
+
+    // blocks whose code never leaves their dominator subtree
+    let mut open = vec![false; n];
+    for &b in order.iter() {
+        for &s in succs[b].iter() {
+            let mut a = Some(b);
+            while let Option::Some(x) = a {
+                if gdom.dominates(x, s) {
+                    break;
+                }
+                open[x] = true;
+                a = gdom.idom(x);
+            }
+        }
+    }
+
+    // code following a construct moves out of it, only code that ends
+    // the method stays in a loop
+    let mut parent: Vec<Option<BlockId>> = (0..n).map(|b| gdom.idom(b)).collect();
+    for y in 0..n {
+        let x = match parent[y] {
+            Option::Some(x) if !is_handler[y] => x,
+            _ => continue
+        };
+        let outer = (0..constructs.len())
+            .filter(|&c| constructs[c].members[x] && !constructs[c].members[y])
+            .filter(|&c| open[y] || !matches!(constructs[c].kind, ConstructKind::Loop))
+            .min_by_key(|&c| (rpo[constructs[c].owner], rank[c]));
+        if let Option::Some(c) = outer {
+            parent[y] = Some(constructs[c].owner);
+        }
+    }
+    let mut children = vec![Vec::new(); n];
+    let mut place = vec![Place::Inline; n];
+    for &y in order.iter() {
+        let x = match parent[y] {
+            Option::Some(x) => x,
+            Option::None => continue
+        };
+        if is_handler[y] {
+            place[y] = Place::Handler;
+            continue;
+        }
+        children[x].push(y);
+        place[y] = match owned[x].iter().find(|&&c| !constructs[c].members[y]) {
+            Option::Some(&c) => Place::Follow(c),
+            Option::None if forward[y] >= 2 => Place::Merge,
+            Option::None => Place::Inline
+        };
+    }
+
+    let mut builder = Builder { cfg, thread, rpo, constructs, owned, children, place, labels: 0, irreducible };
+    let mut body = builder.tree(cfg.entry(), &mut Vec::new());
+    normalize(&mut body);
+    loop_breaks(&mut body);
+    trim(&mut body, &[]);
+    unused_labels(&mut body);
+    straighten(&mut body, cfg);
+    normalize(&mut body);
+    trim(&mut body, &[]);
+    normalize(&mut body);
+    loop_kinds(&mut body, cfg);
+    finally_blocks(&mut body, cfg);
+    normalize(&mut body);
+    relabel(&mut body, &mut Vec::new());
+    Ok(Structure { body, irreducible: builder.irreducible })
+}
+
+impl Region {
+
+    fn write(&self, f: &mut fmt::Formatter, depth: usize) -> fmt::Result {
+        let pad = "    ".repeat(depth);
+        match *self {
+            Region::Code(b) => writeln!(f, "{}B{}", pad, b),
+            Region::Seq(ref items) => {
+                for item in items.iter() {
+                    item.write(f, depth)?;
+                }
+                Ok(())
+            },
+            Region::If { block, ref then, ref otherwise } => {
+                if then.is_empty() {
+                    writeln!(f, "{}if (!B{}) {{", pad, block)?;
+                    otherwise.write(f, depth + 1)?;
+                } else {
+                    writeln!(f, "{}if (B{}) {{", pad, block)?;
+                    then.write(f, depth + 1)?;
+                    if !otherwise.is_empty() {
+                        writeln!(f, "{}}} else {{", pad)?;
+                        otherwise.write(f, depth + 1)?;
+                    }
+                }
+                writeln!(f, "{}}}", pad)
+            },
+            Region::Switch { block, ref cases, ref default } => {
+                writeln!(f, "{}switch (B{}) {{", pad, block)?;
+                for case in cases.iter() {
+                    let keys: Vec<String> = case.keys.iter().map(|k| k.to_string()).collect();
+                    writeln!(f, "{}    case {} -> {{", pad, keys.join(", "))?;
+                    case.body.write(f, depth + 2)?;
+                    writeln!(f, "{}    }}", pad)?;
+                }
+                writeln!(f, "{}    default -> {{", pad)?;
+                default.write(f, depth + 2)?;
+                writeln!(f, "{}    }}", pad)?;
+                writeln!(f, "{}}}", pad)
+            },
+            Region::Loop { label, kind, ref body } => {
+                let not = |taken: bool| if taken { "" } else { "!" };
+                match kind {
+                    LoopKind::Endless => writeln!(f, "{}L{}: while (true) {{", pad, label),
+                    LoopKind::While { block, taken } => writeln!(f, "{}L{}: while ({}B{}) {{", pad, label, not(taken), block),
+                    LoopKind::For { block, taken, update } => {
+                        writeln!(f, "{}L{}: for (; {}B{}; pc {}) {{", pad, label, not(taken), block, update)
+                    },
+                    LoopKind::DoWhile { .. } => writeln!(f, "{}L{}: do {{", pad, label)
+                }?;
+                body.write(f, depth + 1)?;
+                match kind {
+                    LoopKind::DoWhile { block, taken } => writeln!(f, "{}}} while ({}B{});", pad, not(taken), block),
+                    _ => writeln!(f, "{}}}", pad)
+                }
+            },
+            Region::Labeled { label, ref body } => {
+                writeln!(f, "{}L{}: {{", pad, label)?;
+                body.write(f, depth + 1)?;
+                writeln!(f, "{}}}", pad)
+            },
+            Region::Break(label) => writeln!(f, "{}break L{};", pad, label),
+            Region::Continue(label) => writeln!(f, "{}continue L{};", pad, label),
+            Region::Try { ref body, ref catches, ref finally } => {
+                writeln!(f, "{}try {{", pad)?;
+                body.write(f, depth + 1)?;
+                for c in catches.iter() {
+                    let types: Vec<String> = c.types.iter()
+                        .map(|&t| if t == 0 { "any".to_string() } else { format!("#{}", t) })
+                        .collect();
+                    writeln!(f, "{}}} catch ({}) {{", pad, types.join(" | "))?;
+                    c.body.write(f, depth + 1)?;
+                }
+                if let Option::Some(ref x) = *finally {
+                    writeln!(f, "{}}} finally {{", pad)?;
+                    x.write(f, depth + 1)?;
+                }
+                writeln!(f, "{}}}", pad)
+            },
+            Region::Goto { to, .. } => writeln!(f, "{}goto B{};", pad, to)
+        }
+    }
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write(f, 0)
+    }
+}
+
+#[test]
+fn test_structure() {
+    use super::super::clazz::ExceptionEntry;
+    use super::decoder::Decoder;
+
+    let build = |code: &[u8], ex: &[ExceptionEntry]| {
+        let ops = Decoder::new(code).map(Result::unwrap).collect();
+        Cfg::build(ops, code.len(), ex).unwrap()
+    };
+
+    // static int f(int[] a, int n) {
+    //     int s = 0;
+    //     for (int i = 0; i < n; i++) {
+    //         if (a[i] < 0) break;
+    //         if (a[i] == 0) continue;
+    //         s += a[i];
+    //     }
+    //     try { s = s / n; } catch (ArithmeticException e) { s = -1; }
+    //     switch (s) { case 1: s = 10; break; case 2: case 3: s = 20; break; default: s = 0; }
+    //     return s;
+    // }
+    let ex = [ExceptionEntry { start_pc: 39, end_pc: 43, handler_pc: 46, catch_type: 7 }];
+    let cfg = build(&[
+        0x03, 0x3D, 0x03, 0x3E, 0x1D, 0x1B, 0xA2, 0x00, 0x21, 0x2A,
+        0x1D, 0x2E, 0x9C, 0x00, 0x06, 0xA7, 0x00, 0x18, 0x2A, 0x1D,
+        0x2E, 0x9A, 0x00, 0x06, 0xA7, 0x00, 0x09, 0x1C, 0x2A, 0x1D,
+        0x2E, 0x60, 0x3D, 0x84, 0x03, 0x01, 0xA7, 0xFF, 0xE0, 0x1C,
+        0x1B, 0x6C, 0x3D, 0xA7, 0x00, 0x06, 0x4E, 0x02, 0x3D, 0x1C,
+        0xAA, 0x00, 0x00, 0x00, 0x00, 0x26, 0x00, 0x00, 0x00, 0x01,
+        0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00, 0x1A, 0x00, 0x00,
+        0x00, 0x20, 0x00, 0x00, 0x00, 0x20, 0x10, 0x0A, 0x3D, 0xA7,
+        0x00, 0x0B, 0x10, 0x14, 0x3D, 0xA7, 0x00, 0x05, 0x03, 0x3D,
+        0x1C, 0xAC
+    ], &ex);
+    let s = structure(&cfg).unwrap();
+    assert!(s.irreducible.is_empty());
+    assert_eq!(s.body.to_string(), "\
+B0
+L1: for (; !B1; pc 33) {
+    B2
+    if (!B2) {
+        break L1;
+    }
+    B4
+    if (B4) {
+        B6
+    }
+    B7
+}
+try {
+    B8
+} catch (#7) {
+    B10
+}
+B11
+switch (B11) {
+    case 1 -> {
+        B12
+    }
+    case 2, 3 -> {
+        B13
+    }
+    default -> {
+        B14
+    }
+}
+B15
+");
+
+    // two entries into the cycle between 4 and 7
+    // 0: iload_0; ifeq 7
+    // 4: iinc 0 1
+    // 7: iload_0; ifeq 14
+    // 11: goto 4
+    // 14: return
+    let cfg = build(&[
+        0x1A, 0x99, 0x00, 0x06, 0x84, 0x00, 0x01, 0x1A, 0x99, 0x00,
+        0x06, 0xA7, 0xFF, 0xF9, 0xB1
+    ], &[]);
+    let s = structure(&cfg).unwrap();
+    assert_eq!(s.irreducible, vec![(1, 2)]);
+    assert!(s.body.mentions(&Region::Goto { from: 1, to: 2 }));
+
+    // static int g(int n) { int s; try { s = 10 / n; } finally { n++; } return s; }
+    // 0: bipush 10; 2: iload_0; 3: idiv; 4: istore_1; 5: iinc 0 1; 8: goto 17
+    // 11: astore_2; 12: iinc 0 1; 15: aload_2; 16: athrow; 17: iload_1; 18: ireturn
+    let ex = [ExceptionEntry { start_pc: 0, end_pc: 5, handler_pc: 11, catch_type: 0 }];
+    let cfg = build(&[
+        0x10, 0x0A, 0x1A, 0x6C, 0x3C, 0x84, 0x00, 0x01, 0xA7, 0x00,
+        0x09, 0x4D, 0x84, 0x00, 0x01, 0x2C, 0xBF, 0x1B, 0xAC
+    ], &ex);
+    assert_eq!(structure(&cfg).unwrap().body.to_string(), "\
+try {
+    B0
+} finally {
+    B2
+}
+B1
+B3
+");
+
+    // the same without the copy for the normal path, the handler only
+    // runs when something is thrown
+    // 0: bipush 10; 2: iload_0; 3: idiv; 4: istore_1; 5: goto 14
+    // 8: astore_2; 9: iinc 0 1; 12: aload_2; 13: athrow; 14: iload_1; 15: ireturn
+    let ex = [ExceptionEntry { start_pc: 0, end_pc: 5, handler_pc: 8, catch_type: 0 }];
+    let cfg = build(&[
+        0x10, 0x0A, 0x1A, 0x6C, 0x3C, 0xA7, 0x00, 0x09, 0x4D, 0x84,
+        0x00, 0x01, 0x2C, 0xBF, 0x1B, 0xAC
+    ], &ex);
+    assert_eq!(structure(&cfg).unwrap().body.to_string(), "\
+try {
+    B0
+} catch (any) {
+    B2
+}
+B3
+");
+}
@@ -0,0 +1,1224 @@
+//! Bytecode verification, see JVMS 4.10.
+//!
+//! Class files from version 50 onwards are type checked against their
+//! `StackMapTable`. Older class files, and version 50 files that fail
+//! type checking, are verified by type inference instead. Subroutines
+//! are handled approximately by inference: every `ret` may return to
+//! any instruction following a `jsr`.
+
+use super::super::clazz::{
+    Class,
+    CodeAttribute,
+    ConstantsPool,
+    PoolMembers,
+    FieldType,
+    MethodType,
+    MethodAccessFlags,
+    StackMapFrame,
+    VerificationType,
+    find_attribute,
+    read_stack_map_table
+};
+use super::ops::JOp;
+use super::decoder::{Decoder, DecodeFault};
+use super::locals::{LocalAccess, LocalType};
+use super::hierarchy::{ClassHierarchy, OBJECT, is_ref_assignable, element_class};
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+const THROWABLE: &str = "java/lang/Throwable";
+
+/// A verification type
+///
+/// Longs and doubles take two slots, the second holding `Top`.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub enum VType {
+    Top,
+    Int,
+    Float,
+    Long,
+    Double,
+    Null,
+    /// `this` inside a constructor, before the super constructor runs
+    UninitializedThis,
+    /// Created by the `new` at this pc, not yet constructed
+    Uninitialized(u16),
+    /// Internal class name, or a descriptor for arrays (`[I`)
+    Reference(String),
+    /// Pushed by `jsr`, only legal before class version 51
+    ReturnAddress
+}
+impl VType {
+
+    /// The verification type of a field, parameter or return type
+    pub fn from_field(t: &FieldType) -> VType {
+        match *t {
+            FieldType::Byte |
+            FieldType::Char |
+            FieldType::Short |
+            FieldType::Boolean |
+            FieldType::Int => VType::Int,
+            FieldType::Float => VType::Float,
+            FieldType::Long => VType::Long,
+            FieldType::Double => VType::Double,
+            FieldType::Object(ref name) => VType::Reference(name.clone()),
+            FieldType::Array(_) => VType::Reference(t.to_string())
+        }
+    }
+
+    /// Slots occupied
+    pub fn width(&self) -> usize {
+        match *self {
+            VType::Long | VType::Double => 2,
+            _ => 1
+        }
+    }
+
+    /// Any kind of object reference, initialized or not
+    pub fn is_reference(&self) -> bool {
+        matches!(*self,
+            VType::Null | VType::UninitializedThis |
+            VType::Uninitialized(_) | VType::Reference(_))
+    }
+}
+impl fmt::Display for VType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VType::Top => write!(f, "top"),
+            VType::Int => write!(f, "int"),
+            VType::Float => write!(f, "float"),
+            VType::Long => write!(f, "long"),
+            VType::Double => write!(f, "double"),
+            VType::Null => write!(f, "null"),
+            VType::UninitializedThis => write!(f, "uninitializedThis"),
+            VType::Uninitialized(pc) => write!(f, "uninitialized({})", pc),
+            VType::Reference(ref name) => write!(f, "{}", name),
+            VType::ReturnAddress => write!(f, "returnAddress")
+        }
+    }
+}
+
+/// Types of the locals and operand stack at one pc, slot by slot
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct Frame {
+    pub locals: Vec<VType>,
+    pub stack: Vec<VType>
+}
+impl Frame {
+
+    /// The locals on entry to a method, as stack map entries (longs and
+    /// doubles appear once)
+    pub fn initial_entries(ctx: MethodContext) -> Result<Vec<VType>, VerifyFault> {
+        let m = MethodType::parse(ctx.descriptor).ok_or(VerifyFault::BadDescriptor)?;
+        let mut locals = Vec::new();
+        if !ctx.is_static {
+            if ctx.name == "<init>" && ctx.class_name != OBJECT {
+                locals.push(VType::UninitializedThis);
+            } else {
+                locals.push(object(ctx.class_name));
+            }
+        }
+        locals.extend(m.params.iter().map(VType::from_field));
+        Ok(locals)
+    }
+
+    /// The frame on entry to a method with `max_locals` locals
+    pub fn initial(ctx: MethodContext, max_locals: usize) -> Result<Frame, VerifyFault> {
+        let entries = Frame::initial_entries(ctx)?;
+        let locals = Frame::expand(&entries, max_locals)
+            .ok_or(VerifyFault::BadLocal(max_locals as u16))?;
+        Ok(Frame { locals, stack: Vec::new() })
+    }
+
+    /// Expand stack map entries into slots, padding with `Top`
+    ///
+    /// `None` if the entries take more than `pad` slots.
+    pub fn expand(entries: &[VType], pad: usize) -> Option<Vec<VType>> {
+        let mut slots = Vec::with_capacity(pad);
+        for t in entries {
+            slots.push(t.clone());
+            if t.width() == 2 {
+                slots.push(VType::Top);
+            }
+        }
+        if slots.len() > pad {
+            return None;
+        }
+        slots.resize(pad, VType::Top);
+        Some(slots)
+    }
+
+    /// Fold slots back into stack map entries, the reverse of `expand`
+    ///
+    /// Trailing `Top`s are dropped.
+    pub fn collapse(slots: &[VType]) -> Vec<VType> {
+        let mut entries = Vec::with_capacity(slots.len());
+        let mut i = 0;
+        while i < slots.len() {
+            entries.push(slots[i].clone());
+            i += slots[i].width();
+        }
+        while entries.last() == Some(&VType::Top) {
+            entries.pop();
+        }
+        entries
+    }
+}
+
+/// Why verification failed
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum VerifyFault {
+    /// An operand or return value has the wrong type
+    Mismatch { expected: VType, actual: VType },
+    /// A local variable has the wrong type
+    LocalMismatch { index: u16, expected: VType, actual: VType },
+    /// Two frames disagree on the stack height
+    StackHeight { expected: u16, actual: u16 },
+    StackUnderflow,
+    /// More than `max_stack` slots are used
+    StackOverflow,
+    /// A local outside `max_locals`
+    BadLocal(u16),
+    /// The instruction would take half of a long or double
+    SplitValue,
+    /// A branch target, exception handler or instruction following an
+    /// unconditional transfer has no stack map frame
+    MissingFrame,
+    /// The `StackMapTable` is malformed, or places a frame between
+    /// instructions
+    BadStackMap,
+    /// A constant pool operand is missing or of the wrong kind
+    BadConstant(u16),
+    /// A branch or exception handler that is not an instruction
+    BadTarget,
+    /// Execution can run past the end of the code array
+    FallsOffEnd,
+    /// `jsr` and `ret` are forbidden from class version 51
+    JsrNotAllowed,
+    /// A constructor returns before calling its super constructor
+    UninitializedThis,
+    /// The method descriptor is invalid
+    BadDescriptor,
+    Decode(DecodeFault)
+}
+
+/// A verification failure and the pc it concerns
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct VerifyError {
+    pub pc: usize,
+    pub reason: VerifyFault
+}
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.reason {
+            VerifyFault::Mismatch { ref expected, ref actual } => {
+                write!(f, "expected {} but found {}", expected, actual)
+            },
+            VerifyFault::LocalMismatch { index, ref expected, ref actual } => {
+                write!(f, "expected {} in local {} but found {}", expected, index, actual)
+            },
+            VerifyFault::StackHeight { expected, actual } => {
+                write!(f, "expected stack height {} but found {}", expected, actual)
+            },
+            VerifyFault::StackUnderflow => write!(f, "operand stack underflow"),
+            VerifyFault::StackOverflow => write!(f, "operand stack overflow"),
+            VerifyFault::BadLocal(x) => write!(f, "local {} out of range", x),
+            VerifyFault::SplitValue => write!(f, "long or double split in half"),
+            VerifyFault::MissingFrame => write!(f, "missing stack map frame"),
+            VerifyFault::BadStackMap => write!(f, "invalid StackMapTable"),
+            VerifyFault::BadConstant(x) => write!(f, "invalid constant #{}", x),
+            VerifyFault::BadTarget => write!(f, "invalid branch target"),
+            VerifyFault::FallsOffEnd => write!(f, "execution falls off the end of the code"),
+            VerifyFault::JsrNotAllowed => write!(f, "jsr/ret in a class file newer than 50"),
+            VerifyFault::UninitializedThis => write!(f, "returning before this is initialized"),
+            VerifyFault::BadDescriptor => write!(f, "invalid method descriptor"),
+            VerifyFault::Decode(ref x) => write!(f, "decoding failed ({:?})", x)
+        }?;
+        write!(f, " at pc {}", self.pc)
+    }
+}
+
+/// The method being verified
+#[derive(Clone,Copy,Debug)]
+pub struct MethodContext<'c> {
+    /// Internal name of the declaring class
+    pub class_name: &'c str,
+    pub name: &'c str,
+    pub descriptor: &'c str,
+    pub is_static: bool,
+    /// `major_version` of the class file
+    pub major_version: u16
+}
+
+type Res<T> = Result<T, VerifyFault>;
+
+fn object(name: &str) -> VType {
+    VType::Reference(name.to_string())
+}
+
+/// Verifies the instructions of one method
+struct Machine<'v, 'a: 'v> {
+    ctx: MethodContext<'v>,
+    pool: &'v ConstantsPool<'a>,
+    hierarchy: &'v dyn ClassHierarchy,
+    /// Check reference assignability
+    strict: bool,
+    ret: Option<VType>,
+    max_stack: usize,
+    max_locals: usize,
+    /// pc of every `New` and the class it creates
+    news: HashMap<u16, u16>
+}
+
+impl<'v, 'a> Machine<'v, 'a> {
+
+    fn assignable(&self, from: &VType, to: &VType) -> bool {
+        match (from, to) {
+            (_, &VType::Top) => true,
+            (&VType::Null, &VType::Reference(_)) => true,
+            (VType::Reference(a), VType::Reference(b)) => !self.strict || is_ref_assignable(self.hierarchy, a, b),
+            (a, b) => a == b
+        }
+    }
+
+    /// Is the frame `from` assignable to `to`
+    fn frame_assignable(&self, from: &Frame, to: &Frame) -> Res<()> {
+        if from.stack.len() != to.stack.len() {
+            return Err(VerifyFault::StackHeight { expected: to.stack.len() as u16, actual: from.stack.len() as u16 });
+        }
+        for (a, b) in from.stack.iter().zip(to.stack.iter()) {
+            if !self.assignable(a, b) {
+                return Err(VerifyFault::Mismatch { expected: b.clone(), actual: a.clone() });
+            }
+        }
+        for (i, b) in to.locals.iter().enumerate() {
+            let a = from.locals.get(i).unwrap_or(&VType::Top);
+            if !self.assignable(a, b) {
+                return Err(VerifyFault::LocalMismatch { index: i as u16, expected: b.clone(), actual: a.clone() });
+            }
+        }
+        Ok(())
+    }
+
+    fn class_name(&self, index: u16) -> Res<String> {
+        self.pool.get_class_name(index)
+            .map(|x| x.into_owned())
+            .ok_or(VerifyFault::BadConstant(index))
+    }
+
+    fn catch_type(&self, index: u16) -> Res<VType> {
+        match index {
+            0 => Ok(object(THROWABLE)),
+            x => self.class_name(x).map(VType::Reference)
+        }
+    }
+
+    /// Pops `n` slots worth of values, without splitting a long
+    fn check_boundary(&self, f: &Frame, n: usize) -> Res<()> {
+        let len = f.stack.len();
+        if len < n {
+            return Err(VerifyFault::StackUnderflow);
+        }
+        if f.stack[len - n] == VType::Top {
+            return Err(VerifyFault::SplitValue);
+        }
+        Ok(())
+    }
+
+    fn push(&self, f: &mut Frame, t: VType) -> Res<()> {
+        let width = t.width();
+        if f.stack.len() + width > self.max_stack {
+            return Err(VerifyFault::StackOverflow);
+        }
+        f.stack.push(t);
+        if width == 2 {
+            f.stack.push(VType::Top);
+        }
+        Ok(())
+    }
+
+    /// Pop a value assignable to `expected`
+    fn pop(&self, f: &mut Frame, expected: &VType) -> Res<VType> {
+        let width = expected.width();
+        let len = f.stack.len();
+        if len < width {
+            return Err(VerifyFault::StackUnderflow);
+        }
+        // the value on top, looking through the upper half of a long
+        let top = match f.stack[len - 1] {
+            VType::Top if len >= 2 => f.stack[len - 2].clone(),
+            ref x => x.clone()
+        };
+        let fits = if width == 2 {
+            f.stack[len - 1] == VType::Top && top == *expected
+        } else {
+            f.stack[len - 1] != VType::Top && self.assignable(&top, expected)
+        };
+        if !fits {
+            return Err(VerifyFault::Mismatch { expected: expected.clone(), actual: top });
+        }
+        f.stack.truncate(len - width);
+        Ok(top)
+    }
+
+    /// Pop any reference, including uninitialized ones
+    fn pop_ref(&self, f: &mut Frame) -> Res<VType> {
+        self.check_boundary(f, 1)?;
+        let actual = f.stack.pop().unwrap();
+        if !actual.is_reference() {
+            return Err(VerifyFault::Mismatch { expected: object(OBJECT), actual });
+        }
+        Ok(actual)
+    }
+
+    /// Pop an array, `None` if it was `null`, else its component descriptor
+    fn pop_array(&self, f: &mut Frame) -> Res<Option<String>> {
+        match self.pop_ref(f)? {
+            VType::Null => Ok(None),
+            VType::Reference(ref name) if name.starts_with('[') => Ok(Some(name[1..].to_string())),
+            actual => Err(VerifyFault::Mismatch { expected: object("[Ljava/lang/Object;"), actual })
+        }
+    }
+
+    fn array_load(&self, f: &mut Frame, components: &[&str], result: VType) -> Res<()> {
+        self.pop(f, &VType::Int)?;
+        if let Option::Some(c) = self.pop_array(f)? {
+            if !components.contains(&c.as_str()) {
+                let expected = VType::Reference(format!("[{}", components[0]));
+                return Err(VerifyFault::Mismatch { expected, actual: VType::Reference(format!("[{}", c)) });
+            }
+        }
+        self.push(f, result)
+    }
+
+    fn array_store(&self, f: &mut Frame, components: &[&str], value: VType) -> Res<()> {
+        self.pop(f, &value)?;
+        self.pop(f, &VType::Int)?;
+        if let Option::Some(c) = self.pop_array(f)? {
+            if !components.contains(&c.as_str()) {
+                let expected = VType::Reference(format!("[{}", components[0]));
+                return Err(VerifyFault::Mismatch { expected, actual: VType::Reference(format!("[{}", c)) });
+            }
+        }
+        Ok(())
+    }
+
+    fn unary(&self, f: &mut Frame, arg: VType, result: VType) -> Res<()> {
+        self.pop(f, &arg)?;
+        self.push(f, result)
+    }
+
+    /// `b` is on top of the stack, `a` beneath it
+    fn binary(&self, f: &mut Frame, a: VType, b: VType, result: VType) -> Res<()> {
+        self.pop(f, &b)?;
+        self.pop(f, &a)?;
+        self.push(f, result)
+    }
+
+    fn local(&self, f: &Frame, index: u16, width: usize) -> Res<VType> {
+        if index as usize + width > self.max_locals {
+            return Err(VerifyFault::BadLocal(index));
+        }
+        Ok(f.locals[index as usize].clone())
+    }
+
+    fn load(&self, f: &mut Frame, index: u16, t: LocalType) -> Res<()> {
+        let expected = match t {
+            LocalType::Int => VType::Int,
+            LocalType::Long => VType::Long,
+            LocalType::Float => VType::Float,
+            LocalType::Double => VType::Double,
+            LocalType::Reference => object(OBJECT)
+        };
+        let actual = self.local(f, index, expected.width())?;
+        let fits = match t {
+            LocalType::Reference => actual.is_reference(),
+            LocalType::Long | LocalType::Double => {
+                actual == expected && f.locals[index as usize + 1] == VType::Top
+            },
+            _ => actual == expected
+        };
+        if !fits {
+            return Err(VerifyFault::LocalMismatch { index, expected, actual });
+        }
+        self.push(f, actual)
+    }
+
+    fn store(&self, f: &mut Frame, index: u16, t: LocalType) -> Res<()> {
+        let value = match t {
+            LocalType::Int => self.pop(f, &VType::Int)?,
+            LocalType::Long => self.pop(f, &VType::Long)?,
+            LocalType::Float => self.pop(f, &VType::Float)?,
+            LocalType::Double => self.pop(f, &VType::Double)?,
+            LocalType::Reference => {
+                self.check_boundary(f, 1)?;
+                let actual = f.stack.pop().unwrap();
+                if !actual.is_reference() && actual != VType::ReturnAddress {
+                    return Err(VerifyFault::Mismatch { expected: object(OBJECT), actual });
+                }
+                actual
+            }
+        };
+        let width = value.width();
+        self.local(f, index, width)?;
+        let i = index as usize;
+        if i > 0 && f.locals[i - 1].width() == 2 {
+            f.locals[i - 1] = VType::Top;
+        }
+        f.locals[i] = value;
+        if width == 2 {
+            f.locals[i + 1] = VType::Top;
+        }
+        Ok(())
+    }
+
+    fn member(&self, index: u16) -> Res<(String, String, String)> {
+        self.pool.get_member_ref(index)
+            .map(|m| (m.class.into_owned(), m.name.into_owned(), m.descriptor.into_owned()))
+            .ok_or(VerifyFault::BadConstant(index))
+    }
+
+    fn field_type(&self, index: u16) -> Res<(String, VType)> {
+        let (class, _, desc) = self.member(index)?;
+        let t = FieldType::parse(&desc).ok_or(VerifyFault::BadConstant(index))?;
+        Ok((class, VType::from_field(&t)))
+    }
+
+    fn ldc(&self, f: &mut Frame, index: u16, wide: bool) -> Res<()> {
+        let t = match (self.pool.get(index), wide) {
+            (Option::Some(&PoolMembers::Integer(_)), false) => VType::Int,
+            (Option::Some(&PoolMembers::Float(_)), false) => VType::Float,
+            (Option::Some(&PoolMembers::Str(_)), false) => object("java/lang/String"),
+            (Option::Some(&PoolMembers::ClassInfo(_)), false) => object("java/lang/Class"),
+            (Option::Some(&PoolMembers::MethodType(_)), false) => object("java/lang/invoke/MethodType"),
+            (Option::Some(&PoolMembers::MethodHandle(..)), false) => object("java/lang/invoke/MethodHandle"),
+            (Option::Some(&PoolMembers::Long(_)), true) => VType::Long,
+            (Option::Some(&PoolMembers::Double(_)), true) => VType::Double,
+            _ => return Err(VerifyFault::BadConstant(index))
+        };
+        self.push(f, t)
+    }
+
+    fn invoke(&self, f: &mut Frame, op: &JOp) -> Res<()> {
+        let (index, receiver) = match *op {
+            JOp::InvokeStatic(x) | JOp::InvokedDynamic(x) => (x, false),
+            JOp::InvokeVirtual(x) | JOp::InvokeSpecial(x) | JOp::InvokedInterface(x, _) => (x, true),
+            _ => unreachable!()
+        };
+        let (class, name, desc) = match (op, self.pool.get(index)) {
+            (&JOp::InvokedDynamic(_), Option::Some(&PoolMembers::InvokeDynamic(_, nat))) => {
+                let (name, desc) = self.pool.get_name_and_type(nat).ok_or(VerifyFault::BadConstant(index))?;
+                (String::new(), name.into_owned(), desc.into_owned())
+            },
+            (&JOp::InvokedDynamic(_), _) => return Err(VerifyFault::BadConstant(index)),
+            _ => self.member(index)?
+        };
+        let m = MethodType::parse(&desc).ok_or(VerifyFault::BadConstant(index))?;
+        for p in m.params.iter().rev() {
+            self.pop(f, &VType::from_field(p))?;
+        }
+        if receiver {
+            if name == "<init>" {
+                let (from, to) = match self.pop_ref(f)? {
+                    VType::UninitializedThis => (VType::UninitializedThis, object(self.ctx.class_name)),
+                    VType::Uninitialized(pc) => {
+                        let created = self.news.get(&pc).ok_or(VerifyFault::BadConstant(index))?;
+                        (VType::Uninitialized(pc), VType::Reference(self.class_name(*created)?))
+                    },
+                    actual => return Err(VerifyFault::Mismatch { expected: VType::UninitializedThis, actual })
+                };
+                for slot in f.locals.iter_mut().chain(f.stack.iter_mut()) {
+                    if *slot == from {
+                        *slot = to.clone();
+                    }
+                }
+            } else {
+                let expected = match *op {
+                    JOp::InvokedInterface(..) => object(OBJECT),
+                    _ => VType::Reference(class)
+                };
+                self.pop(f, &expected)?;
+            }
+        }
+        match m.ret {
+            Option::Some(ref t) => self.push(f, VType::from_field(t)),
+            Option::None => Ok(())
+        }
+    }
+
+    fn return_value(&self, f: &mut Frame, kind: VType) -> Res<()> {
+        let expected = match self.ret {
+            Option::Some(ref t) if t.is_reference() == kind.is_reference() &&
+                (kind.is_reference() || *t == kind) => t.clone(),
+            Option::Some(ref t) => return Err(VerifyFault::Mismatch { expected: t.clone(), actual: kind }),
+            Option::None => return Err(VerifyFault::Mismatch { expected: VType::Top, actual: kind })
+        };
+        self.pop(f, &expected).map(|_| ())
+    }
+
+    /// Apply one instruction to `f`
+    fn execute(&self, pc: usize, op: &JOp, f: &mut Frame) -> Res<()> {
+        if let Option::Some((access, index)) = op.local_access() {
+            return match access {
+                LocalAccess::Load(t) => self.load(f, index, t),
+                LocalAccess::Store(t) => self.store(f, index, t),
+                LocalAccess::Increment(_) => match self.local(f, index, 1)? {
+                    VType::Int => Ok(()),
+                    actual => Err(VerifyFault::LocalMismatch { index, expected: VType::Int, actual })
+                },
+                LocalAccess::Ret => {
+                    if self.ctx.major_version >= 51 {
+                        return Err(VerifyFault::JsrNotAllowed);
+                    }
+                    match self.local(f, index, 1)? {
+                        VType::ReturnAddress => Ok(()),
+                        actual => Err(VerifyFault::LocalMismatch { index, expected: VType::ReturnAddress, actual })
+                    }
+                }
+            };
+        }
+        use self::VType::{Int, Long, Float, Double};
+        match *op {
+            JOp::Nop | JOp::BreakPoint | JOp::ImpDep1 | JOp::ImpDep2 |
+            JOp::Goto(_) | JOp::GotoW(_) => Ok(()),
+
+            JOp::AConstNull => self.push(f, VType::Null),
+            JOp::IConstM1 | JOp::IConst0 | JOp::IConst1 | JOp::IConst2 |
+            JOp::IConst3 | JOp::IConst4 | JOp::IConst5 |
+            JOp::BiPush(_) | JOp::SIPush(_) => self.push(f, Int),
+            JOp::LConst0 | JOp::LConst1 => self.push(f, Long),
+            JOp::FConst0 | JOp::FConst1 | JOp::FConst2 => self.push(f, Float),
+            JOp::DConst0 | JOp::DConst1 => self.push(f, Double),
+            JOp::LDC(x) => self.ldc(f, x as u16, false),
+            JOp::LDCW(x) => self.ldc(f, x, false),
+            JOp::LDC2W(x) => self.ldc(f, x, true),
+
+            JOp::IALoad => self.array_load(f, &["I"], Int),
+            JOp::LALoad => self.array_load(f, &["J"], Long),
+            JOp::FALoad => self.array_load(f, &["F"], Float),
+            JOp::DALoad => self.array_load(f, &["D"], Double),
+            JOp::BaLoad => self.array_load(f, &["B", "Z"], Int),
+            JOp::CaLoad => self.array_load(f, &["C"], Int),
+            JOp::SALoad => self.array_load(f, &["S"], Int),
+            JOp::AALoad => {
+                self.pop(f, &Int)?;
+                let t = match self.pop_array(f)? {
+                    Option::None => VType::Null,
+                    Option::Some(c) => match element_class(&c) {
+                        Option::Some(e) => object(e),
+                        Option::None => {
+                            let actual = VType::Reference(format!("[{}", c));
+                            return Err(VerifyFault::Mismatch { expected: object("[Ljava/lang/Object;"), actual });
+                        }
+                    }
+                };
+                self.push(f, t)
+            },
+            JOp::IAStore => self.array_store(f, &["I"], Int),
+            JOp::LAStore => self.array_store(f, &["J"], Long),
+            JOp::FAStore => self.array_store(f, &["F"], Float),
+            JOp::DAStore => self.array_store(f, &["D"], Double),
+            JOp::BaStore => self.array_store(f, &["B", "Z"], Int),
+            JOp::CaStore => self.array_store(f, &["C"], Int),
+            JOp::SAStore => self.array_store(f, &["S"], Int),
+            JOp::AAStore => {
+                self.pop_ref(f)?;
+                self.pop(f, &Int)?;
+                if let Option::Some(c) = self.pop_array(f)? {
+                    if element_class(&c).is_none() {
+                        let actual = VType::Reference(format!("[{}", c));
+                        return Err(VerifyFault::Mismatch { expected: object("[Ljava/lang/Object;"), actual });
+                    }
+                }
+                Ok(())
+            },
+
+            JOp::Pop => {
+                self.check_boundary(f, 1)?;
+                f.stack.pop();
+                Ok(())
+            },
+            JOp::Pop2 => {
+                self.check_boundary(f, 2)?;
+                let len = f.stack.len();
+                f.stack.truncate(len - 2);
+                Ok(())
+            },
+            JOp::Dup | JOp::Dupx1 | JOp::Dupx2 | JOp::Dup2 | JOp::Dup2x1 | JOp::Dup2x2 => {
+                // (slots copied, depth they are inserted beneath)
+                let (n, under) = match *op {
+                    JOp::Dup => (1, 0),
+                    JOp::Dupx1 => (1, 1),
+                    JOp::Dupx2 => (1, 2),
+                    JOp::Dup2 => (2, 0),
+                    JOp::Dup2x1 => (2, 1),
+                    _ => (2, 2)
+                };
+                self.check_boundary(f, n)?;
+                self.check_boundary(f, n + under)?;
+                if f.stack.len() + n > self.max_stack {
+                    return Err(VerifyFault::StackOverflow);
+                }
+                let len = f.stack.len();
+                let copy: Vec<VType> = f.stack[len - n..].to_vec();
+                let at = len - n - under;
+                for (i, t) in copy.into_iter().enumerate() {
+                    f.stack.insert(at + i, t);
+                }
+                Ok(())
+            },
+            JOp::Swap => {
+                self.check_boundary(f, 1)?;
+                self.check_boundary(f, 2)?;
+                let len = f.stack.len();
+                f.stack.swap(len - 1, len - 2);
+                Ok(())
+            },
+
+            JOp::IAdd | JOp::ISub | JOp::IMul | JOp::IDiv | JOp::IRem |
+            JOp::IAnd | JOp::IOr | JOp::IXor | JOp::ISHL | JOp::ISHR |
+            JOp::IUSHR => self.binary(f, Int, Int, Int),
+            JOp::LAdd | JOp::LSub | JOp::LMul | JOp::LDiv | JOp::LRem |
+            JOp::LAnd | JOp::LOr | JOp::LXor => self.binary(f, Long, Long, Long),
+            JOp::LSHL | JOp::LSHR | JOp::LUSHR => self.binary(f, Long, Int, Long),
+            JOp::FAdd | JOp::FSub | JOp::FMul | JOp::FDiv | JOp::FRem => self.binary(f, Float, Float, Float),
+            JOp::DAdd | JOp::DSub | JOp::DMul | JOp::DDiv | JOp::DRem => self.binary(f, Double, Double, Double),
+            JOp::Lcmp => self.binary(f, Long, Long, Int),
+            JOp::FcmpL | JOp::FcmpG => self.binary(f, Float, Float, Int),
+            JOp::DcmpL | JOp::DcmpG => self.binary(f, Double, Double, Int),
+            JOp::INeg | JOp::I2B | JOp::I2C | JOp::I2S => self.unary(f, Int, Int),
+            JOp::LNeg => self.unary(f, Long, Long),
+            JOp::FNeg => self.unary(f, Float, Float),
+            JOp::DNeg => self.unary(f, Double, Double),
+            JOp::I2L => self.unary(f, Int, Long),
+            JOp::I2F => self.unary(f, Int, Float),
+            JOp::I2D => self.unary(f, Int, Double),
+            JOp::L2I => self.unary(f, Long, Int),
+            JOp::L2F => self.unary(f, Long, Float),
+            JOp::L2D => self.unary(f, Long, Double),
+            JOp::F2I => self.unary(f, Float, Int),
+            JOp::F2L => self.unary(f, Float, Long),
+            JOp::F2D => self.unary(f, Float, Double),
+            JOp::D2I => self.unary(f, Double, Int),
+            JOp::D2L => self.unary(f, Double, Long),
+            JOp::D2F => self.unary(f, Double, Float),
+
+            JOp::IFEQ(_) | JOp::IFNE(_) | JOp::IFLT(_) | JOp::IFGE(_) |
+            JOp::IFGT(_) | JOp::IFLE(_) |
+            JOp::TableSwitch(..) | JOp::LookUpSwitch(..) => self.pop(f, &Int).map(|_| ()),
+            JOp::IFIcmpEQ(_) | JOp::IFIcmpNE(_) | JOp::IFIcmpLT(_) |
+            JOp::IfIcmpGE(_) | JOp::IFIcmpGT(_) | JOp::IFIcmpLE(_) => {
+                self.pop(f, &Int)?;
+                self.pop(f, &Int).map(|_| ())
+            },
+            JOp::IFAcmpEQ(_) | JOp::IFAcmpNE(_) => {
+                self.pop_ref(f)?;
+                self.pop_ref(f).map(|_| ())
+            },
+            JOp::IFNull(_) | JOp::IFnonNull(_) |
+            JOp::MonitorEnter | JOp::MonitorExit => self.pop_ref(f).map(|_| ()),
+            JOp::JSR(_) | JOp::JSRW(_) => {
+                if self.ctx.major_version >= 51 {
+                    return Err(VerifyFault::JsrNotAllowed);
+                }
+                self.push(f, VType::ReturnAddress)
+            },
+
+            JOp::IReturn => self.return_value(f, Int),
+            JOp::LReturn => self.return_value(f, Long),
+            JOp::FReturn => self.return_value(f, Float),
+            JOp::DReturn => self.return_value(f, Double),
+            JOp::AReturn => self.return_value(f, object(OBJECT)),
+            JOp::Return => {
+                if let Option::Some(ref t) = self.ret {
+                    return Err(VerifyFault::Mismatch { expected: t.clone(), actual: VType::Top });
+                }
+                if self.ctx.name == "<init>" && f.locals.contains(&VType::UninitializedThis) {
+                    return Err(VerifyFault::UninitializedThis);
+                }
+                Ok(())
+            },
+            JOp::AThrow => self.pop(f, &object(THROWABLE)).map(|_| ()),
+
+            JOp::GetStatic(x) => {
+                let (_, t) = self.field_type(x)?;
+                self.push(f, t)
+            },
+            JOp::PutStatic(x) => {
+                let (_, t) = self.field_type(x)?;
+                self.pop(f, &t).map(|_| ())
+            },
+            JOp::GetField(x) => {
+                let (class, t) = self.field_type(x)?;
+                self.pop(f, &VType::Reference(class))?;
+                self.push(f, t)
+            },
+            JOp::PutField(x) => {
+                let (class, t) = self.field_type(x)?;
+                self.pop(f, &t)?;
+                // constructors may set their own fields before calling super
+                let own = self.ctx.name == "<init>" &&
+                    class == self.ctx.class_name &&
+                    f.stack.last() == Some(&VType::UninitializedThis);
+                if own {
+                    f.stack.pop();
+                    Ok(())
+                } else {
+                    self.pop(f, &VType::Reference(class)).map(|_| ())
+                }
+            },
+            JOp::InvokeVirtual(_) | JOp::InvokeSpecial(_) | JOp::InvokeStatic(_) |
+            JOp::InvokedInterface(..) | JOp::InvokedDynamic(_) => self.invoke(f, op),
+
+            JOp::New(_) => self.push(f, VType::Uninitialized(pc as u16)),
+            JOp::NewArray(atype) => {
+                let desc = match atype {
+                    4 => "[Z",
+                    5 => "[C",
+                    6 => "[F",
+                    7 => "[D",
+                    8 => "[B",
+                    9 => "[S",
+                    10 => "[I",
+                    11 => "[J",
+                    x => return Err(VerifyFault::BadConstant(x as u16))
+                };
+                self.unary(f, Int, object(desc))
+            },
+            JOp::ANewArray(x) => {
+                let name = self.class_name(x)?;
+                let desc = if name.starts_with('[') {
+                    format!("[{}", name)
+                } else {
+                    format!("[L{};", name)
+                };
+                self.unary(f, Int, VType::Reference(desc))
+            },
+            JOp::MultiAneWArray(x, dims) => {
+                let name = self.class_name(x)?;
+                if dims == 0 || name.bytes().take_while(|b| *b == b'[').count() < dims as usize {
+                    return Err(VerifyFault::BadConstant(x));
+                }
+                for _ in 0..dims {
+                    self.pop(f, &Int)?;
+                }
+                self.push(f, VType::Reference(name))
+            },
+            JOp::ArrayLength => {
+                self.pop_array(f)?;
+                self.push(f, Int)
+            },
+            JOp::CheckCast(x) => {
+                self.pop_ref(f)?;
+                let name = self.class_name(x)?;
+                self.push(f, VType::Reference(name))
+            },
+            JOp::InstanceOf(_) => {
+                self.pop_ref(f)?;
+                self.push(f, Int)
+            },
+
+            // local variable instructions are handled above
+            _ => Ok(())
+        }
+    }
+
+    fn convert_stack_map(&self, t: &VerificationType) -> Res<VType> {
+        Ok(match *t {
+            VerificationType::Top => VType::Top,
+            VerificationType::Integer => VType::Int,
+            VerificationType::Float => VType::Float,
+            VerificationType::Double => VType::Double,
+            VerificationType::Long => VType::Long,
+            VerificationType::Null => VType::Null,
+            VerificationType::UninitializedThis => VType::UninitializedThis,
+            VerificationType::Object(x) => VType::Reference(self.class_name(x)?),
+            VerificationType::Uninitialized(pc) => VType::Uninitialized(pc)
+        })
+    }
+
+    /// Decode the `StackMapTable` into a frame per pc
+    fn stack_map(&self, code: &CodeAttribute, pcs: &HashMap<usize, usize>) -> Result<BTreeMap<usize, Frame>, VerifyError> {
+        let bad = |pc: usize| VerifyError { pc, reason: VerifyFault::BadStackMap };
+        let mut frames = BTreeMap::new();
+        let table = match find_attribute(&code.attributes, self.pool, "StackMapTable") {
+            Option::Some(attr) => read_stack_map_table(attr.data).ok_or_else(|| bad(0))?,
+            Option::None => Vec::new()
+        };
+        let mut locals = Frame::initial_entries(self.ctx).map_err(|reason| VerifyError { pc: 0, reason })?;
+        let mut pc: Option<usize> = None;
+        for frame in table.iter() {
+            let here = pc.map_or(0, |x| x + 1) + frame.offset_delta() as usize;
+            let convert = |v: &[VerificationType]| -> Result<Vec<VType>, VerifyError> {
+                v.iter()
+                    .map(|t| self.convert_stack_map(t).map_err(|reason| VerifyError { pc: here, reason }))
+                    .collect()
+            };
+            let stack = match *frame {
+                StackMapFrame::Same { .. } => Vec::new(),
+                StackMapFrame::SameLocals1StackItem { ref stack, .. } => convert(&[*stack])?,
+                StackMapFrame::Chop { k, .. } => {
+                    if k as usize > locals.len() {
+                        return Err(bad(here));
+                    }
+                    let len = locals.len() - k as usize;
+                    locals.truncate(len);
+                    Vec::new()
+                },
+                StackMapFrame::Append { locals: ref extra, .. } => {
+                    locals.extend(convert(extra)?);
+                    Vec::new()
+                },
+                StackMapFrame::Full { locals: ref l, ref stack, .. } => {
+                    locals = convert(l)?;
+                    convert(stack)?
+                }
+            };
+            if !pcs.contains_key(&here) {
+                return Err(bad(here));
+            }
+            let frame = Frame {
+                locals: Frame::expand(&locals, self.max_locals).ok_or_else(|| bad(here))?,
+                stack: stack_slots(&stack)
+            };
+            if frame.stack.len() > self.max_stack {
+                return Err(VerifyError { pc: here, reason: VerifyFault::StackOverflow });
+            }
+            frames.insert(here, frame);
+            pc = Some(here);
+        }
+        Ok(frames)
+    }
+
+    fn initial_frame(&self) -> Result<Frame, VerifyError> {
+        Frame::initial(self.ctx, self.max_locals).map_err(|reason| VerifyError { pc: 0, reason })
+    }
+
+    /// Type checking against the `StackMapTable`, JVMS 4.10.1
+    fn type_check(&self, code: &CodeAttribute, ops: &[(usize, JOp)], pcs: &HashMap<usize, usize>) -> Result<(), VerifyError> {
+        let frames = self.stack_map(code, pcs)?;
+        let mut current = Some(self.initial_frame()?);
+        for &(pc, ref op) in ops.iter() {
+            let at = |reason| VerifyError { pc, reason };
+            if let Option::Some(f) = frames.get(&pc) {
+                if let Option::Some(ref c) = current {
+                    self.frame_assignable(c, f).map_err(at)?;
+                }
+                current = Some(f.clone());
+            }
+            let mut f = current.take().ok_or(at(VerifyFault::MissingFrame))?;
+            self.check_handlers(code, pc, &f, &frames)?;
+            self.execute(pc, op, &mut f).map_err(at)?;
+            // stores inside a protected range are visible to the handler
+            if op.local_access().is_some_and(|(a, _)| a.writes()) {
+                self.check_handlers(code, pc, &f, &frames)?;
+            }
+            for target in op.branch_targets(pc) {
+                if target < 0 || !pcs.contains_key(&(target as usize)) {
+                    return Err(at(VerifyFault::BadTarget));
+                }
+                let t = frames.get(&(target as usize)).ok_or(at(VerifyFault::MissingFrame))?;
+                self.frame_assignable(&f, t).map_err(at)?;
+            }
+            if !op.ends_flow() {
+                current = Some(f);
+            }
+        }
+        match (current, ops.last()) {
+            (Option::Some(_), Option::Some(&(pc, _))) => Err(VerifyError { pc, reason: VerifyFault::FallsOffEnd }),
+            _ => Ok(())
+        }
+    }
+
+    fn check_handlers(&self, code: &CodeAttribute, pc: usize, f: &Frame, frames: &BTreeMap<usize, Frame>) -> Result<(), VerifyError> {
+        let at = |reason| VerifyError { pc, reason };
+        for entry in code.exception_table.iter() {
+            if (entry.start_pc as usize) <= pc && pc < entry.end_pc as usize {
+                let handler = frames.get(&(entry.handler_pc as usize)).ok_or(at(VerifyFault::MissingFrame))?;
+                let thrown = Frame {
+                    locals: f.locals.clone(),
+                    stack: vec![self.catch_type(entry.catch_type).map_err(at)?]
+                };
+                self.frame_assignable(&thrown, handler).map_err(at)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Least upper bound of two types, `None` if they cannot merge
+    fn merge_type(&self, a: &VType, b: &VType) -> Option<VType> {
+        match (a, b) {
+            (a, b) if a == b => Some(a.clone()),
+            (&VType::Null, &VType::Reference(_)) => Some(b.clone()),
+            (&VType::Reference(_), &VType::Null) => Some(a.clone()),
+            (VType::Reference(x), VType::Reference(y)) => {
+                let common = match (x.starts_with('['), y.starts_with('[')) {
+                    (true, true) => match (element_class(&x[1..]), element_class(&y[1..])) {
+                        (Option::Some(ex), Option::Some(ey)) if !ex.starts_with('[') && !ey.starts_with('[') => {
+                            format!("[L{};", self.hierarchy.common_superclass(ex, ey))
+                        },
+                        (Option::Some(_), Option::Some(_)) => "[Ljava/lang/Object;".to_string(),
+                        _ => OBJECT.to_string()
+                    },
+                    (false, false) => self.hierarchy.common_superclass(x, y),
+                    _ => OBJECT.to_string()
+                };
+                Some(VType::Reference(common))
+            },
+            _ => None
+        }
+    }
+
+    fn merge(&self, old: &Frame, new: &Frame) -> Res<Frame> {
+        if old.stack.len() != new.stack.len() {
+            return Err(VerifyFault::StackHeight { expected: old.stack.len() as u16, actual: new.stack.len() as u16 });
+        }
+        let mut stack = Vec::with_capacity(old.stack.len());
+        for (a, b) in old.stack.iter().zip(new.stack.iter()) {
+            match self.merge_type(a, b) {
+                Option::Some(t) => stack.push(t),
+                Option::None => return Err(VerifyFault::Mismatch { expected: a.clone(), actual: b.clone() })
+            };
+        }
+        let locals = old.locals.iter()
+            .zip(new.locals.iter())
+            .map(|(a, b)| self.merge_type(a, b).unwrap_or(VType::Top))
+            .collect();
+        Ok(Frame { locals, stack })
+    }
+
+    /// Type inference by data flow, JVMS 4.10.2
+    fn infer(&self, code: &CodeAttribute, ops: &[(usize, JOp)], pcs: &HashMap<usize, usize>) -> Result<Vec<Option<Frame>>, VerifyError> {
+        let mut state: Vec<Option<Frame>> = vec![None; ops.len()];
+        state[0] = Some(self.initial_frame()?);
+        let mut work = vec![0];
+        let jsr_returns: Vec<usize> = ops.iter()
+            .enumerate()
+            .filter(|&(_, (_, op))| matches!(*op, JOp::JSR(_) | JOp::JSRW(_)))
+            .map(|(i, _)| i + 1)
+            .collect();
+        while let Option::Some(i) = work.pop() {
+            let (pc, ref op) = ops[i];
+            let at = |reason| VerifyError { pc, reason };
+            let mut f = state[i].clone().unwrap();
+            let mut succs: Vec<(usize, Frame)> = Vec::new();
+            let handlers = |f: &Frame, succs: &mut Vec<(usize, Frame)>| -> Result<(), VerifyError> {
+                for entry in code.exception_table.iter() {
+                    if (entry.start_pc as usize) <= pc && pc < entry.end_pc as usize {
+                        let h = *pcs.get(&(entry.handler_pc as usize)).ok_or(at(VerifyFault::BadTarget))?;
+                        let thrown = Frame {
+                            locals: f.locals.clone(),
+                            stack: vec![self.catch_type(entry.catch_type).map_err(at)?]
+                        };
+                        succs.push((h, thrown));
+                    }
+                }
+                Ok(())
+            };
+            handlers(&f, &mut succs)?;
+            self.execute(pc, op, &mut f).map_err(at)?;
+            if op.local_access().is_some_and(|(a, _)| a.writes()) {
+                handlers(&f, &mut succs)?;
+            }
+            for target in op.branch_targets(pc) {
+                match pcs.get(&(target as usize)) {
+                    Option::Some(&t) if target >= 0 => succs.push((t, f.clone())),
+                    _ => return Err(at(VerifyFault::BadTarget))
+                };
+            }
+            if matches!(*op, JOp::Ret(_) | JOp::RetWide(_)) {
+                for &r in jsr_returns.iter() {
+                    if r >= ops.len() {
+                        return Err(at(VerifyFault::FallsOffEnd));
+                    }
+                    succs.push((r, f.clone()));
+                }
+            } else if matches!(*op, JOp::JSR(_) | JOp::JSRW(_)) {
+                // continues at the following instruction once `ret` runs
+            } else if !op.ends_flow() {
+                if i + 1 >= ops.len() {
+                    return Err(at(VerifyFault::FallsOffEnd));
+                }
+                succs.push((i + 1, f));
+            }
+            for (j, incoming) in succs {
+                let merged = match state[j] {
+                    Option::None => incoming,
+                    Option::Some(ref old) => {
+                        let merged = self.merge(old, &incoming)
+                            .map_err(|reason| VerifyError { pc: ops[j].0, reason })?;
+                        if merged == *old {
+                            continue;
+                        }
+                        merged
+                    }
+                };
+                state[j] = Some(merged);
+                work.push(j);
+            }
+        }
+        Ok(state)
+    }
+}
+
+fn stack_slots(entries: &[VType]) -> Vec<VType> {
+    Frame::expand(entries, entries.iter().map(VType::width).sum()).unwrap()
+}
+
+/// A `Machine`, the decoded instructions, and the index of each pc
+type Prepared<'v, 'a> = (Machine<'v, 'a>, Vec<(usize, JOp)>, HashMap<usize, usize>);
+
+/// Decode a method and set up a `Machine` for it
+fn prepare<'v, 'a>(ctx: MethodContext<'v>, code: &CodeAttribute, pool: &'v ConstantsPool<'a>, hierarchy: &'v dyn ClassHierarchy, strict: bool) -> Result<Prepared<'v, 'a>, VerifyError> {
+    let mut ops = Vec::new();
+    for op in Decoder::new(code.code) {
+        match op {
+            Ok(x) => ops.push(x),
+            Err(e) => return Err(VerifyError { pc: e.pc, reason: VerifyFault::Decode(e.reason) })
+        };
+    }
+    if ops.is_empty() {
+        return Err(VerifyError { pc: 0, reason: VerifyFault::FallsOffEnd });
+    }
+    let pcs: HashMap<usize, usize> = ops.iter().enumerate().map(|(i, &(pc, _))| (pc, i)).collect();
+    let news = ops.iter()
+        .filter_map(|&(pc, ref op)| match *op {
+            JOp::New(x) => Some((pc as u16, x)),
+            _ => None
+        })
+        .collect();
+    let ret = MethodType::parse(ctx.descriptor)
+        .ok_or(VerifyError { pc: 0, reason: VerifyFault::BadDescriptor })?
+        .ret
+        .map(|t| VType::from_field(&t));
+    let machine = Machine {
+        ctx,
+        pool,
+        hierarchy,
+        strict,
+        ret,
+        max_stack: code.max_stack as usize,
+        max_locals: code.max_locals as usize,
+        news
+    };
+    Ok((machine, ops, pcs))
+}
+
+/// The frame before every instruction, found by type inference
+///
+/// Unlike verification, reference types are never checked against
+/// each other, so `hierarchy` only has to answer `common_superclass`.
+/// Unreachable instructions have no frame.
+pub fn infer_frames(ctx: MethodContext, code: &CodeAttribute, pool: &ConstantsPool, hierarchy: &dyn ClassHierarchy) -> Result<Vec<(usize, Option<Frame>)>, VerifyError> {
+    let (machine, ops, pcs) = prepare(ctx, code, pool, hierarchy, false)?;
+    let state = machine.infer(code, &ops, &pcs)?;
+    Ok(ops.iter().map(|x| x.0).zip(state).collect())
+}
+
+/// Verify the `Code` attribute of one method
+pub fn verify_code(ctx: MethodContext, code: &CodeAttribute, pool: &ConstantsPool, hierarchy: &dyn ClassHierarchy) -> Result<(), VerifyError> {
+    let (machine, ops, pcs) = prepare(ctx, code, pool, hierarchy, true)?;
+    if ctx.major_version < 50 {
+        return machine.infer(code, &ops, &pcs).map(|_| ());
+    }
+    match machine.type_check(code, &ops, &pcs) {
+        Err(_) if ctx.major_version == 50 => machine.infer(code, &ops, &pcs).map(|_| ()),
+        x => x
+    }
+}
+
+/// Verifies the methods of a parsed class
+pub struct Verifier<'c, 'a: 'c> {
+    class: &'c Class<'a>,
+    hierarchy: &'c dyn ClassHierarchy
+}
+impl<'c, 'a> Verifier<'c, 'a> {
+
+    pub fn new(class: &'c Class<'a>, hierarchy: &'c dyn ClassHierarchy) -> Verifier<'c, 'a> {
+        Verifier { class, hierarchy }
+    }
+
+    /// Verify the method at `index`, methods without code always pass
+    pub fn verify_method(&self, index: usize) -> Result<(), VerifyError> {
+        let fail = |reason| VerifyError { pc: 0, reason };
+        let pool = self.class.get_constants();
+        let method = self.class.get_method(index).ok_or(fail(VerifyFault::BadDescriptor))?;
+        let class_name = self.class.get_this_class().map_err(|_| fail(VerifyFault::BadConstant(0)))?;
+        let name = pool.get_utf8(method.name_index).ok_or(fail(VerifyFault::BadConstant(method.name_index)))?;
+        let descriptor = pool.get_utf8(method.descriptor_index).ok_or(fail(VerifyFault::BadDescriptor))?;
+        let code = match self.class.get_method_code(index) {
+            Ok(Option::Some(code)) => code,
+            Ok(Option::None) => return Ok(()),
+            Err(_) => return Err(fail(VerifyFault::Decode(DecodeFault::Malformed)))
+        };
+        let ctx = MethodContext {
+            class_name: &class_name,
+            name: &name,
+            descriptor: &descriptor,
+            is_static: method.access_flags & (MethodAccessFlags::Static as u16) != 0,
+            major_version: self.class.get_major_version()
+        };
+        verify_code(ctx, &code, pool, self.hierarchy)
+    }
+
+    /// Verify every method, returning the index of each failing one
+    pub fn verify_all(&self) -> Vec<(usize, VerifyError)> {
+        (0..self.class.get_methods_count())
+            .filter_map(|i| self.verify_method(i).err().map(|e| (i, e)))
+            .collect()
+    }
+}
+
+#[test]
+fn test_verify() {
+    use super::super::clazz::parse_constant_pool;
+    use super::super::clazz::AttributeInfo;
+    use super::super::nom::IResult;
+    use super::hierarchy::MapHierarchy;
+
+    // #1 "StackMapTable", #3 class java/lang/String
+    let pool = match parse_constant_pool(b"\x00\x04\
+        \x01\x00\x0DStackMapTable\x01\x00\x10java/lang/String\x07\x00\x02") {
+        IResult::Done(_, pool) => pool,
+        _ => panic!()
+    };
+    let h = MapHierarchy::new();
+    // 0: iload_0; ifeq 8; iload_0; goto 9; 8: iconst_0; 9: ireturn
+    let body: &[u8] = &[0x1A, 0x99, 0x00, 0x07, 0x1A, 0xA7, 0x00, 0x04, 0x03, 0xAC];
+    let table = [AttributeInfo { name_index: 1, data: b"\x00\x02\x08\x40\x01" }];
+    let code = CodeAttribute {
+        max_stack: 1,
+        max_locals: 1,
+        code: body,
+        exception_table: Vec::new(),
+        attributes: table.to_vec()
+    };
+    let ctx = MethodContext {
+        class_name: "T",
+        name: "f",
+        descriptor: "(I)I",
+        is_static: true,
+        major_version: 52
+    };
+    assert_eq!(verify_code(ctx, &code, &pool, &h), Ok(()));
+
+    let err = verify_code(MethodContext { descriptor: "(Ljava/lang/String;)I", ..ctx }, &code, &pool, &h).unwrap_err();
+    assert_eq!(err, VerifyError {
+        pc: 0,
+        reason: VerifyFault::LocalMismatch { index: 0, expected: VType::Int, actual: object("java/lang/String") }
+    });
+
+    // the branch at pc 1 has no frame to check against, unless we infer
+    let bare = CodeAttribute { attributes: Vec::new(), ..code.clone() };
+    let err = verify_code(ctx, &bare, &pool, &h).unwrap_err();
+    assert_eq!(err, VerifyError { pc: 1, reason: VerifyFault::MissingFrame });
+    assert_eq!(verify_code(MethodContext { major_version: 49, ..ctx }, &bare, &pool, &h), Ok(()));
+
+    // returning a long where an int is expected
+    let body: &[u8] = &[0x0A, 0xAC];
+    let code = CodeAttribute { max_stack: 2, code: body, ..bare };
+    let err = verify_code(ctx, &code, &pool, &h).unwrap_err();
+    assert_eq!(err.reason, VerifyFault::Mismatch { expected: VType::Int, actual: VType::Long });
+    assert_eq!(err.to_string(), "expected int but found long at pc 1");
+}
@@ -5,13 +5,8 @@ use super::super::nom::{
 };
 use super::ConstantsPool;
 
-use std::borrow::{
-    Cow,
-    Borrow
-};
-
 ///Represents an attribute
-#[derive(Debug)]
+#[derive(Clone,Debug)]
 pub struct AttributeInfo<'a>{
     pub name_index: u16,
     pub data: &'a [u8]
@@ -26,3 +21,20 @@ named!(pub parse_attribute<AttributeInfo>, do_parse!(
     })
 ));
 
+
+/// Find an attribute by name
+pub fn find_attribute<'a, 'b>(attributes: &'b [AttributeInfo<'a>], pool: &ConstantsPool, name: &str) -> Option<&'b AttributeInfo<'a>> {
+    attributes.iter().find(|attr| match pool.get_utf8(attr.name_index) {
+        Option::Some(x) => x == name,
+        Option::None => false
+    })
+}
+
+/// Serialize an attribute, header included
+pub fn write_attribute(name_index: u16, data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + 6);
+    out.extend_from_slice(&name_index.to_be_bytes());
+    out.extend_from_slice(&(data.len() as u32).to_be_bytes());
+    out.extend_from_slice(data);
+    out
+}
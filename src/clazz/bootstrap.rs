@@ -0,0 +1,46 @@
+//! The `BootstrapMethods` attribute, see JVMS 4.7.23.
+
+use super::super::nom::{
+    be_u16,
+    IResult
+};
+
+/// One entry of a `BootstrapMethods` attribute
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct BootstrapMethod {
+    /// A `MethodHandle` constant
+    pub method_ref: u16,
+    /// Loadable constants passed as static arguments
+    pub arguments: Vec<u16>
+}
+named!(parse_bootstrap_method<BootstrapMethod>, do_parse!(
+    method: be_u16 >>
+    len: be_u16 >>
+    args: count!(be_u16, len as usize) >>
+    (BootstrapMethod {
+        method_ref: method,
+        arguments: args
+    })
+));
+
+named!(parse_bootstrap_methods<Vec<BootstrapMethod> >, do_parse!(
+    len: be_u16 >>
+    methods: count!(parse_bootstrap_method, len as usize) >>
+    (methods)
+));
+
+/// Parse the body of a `BootstrapMethods` attribute
+pub fn read_bootstrap_methods(data: &[u8]) -> Option<Vec<BootstrapMethod>> {
+    match parse_bootstrap_methods(data) {
+        IResult::Done([], methods) => Some(methods),
+        _ => None
+    }
+}
+
+#[test]
+fn test_bootstrap_methods() {
+    let methods = read_bootstrap_methods(b"\x00\x02\x00\x10\x00\x00\x00\x11\x00\x02\x00\x03\x00\x04").unwrap();
+    assert_eq!(methods[0], BootstrapMethod { method_ref: 16, arguments: vec![] });
+    assert_eq!(methods[1], BootstrapMethod { method_ref: 17, arguments: vec![3, 4] });
+    assert_eq!(read_bootstrap_methods(b"\x00\x01\x00\x10\x00\x01"), None);
+}
@@ -0,0 +1,93 @@
+
+use super::super::nom::{
+    be_u16,
+    be_u32,
+    IResult
+};
+use super::attributes::{
+    AttributeInfo,
+    parse_attribute,
+    write_attribute
+};
+
+/// One entry of a `Code` attribute's exception table
+///
+/// The handler covers pcs in `start_pc..end_pc`
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct ExceptionEntry {
+    pub start_pc: u16,
+    pub end_pc: u16,
+    pub handler_pc: u16,
+    /// Constant pool index of the caught class, `0` catches everything
+    pub catch_type: u16
+}
+named!(parse_exception_entry<ExceptionEntry>, do_parse!(
+    start: be_u16 >>
+    end: be_u16 >>
+    handler: be_u16 >>
+    catch: be_u16 >>
+    (ExceptionEntry {
+        start_pc: start,
+        end_pc: end,
+        handler_pc: handler,
+        catch_type: catch
+    })
+));
+
+/// The `Code` attribute of a method
+#[derive(Clone,Debug)]
+pub struct CodeAttribute<'a> {
+    pub max_stack: u16,
+    pub max_locals: u16,
+    pub code: &'a [u8],
+    pub exception_table: Vec<ExceptionEntry>,
+    pub attributes: Vec<AttributeInfo<'a>>
+}
+named!(pub parse_code_attribute<CodeAttribute>, do_parse!(
+    stack: be_u16 >>
+    locals: be_u16 >>
+    code_len: be_u32 >>
+    code: take!(code_len as usize) >>
+    ex_len: be_u16 >>
+    ex: count!(parse_exception_entry, ex_len as usize) >>
+    attr_len: be_u16 >>
+    attr: count!(parse_attribute, attr_len as usize) >>
+    (CodeAttribute {
+        max_stack: stack,
+        max_locals: locals,
+        code,
+        exception_table: ex,
+        attributes: attr
+    })
+));
+
+impl<'a> CodeAttribute<'a> {
+
+    /// Parse the body of a `Code` attribute
+    pub fn parse(data: &'a [u8]) -> Option<CodeAttribute<'a>> {
+        match parse_code_attribute(data) {
+            IResult::Done(_, code) => Some(code),
+            _ => None
+        }
+    }
+
+    /// Serialize the body of a `Code` attribute
+    pub fn write(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(12 + self.code.len() + 8 * self.exception_table.len());
+        out.extend_from_slice(&self.max_stack.to_be_bytes());
+        out.extend_from_slice(&self.max_locals.to_be_bytes());
+        out.extend_from_slice(&(self.code.len() as u32).to_be_bytes());
+        out.extend_from_slice(self.code);
+        out.extend_from_slice(&(self.exception_table.len() as u16).to_be_bytes());
+        for e in self.exception_table.iter() {
+            for x in [e.start_pc, e.end_pc, e.handler_pc, e.catch_type].iter() {
+                out.extend_from_slice(&x.to_be_bytes());
+            }
+        }
+        out.extend_from_slice(&(self.attributes.len() as u16).to_be_bytes());
+        for attr in self.attributes.iter() {
+            out.extend_from_slice(&write_attribute(attr.name_index, attr.data));
+        }
+        out
+    }
+}
@@ -7,6 +7,7 @@ use super::super::nom::{
     be_i32,
     be_u32,
     IResult,
+    ErrorKind
 };
 use super::enum_constants::{
     PoolTag,
@@ -14,7 +15,10 @@ use super::enum_constants::{
     MethodDescriptor,
     parse_method_descriptor
 };
-use super::javautf8::decode_java_utf8;
+use super::javautf8::{
+    JavaString,
+    decode_java_utf8_lossless
+};
 
 use std::borrow::Cow;
 use std::ops::Index;
@@ -60,13 +64,18 @@ pub enum PoolMembers<'a> {
     Long(i64),
     Double(f64),
     NameAndType(u16, u16),
-    Utf8(Cow<'a, str>),
+    /// Unpaired surrogates are kept, see `JavaString`
+    Utf8(JavaString<'a>),
     /// handle to a method value is a 
     MethodHandle(u8, u16),
     /// Describes a method. Value is a index in the constant pool
     /// that points to a PoolMembers::
     MethodType(u16),
-    InvokeDynamic(u16, u16)
+    InvokeDynamic(u16, u16),
+    /// Index of the module's name, only in `module-info`
+    Module(u16),
+    /// Index of the package's internal name, only in `module-info`
+    Package(u16)
 }
 impl<'a> PoolMembers<'a> {
     #[inline(always)]
@@ -80,7 +89,7 @@ impl<'a> PoolMembers<'a> {
     #[inline(always)]
     pub fn get_utf8(&self) -> Option<String> {
         match self {
-            &PoolMembers::Utf8(ref val) => Some(val.to_string()),
+            &PoolMembers::Utf8(ref val) => Some(val.to_string_lossy().into_owned()),
             _ => None
         }
     }
@@ -104,6 +113,8 @@ impl<'a> PoolMembers<'a> {
     build_is!(@2 is_method_handle; MethodHandle);
     build_is!(@1 is_method_type; MethodType);
     build_is!(@2 is_invoke_dynamic; InvokeDynamic);
+    build_is!(@1 is_module; Module);
+    build_is!(@1 is_package; Package);
 }
 
 
@@ -149,7 +160,7 @@ named!(parse_pool_tag<PoolMembers>, switch!( parse_const_pool_tag,
             d: be_u16 >>
             (PoolMembers::NameAndType(n,d))) |
         PoolTag::Utf8 => do_parse!(
-            buf: decode_java_utf8 >>
+            buf: decode_java_utf8_lossless >>
             (PoolMembers::Utf8(buf))) |
         PoolTag::MethodHandle => do_parse!(
             d: be_u8 >>
@@ -161,16 +172,29 @@ named!(parse_pool_tag<PoolMembers>, switch!( parse_const_pool_tag,
         PoolTag::InvokeDynamic => do_parse!(
             b: be_u16 >>
             n: be_u16 >>
-            (PoolMembers::InvokeDynamic(b,n)))
+            (PoolMembers::InvokeDynamic(b,n))) |
+        PoolTag::Module => do_parse!(
+            i: be_u16 >>
+            (PoolMembers::Module(i))) |
+        PoolTag::Package => do_parse!(
+            i: be_u16 >>
+            (PoolMembers::Package(i)))
 ));
 
+/// Error code for a `constant_pool_count` of 0, which leaves no room
+/// for the unused index 0
+const EMPTY_CONSTANT_POOL: u32 = 9;
+
 /// Parse a constant pool
 pub fn parse_constant_pool<'a>(buffer: &'a [u8]) -> IResult<&'a [u8], ConstantsPool<'a>> {
     match be_u16(buffer) {
         IResult::Error(e) => IResult::Error(e),
         IResult::Incomplete(n) => IResult::Incomplete(n),
         IResult::Done(mut rem, pool_count) =>{
-            let pool_count = (pool_count as usize) - 1;
+            let pool_count = match (pool_count as usize).checked_sub(1) {
+                Option::Some(x) => x,
+                Option::None => return IResult::Error(ErrorKind::Custom(EMPTY_CONSTANT_POOL))
+            };
             let mut pool = Vec::with_capacity(pool_count);
             loop {
                 if pool.len() == pool_count {
@@ -203,17 +227,26 @@ pub struct ConstantsPool<'a> {
 
 impl<'a> ConstantsPool<'a> {
 
+    // pool indices start at 1
     fn get_ptr<'b>(&'b self, index: usize) -> Option<&'b PoolMembers<'a>> {
-        self.data.as_slice().get(index)
+        match index {
+            0 => None,
+            x => self.data.as_slice().get(x - 1)
+        }
     }
 
+    /// Fetch a UTF8 constant
+    ///
+    /// Unpaired surrogates are replaced with `U+FFFD`, use
+    /// `get_java_string` when the exact value matters.
     pub fn get_utf8<'b>(&'b self, index: u16) -> Option<Cow<'b, str>> {
-        let index = index as usize;
-        match self.get_ptr(index) {
-            Option::Some(&PoolMembers::Utf8(ref str_ptr)) => match str_ptr {
-                &Cow::Borrowed(s_ptr) => Some(Cow::Borrowed(s_ptr)),
-                &Cow::Owned(ref string) => Some(Cow::Borrowed(string))
-            },
+        self.get_java_string(index).map(|s| s.to_string_lossy())
+    }
+
+    /// Fetch a UTF8 constant exactly as it is stored
+    pub fn get_java_string<'b>(&'b self, index: u16) -> Option<&'b JavaString<'a>> {
+        match self.get_ptr(index as usize) {
+            Option::Some(PoolMembers::Utf8(s)) => Some(s),
             _ => None
         }
     }
@@ -227,11 +260,64 @@ impl<'a> ConstantsPool<'a> {
         }
     }
 
+    /// Index of the `ClassInfo` constant naming `name`, if there is one
+    pub fn find_class(&self, name: &str) -> Option<u16> {
+        (1..self.data.len() + 1)
+            .map(|i| i as u16)
+            .find(|i| self.get_class_name(*i).is_some_and(|x| x == name))
+    }
+
+    /// One past the highest valid index, the `constant_pool_count`
+    /// of the class file
+    pub fn count(&self) -> usize {
+        self.data.len() + 1
+    }
+
+    /// Fetch any constant
+    pub fn get<'b>(&'b self, index: u16) -> Option<&'b PoolMembers<'a>> {
+        self.get_ptr(index as usize)
+    }
+
+    /// Walks a `NameAndType` constant to fetch its name and descriptor
+    pub fn get_name_and_type<'b>(&'b self, index: u16) -> Option<(Cow<'b, str>, Cow<'b, str>)> {
+        match self.get_ptr(index as usize) {
+            Option::Some(&PoolMembers::NameAndType(name, desc)) => {
+                Some((self.get_utf8(name)?, self.get_utf8(desc)?))
+            },
+            _ => None
+        }
+    }
+
+    /// Walks a `FieldRef`, `MethodRef` or `InterfaceMethodRef` constant
+    pub fn get_member_ref<'b>(&'b self, index: u16) -> Option<MemberRef<'b>> {
+        let (class, nat) = match self.get_ptr(index as usize) {
+            Option::Some(&PoolMembers::FieldRef(c, n)) |
+            Option::Some(&PoolMembers::MethodRef(c, n)) |
+            Option::Some(&PoolMembers::InterfaceMethodRef(c, n)) => (c, n),
+            _ => return None
+        };
+        let (name, descriptor) = self.get_name_and_type(nat)?;
+        Some(MemberRef {
+            class: self.get_class_name(class)?,
+            name,
+            descriptor
+        })
+    }
+
     pub fn build_attribute_list(&self) -> AttributePool {
         AttributePool::new(self)
     }
 }
 
+/// A field or method reference with its constants resolved
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct MemberRef<'b> {
+    /// Internal name of the owning class
+    pub class: Cow<'b, str>,
+    pub name: Cow<'b, str>,
+    pub descriptor: Cow<'b, str>
+}
+
 ///Describes common attribute types
 #[derive(Clone,Copy,Debug)]
 pub enum AttributeKind {
@@ -301,10 +387,11 @@ impl AttributePool {
 
     pub fn new(pool: &ConstantsPool) -> AttributePool {
         //actual function start
-        let mut retvec = Vec::<Option<AttributeKind>>::with_capacity(pool.data.len());
+        let mut retvec = Vec::<Option<AttributeKind>>::with_capacity(pool.data.len() + 1);
+        retvec.push(None);
         for constant in pool.data.iter() {
             let var: Option<AttributeKind> = match constant {
-                &PoolMembers::Utf8(ref string) => Self::dummy(string),
+                &PoolMembers::Utf8(ref string) => Self::dummy(&string.to_string_lossy()),
                 _ => None
             };
             retvec.push(var);
@@ -312,3 +399,26 @@ impl AttributePool {
         AttributePool { data: retvec }
     }
 }
+
+#[test]
+fn test_pool_index() {
+    // 1: Utf8 "A"; 2: Class #1; 3: Long 7, which also takes index 4
+    let data = b"\x00\x05\x01\x00\x01A\x07\x00\x01\x05\x00\x00\x00\x00\x00\x00\x00\x07";
+    let pool = match parse_constant_pool(data) {
+        IResult::Done(rest, pool) => {
+            assert!(rest.is_empty());
+            pool
+        },
+        _ => panic!("pool failed to parse")
+    };
+    assert_eq!(pool.count(), 5);
+    assert_eq!(pool.get_utf8(1).unwrap(), "A");
+    assert_eq!(pool.get_class_name(2).unwrap(), "A");
+    assert_eq!(pool.find_class("A"), Some(2));
+    assert!(pool.get(3).unwrap().is_double_long());
+    assert!(pool.get(0).is_none());
+    assert!(pool.get_utf8(0).is_none());
+    assert!(pool.get_class_name(0).is_none());
+    assert!(pool.get(5).is_none());
+    assert!(parse_constant_pool(b"\x00\x00").is_err());
+}
@@ -0,0 +1,113 @@
+//! The `LineNumberTable` and `LocalVariableTable` attributes,
+//! see JVMS 4.7.12 and 4.7.13.
+
+use super::super::nom::{
+    be_u16,
+    IResult
+};
+
+/// One entry of a `LineNumberTable`
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct LineNumber {
+    pub start_pc: u16,
+    pub line_number: u16
+}
+named!(parse_line_number<LineNumber>, do_parse!(
+    start: be_u16 >>
+    line: be_u16 >>
+    (LineNumber {
+        start_pc: start,
+        line_number: line
+    })
+));
+
+/// One entry of a `LocalVariableTable`
+///
+/// The variable is live in `start_pc..start_pc + length`. The same layout
+/// is used by `LocalVariableTypeTable`, where `descriptor_index` points
+/// at a generic signature instead.
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub struct LocalVariable {
+    pub start_pc: u16,
+    pub length: u16,
+    pub name_index: u16,
+    pub descriptor_index: u16,
+    /// Local variable slot
+    pub index: u16
+}
+named!(parse_local_variable<LocalVariable>, do_parse!(
+    start: be_u16 >>
+    length: be_u16 >>
+    name: be_u16 >>
+    desc: be_u16 >>
+    index: be_u16 >>
+    (LocalVariable {
+        start_pc: start,
+        length,
+        name_index: name,
+        descriptor_index: desc,
+        index
+    })
+));
+
+named!(parse_line_number_table<Vec<LineNumber> >, do_parse!(
+    len: be_u16 >>
+    lines: count!(parse_line_number, len as usize) >>
+    (lines)
+));
+
+named!(parse_local_variable_table<Vec<LocalVariable> >, do_parse!(
+    len: be_u16 >>
+    vars: count!(parse_local_variable, len as usize) >>
+    (vars)
+));
+
+/// Parse the body of a `LineNumberTable` attribute
+pub fn read_line_number_table(data: &[u8]) -> Option<Vec<LineNumber>> {
+    match parse_line_number_table(data) {
+        IResult::Done([], lines) => Some(lines),
+        _ => None
+    }
+}
+
+/// Parse the body of a `LocalVariableTable` or `LocalVariableTypeTable` attribute
+pub fn read_local_variable_table(data: &[u8]) -> Option<Vec<LocalVariable>> {
+    match parse_local_variable_table(data) {
+        IResult::Done([], vars) => Some(vars),
+        _ => None
+    }
+}
+
+/// Serialize the body of a `LineNumberTable` attribute
+pub fn write_line_number_table(lines: &[LineNumber]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(2 + 4 * lines.len());
+    out.extend_from_slice(&(lines.len() as u16).to_be_bytes());
+    for l in lines {
+        out.extend_from_slice(&l.start_pc.to_be_bytes());
+        out.extend_from_slice(&l.line_number.to_be_bytes());
+    }
+    out
+}
+
+/// Serialize the body of a `LocalVariableTable` or `LocalVariableTypeTable` attribute
+pub fn write_local_variable_table(vars: &[LocalVariable]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(2 + 10 * vars.len());
+    out.extend_from_slice(&(vars.len() as u16).to_be_bytes());
+    for v in vars {
+        for x in [v.start_pc, v.length, v.name_index, v.descriptor_index, v.index].iter() {
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+    }
+    out
+}
+
+#[test]
+fn test_debug_info() {
+    let lines = read_line_number_table(b"\x00\x02\x00\x00\x00\x04\x00\x05\x00\x06").unwrap();
+    assert_eq!(lines[1], LineNumber { start_pc: 5, line_number: 6 });
+    let vars = read_local_variable_table(b"\x00\x01\x00\x00\x00\x0C\x00\x07\x00\x08\x00\x01").unwrap();
+    assert_eq!(vars, vec![LocalVariable { start_pc: 0, length: 12, name_index: 7, descriptor_index: 8, index: 1 }]);
+    assert_eq!(read_line_number_table(b"\x00\x01\x00\x00"), None);
+    assert_eq!(read_line_number_table(&write_line_number_table(&lines)), Some(lines));
+    assert_eq!(read_local_variable_table(&write_local_variable_table(&vars)), Some(vars));
+}
@@ -0,0 +1,154 @@
+//! Field and method descriptors, see JVMS 4.3.
+
+use std::fmt;
+
+/// The type of a field, parameter or return value
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub enum FieldType {
+    Byte,
+    Char,
+    Double,
+    Float,
+    Int,
+    Long,
+    Short,
+    Boolean,
+    /// Holds the internal class name, `java/lang/String`
+    Object(String),
+    Array(Box<FieldType>)
+}
+impl FieldType {
+
+    /// Parse a field descriptor such as `[Ljava/lang/String;`
+    pub fn parse(desc: &str) -> Option<FieldType> {
+        match FieldType::parse_prefix(desc) {
+            Option::Some((t, "")) => Some(t),
+            _ => None
+        }
+    }
+
+    /// Parse one field type off the front of `desc`
+    fn parse_prefix(desc: &str) -> Option<(FieldType, &str)> {
+        let rest = desc.get(1..).unwrap_or("");
+        let t = match desc.as_bytes().first() {
+            Option::Some(&b'B') => FieldType::Byte,
+            Option::Some(&b'C') => FieldType::Char,
+            Option::Some(&b'D') => FieldType::Double,
+            Option::Some(&b'F') => FieldType::Float,
+            Option::Some(&b'I') => FieldType::Int,
+            Option::Some(&b'J') => FieldType::Long,
+            Option::Some(&b'S') => FieldType::Short,
+            Option::Some(&b'Z') => FieldType::Boolean,
+            Option::Some(&b'L') => {
+                let end = rest.find(';')?;
+                if end == 0 {
+                    return None;
+                }
+                return Some((FieldType::Object(rest[..end].to_string()), &rest[end + 1..]));
+            },
+            Option::Some(&b'[') => {
+                let (inner, rest) = FieldType::parse_prefix(rest)?;
+                return Some((FieldType::Array(Box::new(inner)), rest));
+            },
+            _ => return None
+        };
+        Some((t, rest))
+    }
+
+    /// Operand stack and local variable slots a value occupies
+    pub fn slots(&self) -> u16 {
+        match *self {
+            FieldType::Long | FieldType::Double => 2,
+            _ => 1
+        }
+    }
+
+    /// Is this a class or array type
+    pub fn is_reference(&self) -> bool {
+        matches!(*self, FieldType::Object(_) | FieldType::Array(_))
+    }
+}
+impl fmt::Display for FieldType {
+
+    /// Writes the descriptor form
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FieldType::Byte => write!(f, "B"),
+            FieldType::Char => write!(f, "C"),
+            FieldType::Double => write!(f, "D"),
+            FieldType::Float => write!(f, "F"),
+            FieldType::Int => write!(f, "I"),
+            FieldType::Long => write!(f, "J"),
+            FieldType::Short => write!(f, "S"),
+            FieldType::Boolean => write!(f, "Z"),
+            FieldType::Object(ref name) => write!(f, "L{};", name),
+            FieldType::Array(ref inner) => write!(f, "[{}", inner)
+        }
+    }
+}
+
+/// Parameters and return type of a method
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub struct MethodType {
+    pub params: Vec<FieldType>,
+    /// `None` for `void`
+    pub ret: Option<FieldType>
+}
+impl MethodType {
+
+    /// Parse a method descriptor such as `(IJ)Ljava/lang/String;`
+    pub fn parse(desc: &str) -> Option<MethodType> {
+        if !desc.starts_with('(') {
+            return None;
+        }
+        let mut rest = &desc[1..];
+        let mut params = Vec::new();
+        while !rest.starts_with(')') {
+            let (t, r) = FieldType::parse_prefix(rest)?;
+            params.push(t);
+            rest = r;
+        }
+        let ret = match &rest[1..] {
+            "V" => None,
+            x => Some(FieldType::parse(x)?)
+        };
+        Some(MethodType { params, ret })
+    }
+
+    /// Slots taken by the parameters, excluding any `this`
+    pub fn param_slots(&self) -> u16 {
+        self.params.iter().map(FieldType::slots).sum()
+    }
+
+    /// Slots taken by the return value
+    pub fn ret_slots(&self) -> u16 {
+        self.ret.as_ref().map_or(0, FieldType::slots)
+    }
+}
+impl fmt::Display for MethodType {
+
+    /// Writes the descriptor form
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "(")?;
+        for p in self.params.iter() {
+            write!(f, "{}", p)?;
+        }
+        match self.ret {
+            Option::Some(ref r) => write!(f, "){}", r),
+            Option::None => write!(f, ")V")
+        }
+    }
+}
+
+#[test]
+fn test_descriptors() {
+    let m = MethodType::parse("(I[[JLjava/lang/String;D)Ljava/lang/Object;").unwrap();
+    assert_eq!(m.params[1], FieldType::Array(Box::new(FieldType::Array(Box::new(FieldType::Long)))));
+    assert_eq!(m.params[2], FieldType::Object("java/lang/String".to_string()));
+    assert_eq!((m.param_slots(), m.ret_slots()), (5, 1));
+    assert_eq!(m.to_string(), "(I[[JLjava/lang/String;D)Ljava/lang/Object;");
+    assert_eq!(MethodType::parse("()V").unwrap().ret, None);
+    assert_eq!(FieldType::parse("L;"), None);
+    assert_eq!(FieldType::parse("II"), None);
+    assert_eq!(MethodType::parse("(I"), None);
+}
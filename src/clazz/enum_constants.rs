@@ -174,7 +174,9 @@ EnumBuilder! {@U8
         Utf8 => 1,
         MethodHandle => 15,
         MethodType => 16,
-        InvokeDynamic => 18
+        InvokeDynamic => 18,
+        Module => 19,
+        Package => 20
     };
     NOM_PARSER: be_u8;
     ERROR_CODE: 2u32;
@@ -223,6 +225,7 @@ EnumBuilder! {@U16
         Static => 0x0008,
         Final => 0x0010,
         Volatile => 0x0040,
+        Transient => 0x0080,
         Synthetic => 0x1000,
         Enum => 0x4000
     };
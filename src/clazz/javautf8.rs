@@ -3,10 +3,10 @@
 use super::super::nom::{
     IResult,
     ErrorKind,
-    Needed,
     be_u16
 };
 use std::borrow::Cow;
+use std::fmt;
 
 /*
  * Finite state machine to decode Java's "UTF-8" implementation
@@ -14,11 +14,19 @@ use std::borrow::Cow;
  * This defines an array about ~256bytes large which should fit nicely
  * in L1
  *
+ * Java's "Modified UTF-8" differs from standard UTF-8 in two ways:
  *
+ * - NUL is encoded as the two bytes `0xC0 0x80`, a raw `0x00` is illegal
+ * - Supplementary characters are stored as a UTF-16 surrogate pair, each
+ *   half encoded as its own 3 byte sequence. So there are no 4 byte
+ *   sequences.
+ *
+ * The decoder works in UTF-16 code units, which is what the JVM does.
+ * Surrogates are only paired up after the whole buffer is read.
  */
 
 const CODE: &[u8;256] = &[
-    0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
     0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
@@ -32,10 +40,11 @@ const CODE: &[u8;256] = &[
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
     // 192 - 223 2 bytes
-    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 
-    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 
-    // 224 - 239 3 bytes (with a 6 thrown in)
-    3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 6, 3, 3, 
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2, 2,
+    // 224 - 239 3 bytes (surrogate halves live under 0xED)
+    3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3, 3,
+    // 4 byte sequences never appear in modified UTF-8
     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1
 ];
 
@@ -43,11 +52,105 @@ const CODE: &[u8;256] = &[
 ///Wraps an unsafe method
 #[inline(always)]
 fn get_code(x: u8) -> u8 {
-    let index: usize = x.clone() as usize;
-    unsafe { CODE.get_unchecked(index) }.clone()
+    let index: usize = x as usize;
+    *unsafe { CODE.get_unchecked(index) }
+}
+
+/// Why a modified UTF-8 buffer could not be decoded
+#[derive(Clone,Copy,Debug,PartialEq,Eq)]
+pub enum Utf8Fault {
+    /// Illegal lead byte, or bad continuation byte at this offset
+    Malformed(usize),
+    /// The buffer ends in the middle of a sequence
+    Truncated,
+    /// A surrogate half without its partner.
+    ///
+    /// Legal within the JVM, but not representable as a Rust `str`
+    UnpairedSurrogate(u16)
+}
+impl Utf8Fault {
+
+    /// Maps the fault onto the nom error codes this module reserves
+    fn error_kind(&self) -> ErrorKind {
+        match *self {
+            Utf8Fault::Malformed(_) |
+            Utf8Fault::Truncated => ErrorKind::Custom(4),
+            Utf8Fault::UnpairedSurrogate(_) => ErrorKind::Custom(5)
+        }
+    }
+}
+
+/// A string as the JVM sees it
+///
+/// Nearly every string in a class file is valid unicode and is kept
+/// as a (usually borrowed) `str`. Java strings are really sequences of
+/// UTF-16 code units though, and may contain unpaired surrogates
+/// (obfuscators love these). Those are kept as raw code units so
+/// nothing is lost when re-encoding.
+#[derive(Clone,Debug,PartialEq,Eq,Hash)]
+pub enum JavaString<'a> {
+    Unicode(Cow<'a, str>),
+    Utf16(Vec<u16>)
 }
+impl<'a> JavaString<'a> {
 
-/// Get a buffer
+    /// Build from UTF-16 code units
+    ///
+    /// Falls back to `JavaString::Utf16` only when a surrogate is unpaired
+    pub fn from_utf16(units: Vec<u16>) -> JavaString<'static> {
+        match String::from_utf16(&units) {
+            Ok(s) => JavaString::Unicode(Cow::Owned(s)),
+            Err(_) => JavaString::Utf16(units)
+        }
+    }
+
+    /// Returns the value if it is valid unicode
+    pub fn as_str(&self) -> Option<&str> {
+        match *self {
+            JavaString::Unicode(ref s) => Some(s),
+            JavaString::Utf16(_) => None
+        }
+    }
+
+    /// Unpaired surrogates are replaced with `U+FFFD`
+    pub fn to_string_lossy(&self) -> Cow<'_, str> {
+        match *self {
+            JavaString::Unicode(ref s) => Cow::Borrowed(s),
+            JavaString::Utf16(ref units) => Cow::Owned(String::from_utf16_lossy(units))
+        }
+    }
+
+    /// The UTF-16 code units, what `String.toCharArray()` would return
+    pub fn to_utf16(&self) -> Vec<u16> {
+        match *self {
+            JavaString::Unicode(ref s) => s.encode_utf16().collect(),
+            JavaString::Utf16(ref units) => units.clone()
+        }
+    }
+
+    /// Encode back to the class file form (without the length prefix)
+    pub fn encode(&self) -> Vec<u8> {
+        match *self {
+            JavaString::Unicode(ref s) => encode_java_utf8(s),
+            JavaString::Utf16(ref units) => encode_java_utf16(units)
+        }
+    }
+
+    /// Detach from the buffer the string was read from
+    pub fn into_owned(self) -> JavaString<'static> {
+        match self {
+            JavaString::Unicode(s) => JavaString::Unicode(Cow::Owned(s.into_owned())),
+            JavaString::Utf16(units) => JavaString::Utf16(units)
+        }
+    }
+}
+impl<'a> fmt::Display for JavaString<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+// Get a buffer
 named!(get_buffer, do_parse!(
     sized: be_u16 >>
     buffer: take!(sized as usize) >>
@@ -60,110 +163,172 @@ named!(get_buffer, do_parse!(
 ///
 /// 4 (malformed character)
 ///
-/// 5 (Illegal codepoint)
-pub fn decode_java_utf8<'a>(buffer: &'a [u8]) -> IResult<&'a [u8], Cow<'a, str>> {
+/// 5 (Illegal codepoint, an unpaired surrogate)
+pub fn decode_java_utf8(buffer: &[u8]) -> IResult<&[u8], Cow<'_, str>> {
     match get_buffer(buffer) {
         IResult::Error(e) => IResult::Error(e),
         IResult::Incomplete(n) => IResult::Incomplete(n),
-        IResult::Done(rem, buff) => {
-            match ::std::str::from_utf8(buff) {
-                Ok(x) => return IResult::Done(rem, Cow::Borrowed(x)),
-                _ => { }
-            };
-            match decode(buff) {
-                IResult::Error(e) => IResult::Error(e),
-                IResult::Incomplete(n) => IResult::Incomplete(n),
-                IResult::Done(_,var) => IResult::Done(rem, var)
+        IResult::Done(rem, buff) => match decode_modified_utf8(buff) {
+            Ok(s) => IResult::Done(rem, s),
+            Err(e) => IResult::Error(e.error_kind())
+        }
+    }
+}
+
+/// Decodes a Java UTF8 string without losing unpaired surrogates
+///
+/// Reserves error code 4 (malformed character)
+pub fn decode_java_utf8_lossless(buffer: &[u8]) -> IResult<&[u8], JavaString<'_>> {
+    match get_buffer(buffer) {
+        IResult::Error(e) => IResult::Error(e),
+        IResult::Incomplete(n) => IResult::Incomplete(n),
+        IResult::Done(rem, buff) => match decode_modified_utf8_lossless(buff) {
+            Ok(s) => IResult::Done(rem, s),
+            Err(e) => IResult::Error(e.error_kind())
+        }
+    }
+}
+
+/// Decodes modified UTF-8 (without a length prefix) to a Rust string
+///
+/// Borrows the buffer when it is already standard UTF-8. Unpaired
+/// surrogates are an error, see `decode_modified_utf8_lossless`.
+pub fn decode_modified_utf8(buffer: &[u8]) -> Result<Cow<'_, str>, Utf8Fault> {
+    if let Some(s) = fast_path(buffer) {
+        return Ok(Cow::Borrowed(s));
+    }
+    let units = decode_units(buffer)?;
+    match String::from_utf16(&units) {
+        Ok(s) => Ok(Cow::Owned(s)),
+        Err(_) => Err(Utf8Fault::UnpairedSurrogate(first_unpaired(&units)))
+    }
+}
+
+/// Decodes modified UTF-8 (without a length prefix) keeping every code unit
+pub fn decode_modified_utf8_lossless(buffer: &[u8]) -> Result<JavaString<'_>, Utf8Fault> {
+    if let Some(s) = fast_path(buffer) {
+        return Ok(JavaString::Unicode(Cow::Borrowed(s)));
+    }
+    Ok(JavaString::from_utf16(decode_units(buffer)?))
+}
+
+/// Encodes a Rust string to modified UTF-8 (without a length prefix)
+pub fn encode_java_utf8(s: &str) -> Vec<u8> {
+    // ASCII without NUL is byte for byte identical
+    if s.bytes().all(|b| b != 0 && b < 0x80) {
+        return s.as_bytes().to_vec();
+    }
+    let units: Vec<u16> = s.encode_utf16().collect();
+    encode_java_utf16(&units)
+}
+
+/// Encodes UTF-16 code units to modified UTF-8 (without a length prefix)
+///
+/// Surrogates, paired or not, are each written as a 3 byte sequence.
+pub fn encode_java_utf16(units: &[u16]) -> Vec<u8> {
+    let mut ret = Vec::with_capacity(units.len());
+    for unit in units.iter().cloned() {
+        match unit {
+            0x0001..=0x007F => ret.push(unit as u8),
+            0x0000 |
+            0x0080..=0x07FF => {
+                ret.push(0xC0 | ((unit >> 6) as u8 & 0x1F));
+                ret.push(0x80 | (unit as u8 & 0x3F));
+            },
+            _ => {
+                ret.push(0xE0 | ((unit >> 12) as u8 & 0x0F));
+                ret.push(0x80 | ((unit >> 6) as u8 & 0x3F));
+                ret.push(0x80 | (unit as u8 & 0x3F));
             }
         }
     }
+    ret
+}
+
+/*
+ * Standard UTF-8 is only valid modified UTF-8 if it has no NUL bytes
+ * and no 4 byte sequences.
+ */
+#[inline]
+fn fast_path(buffer: &[u8]) -> Option<&str> {
+    if buffer.iter().any(|b| *b == 0 || *b >= 0xF0) {
+        return None;
+    }
+    ::std::str::from_utf8(buffer).ok()
+}
+
+fn first_unpaired(units: &[u16]) -> u16 {
+    match ::std::char::decode_utf16(units.iter().cloned()).find(|r| r.is_err()) {
+        Some(Err(e)) => e.unpaired_surrogate(),
+        _ => 0
+    }
 }
 
 /*
  * Happily re-allocates the string
  */
-fn decode<'a>(buffer: &'a [u8]) -> IResult<&'a [u8], Cow<'a, str>> {
-    use std::char;
+fn decode_units(buffer: &[u8]) -> Result<Vec<u16>, Utf8Fault> {
+
+    #[inline(always)]
+    fn cont(buffer: &[u8], index: usize) -> Result<u16, Utf8Fault> {
+        match buffer.get(index) {
+            Option::Some(b) if b & 0xC0 == 0x80 => Ok((b & 0x3F) as u16),
+            Option::Some(_) => Err(Utf8Fault::Malformed(index)),
+            Option::None => Err(Utf8Fault::Truncated)
+        }
+    }
 
     let len = buffer.len();
-    let mut ret = String::with_capacity(len);
+    let mut ret = Vec::with_capacity(len);
     let mut index = 0;
-    loop {
-        if index == len {
-            return IResult::Done(&buffer[0..0],Cow::Owned(ret));
-        }
-        let var = unsafe{buffer.get_unchecked(index)}.clone();
-        index += 1;
+    while index < len {
+        let var = buffer[index];
         match get_code(var) {
             0 => {
-                if var == 0 {
-                    return IResult::Error(ErrorKind::Custom(5));
-                }
-                match char::from_u32(var as u32) {
-                    Option::Some(c) => ret.push(c),
-                    Option::None => return IResult::Error(ErrorKind::Custom(4))
-                }
+                ret.push(var as u16);
+                index += 1;
             },
             2 => {
-                let x = var as u32;
-                if index+1 > len {
-                    return IResult::Incomplete(Needed::Size(1));
-                }
-                let y = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                let c = ((x&0x1Fu32) << 6) + (y&0x3Fu32);
-                match char::from_u32(c) {
-                    Option::Some(c) => ret.push(c),
-                    Option::None => return IResult::Error(ErrorKind::Custom(4))
-                }
+                let y = cont(buffer, index + 1)?;
+                ret.push((((var & 0x1F) as u16) << 6) | y);
+                index += 2;
             },
             3 => {
-                let x = var as u32;
-                if index+2 > len {
-                    return IResult::Incomplete(Needed::Size(2));
-                }
-                let y = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                let z = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                let c = ((x&0xFu32) << 12) + ((y&0x3F) << 6) + (z&0x3F);
-                match char::from_u32(c) {
-                    Option::Some(c) => ret.push(c),
-                    Option::None => return IResult::Error(ErrorKind::Custom(4))
-                }
+                let y = cont(buffer, index + 1)?;
+                let z = cont(buffer, index + 2)?;
+                ret.push((((var & 0x0F) as u16) << 12) | (y << 6) | z);
+                index += 3;
             },
-            6 => {
-                if index+5 > len {
-                    return IResult::Incomplete(Needed::Size(5));
-                }
-                let v = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                let w = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                index += 1;
-                let y = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                let z = unsafe{buffer.get_unchecked(index)}.clone() as u32;
-                index += 1;
-                let c = 0x10000u32 + ((v&0x0F) << 16) + ((w&0x3F) << 10) + ((y&0xF) << 6) + (z & 0x3F);
-                match char::from_u32(c) {
-                    Option::Some(c) => ret.push(c),
-                    Option::None => return IResult::Error(ErrorKind::Custom(4))
-                }
-            },
-            _ => return IResult::Error(ErrorKind::Custom(5))
+            _ => return Err(Utf8Fault::Malformed(index))
         };
     }
+    Ok(ret)
 }
 
 #[test]
 fn test_decode() {
 
-    let (_,var) = decode(b"Hello World!").unwrap();
+    let var = decode_modified_utf8(b"Hello World!").unwrap();
     assert_eq!(var, "Hello World!");
+    assert!(matches!(var, Cow::Borrowed(_)));
 }
 
+#[test]
+fn test_round_trip() {
+    // NUL and U+1F600 (a supplementary character)
+    let s = "a\u{0}b\u{1F600}\u{E9}";
+    let encoded = encode_java_utf8(s);
+    assert_eq!(encoded, b"a\xC0\x80b\xED\xA0\xBD\xED\xB8\x80\xC3\xA9".to_vec());
+    assert_eq!(decode_modified_utf8(&encoded).unwrap(), s);
+}
 
-
-
-
+#[test]
+fn test_unpaired_surrogate() {
+    let raw = b"x\xED\xA0\x80y";
+    assert_eq!(decode_modified_utf8(raw), Err(Utf8Fault::UnpairedSurrogate(0xD800)));
+    let lossless = decode_modified_utf8_lossless(raw).unwrap();
+    assert_eq!(lossless, JavaString::Utf16(vec![0x78, 0xD800, 0x79]));
+    assert_eq!(lossless.encode(), raw.to_vec());
+    assert_eq!(decode_modified_utf8(b"\xED\xA0"), Err(Utf8Fault::Truncated));
+    assert_eq!(decode_modified_utf8(b"a\x00"), Err(Utf8Fault::Malformed(1)));
+}
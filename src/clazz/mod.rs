@@ -18,15 +18,49 @@ use self::enum_constants::parse_class_access_flag;
 mod const_pool;
 pub use self::const_pool::{
     PoolMembers,
-    ConstantsPool
+    ConstantsPool,
+    MemberRef
 };
-use self::const_pool::parse_constant_pool;
+pub use self::const_pool::parse_constant_pool;
 
 mod javautf8;
+pub use self::javautf8::{
+    JavaString,
+    Utf8Fault,
+    decode_modified_utf8,
+    decode_modified_utf8_lossless,
+    encode_java_utf8,
+    encode_java_utf16
+};
 mod attributes;
-pub use self::attributes::AttributeInfo;
+pub use self::attributes::{AttributeInfo, find_attribute, write_attribute};
 use self::attributes::parse_attribute;
 
+mod code;
+pub use self::code::{CodeAttribute, ExceptionEntry};
+
+mod descriptor;
+pub use self::descriptor::{FieldType, MethodType};
+
+mod stackmap;
+pub use self::stackmap::{StackMapFrame, VerificationType, read_stack_map_table, write_stack_map_table};
+
+mod debuginfo;
+pub use self::debuginfo::{
+    LineNumber,
+    LocalVariable,
+    read_line_number_table,
+    read_local_variable_table,
+    write_line_number_table,
+    write_local_variable_table
+};
+
+mod bootstrap;
+pub use self::bootstrap::{BootstrapMethod, read_bootstrap_methods};
+
+mod module;
+pub use self::module::{Exports, ModuleAttribute, Provides, Requires, read_module};
+
 mod fields;
 pub use self::fields::FieldInfo;
 use self::fields::parse_field;
@@ -97,7 +131,11 @@ pub enum Fault {
     /// Your class file failed to parse
     ParseError,
     ClassLookUpFailure,
-    UTF8LookupFailure
+    UTF8LookupFailure,
+    /// No method at that index
+    MethodLookUpFailure,
+    /// An attribute's body failed to parse
+    AttributeParseError
 }
 
 impl<'a> Class<'a> {
@@ -127,6 +165,72 @@ impl<'a> Class<'a> {
         }
     }
 
+    /// The `major_version` of the class file format
+    pub fn get_major_version(&self) -> u16 {
+        self.major_version
+    }
+
+    pub fn get_minor_version(&self) -> u16 {
+        self.minor_version
+    }
+
+    /// Raw `access_flags` of the class
+    pub fn get_access_flags(&self) -> u16 {
+        self.access_flags
+    }
+
+    /// The method at `index`
+    pub fn get_method(&self, index: usize) -> Option<&MethodInfo<'a>> {
+        self.methods.get(index)
+    }
+
+    /// The field at `index`
+    pub fn get_field(&self, index: usize) -> Option<&FieldInfo<'a>> {
+        self.fields.get(index)
+    }
+
+    /// Attributes of the class itself, such as `SourceFile`
+    pub fn get_attributes(&self) -> &[AttributeInfo<'a>] {
+        &self.attributes
+    }
+
+    /// Raw constant pool index of `this_class`
+    pub fn get_this_class_index(&self) -> u16 {
+        self.this_class
+    }
+
+    /// Raw constant pool index of `super_class`, `0` for `java/lang/Object`
+    pub fn get_super_class_index(&self) -> u16 {
+        self.super_class
+    }
+
+    /// Raw constant pool indices of the direct superinterfaces
+    pub fn get_interface_indices(&self) -> &[u16] {
+        &self.interfaces
+    }
+
+    /// The constant pool
+    pub fn get_constants(&self) -> &ConstantsPool<'a> {
+        &self.constants
+    }
+
+    /// Reads the `Code` attribute of the method at `index`
+    ///
+    /// `Ok(None)` for methods without code, such as `abstract` and `native` ones
+    pub fn get_method_code(&self, index: usize) -> Result<Option<CodeAttribute<'a>>, Fault> {
+        let method = match self.methods.get(index) {
+            Option::Some(m) => m,
+            Option::None => return Err(Fault::MethodLookUpFailure)
+        };
+        match find_attribute(&method.attributes, &self.constants, "Code") {
+            Option::Some(attr) => match CodeAttribute::parse(attr.data) {
+                Option::Some(code) => Ok(Some(code)),
+                Option::None => Err(Fault::AttributeParseError)
+            },
+            Option::None => Ok(None)
+        }
+    }
+
     pub fn get_interfaces_count(&self) -> usize {
         self.interfaces.len()
     }
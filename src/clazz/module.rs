@@ -0,0 +1,111 @@
+//! The `Module` attribute of `module-info`, see JVMS 4.7.25.
+
+use super::super::nom::{
+    be_u16,
+    IResult
+};
+
+/// A `requires` directive
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Requires {
+    /// A `Module` constant
+    pub module: u16,
+    pub flags: u16,
+    /// A `Utf8` constant, `0` when no version was recorded
+    pub version: u16
+}
+named!(parse_requires<Requires>, do_parse!(
+    module: be_u16 >>
+    flags: be_u16 >>
+    version: be_u16 >>
+    (Requires { module, flags, version })
+));
+
+/// An `exports` or `opens` directive
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Exports {
+    /// A `Package` constant
+    pub package: u16,
+    pub flags: u16,
+    /// `Module` constants of a qualified directive, empty otherwise
+    pub to: Vec<u16>
+}
+named!(parse_exports<Exports>, do_parse!(
+    package: be_u16 >>
+    flags: be_u16 >>
+    len: be_u16 >>
+    to: count!(be_u16, len as usize) >>
+    (Exports { package, flags, to })
+));
+
+/// A `provides` directive
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct Provides {
+    /// A `Class` constant naming the service
+    pub service: u16,
+    /// `Class` constants of the implementations
+    pub with: Vec<u16>
+}
+named!(parse_provides<Provides>, do_parse!(
+    service: be_u16 >>
+    len: be_u16 >>
+    with: count!(be_u16, len as usize) >>
+    (Provides { service, with })
+));
+
+/// The body of a `Module` attribute, as constant pool indices
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub struct ModuleAttribute {
+    /// A `Module` constant
+    pub name: u16,
+    pub flags: u16,
+    /// A `Utf8` constant, `0` when no version was recorded
+    pub version: u16,
+    pub requires: Vec<Requires>,
+    pub exports: Vec<Exports>,
+    pub opens: Vec<Exports>,
+    /// `Class` constants of the services used
+    pub uses: Vec<u16>,
+    pub provides: Vec<Provides>
+}
+named!(parse_module<ModuleAttribute>, do_parse!(
+    name: be_u16 >>
+    flags: be_u16 >>
+    version: be_u16 >>
+    requires_len: be_u16 >>
+    requires: count!(parse_requires, requires_len as usize) >>
+    exports_len: be_u16 >>
+    exports: count!(parse_exports, exports_len as usize) >>
+    opens_len: be_u16 >>
+    opens: count!(parse_exports, opens_len as usize) >>
+    uses_len: be_u16 >>
+    uses: count!(be_u16, uses_len as usize) >>
+    provides_len: be_u16 >>
+    provides: count!(parse_provides, provides_len as usize) >>
+    (ModuleAttribute { name, flags, version, requires, exports, opens, uses, provides })
+));
+
+/// Parse the body of a `Module` attribute
+pub fn read_module(data: &[u8]) -> Option<ModuleAttribute> {
+    match parse_module(data) {
+        IResult::Done([], module) => Some(module),
+        _ => None
+    }
+}
+
+#[test]
+fn test_module() {
+    let module = read_module(b"\x00\x05\x00\x00\x00\x00\
+        \x00\x01\x00\x07\x80\x00\x00\x00\
+        \x00\x01\x00\x09\x00\x00\x00\x01\x00\x0b\
+        \x00\x00\
+        \x00\x01\x00\x0c\
+        \x00\x01\x00\x0c\x00\x02\x00\x0d\x00\x0e").unwrap();
+    assert_eq!(module.name, 5);
+    assert_eq!(module.requires, [Requires { module: 7, flags: 0x8000, version: 0 }]);
+    assert_eq!(module.exports, [Exports { package: 9, flags: 0, to: vec![11] }]);
+    assert!(module.opens.is_empty());
+    assert_eq!(module.uses, [12]);
+    assert_eq!(module.provides, [Provides { service: 12, with: vec![13, 14] }]);
+    assert_eq!(read_module(b"\x00\x05\x00\x00\x00\x00\x00\x01"), None);
+}
@@ -0,0 +1,214 @@
+//! The `StackMapTable` attribute, see JVMS 4.7.4.
+
+use super::super::nom::{
+    be_u8,
+    be_u16,
+    IResult,
+    ErrorKind
+};
+
+/// Error code for an unassigned frame or verification type tag
+const BAD_STACK_MAP_TAG: u32 = 8;
+
+/// A `verification_type_info` item
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash)]
+pub enum VerificationType {
+    Top,
+    Integer,
+    Float,
+    Double,
+    Long,
+    Null,
+    UninitializedThis,
+    /// Constant pool index of a `ClassInfo`
+    Object(u16),
+    /// pc of the `new` instruction creating the object
+    Uninitialized(u16)
+}
+
+fn parse_verification_type(input: &[u8]) -> IResult<&[u8], VerificationType> {
+    let (rest, tag) = match be_u8(input) {
+        IResult::Done(rest, tag) => (rest, tag),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e)
+    };
+    match tag {
+        0 => IResult::Done(rest, VerificationType::Top),
+        1 => IResult::Done(rest, VerificationType::Integer),
+        2 => IResult::Done(rest, VerificationType::Float),
+        3 => IResult::Done(rest, VerificationType::Double),
+        4 => IResult::Done(rest, VerificationType::Long),
+        5 => IResult::Done(rest, VerificationType::Null),
+        6 => IResult::Done(rest, VerificationType::UninitializedThis),
+        7 => map!(rest, be_u16, VerificationType::Object),
+        8 => map!(rest, be_u16, VerificationType::Uninitialized),
+        _ => IResult::Error(ErrorKind::Custom(BAD_STACK_MAP_TAG))
+    }
+}
+
+/// One frame of a `StackMapTable`
+///
+/// Short and extended forms of the same frame share a variant.
+#[derive(Clone,Debug,PartialEq,Eq)]
+pub enum StackMapFrame {
+    /// Same locals as the previous frame, empty stack
+    Same { offset_delta: u16 },
+    /// Same locals as the previous frame, one stack item
+    SameLocals1StackItem { offset_delta: u16, stack: VerificationType },
+    /// The last `k` locals are gone, empty stack
+    Chop { offset_delta: u16, k: u8 },
+    /// Locals are added, empty stack
+    Append { offset_delta: u16, locals: Vec<VerificationType> },
+    Full { offset_delta: u16, locals: Vec<VerificationType>, stack: Vec<VerificationType> }
+}
+impl StackMapFrame {
+
+    pub fn offset_delta(&self) -> u16 {
+        match *self {
+            StackMapFrame::Same { offset_delta } |
+            StackMapFrame::SameLocals1StackItem { offset_delta, .. } |
+            StackMapFrame::Chop { offset_delta, .. } |
+            StackMapFrame::Append { offset_delta, .. } |
+            StackMapFrame::Full { offset_delta, .. } => offset_delta
+        }
+    }
+}
+
+fn parse_frame(input: &[u8]) -> IResult<&[u8], StackMapFrame> {
+    let (rest, tag) = match be_u8(input) {
+        IResult::Done(rest, tag) => (rest, tag),
+        IResult::Incomplete(n) => return IResult::Incomplete(n),
+        IResult::Error(e) => return IResult::Error(e)
+    };
+    match tag {
+        0..=63 => IResult::Done(rest, StackMapFrame::Same { offset_delta: tag as u16 }),
+        64..=127 => map!(rest, parse_verification_type, |stack| {
+            StackMapFrame::SameLocals1StackItem { offset_delta: tag as u16 - 64, stack }
+        }),
+        247 => do_parse!(rest,
+            offset_delta: be_u16 >>
+            stack: parse_verification_type >>
+            (StackMapFrame::SameLocals1StackItem { offset_delta, stack })),
+        248..=250 => map!(rest, be_u16, |offset_delta| StackMapFrame::Chop { offset_delta, k: 251 - tag }),
+        251 => map!(rest, be_u16, |offset_delta| StackMapFrame::Same { offset_delta }),
+        252..=254 => do_parse!(rest,
+            offset_delta: be_u16 >>
+            locals: count!(parse_verification_type, tag as usize - 251) >>
+            (StackMapFrame::Append { offset_delta, locals })),
+        255 => do_parse!(rest,
+            offset_delta: be_u16 >>
+            local_count: be_u16 >>
+            locals: count!(parse_verification_type, local_count as usize) >>
+            stack_count: be_u16 >>
+            stack: count!(parse_verification_type, stack_count as usize) >>
+            (StackMapFrame::Full { offset_delta, locals, stack })),
+        _ => IResult::Error(ErrorKind::Custom(BAD_STACK_MAP_TAG))
+    }
+}
+
+// Parse the body of a `StackMapTable` attribute
+named!(pub parse_stack_map_table<Vec<StackMapFrame> >, do_parse!(
+    len: be_u16 >>
+    frames: count!(parse_frame, len as usize) >>
+    (frames)
+));
+
+/// Parse the body of a `StackMapTable` attribute
+pub fn read_stack_map_table(data: &[u8]) -> Option<Vec<StackMapFrame>> {
+    match parse_stack_map_table(data) {
+        IResult::Done([], frames) => Some(frames),
+        _ => None
+    }
+}
+
+fn write_verification_type(out: &mut Vec<u8>, t: &VerificationType) {
+    match *t {
+        VerificationType::Top => out.push(0),
+        VerificationType::Integer => out.push(1),
+        VerificationType::Float => out.push(2),
+        VerificationType::Double => out.push(3),
+        VerificationType::Long => out.push(4),
+        VerificationType::Null => out.push(5),
+        VerificationType::UninitializedThis => out.push(6),
+        VerificationType::Object(x) => {
+            out.push(7);
+            out.extend_from_slice(&x.to_be_bytes());
+        },
+        VerificationType::Uninitialized(x) => {
+            out.push(8);
+            out.extend_from_slice(&x.to_be_bytes());
+        }
+    };
+}
+
+/// Serialize the body of a `StackMapTable` attribute
+///
+/// Each frame takes its shortest encoding. `Chop` and `Append` frames
+/// must change between 1 and 3 locals.
+pub fn write_stack_map_table(frames: &[StackMapFrame]) -> Vec<u8> {
+    let mut out = Vec::new();
+    out.extend_from_slice(&(frames.len() as u16).to_be_bytes());
+    for frame in frames {
+        let delta = frame.offset_delta();
+        let short = delta < 64;
+        match *frame {
+            StackMapFrame::Same { .. } if short => out.push(delta as u8),
+            StackMapFrame::Same { .. } => {
+                out.push(251);
+                out.extend_from_slice(&delta.to_be_bytes());
+            },
+            StackMapFrame::SameLocals1StackItem { ref stack, .. } => {
+                if short {
+                    out.push(64 + delta as u8);
+                } else {
+                    out.push(247);
+                    out.extend_from_slice(&delta.to_be_bytes());
+                }
+                write_verification_type(&mut out, stack);
+            },
+            StackMapFrame::Chop { k, .. } => {
+                out.push(251 - k);
+                out.extend_from_slice(&delta.to_be_bytes());
+            },
+            StackMapFrame::Append { ref locals, .. } => {
+                out.push(251 + locals.len() as u8);
+                out.extend_from_slice(&delta.to_be_bytes());
+                for t in locals {
+                    write_verification_type(&mut out, t);
+                }
+            },
+            StackMapFrame::Full { ref locals, ref stack, .. } => {
+                out.push(255);
+                out.extend_from_slice(&delta.to_be_bytes());
+                for list in [locals, stack].iter() {
+                    out.extend_from_slice(&(list.len() as u16).to_be_bytes());
+                    for t in list.iter() {
+                        write_verification_type(&mut out, t);
+                    }
+                }
+            }
+        };
+    }
+    out
+}
+
+#[test]
+fn test_stack_map_table() {
+    let frames = read_stack_map_table(b"\x00\x04\x08\x40\x01\xFC\x00\x03\x07\x00\x02\
+        \xFF\x00\x01\x00\x01\x04\x00\x01\x08\x00\x00").unwrap();
+    assert_eq!(frames, vec![
+        StackMapFrame::Same { offset_delta: 8 },
+        StackMapFrame::SameLocals1StackItem { offset_delta: 0, stack: VerificationType::Integer },
+        StackMapFrame::Append { offset_delta: 3, locals: vec![VerificationType::Object(2)] },
+        StackMapFrame::Full {
+            offset_delta: 1,
+            locals: vec![VerificationType::Long],
+            stack: vec![VerificationType::Uninitialized(0)]
+        }
+    ]);
+    assert_eq!(read_stack_map_table(b"\x00\x01\xF0"), None);
+    let mut frames = frames;
+    frames.push(StackMapFrame::Same { offset_delta: 300 });
+    frames.push(StackMapFrame::Chop { offset_delta: 2, k: 2 });
+    assert_eq!(read_stack_map_table(&write_stack_map_table(&frames)), Some(frames));
+}
@@ -13,3 +13,5 @@ pub mod clazz;
 /// work in progress
 pub mod bytecode;
 
+/// Reading classes out of jars and other archives
+pub mod archive;